@@ -6,11 +6,15 @@
 //! The protocol is simple: the client sends a request with the number of
 //! milliseconds to wait, the server waits that long and sends back an empty
 //! reply.
+//!
+//! The server shuts down cleanly on Ctrl-C: workers stop accepting new
+//! requests but every request that was already received is answered before
+//! the socket is closed.
 use std::time::{Duration, Instant};
-use std::{env, process, thread};
+use std::{env, process};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use nng::{Aio, AioResult, Context, Message, Protocol, Socket};
+use nng::{Aio, AioGroup, AioResult, Context, Error, Message, Protocol, ShutdownToken, Socket};
 
 /// Number of outstanding requests that we can handle at a given time.
 ///
@@ -62,28 +66,32 @@ fn client(url: &str, ms: u64) -> Result<(), nng::Error> {
 
 /// Run the server portion of the program.
 fn server(url: &str) -> Result<(), nng::Error> {
+    // Trigger the shutdown when the user presses Ctrl-C.
+    let token = ShutdownToken::new();
+    let handler_token = token.clone();
+    ctrlc::set_handler(move || handler_token.trigger()).expect("Failed to set Ctrl-C handler");
+
     // Create the socket
     let s = Socket::new(Protocol::Rep0)?;
 
     // Create all of the worker contexts
-    let workers: Vec<_> = (0..PARALLEL)
-        .map(|_| {
-            let ctx = Context::new(&s)?;
-            let ctx_clone = ctx.clone();
-            let aio = Aio::new(move |aio, res| worker_callback(aio, &ctx_clone, res))?;
-            Ok((aio, ctx))
-        })
-        .collect::<Result<_, nng::Error>>()?;
+    let workers = AioGroup::new(&s, PARALLEL, worker_callback)?;
 
     // Only after we have the workers do we start listening.
     s.listen(url)?;
 
     // Now start all of the workers listening.
-    for (a, c) in &workers {
-        c.recv(a)?;
-    }
+    workers.start_recv()?;
+
+    // Let the workers handle requests until we are told to stop.
+    token.wait();
+    println!("Shutting down, answering outstanding requests");
 
-    thread::sleep(Duration::from_secs(60 * 60 * 24 * 365));
+    // Draining cancels the workers that are waiting for a request, while the
+    // ones in the middle of a request still send their reply. Once it
+    // returns, every request that was received has been answered.
+    workers.drain();
+    s.close();
 
     Ok(())
 }
@@ -91,24 +99,37 @@ fn server(url: &str) -> Result<(), nng::Error> {
 /// Callback function for workers.
 fn worker_callback(aio: Aio, ctx: &Context, res: AioResult) {
     match res {
-        // We successfully sent the message, wait for a new one.
-        AioResult::SendOk => ctx.recv(&aio).unwrap(),
-
-        // We successfully received a message.
+        // We successfully sent the message, wait for a new one. This fails
+        // once the workers are being drained, which stops the worker.
+        AioResult::SendOk => match ctx.recv(&aio) {
+            Ok(()) | Err(Error::Closed) => {}
+            Err(e) => panic!("Error: {}", e),
+        },
+
+        // We successfully received a message. Keep it so that it can be
+        // reused as the reply.
         AioResult::RecvOk(m) => {
             let ms = m.as_slice().read_u64::<LittleEndian>().unwrap();
+            aio.stash(m);
             aio.sleep(Duration::from_millis(ms)).unwrap();
         }
 
-        // We successfully slept.
+        // We were waiting for a request when the shutdown began.
+        AioResult::RecvErr(Error::Canceled) => {}
+
+        // We slept, so reply. The reply is empty, so the request only needs to
+        // be cleared.
         AioResult::SleepOk => {
-            let msg = Message::new().unwrap();
+            let mut msg = aio.take_stash().unwrap();
+            msg.clear();
             ctx.send(&aio, msg).unwrap();
         }
 
         // Anything else is an error and we will just panic.
-        AioResult::SendErr(_, e) | AioResult::RecvErr(e) | AioResult::SleepErr(e) => {
-            panic!("Error: {}", e)
-        }
+        AioResult::SendErr(_, e)
+        | AioResult::SendErrLost(e)
+        | AioResult::RecvErr(e)
+        | AioResult::SleepErr(e) => panic!("Error: {}", e),
+        res => panic!("Unexpected result: {:?}", res),
     }
 }
@@ -0,0 +1,99 @@
+//! A rough benchmark of publishing with and without batching.
+//!
+//! The publisher sends a large number of tiny records over the given URL,
+//! first as one message per record and then with a `BatchingPublisher` at
+//! various batch sizes. A subscriber in a separate thread counts the records
+//! it receives. Publish/subscribe is best-effort, so a subscriber that can't
+//! keep up loses records and the number that made it is reported as well.
+//!
+//! Run it in release mode for meaningful numbers:
+//!
+//! ```text
+//! cargo run --release --example batching tcp://127.0.0.1:5555
+//! ```
+use std::time::{Duration, Instant};
+use std::{env, thread};
+
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::{Options, RecvBufferSize, RecvTimeout};
+use nng::{BatchReader, BatchingPublisher, Error, Protocol, Socket};
+
+/// Number of records published in every run.
+const RECORDS: usize = 200_000;
+
+/// The topic of every record.
+const TOPIC: &str = "prices/XYZ";
+
+/// The payload of every record.
+const PAYLOAD: &[u8] = b"12345.67";
+
+/// Batch sizes to compare, in bytes. Zero means no batching.
+const BATCH_SIZES: &[usize] = &[0, 256, 4 * 1024, 64 * 1024];
+
+/// Entry point of the application.
+fn main() -> Result<(), nng::Error> {
+    let url = env::args().nth(1).unwrap_or_else(|| "inproc://nng/batching".to_string());
+
+    println!("{:>10} {:>12} {:>12} {:>10}", "batch", "records/s", "delivered", "messages");
+    for &size in BATCH_SIZES {
+        run(&url, size)?;
+    }
+
+    Ok(())
+}
+
+/// Publishes all records with the given batch size and prints the results.
+fn run(url: &str, batch_size: usize) -> Result<(), nng::Error> {
+    let socket = Socket::new(Protocol::Pub0)?;
+    socket.listen(url)?;
+
+    let subscriber = Socket::new(Protocol::Sub0)?;
+    subscriber.set_opt::<Subscribe>(Vec::new())?;
+    subscriber.set_opt::<RecvBufferSize>(8192)?;
+    subscriber.set_opt::<RecvTimeout>(Some(Duration::from_millis(500)))?;
+    subscriber.dial(url)?;
+    thread::sleep(Duration::from_millis(100));
+
+    let counter = thread::spawn(move || count(&subscriber, batch_size != 0));
+
+    let start = Instant::now();
+    if batch_size == 0 {
+        let mut record = TOPIC.as_bytes().to_vec();
+        record.extend_from_slice(PAYLOAD);
+        for _ in 0..RECORDS {
+            socket.send(&record[..])?;
+        }
+    } else {
+        let publisher = BatchingPublisher::new(socket.clone(), batch_size, Duration::from_millis(1))?;
+        for _ in 0..RECORDS {
+            publisher.publish(TOPIC, PAYLOAD)?;
+        }
+        publisher.shutdown()?;
+    }
+    let elapsed = start.elapsed();
+
+    let (delivered, messages) = counter.join().unwrap()?;
+    socket.close();
+
+    let rate = RECORDS as f64 / elapsed.as_secs_f64();
+    let label = if batch_size == 0 { "none".to_string() } else { batch_size.to_string() };
+    println!("{:>10} {:>12.0} {:>12} {:>10}", label, rate, delivered, messages);
+
+    Ok(())
+}
+
+/// Counts the records and messages received until the publisher goes quiet.
+fn count(subscriber: &Socket, batched: bool) -> Result<(usize, usize), nng::Error> {
+    let (mut records, mut messages) = (0, 0);
+
+    loop {
+        let msg = match subscriber.recv() {
+            Ok(m) => m,
+            Err(Error::TimedOut) => return Ok((records, messages)),
+            Err(e) => return Err(e),
+        };
+
+        messages += 1;
+        records += if batched { BatchReader::frames(&msg)?.count() } else { 1 };
+    }
+}
@@ -0,0 +1,318 @@
+//! A rough benchmark of fanning messages out over `inproc` with _pub_/_sub_.
+//!
+//! A _pub_ socket duplicates every message for all but the last of its
+//! subscribers, so a broadcast to `n` subscribers costs one message from the
+//! publisher and `n - 1` copies made by NNG. This measures how much the
+//! wrapper adds to that by running the same broadcast through it and through
+//! the raw bindings:
+//!
+//! * `broadcast` publishes batches of messages to every subscriber, each on
+//!   its own thread, and reports the deliveries per second. The batches are
+//!   small enough for the queues of the _pub_ socket, which drops messages
+//!   for subscribers that fall behind, so every message is delivered.
+//! * `clone` copies a prepared message, which is what NNG does for every
+//!   subscriber and what applications do when sending one message on several
+//!   sockets.
+//!
+//! Allocations made by Rust code while the messages are sent and received are
+//! counted as well. Messages are allocated by NNG, which this can't count, so
+//! that column shows how many message allocations NNG makes for each publish.
+//!
+//! The wrapper makes no allocations of its own on this path: messages are
+//! copied into NNG once when they are created from a slice, cloned with
+//! `nng_msg_dup`, and passed to and from NNG by pointer. Both sides should
+//! therefore come out the same, within the noise of the thread handoffs,
+//! which dominate the broadcast numbers.
+//!
+//! Run it in release mode for meaningful numbers:
+//!
+//! ```text
+//! cargo run --release --example broadcast
+//! ```
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::ffi::CString;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, Instant};
+use std::{ptr, thread};
+
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::{Options, RecvBufferSize, RecvTimeout};
+use nng::{Error, Message, Protocol, Socket};
+
+/// Number of messages published before the subscribers catch up.
+///
+/// This is the depth of the queue NNG keeps for every subscriber.
+const BATCH: usize = 16;
+
+/// Number of batches published in every run.
+const ROUNDS: usize = 2048;
+
+/// Number of copies made in every run of `clone`.
+const CLONES: usize = 200_000;
+
+/// Subscriber counts to compare.
+const SUBSCRIBERS: &[usize] = &[1, 4, 16];
+
+/// Message sizes to compare, in bytes.
+const SIZES: &[usize] = &[64, 16 * 1024];
+
+/// The number of allocations made through the global allocator.
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting every allocation.
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// One end of the broadcast.
+#[derive(Clone)]
+enum Endpoint {
+    /// A socket of this crate.
+    Wrapper(Socket),
+
+    /// A socket opened and used through the bindings directly.
+    Raw(nng_sys::nng_socket),
+}
+
+impl Endpoint {
+    /// Opens a _pub_ socket listening on `url`.
+    fn publisher(raw: bool, url: &str) -> Result<Self, Error> {
+        if !raw {
+            let socket = Socket::new(Protocol::Pub0)?;
+            socket.listen(url)?;
+            return Ok(Endpoint::Wrapper(socket));
+        }
+
+        let url = CString::new(url).unwrap();
+        let mut socket = nng_sys::nng_socket::NNG_SOCKET_INITIALIZER;
+        unsafe {
+            assert_eq!(nng_sys::nng_pub0_open(&mut socket), 0);
+            assert_eq!(
+                nng_sys::nng_listen(socket, url.as_ptr(), ptr::null_mut(), 0),
+                0
+            );
+        }
+        Ok(Endpoint::Raw(socket))
+    }
+
+    /// Opens a _sub_ socket subscribed to everything and dials `url`.
+    ///
+    /// The receive buffer holds a whole batch and receives time out, so that
+    /// a lost message shows up as an error instead of a hang.
+    fn subscriber(raw: bool, url: &str) -> Result<Self, Error> {
+        let timeout = Duration::from_secs(1);
+        if !raw {
+            let socket = Socket::new(Protocol::Sub0)?;
+            socket.set_opt::<Subscribe>(Vec::new())?;
+            socket.set_opt::<RecvBufferSize>(BATCH as i32)?;
+            socket.set_opt::<RecvTimeout>(Some(timeout))?;
+            socket.dial(url)?;
+            return Ok(Endpoint::Wrapper(socket));
+        }
+
+        let url = CString::new(url).unwrap();
+        let mut socket = nng_sys::nng_socket::NNG_SOCKET_INITIALIZER;
+        unsafe {
+            assert_eq!(nng_sys::nng_sub0_open(&mut socket), 0);
+            let opt = nng_sys::NNG_OPT_SUB_SUBSCRIBE.as_ptr() as _;
+            assert_eq!(nng_sys::nng_setopt(socket, opt, ptr::null(), 0), 0);
+            let opt = nng_sys::NNG_OPT_RECVBUF.as_ptr() as _;
+            assert_eq!(nng_sys::nng_setopt_int(socket, opt, BATCH as c_int), 0);
+            let opt = nng_sys::NNG_OPT_RECVTIMEO.as_ptr() as _;
+            let ms = timeout.as_millis() as nng_sys::nng_duration;
+            assert_eq!(nng_sys::nng_setopt_ms(socket, opt, ms), 0);
+            assert_eq!(
+                nng_sys::nng_dial(socket, url.as_ptr(), ptr::null_mut(), 0),
+                0
+            );
+        }
+        Ok(Endpoint::Raw(socket))
+    }
+
+    /// Publishes a copy of the payload.
+    ///
+    /// Errors of the raw side are reported with their NNG code.
+    fn send(&self, payload: &[u8]) -> Result<(), Error> {
+        match self {
+            Endpoint::Wrapper(socket) => socket.send(payload).map_err(Error::from),
+            Endpoint::Raw(socket) => unsafe {
+                let mut msgp: *mut nng_sys::nng_msg = ptr::null_mut();
+                assert_eq!(nng_sys::nng_msg_alloc(&mut msgp, payload.len()), 0);
+                let body = nng_sys::nng_msg_body(msgp) as *mut u8;
+                ptr::copy_nonoverlapping(payload.as_ptr(), body, payload.len());
+                match nng_sys::nng_sendmsg(*socket, msgp, 0) {
+                    0 => Ok(()),
+                    rv => {
+                        nng_sys::nng_msg_free(msgp);
+                        Err(Error::Unknown(rv as u32))
+                    }
+                }
+            },
+        }
+    }
+
+    /// Receives and frees a single message.
+    fn recv(&self) -> Result<(), Error> {
+        match self {
+            Endpoint::Wrapper(socket) => socket.recv().map(drop),
+            Endpoint::Raw(socket) => unsafe {
+                let mut msgp: *mut nng_sys::nng_msg = ptr::null_mut();
+                match nng_sys::nng_recvmsg(*socket, &mut msgp, 0) {
+                    0 => {
+                        nng_sys::nng_msg_free(msgp);
+                        Ok(())
+                    }
+                    rv => Err(Error::Unknown(rv as u32)),
+                }
+            },
+        }
+    }
+
+    /// Closes the socket.
+    fn close(&self) {
+        match self {
+            Endpoint::Wrapper(socket) => socket.close(),
+            Endpoint::Raw(socket) => unsafe {
+                nng_sys::nng_close(*socket);
+            },
+        }
+    }
+}
+
+/// Entry point of the application.
+fn main() -> Result<(), Error> {
+    println!(
+        "{:>9} {:>5} {:>8} {:>8} {:>14} {:>10} {:>10}",
+        "test", "subs", "size", "side", "deliveries/s", "rust/msg", "nng/msg"
+    );
+    for &size in SIZES {
+        for &subs in SUBSCRIBERS {
+            for &(raw, label) in &[(false, "wrapper"), (true, "raw")] {
+                let (rate, allocs) = broadcast(raw, subs, size)?;
+                println!(
+                    "{:>9} {:>5} {:>8} {:>8} {:>14.0} {:>10.2} {:>10}",
+                    "broadcast", subs, size, label, rate, allocs, subs
+                );
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "{:>9} {:>8} {:>8} {:>10}",
+        "test", "size", "side", "ns/copy"
+    );
+    for &size in SIZES {
+        for &(raw, label) in &[(false, "wrapper"), (true, "raw")] {
+            println!(
+                "{:>9} {:>8} {:>8} {:>10.0}",
+                "clone",
+                size,
+                label,
+                clone(raw, size)?
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Publishes to the subscribers and returns the deliveries per second along
+/// with the Rust allocations made for every published message.
+fn broadcast(raw: bool, subs: usize, size: usize) -> Result<(f64, f64), Error> {
+    let url = format!("inproc://nng/broadcast/{}/{}/{}", raw, subs, size);
+    let publisher = Endpoint::publisher(raw, &url)?;
+    let subscribers = (0..subs)
+        .map(|_| Endpoint::subscriber(raw, &url))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Give the subscribers a moment to connect, as NNG drops messages for
+    // pipes that aren't there yet.
+    thread::sleep(Duration::from_millis(50));
+
+    let barrier = Arc::new(Barrier::new(subs + 1));
+    let threads: Vec<_> = subscribers
+        .iter()
+        .cloned()
+        .map(|sub| {
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || -> Result<(), Error> {
+                for _ in 0..ROUNDS {
+                    for _ in 0..BATCH {
+                        sub.recv()?;
+                    }
+                    barrier.wait();
+                }
+                Ok(())
+            })
+        })
+        .collect();
+
+    let payload = vec![0xA5; size];
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for _ in 0..BATCH {
+            publisher.send(&payload)?;
+        }
+        barrier.wait();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+    for endpoint in subscribers.iter().chain(Some(&publisher)) {
+        endpoint.close();
+    }
+
+    let published = (ROUNDS * BATCH) as f64;
+    let rate = published * subs as f64 / elapsed.as_secs_f64();
+    Ok((rate, allocations as f64 / published))
+}
+
+/// Returns the average time it takes to copy a message of the size.
+fn clone(raw: bool, size: usize) -> Result<f64, Error> {
+    let payload = vec![0xA5; size];
+    if !raw {
+        let template = Message::from(&payload[..]);
+        let start = Instant::now();
+        for _ in 0..CLONES {
+            drop(template.clone());
+        }
+        return Ok(start.elapsed().as_nanos() as f64 / CLONES as f64);
+    }
+
+    unsafe {
+        let mut template: *mut nng_sys::nng_msg = ptr::null_mut();
+        assert_eq!(nng_sys::nng_msg_alloc(&mut template, size), 0);
+        let body = nng_sys::nng_msg_body(template) as *mut u8;
+        ptr::copy_nonoverlapping(payload.as_ptr(), body, size);
+
+        let start = Instant::now();
+        for _ in 0..CLONES {
+            let mut copy: *mut nng_sys::nng_msg = ptr::null_mut();
+            assert_eq!(nng_sys::nng_msg_dup(&mut copy, template), 0);
+            nng_sys::nng_msg_free(copy);
+        }
+        let elapsed = start.elapsed();
+
+        nng_sys::nng_msg_free(template);
+        Ok(elapsed.as_nanos() as f64 / CLONES as f64)
+    }
+}
@@ -0,0 +1,147 @@
+//! A load-balanced request/reply example.
+//!
+//! This example shows how to spread requests from any number of clients over
+//! any number of workers that all sit behind a single address. A broker in the
+//! middle owns both addresses, so clients and workers can come and go without
+//! knowing about each other:
+//!
+//! ```text
+//!                           +--------------------------+
+//! client (REQ) --+          |          broker          |          +-- worker (REP)
+//!                |  front   |                          |   back   |
+//! client (REQ) --+--------> |  raw REP <---> raw REQ   | <--------+-- worker (REP)
+//!                |          |        forwarder         |          |
+//! client (REQ) --+          +--------------------------+          +-- worker (REP)
+//! ```
+//!
+//! The broker is a raw _rep_ socket that the clients dial and a raw _req_
+//! socket that the workers dial, joined with `nng::forwarder`. The raw sockets
+//! don't interpret the messages, they only carry the routing information in
+//! the headers along, so each reply finds its way back to the client that sent
+//! the request. Everything else is regular _req_ and _rep_ code: the clients
+//! keep their request resending and timeouts, and a worker that goes away is
+//! replaced by the others when the request is resent.
+//!
+//! The broker hands the requests out to the connected workers in turn. It
+//! doesn't know how busy a worker is, so slow workers get as many requests as
+//! fast ones. Run the example with `demo` to see the distribution without
+//! starting any processes:
+//!
+//! ```text
+//! loadbalance demo
+//! loadbalance broker tcp://127.0.0.1:5555 tcp://127.0.0.1:5556
+//! loadbalance worker tcp://127.0.0.1:5556 alpha
+//! loadbalance client tcp://127.0.0.1:5555 100
+//! ```
+use std::collections::BTreeMap;
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+use std::{env, process, thread};
+
+use nng::{forwarder, PipeEvent, Protocol, Socket};
+
+/// The number of workers started by the demo.
+const DEMO_WORKERS: usize = 4;
+
+/// The number of requests sent by the demo.
+const DEMO_REQUESTS: usize = 100;
+
+/// Entry point of the application.
+fn main() -> Result<(), nng::Error> {
+    let args: Vec<_> = env::args().collect();
+
+    match &args[..] {
+        [_, t] if t == "demo" => demo(),
+        [_, t, front, back] if t == "broker" => broker(front, back),
+        [_, t, back, name] if t == "worker" => worker(back, name),
+        [_, t, front, count] if t == "client" => client(front, count.parse().unwrap()),
+        _ => {
+            println!(
+                "Usage:\nloadbalance demo\n  or\nloadbalance broker <front url> <back url>\n  or\n\
+                 loadbalance worker <back url> <name>\n  or\nloadbalance client <front url> <count>"
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Run the broker, which forwards requests from the clients to the workers.
+fn broker(front: &str, back: &str) -> Result<(), nng::Error> {
+    let front_socket = Socket::new_raw(Protocol::Rep0)?;
+    front_socket.listen(front)?;
+
+    let back_socket = Socket::new_raw(Protocol::Req0)?;
+    back_socket.listen(back)?;
+
+    println!("BROKER: Forwarding from {} to {}", front, back);
+    forwarder(&front_socket, &back_socket)
+}
+
+/// Run a worker, which answers each request with its name.
+fn worker(back: &str, name: &str) -> Result<(), nng::Error> {
+    let s = Socket::new(Protocol::Rep0)?;
+    s.dial(back)?;
+
+    loop {
+        let mut msg = s.recv()?;
+
+        // Pretend to do some work.
+        thread::sleep(Duration::from_millis(10));
+
+        msg.clear();
+        msg.push_back(name.as_bytes())?;
+        s.send(msg)?;
+    }
+}
+
+/// Run a client, which sends the requests and counts who answered them.
+fn client(front: &str, count: usize) -> Result<(), nng::Error> {
+    let s = Socket::new(Protocol::Req0)?;
+    s.dial(front)?;
+
+    let mut served = BTreeMap::new();
+    for _ in 0..count {
+        s.send(&b"work"[..])?;
+        let reply = s.recv()?;
+        let name = String::from_utf8_lossy(&reply).into_owned();
+        *served.entry(name).or_insert(0) += 1;
+    }
+
+    for (name, n) in served {
+        println!("CLIENT: {} answered {} of {} requests", name, n, count);
+    }
+
+    Ok(())
+}
+
+/// Run the broker, the workers, and a client inside of this process.
+fn demo() -> Result<(), nng::Error> {
+    const FRONT: &str = "inproc://loadbalance/front";
+    const BACK: &str = "inproc://loadbalance/back";
+
+    // The broker and the workers run until the process exits.
+    let front_socket = Socket::new_raw(Protocol::Rep0)?;
+    front_socket.listen(FRONT)?;
+    let back_socket = Socket::new_raw(Protocol::Req0)?;
+
+    // Requests only go to the workers that are connected when they are sent,
+    // so the client waits until every worker has connected.
+    let (connected_tx, connected_rx) = mpsc::channel();
+    let connected_tx = Mutex::new(connected_tx);
+    back_socket.pipe_notify(move |_, ev| {
+        if ev == PipeEvent::AddPost {
+            let _ = connected_tx.lock().unwrap().send(());
+        }
+    })?;
+    back_socket.listen(BACK)?;
+    thread::spawn(move || forwarder(&front_socket, &back_socket));
+
+    for i in 0..DEMO_WORKERS {
+        thread::spawn(move || worker(BACK, &format!("worker-{}", i)));
+    }
+    for _ in 0..DEMO_WORKERS {
+        connected_rx.recv().unwrap();
+    }
+
+    client(FRONT, DEMO_REQUESTS)
+}
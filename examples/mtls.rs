@@ -0,0 +1,128 @@
+//! A REQ/REP service over TLS where both sides present a certificate.
+//!
+//! With mutual TLS, the server doesn't only prove its identity to its clients,
+//! it also requires every client to present a certificate signed by an
+//! authority it trusts, and refuses the handshake otherwise. Both sides are set
+//! up with a `TlsConfig` that is handed to the listener or dialer before it is
+//! started, which is what `ListenerOptions` and `DialerOptions` are for. A
+//! listener started with `Socket::listen` would start accepting without any
+//! certificate at all.
+//!
+//! The certificates in `examples/certs` are issued by a test authority for
+//! `localhost` and must not be used for anything else. NNG has to be built with
+//! TLS support, which the `nng-tls` feature does:
+//!
+//! ```text
+//! cargo run --features nng-tls --example mtls server tls+tcp://127.0.0.1:5556
+//! cargo run --features nng-tls --example mtls client tls+tcp://localhost:5556
+//! ```
+//!
+//! A client started with `anonymous` in place of `client` presents no
+//! certificate and is turned away by the server.
+use std::time::Duration;
+use std::{env, process};
+
+use nng::options::transport::tls::Verified;
+use nng::options::{Options, RecvTimeout};
+use nng::{
+    DialerOptions, Error, ListenerOptions, Protocol, Socket, TlsAuthMode, TlsConfig, TlsMode,
+};
+
+/// The authority that issued the certificates of both sides.
+const CA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/certs/ca.pem");
+
+/// The certificate and private key of the server.
+const SERVER_CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/certs/server.pem");
+
+/// The certificate and private key of the client.
+const CLIENT_CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/certs/client.pem");
+
+/// The name the certificate of the server is issued for.
+const SERVER_NAME: &str = "localhost";
+
+/// Entry point of the application
+fn main() -> Result<(), nng::Error> {
+    let args: Vec<_> = env::args().take(3).collect();
+
+    let res = match &args[..] {
+        [_, t, url] if t == "server" => server(url),
+        [_, t, url] if t == "client" => client(url, true),
+        [_, t, url] if t == "anonymous" => client(url, false),
+        _ => {
+            println!("Usage: mtls server|client|anonymous <URL>");
+            process::exit(1);
+        }
+    };
+
+    if res == Err(Error::NotSupported) {
+        println!("NNG was built without TLS support, try the nng-tls feature");
+        process::exit(1);
+    }
+    res
+}
+
+/// Run the server portion of the program.
+fn server(url: &str) -> Result<(), nng::Error> {
+    let config = TlsConfig::new(TlsMode::Server)?;
+    config.cert_key_file(SERVER_CERT, None)?;
+    config.ca_file(CA)?;
+    config.auth_mode(TlsAuthMode::Required)?;
+
+    let s = Socket::new(Protocol::Rep0)?;
+    let options = ListenerOptions::new(&s, url)?;
+    options.set_tls_config(&config)?;
+    let _listener = options.start(false).map_err(|(_, e)| e)?;
+
+    println!("SERVER: WAITING FOR REQUESTS");
+    loop {
+        let mut msg = s.recv()?;
+
+        // Only clients with a valid certificate get this far, which the pipe
+        // confirms.
+        let verified = match msg.pipe() {
+            Some(p) => p.get_opt::<Verified>()?,
+            None => false,
+        };
+        println!(
+            "SERVER: RECEIVED {:?} (VERIFIED: {})",
+            String::from_utf8_lossy(&msg),
+            verified
+        );
+
+        msg.clear();
+        msg.push_back(b"hello, verified client")?;
+        s.send(msg)?;
+    }
+}
+
+/// Run the client portion of the program, with or without a certificate.
+fn client(url: &str, with_cert: bool) -> Result<(), nng::Error> {
+    // The configuration replaces the one the dialer would set the host of the
+    // URL on, so it has to name the server itself.
+    let config = TlsConfig::new(TlsMode::Client)?;
+    config.ca_file(CA)?;
+    config.server_name(SERVER_NAME)?;
+    if with_cert {
+        config.cert_key_file(CLIENT_CERT, None)?;
+    }
+
+    // The handshake fails for a client without a certificate, which the
+    // synchronous start reports. Don't wait forever if the server goes away.
+    let s = Socket::new(Protocol::Req0)?;
+    s.set_opt::<RecvTimeout>(Some(Duration::from_secs(5)))?;
+    let options = DialerOptions::new(&s, url)?;
+    options.set_tls_config(&config)?;
+    if let Err((_, e)) = options.start(false) {
+        println!("CLIENT: UNABLE TO CONNECT ({})", e);
+        return Err(e);
+    }
+
+    println!("CLIENT: SENDING REQUEST");
+    s.send(&b"hello, server"[..])?;
+
+    match s.recv() {
+        Ok(reply) => println!("CLIENT: RECEIVED {:?}", String::from_utf8_lossy(&reply)),
+        Err(e) => println!("CLIENT: NO REPLY ({})", e),
+    }
+    Ok(())
+}
@@ -1,16 +1,21 @@
 //! A simple PUB/SUB demonstration application.
 //!
-//! This application simply publishes current number of subscribers every few
-//! seconds.
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+//! The publisher announces when it started once and then publishes its uptime
+//! every few seconds. It uses a last-value cache, so a subscriber that
+//! connects late still immediately receives the startup announcement and the
+//! most recent uptime instead of waiting for the next update.
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{env, process, thread};
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use nng::options::protocol::pubsub::Subscribe;
-use nng::options::Options;
-use nng::{Message, PipeEvent, Protocol, Socket};
+use byteorder::{LittleEndian, ReadBytesExt};
+use nng::options::{Options, RecvBufferSize};
+use nng::{LastValueCache, Protocol, Socket, Subscriber};
+
+/// Topic announcing when the publisher started, in seconds since the epoch.
+const STARTED: &str = "status/started";
+
+/// Topic carrying the uptime of the publisher, in seconds.
+const UPTIME: &str = "status/uptime";
 
 /// Entry point of the application.
 fn main() -> Result<(), nng::Error> {
@@ -30,46 +35,49 @@ fn main() -> Result<(), nng::Error> {
 
 /// Run the publisher portion of the program.
 fn publisher(url: &str) -> Result<(), nng::Error> {
-    let s = Socket::new(Protocol::Pub0)?;
-    let count = Arc::new(AtomicUsize::new(0));
-    let count_clone = count.clone();
+    let cache = LastValueCache::new(Socket::new(Protocol::Pub0)?)?;
+    cache.socket().listen(url)?;
 
-    s.pipe_notify(move |_, ev| {
-        match ev {
-            PipeEvent::AddPost => count_clone.fetch_add(1, Ordering::Relaxed),
-            PipeEvent::RemovePost => count_clone.fetch_sub(1, Ordering::Relaxed),
-            _ => 0,
-        };
-    })?;
-
-    s.listen(url)?;
+    // This is only ever published once, but every subscriber will get it.
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Current system time is before the Unix epoch")
+        .as_secs();
+    println!("PUBLISHER: STARTED AT {}", started);
+    cache.publish(STARTED, &started.to_le_bytes())?;
 
+    let start = Instant::now();
     loop {
         // Sleep for a little bit before sending the next message.
         thread::sleep(Duration::from_secs(3));
 
-        // Load the number of subscribers and send the value across
-        let data = count.load(Ordering::Relaxed) as u64;
-        let mut msg = Message::new()?;
-        msg.write_u64::<LittleEndian>(data).unwrap();
-
-        println!("PUBLISHER: SENDING {}", data);
-        s.send(msg)?;
+        let uptime = start.elapsed().as_secs();
+        println!("PUBLISHER: UP FOR {} SECONDS", uptime);
+        cache.publish(UPTIME, &uptime.to_le_bytes())?;
     }
 }
 
 /// Run the subscriber portion of the program.
 fn subscriber(url: &str) -> Result<(), nng::Error> {
-    let s = Socket::new(Protocol::Sub0)?;
-    s.dial(url)?;
+    let sub = Subscriber::new()?;
 
-    println!("SUBSCRIBER: SUBSCRIBING TO ALL TOPICS");
-    let all_topics = vec![];
-    s.set_opt::<Subscribe>(all_topics)?;
+    // The retained values are replayed as soon as we connect, so subscribe
+    // first and make sure there is room to buffer all of them.
+    println!("SUBSCRIBER: SUBSCRIBING TO ALL STATUS TOPICS");
+    sub.subscribe("status/")?;
+    sub.socket().set_opt::<RecvBufferSize>(16)?;
+    sub.socket().dial(url)?;
 
-    loop {
-        let msg = s.recv()?;
-        let subs = msg.as_slice().read_u64::<LittleEndian>().unwrap();
-        println!("SUBSCRIBER: THERE ARE {} SUBSCRIBERS", subs);
+    for res in &sub {
+        let (topic, payload) = res?;
+        let value = payload.as_slice().read_u64::<LittleEndian>().unwrap();
+
+        match &topic[..] {
+            STARTED => println!("SUBSCRIBER: PUBLISHER STARTED AT {}", value),
+            UPTIME => println!("SUBSCRIBER: PUBLISHER UP FOR {} SECONDS", value),
+            _ => println!("SUBSCRIBER: UNKNOWN TOPIC {}", topic),
+        }
     }
+
+    Ok(())
 }
@@ -0,0 +1,152 @@
+//! A rough benchmark of the per-call cost of receiving messages.
+//!
+//! `Socket::recv` is a thin layer over `nng_recvmsg`: it picks the flags from
+//! the nonblocking setting, passes a pointer for NNG to fill in, and wraps the
+//! result. This measures how much that layer adds by receiving the same way
+//! through the wrapper and through the raw bindings:
+//!
+//! * `empty` makes nonblocking receives from a socket with nothing queued, so
+//!   every call fails with `TryAgain`. Nothing is allocated or copied, which
+//!   leaves the cost of the call itself.
+//! * `queued` fills the receive buffer of the socket and then drains it, so
+//!   every call returns a small message, which is then freed.
+//!
+//! Run it in release mode for meaningful numbers:
+//!
+//! ```text
+//! cargo run --release --example recv_overhead
+//! ```
+use std::ffi::CString;
+use std::os::raw::c_int;
+use std::time::{Duration, Instant};
+use std::{ptr, thread};
+
+use nng::options::{Options, RecvBufferSize};
+use nng::{Error, Protocol, Socket};
+
+/// Number of receives made on an empty socket in every run.
+const EMPTY_CALLS: usize = 1_000_000;
+
+/// Number of messages queued before every drain of the socket.
+const BATCH: usize = 4096;
+
+/// Number of times the socket is filled and drained in every run.
+const ROUNDS: usize = 50;
+
+/// The receiving side of the comparison.
+enum Receiver {
+    /// A socket of this crate, received from with `Socket::recv`, along with
+    /// a nonblocking clone of it.
+    Wrapper(Socket, Socket),
+
+    /// A socket opened and received from using the bindings directly.
+    Raw(nng_sys::nng_socket),
+}
+
+impl Receiver {
+    /// Opens a _pull_ socket that queues a whole batch and listens on `url`.
+    fn open(raw: bool, url: &str) -> Result<Self, Error> {
+        if !raw {
+            let socket = Socket::new(Protocol::Pull0)?;
+            socket.set_opt::<RecvBufferSize>(BATCH as i32)?;
+            socket.listen(url)?;
+            let mut nonblocking = socket.clone();
+            nonblocking.set_nonblocking(true);
+            return Ok(Receiver::Wrapper(socket, nonblocking));
+        }
+
+        let url = CString::new(url).unwrap();
+        let mut socket = nng_sys::nng_socket::NNG_SOCKET_INITIALIZER;
+        unsafe {
+            assert_eq!(nng_sys::nng_pull0_open(&mut socket), 0);
+            let opt = nng_sys::NNG_OPT_RECVBUF.as_ptr() as _;
+            assert_eq!(nng_sys::nng_setopt_int(socket, opt, BATCH as c_int), 0);
+            assert_eq!(
+                nng_sys::nng_listen(socket, url.as_ptr(), ptr::null_mut(), 0),
+                0
+            );
+        }
+        Ok(Receiver::Raw(socket))
+    }
+
+    /// Receives and frees a single message, returning whether there was one.
+    fn recv(&self, nonblocking: bool) -> bool {
+        match self {
+            Receiver::Wrapper(_, socket) if nonblocking => socket.recv().is_ok(),
+            Receiver::Wrapper(socket, _) => socket.recv().is_ok(),
+            Receiver::Raw(socket) => {
+                let flags = if nonblocking {
+                    nng_sys::NNG_FLAG_NONBLOCK
+                } else {
+                    0
+                };
+                let mut msgp: *mut nng_sys::nng_msg = ptr::null_mut();
+                let rv = unsafe { nng_sys::nng_recvmsg(*socket, &mut msgp, flags as c_int) };
+                if rv == 0 {
+                    unsafe { nng_sys::nng_msg_free(msgp) };
+                }
+                rv == 0
+            }
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        if let Receiver::Raw(socket) = self {
+            unsafe { nng_sys::nng_close(*socket) };
+        }
+    }
+}
+
+/// Entry point of the application.
+fn main() -> Result<(), Error> {
+    println!("{:>8} {:>10} {:>10}", "queue", "receiver", "ns/recv");
+    for &(raw, label) in &[(false, "wrapper"), (true, "raw")] {
+        let url = format!("inproc://nng/recv_overhead/{}", label);
+        let receiver = Receiver::open(raw, &url)?;
+
+        let empty = time_empty(&receiver);
+        println!("{:>8} {:>10} {:>10.0}", "empty", label, empty);
+
+        let queued = time_queued(&receiver, &url)?;
+        println!("{:>8} {:>10} {:>10.0}", "queued", label, queued);
+    }
+
+    Ok(())
+}
+
+/// Returns the average time of a receive when nothing is queued.
+fn time_empty(receiver: &Receiver) -> f64 {
+    let start = Instant::now();
+    for _ in 0..EMPTY_CALLS {
+        assert!(!receiver.recv(true));
+    }
+
+    start.elapsed().as_nanos() as f64 / EMPTY_CALLS as f64
+}
+
+/// Returns the average time of a receive when a message is queued.
+fn time_queued(receiver: &Receiver, url: &str) -> Result<f64, Error> {
+    let push = Socket::new(Protocol::Push0)?;
+    push.dial(url)?;
+
+    let mut elapsed = Duration::from_secs(0);
+    for _ in 0..ROUNDS {
+        for _ in 0..BATCH {
+            push.send(&b"overhead"[..])?;
+        }
+
+        // Let the last messages reach the receive buffer, so that the drain
+        // doesn't wait for them.
+        thread::sleep(Duration::from_millis(10));
+
+        let start = Instant::now();
+        for _ in 0..BATCH {
+            assert!(receiver.recv(false));
+        }
+        elapsed += start.elapsed();
+    }
+
+    Ok(elapsed.as_nanos() as f64 / (BATCH * ROUNDS) as f64)
+}
@@ -0,0 +1,129 @@
+//! A rough benchmark of reusing the request as the reply in an AIO server.
+//!
+//! The server answers every request from a pool of AIO workers. Each worker
+//! holds on to the request with `Aio::stash` while it "works" and then either
+//! clears and reuses it for the reply or allocates a fresh `Message`. A number
+//! of client threads keep the server busy and the number of round trips per
+//! second is reported for both.
+//!
+//! Run it in release mode for meaningful numbers:
+//!
+//! ```text
+//! cargo run --release --example reply_reuse tcp://127.0.0.1:5555
+//! ```
+use std::time::{Duration, Instant};
+use std::{env, thread};
+
+use nng::{Aio, AioResult, Context, Message, Protocol, Socket};
+
+/// Number of round trips made by every client.
+const REQUESTS: usize = 20_000;
+
+/// Number of client threads.
+const CLIENTS: usize = 4;
+
+/// Number of AIO workers in the server.
+const WORKERS: usize = 8;
+
+/// The size of every request and reply, in bytes.
+const SIZE: usize = 4096;
+
+/// Entry point of the application.
+fn main() -> Result<(), nng::Error> {
+    let url = env::args().nth(1).unwrap_or_else(|| "inproc://nng/reply_reuse".to_string());
+
+    println!("{:>8} {:>12}", "reply", "requests/s");
+    for &reuse in &[false, true] {
+        run(&url, reuse)?;
+    }
+
+    Ok(())
+}
+
+/// Runs all of the clients against a server and prints the results.
+fn run(url: &str, reuse: bool) -> Result<(), nng::Error> {
+    let server = Socket::new(Protocol::Rep0)?;
+    server.listen(url)?;
+
+    let workers = (0..WORKERS)
+        .map(|_| {
+            let ctx = Context::new(&server)?;
+            let aio = Aio::for_context(&ctx, move |aio, ctx, res| {
+                worker_callback(&aio, ctx, res, reuse)
+            })?;
+            ctx.recv(&aio)?;
+            Ok((aio, ctx))
+        })
+        .collect::<Result<Vec<_>, nng::Error>>()?;
+
+    let start = Instant::now();
+    let clients = (0..CLIENTS)
+        .map(|_| {
+            let url = url.to_string();
+            thread::spawn(move || client(&url))
+        })
+        .collect::<Vec<_>>();
+    for client in clients {
+        client.join().unwrap()?;
+    }
+    let elapsed = start.elapsed();
+
+    drop(workers);
+    server.close();
+
+    let rate = (CLIENTS * REQUESTS) as f64 / elapsed.as_secs_f64();
+    let label = if reuse { "reused" } else { "fresh" };
+    println!("{:>8} {:>12.0}", label, rate);
+
+    Ok(())
+}
+
+/// Sends requests to the server as fast as the replies come in.
+fn client(url: &str) -> Result<(), nng::Error> {
+    let socket = Socket::new(Protocol::Req0)?;
+    socket.dial(url)?;
+
+    let mut msg = Message::with_zeros(SIZE)?;
+    for _ in 0..REQUESTS {
+        socket.send(msg)?;
+        msg = socket.recv()?;
+
+        // The reply carries the request ID in its header, which must not be
+        // sent along with the next request.
+        msg.as_mut_header().clear();
+    }
+
+    Ok(())
+}
+
+/// Callback function for the server workers.
+fn worker_callback(aio: &Aio, ctx: &Context, res: AioResult, reuse: bool) {
+    match res {
+        // Keep the request while the (nonexistent) work is done.
+        AioResult::RecvOk(m) => {
+            aio.stash(m);
+            aio.sleep(Duration::from_millis(0)).unwrap();
+        }
+
+        // Build the reply, either from the stashed request or from scratch.
+        AioResult::SleepOk => {
+            let request = aio.take_stash().unwrap();
+            let reply = if reuse {
+                let mut msg = request;
+                msg.clear();
+                msg.push_back(&[0; SIZE]).unwrap();
+                msg
+            } else {
+                let mut msg = Message::new().unwrap();
+                msg.push_back(&[0; SIZE]).unwrap();
+                msg
+            };
+            ctx.send(aio, reply).unwrap();
+        }
+
+        AioResult::SendOk => ctx.recv(aio).unwrap(),
+
+        // The server is shutting down.
+        _ => {}
+    }
+}
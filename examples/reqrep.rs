@@ -4,11 +4,15 @@
 //! derived from the legacy nanomsg demonstration program. The program
 //! implements a simple RPC style service, which just returns the number of
 //! seconds since the Unix epoch.
-use std::time::SystemTime;
+//!
+//! The server shuts down cleanly on Ctrl-C: any request that has already been
+//! received is answered before the socket is closed.
+use std::time::{Duration, SystemTime};
 use std::{env, process};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use nng::{Message, Protocol, Socket};
+use nng::options::{Options, RecvTimeout};
+use nng::{Error, Message, MessageTransport, Protocol, ShutdownToken, Socket};
 
 /// Message representing a date request
 const DATE_REQUEST: u64 = 1;
@@ -49,29 +53,52 @@ fn client(url: &str) -> Result<(), nng::Error> {
 
 /// Run the server portion of the program.
 fn server(url: &str) -> Result<(), nng::Error> {
+    let token = ShutdownToken::new();
+    let handler_token = token.clone();
+    ctrlc::set_handler(move || handler_token.trigger()).expect("Failed to set Ctrl-C handler");
+
+    // Wake up periodically so we notice when it is time to shut down.
     let s = Socket::new(Protocol::Rep0)?;
+    s.set_opt::<RecvTimeout>(Some(Duration::from_millis(250)))?;
     s.listen(url)?;
 
-    loop {
-        println!("SERVER: WAITING FOR COMMAND");
-        let mut msg = s.recv()?;
-
-        let cmd = msg.as_slice().read_u64::<LittleEndian>().unwrap();
-        if cmd != DATE_REQUEST {
-            println!("SERVER: UNKNOWN COMMAND");
-            continue;
+    println!("SERVER: WAITING FOR COMMANDS");
+    while !token.is_shutdown() {
+        match serve_one(&s) {
+            Ok(()) | Err(Error::TimedOut) => {}
+            Err(e) => return Err(e),
         }
+    }
 
-        println!("SERVER: RECEIVED DATE REQUEST");
-        let rep = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("Current system time is before Unix epoch")
-            .as_secs();
+    // Requests are answered one at a time, so every request that was received
+    // has been answered by now.
+    println!("SERVER: SHUTTING DOWN");
+    s.close();
+    Ok(())
+}
 
-        msg.clear();
-        msg.write_u64::<LittleEndian>(rep).unwrap();
+/// Answers a single request received on the transport.
+///
+/// This only relies on `MessageTransport`, so it can be unit-tested with the
+/// `MockTransport` of the `testing` feature instead of a real socket.
+fn serve_one<T: MessageTransport>(transport: &T) -> Result<(), nng::Error> {
+    let mut msg = transport.recv()?;
 
-        println!("SERVER: SENDING {}", rep);
-        s.send(msg)?;
+    let cmd = msg.as_slice().read_u64::<LittleEndian>().unwrap();
+    if cmd != DATE_REQUEST {
+        println!("SERVER: UNKNOWN COMMAND");
+        return Ok(());
     }
+
+    println!("SERVER: RECEIVED DATE REQUEST");
+    let rep = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("Current system time is before Unix epoch")
+        .as_secs();
+
+    msg.clear();
+    msg.write_u64::<LittleEndian>(rep).unwrap();
+
+    println!("SERVER: SENDING {}", rep);
+    transport.send(msg).map_err(Error::from)
 }
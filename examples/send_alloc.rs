@@ -0,0 +1,109 @@
+//! A rough benchmark of the allocation cost of sending messages.
+//!
+//! NNG takes ownership of every message that is sent and frees it once it has
+//! been delivered, so tight send loops allocate a new message for every send.
+//! This compares three ways of doing that at various message sizes:
+//!
+//! * `slice` sends a byte slice, which is copied into a new message.
+//! * `clone` sends a clone of a prepared message.
+//! * `recycled` has the receiver hand every message back to the sender, which
+//!   refills and resends it, only allocating when none is available.
+//!
+//! Run it in release mode for meaningful numbers:
+//!
+//! ```text
+//! cargo run --release --example send_alloc tcp://127.0.0.1:5555
+//! ```
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Instant;
+use std::{env, thread};
+
+use nng::{Message, Protocol, Socket};
+
+/// Number of messages sent in every run.
+const MESSAGES: usize = 100_000;
+
+/// Message sizes to compare, in bytes.
+const SIZES: &[usize] = &[64, 4 * 1024, 64 * 1024];
+
+/// The ways of producing the messages that are compared.
+#[derive(Clone, Copy)]
+enum Mode {
+    Slice,
+    Clone,
+    Recycled,
+}
+
+/// Entry point of the application.
+fn main() -> Result<(), nng::Error> {
+    let url = env::args().nth(1).unwrap_or_else(|| "inproc://nng/send_alloc".to_string());
+
+    println!("{:>8} {:>10} {:>12}", "size", "mode", "messages/s");
+    for &size in SIZES {
+        for &(mode, label) in &[
+            (Mode::Slice, "slice"),
+            (Mode::Clone, "clone"),
+            (Mode::Recycled, "recycled"),
+        ] {
+            let rate = run(&url, size, mode)?;
+            println!("{:>8} {:>10} {:>12.0}", size, label, rate);
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends all of the messages in the given mode and returns the rate.
+fn run(url: &str, size: usize, mode: Mode) -> Result<f64, nng::Error> {
+    let pull = Socket::new(Protocol::Pull0)?;
+    pull.listen(url)?;
+    let push = Socket::new(Protocol::Push0)?;
+    push.dial(url)?;
+
+    // Only the recycling sender listens for the returned messages.
+    let (tx, rx) = mpsc::channel();
+    let recycle = if let Mode::Recycled = mode { Some(tx) } else { None };
+    let receiver = thread::spawn(move || receive(&pull, recycle));
+
+    let payload = vec![0xA5; size];
+    let template = Message::from_slice(&payload)?;
+
+    let start = Instant::now();
+    for _ in 0..MESSAGES {
+        let res = match mode {
+            Mode::Slice => push.send(&payload[..]),
+            Mode::Clone => push.send(template.clone()),
+            Mode::Recycled => push.send(refill(&rx, &payload)?),
+        };
+        res?;
+    }
+    receiver.join().unwrap()?;
+    let elapsed = start.elapsed();
+
+    Ok(MESSAGES as f64 / elapsed.as_secs_f64())
+}
+
+/// Takes a returned message, or a new one if there is none, and fills it.
+fn refill(returned: &Receiver<Message>, payload: &[u8]) -> Result<Message, nng::Error> {
+    match returned.try_recv() {
+        Ok(mut msg) => {
+            msg.clear();
+            msg.push_back(payload)?;
+            Ok(msg)
+        }
+        Err(_) => Message::from_slice(payload),
+    }
+}
+
+/// Receives all of the messages, handing them back to the sender if requested.
+fn receive(socket: &Socket, recycle: Option<Sender<Message>>) -> Result<(), nng::Error> {
+    for _ in 0..MESSAGES {
+        let msg = socket.recv()?;
+        if let Some(tx) = &recycle {
+            // The sender stops listening once it has sent everything.
+            let _ = tx.send(msg);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! A concurrent survey respondent example.
+//!
+//! This example shows how to write a respondent that answers many surveys at
+//! the same time using Contexts, in the same way the `async` example serves
+//! requests.
+//!
+//! The surveyor asks everyone connected to it for their name. Each respondent
+//! takes the given number of milliseconds to "think" before answering, so
+//! respondents slower than the survey time are not heard from. Because every
+//! worker has its own context, a slow answer to one surveyor does not hold up
+//! the answers to any of the others.
+//!
+//! Contexts keep track of the survey ID header themselves, so the response can
+//! be a brand new message. Only raw sockets have to copy the header over.
+use std::time::Duration;
+use std::{env, process};
+
+use nng::options::{protocol::survey::SurveyTime, Options};
+use nng::{Aio, AioGroup, AioResult, Context, Error, Message, Protocol, ShutdownToken, Socket};
+
+/// Number of surveys that a respondent can be answering at a given time.
+const PARALLEL: usize = 16;
+
+/// Entry point of the application.
+fn main() -> Result<(), nng::Error> {
+    let args: Vec<_> = env::args().collect();
+
+    match &args[..] {
+        [_, t, url, ms] if t == "surveyor" => surveyor(url, ms.parse().unwrap()),
+        [_, t, url, name, ms] if t == "respondent" => respondent(url, name, ms.parse().unwrap()),
+        _ => {
+            println!(
+                "Usage:\nsurvey surveyor <url> <survey ms>\n  or\nsurvey respondent <url> <name> <ms>"
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Run the surveyor portion of the program.
+fn surveyor(url: &str, ms: u64) -> Result<(), nng::Error> {
+    let s = Socket::new(Protocol::Surveyor0)?;
+    s.set_opt::<SurveyTime>(Some(Duration::from_millis(ms)))?;
+    s.listen(url)?;
+
+    loop {
+        // Surveys only go to the respondents that are connected when it is
+        // sent, so give them a chance to show up.
+        std::thread::sleep(Duration::from_secs(1));
+
+        println!("SURVEYOR: Asking for names");
+        s.send(Message::new()?)?;
+
+        // Keep collecting responses until the survey expires.
+        loop {
+            match s.recv() {
+                Ok(m) => println!("SURVEYOR: Heard from {}", String::from_utf8_lossy(&m)),
+                Err(Error::TimedOut) => break,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Run the respondent portion of the program.
+fn respondent(url: &str, name: &str, ms: u64) -> Result<(), nng::Error> {
+    // Trigger the shutdown when the user presses Ctrl-C.
+    let token = ShutdownToken::new();
+    let handler_token = token.clone();
+    ctrlc::set_handler(move || handler_token.trigger()).expect("Failed to set Ctrl-C handler");
+
+    let s = Socket::new(Protocol::Respondent0)?;
+
+    let name = name.to_owned();
+    let workers = AioGroup::new(&s, PARALLEL, move |aio, ctx, res| {
+        worker_callback(aio, ctx, res, &name, ms)
+    })?;
+
+    s.dial(url)?;
+    workers.start_recv()?;
+
+    // Surveys are best-effort, so there is no need to finish the ones that
+    // are in progress. Stopping the workers simply abandons them.
+    token.wait();
+    workers.shutdown();
+    s.close();
+
+    Ok(())
+}
+
+/// Callback function for workers.
+///
+/// The worker receives a survey, sleeps for the thinking time and then
+/// responds, before waiting for the next survey. The response must be sent
+/// before the next receive is started, otherwise the context forgets about
+/// the survey and the response is dropped.
+fn worker_callback(aio: Aio, ctx: &Context, res: AioResult, name: &str, ms: u64) {
+    let res = match res {
+        // We have a new survey to answer, so start thinking. The sleep is
+        // the only state the worker needs, everything else is in the context.
+        AioResult::RecvOk(_) => aio.sleep(Duration::from_millis(ms)),
+
+        // Done thinking, send the answer.
+        AioResult::SleepOk => {
+            let mut msg = Message::new().unwrap();
+            msg.push_back(name.as_bytes()).unwrap();
+            ctx.send(&aio, msg).map_err(|e| e.error)
+        }
+
+        // Whether or not the answer made it in time, wait for the next survey.
+        AioResult::SendOk | AioResult::SendErr(_, Error::IncorrectState) => ctx.recv(&aio),
+
+        // Everything else means the worker is being stopped.
+        AioResult::SendErr(_, e)
+        | AioResult::SendErrLost(e)
+        | AioResult::RecvErr(e)
+        | AioResult::SleepErr(e) => Err(e),
+        res => panic!("Unexpected result: {:?}", res),
+    };
+
+    match res {
+        Ok(()) | Err(Error::Closed) | Err(Error::Canceled) => {}
+        Err(e) => panic!("Error: {}", e),
+    }
+}
@@ -1,12 +1,14 @@
 use std::{
-	fmt,
+	fmt, mem,
 	net::{SocketAddrV4, SocketAddrV6},
 	os::raw::c_char,
 	path::PathBuf,
+	slice,
 };
 
 /// Represents the addresses used by the underlying transports.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
 pub enum SocketAddr
 {
 	/// An address associated with intra-process communication.
@@ -25,6 +27,12 @@ pub enum SocketAddr
 	/// Used to represent a ZeroTier address.
 	ZeroTier(SocketAddrZt),
 
+	/// An address of a family that this crate does not know about.
+	///
+	/// This preserves the raw address so that it can still be inspected if
+	/// NNG adds new transports.
+	Unknown(RawSocketAddr),
+
 	/// An invalid address type.
 	#[doc(hidden)]
 	Unspecified,
@@ -44,6 +52,7 @@ impl fmt::Display for SocketAddr
 			SocketAddr::Inet(s) => write!(f, "tcp://{}", s),
 			SocketAddr::Inet6(s) => write!(f, "tcp://{}", s),
 			SocketAddr::ZeroTier(s) => write!(f, "zt://{}", s),
+			SocketAddr::Unknown(s) => write!(f, "unknown({})", s.family()),
 			SocketAddr::Unspecified => write!(f, "unspecified"),
 		}
 	}
@@ -64,22 +73,58 @@ impl From<nng_sys::nng_sockaddr> for SocketAddr
 				},
 				Ok(nng_sys::nng_sockaddr_family::NNG_AF_INET) => {
 					let v4_addr = u32::from_be(addr.s_in.sa_addr).into();
-					SocketAddr::Inet(SocketAddrV4::new(v4_addr, addr.s_in.sa_port))
+					SocketAddr::Inet(SocketAddrV4::new(v4_addr, u16::from_be(addr.s_in.sa_port)))
 				},
 				Ok(nng_sys::nng_sockaddr_family::NNG_AF_INET6) => {
 					let v6_addr = addr.s_in6.sa_addr.into();
-					let port = addr.s_in6.sa_port;
+					let port = u16::from_be(addr.s_in6.sa_port);
 					SocketAddr::Inet6(SocketAddrV6::new(v6_addr, port, 0, 0))
 				},
 				Ok(nng_sys::nng_sockaddr_family::NNG_AF_ZT) => {
 					SocketAddr::ZeroTier(SocketAddrZt::new(&addr.s_zt))
 				},
-				Ok(nng_sys::nng_sockaddr_family::NNG_AF_UNSPEC) | Err(_) => SocketAddr::Unspecified,
+				Ok(nng_sys::nng_sockaddr_family::NNG_AF_UNSPEC) => SocketAddr::Unspecified,
+				Err(_) => SocketAddr::Unknown(RawSocketAddr::new(&addr)),
 			}
 		}
 	}
 }
 
+/// The raw contents of a socket address of an unknown family.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RawSocketAddr
+{
+	/// The family code of the address.
+	family: u16,
+
+	/// The bytes of the whole `nng_sockaddr`, including the family.
+	bytes: Vec<u8>,
+}
+impl RawSocketAddr
+{
+	/// Copies the raw bytes out of an `nng_sockaddr`.
+	fn new(addr: &nng_sys::nng_sockaddr) -> RawSocketAddr
+	{
+		// Every variant of the union is plain old data, so viewing it as bytes is fine.
+		let bytes = unsafe {
+			let ptr = addr as *const nng_sys::nng_sockaddr as *const u8;
+			slice::from_raw_parts(ptr, mem::size_of::<nng_sys::nng_sockaddr>()).to_vec()
+		};
+
+		RawSocketAddr { family: unsafe { addr.s_family }, bytes }
+	}
+
+	/// Returns the NNG family code of the address.
+	pub const fn family(&self) -> u16 { self.family }
+
+	/// Returns the bytes of the underlying `nng_sockaddr`.
+	///
+	/// This is the entire union as provided by NNG, starting with the family
+	/// code in native byte order. The layout of the rest depends on the
+	/// family.
+	pub fn as_bytes(&self) -> &[u8] { &self.bytes }
+}
+
 /// A ZeroTier socket address.
 #[doc(hidden)]
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -1,24 +1,31 @@
 //! Asynchonous I/O operaions.
 use std::{
+	cell::Cell,
+	fmt,
 	hash::{Hash, Hasher},
 	os::raw::c_void,
 	panic::catch_unwind,
 	ptr::{self, NonNull},
 	sync::{
-		atomic::{AtomicPtr, AtomicUsize, Ordering},
-		Arc,
+		atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering},
+		mpsc, Arc, Condvar, Mutex, OnceLock, PoisonError,
 	},
-	time::Duration,
+	thread,
+	time::{Duration, Instant},
 };
 
 use crate::{
 	ctx::Context,
-	error::{Error, Result, SendResult},
+	error::{Error, Result, SendError, SendResult},
 	message::Message,
+	panic_policy::{self, PanicSource},
 	socket::Socket,
-	util::{duration_to_nng, validate_ptr},
+	time::to_nng_ms,
+	util::validate_ptr,
 };
-use log::error;
+
+#[cfg(feature = "async-api")]
+use crate::future::PreReceived;
 
 /// An asynchronous I/O context.
 ///
@@ -53,10 +60,11 @@ use log::error;
 ///     let workers: Vec<_> = (0..WORKERS)
 ///         .map(|_| {
 ///             let ctx = Context::new(&server)?;
-///             let ctx_clone = ctx.clone();
 ///
 ///             // An actual program should have better error handling.
-///             let aio = Aio::new(move |aio, res| callback(&aio, &ctx_clone, res).unwrap())?;
+///             let aio = Aio::for_context(&ctx, |aio, ctx, res| {
+///                 callback(&aio, ctx, res).unwrap()
+///             })?;
 ///             Ok((aio, ctx))
 ///         })
 ///         .collect::<Result<_>>()?;
@@ -79,16 +87,20 @@ use log::error;
 ///         // We successfully send the reply, wait for a new request.
 ///         AioResult::SendOk => ctx.recv(aio),
 ///
-///         // We successfully received a message.
+///         // We successfully received a message. Keep it around so that it
+///         // can be reused for the reply.
 ///         AioResult::RecvOk(m) => {
 ///             let ms = m.as_slice().read_u64::<LittleEndian>().unwrap();
+///             aio.stash(m);
 ///             aio.sleep(Duration::from_millis(ms))
 ///         },
 ///
-///         // We successfully slept.
+///         // We successfully slept. Reusing the request `Message` avoids an
+///         // allocation.
 ///         AioResult::SleepOk => {
-///             // We could have hung on to the request `Message` to avoid an
-///             let _ = ctx.send(aio, Message::new()?)?;
+///             let mut msg = aio.take_stash().map_or_else(Message::new, Ok)?;
+///             msg.clear();
+///             let _ = ctx.send(aio, msg)?;
 ///             Ok(())
 ///         },
 ///
@@ -130,7 +142,11 @@ impl Aio
 	///
 	/// The provided callback will be called on every single I/O event,
 	/// successful or not. It is possible that the callback will be entered
-	/// multiple times simultaneously.
+	/// multiple times simultaneously: the AIO is free for a new operation as
+	/// soon as the callback is entered, so an operation started from within
+	/// the callback can complete and call it again on another thread before
+	/// the first call has returned. Calls are never nested on the same thread.
+	/// Use `Aio::new_mut` for callbacks that need mutable state.
 	///
 	/// ## Panicking
 	///
@@ -140,7 +156,40 @@ impl Aio
 	/// produce the abort regardless of which version of Rustc is being used.
 	///
 	/// The user is responsible for either having a callback that never panics
-	/// or catching and handling the panic within the callback.
+	/// or catching and handling the panic within the callback. Test harnesses
+	/// can capture the panics instead with `panic_policy::set`.
+	///
+	/// ## Dropping
+	///
+	/// The callback is given a handle to the AIO, which may turn out to be the
+	/// last one if everything else dropped theirs while it was running. The AIO
+	/// and the callback are then freed by a background thread, shared by all
+	/// AIOs, once the callback has returned, as NNG cannot free an AIO from
+	/// within its own callback.
+	///
+	/// ```
+	/// use std::{sync::{mpsc, Mutex}, thread, time::Duration};
+	/// use nng::Aio;
+	///
+	/// let (started_tx, started_rx) = mpsc::channel();
+	/// let (freed_tx, freed_rx) = mpsc::channel::<()>();
+	/// let started_tx = Mutex::new(started_tx);
+	///
+	/// let aio = Aio::new(move |_, _| {
+	///     // The sender is dropped when the callback is freed.
+	///     let _ = &freed_tx;
+	///     started_tx.lock().unwrap().send(()).unwrap();
+	///     thread::sleep(Duration::from_millis(50));
+	/// })?;
+	/// aio.sleep(Duration::from_millis(0))?;
+	///
+	/// // Leave the callback with the last handle.
+	/// started_rx.recv().unwrap();
+	/// drop(aio);
+	///
+	/// assert!(freed_rx.recv().is_err());
+	/// # Ok::<(), nng::Error>(())
+	/// ```
 	pub fn new<F>(callback: F) -> Result<Self>
 	where
 		F: Fn(Aio, AioResult) + Sync + Send + 'static,
@@ -151,7 +200,20 @@ impl Aio
 		let inner = Arc::new(Inner {
 			handle: AtomicPtr::new(ptr::null_mut()),
 			state:  AtomicUsize::new(State::Inactive as usize),
+			stopped: AtomicBool::new(false),
+			recv_closed: AtomicBool::new(false),
+			count: AtomicUsize::new(0),
+			send_len: AtomicUsize::new(0),
 			callback: AtomicPtr::new(ptr::null_mut()),
+			result: Mutex::new(None),
+			stash: Mutex::new(None),
+			recv_filter: Mutex::new(None),
+			recv_target: AtomicU64::new(0),
+			#[cfg(feature = "async-api")]
+			recv_kept: Mutex::new(None),
+			owner: AtomicU64::new(0),
+			waiting: AtomicUsize::new(0),
+			waiters: Mutex::new(Vec::new()),
 		});
 
 		// Now, we create the weak reference to the inner bits that will be stored
@@ -160,30 +222,45 @@ impl Aio
 
 		// Wrap the user's callback in our own state-keeping logic
 		let bounce = move || {
+			// The guard has to outlive every handle to the AIO in here, as dropping the
+			// last one needs to know that it is happening inside of the callback.
+			let _guard = CallbackGuard::new();
+
 			// If we can't upgrade the pointer, then we are in the middle of dropping,
 			// so we can't do anything except return.
 			let cb_aio = match weak.upgrade() {
 				Some(i) => Aio { inner: i },
 				None => return,
 			};
+			CALLBACK_AIO.with(|c| c.set(cb_aio.inner.handle.load(Ordering::Relaxed)));
 
 			let res = unsafe {
 				let state = cb_aio.inner.state.load(Ordering::Acquire).into();
 				let aiop = cb_aio.inner.handle.load(Ordering::Relaxed);
 				let rv = nng_sys::nng_aio_result(aiop) as u32;
+				let count = nng_sys::nng_aio_count(aiop);
 
 				let res = match (state, rv) {
 					(State::Sending, 0) => AioResult::SendOk,
+					// Panicking here would abort the whole process, so we can't trust NNG to
+					// always hand the message back, especially while the socket is closing.
 					(State::Sending, e) => {
 						let msgp = nng_sys::nng_aio_get_msg(aiop);
-						let msg = Message::from_ptr(NonNull::new(msgp).unwrap());
-						AioResult::SendErr(msg, Error::from_code(e))
+						match NonNull::new(msgp) {
+							Some(p) => AioResult::SendErr(Message::from_ptr(p), Error::from_code(e)),
+							None => AioResult::SendErrLost(Error::from_code(e)),
+						}
 					},
 
 					(State::Receiving, 0) => {
 						let msgp = nng_sys::nng_aio_get_msg(aiop);
-						let msg = Message::from_ptr(NonNull::new(msgp).unwrap());
-						AioResult::RecvOk(msg)
+						match NonNull::new(msgp) {
+							Some(p) => AioResult::RecvOk(Message::from_ptr(p)),
+							None => {
+								log_error!("NNG reported a successful receive without a message");
+								AioResult::RecvErr(Error::Internal)
+							},
+						}
 					},
 					(State::Receiving, e) => AioResult::RecvErr(Error::from_code(e)),
 
@@ -194,10 +271,50 @@ impl Aio
 					(State::Inactive, _) => unreachable!(),
 				};
 
-				cb_aio.inner.state.store(State::Inactive as usize, Ordering::Release);
+				trace_event!(tracing::Level::TRACE, op = ?state, rv, "aio.complete");
+
+				// Messages rejected by the receive filter never make it to the callback.
+				// The receive has already been started again, so the AIO has to stay in
+				// the `Receiving` state.
+				if let AioResult::RecvOk(ref m) = res {
+					if cb_aio.filter_recv(aiop, m) {
+						return;
+					}
+				}
+
+				// Not every protocol reports the size of the messages it sends or receives,
+				// so fill in the size of the message when NNG doesn't.
+				let count = match res {
+					AioResult::SendOk if count == 0 => cb_aio.inner.send_len.load(Ordering::Relaxed),
+					AioResult::RecvOk(ref m) if count == 0 => m.len(),
+					_ => count,
+				};
+				cb_aio.inner.count.store(count, Ordering::Relaxed);
+
+				cb_aio.inner.state.store(State::Inactive as usize, Ordering::SeqCst);
 				res
 			};
-			callback(cb_aio, res)
+
+			// Only hold on to the AIO past the callback if someone is waiting for it in
+			// `Aio::wait_any` or `Aio::wait_all`. A wait that starts while the callback is
+			// running sees the `Inactive` state instead.
+			let waited = if cb_aio.inner.waiting.load(Ordering::SeqCst) != 0 {
+				Some(cb_aio.clone())
+			}
+			else {
+				None
+			};
+
+			match panic_policy::handler() {
+				None => callback(cb_aio, res),
+				Some(h) => {
+					let aio = cb_aio.clone();
+					panic_policy::run(Some(&*h), || callback(cb_aio, res), || PanicSource::Aio(aio));
+				},
+			}
+			if let Some(aio) = waited {
+				aio.notify_waiters();
+			}
 		};
 
 		// There are ways to avoid the double boxing, but unfortunately storing
@@ -221,7 +338,7 @@ impl Aio
 		// This might leak memory (I'm not sure, depends on what NNG did), but a small
 		// amount of lost memory is better than a segfaulting Rust library.
 		if rv != 0 && !aio.is_null() {
-			error!("NNG returned a non-null pointer from a failed function");
+			log_error!("NNG returned a non-null pointer from a failed function");
 			return Err(Error::Unknown(0));
 		}
 		validate_ptr(rv, aio)?;
@@ -231,6 +348,387 @@ impl Aio
 		Ok(Self { inner })
 	}
 
+	/// Creates a new asynchronous I/O handle with a callback that can mutate
+	/// its own state.
+	///
+	/// This is the same as `Aio::new`, except that calls to the callback are
+	/// serialized with a lock, so it never runs concurrently with itself and
+	/// can keep mutable state, such as counters or an `mpsc::Sender`, without
+	/// wrapping it in a `Mutex` or `RefCell`. An operation that completes while
+	/// the callback is still running waits for it to return before its own
+	/// call starts, which holds up one of the NNG threads for that long. This
+	/// is one more reason to keep the callback short.
+	///
+	/// The same panicking rules apply to the callback as to `Aio::new`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{sync::mpsc, time::Duration};
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/aio/new_mut";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	///
+	/// // Report the running total of the bytes received every three messages.
+	/// let (tx, rx) = mpsc::channel();
+	/// let (mut total, mut count) = (0, 0);
+	/// let cb_pull = pull.clone();
+	/// let aio = Aio::new_mut(move |aio, res| match res {
+	///     AioResult::RecvOk(m) => {
+	///         total += m.len();
+	///         count += 1;
+	///         if count % 3 == 0 {
+	///             tx.send(total).unwrap();
+	///         }
+	///         cb_pull.recv_async(&aio).unwrap();
+	///     },
+	///     AioResult::RecvErr(Error::Closed) => {},
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// })?;
+	/// pull.recv_async(&aio)?;
+	///
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	/// for i in 1..=6 {
+	///     push.send(Message::with_zeros(i)?)?;
+	/// }
+	///
+	/// let timeout = Duration::from_secs(1);
+	/// assert_eq!(rx.recv_timeout(timeout).unwrap(), 1 + 2 + 3);
+	/// assert_eq!(rx.recv_timeout(timeout).unwrap(), 1 + 2 + 3 + 4 + 5 + 6);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn new_mut<F>(callback: F) -> Result<Self>
+	where
+		F: FnMut(Aio, AioResult) + Send + 'static,
+	{
+		// A panic captured by the panic policy poisons the lock, but the callback is
+		// still expected to run for the following operations.
+		let callback = Mutex::new(callback);
+		Aio::new(move |aio, res| (callback.lock().unwrap_or_else(PoisonError::into_inner))(aio, res))
+	}
+
+	/// Creates a new asynchronous I/O handle for operations on a context.
+	///
+	/// This is the same as `Aio::new`, except that the callback is also handed
+	/// the context, so it doesn't have to be cloned into the closure. This is
+	/// the usual shape of a worker that receives a request on the context,
+	/// replies on it, and starts receiving again. The AIO keeps a handle to the
+	/// context, so the context stays open at least as long as the AIO.
+	///
+	/// Nothing prevents the AIO from being used with other contexts or
+	/// sockets, but the callback is always given this context. Use
+	/// `Aio::bind_to` to catch accidental uses with anything else.
+	///
+	/// The same panicking rules apply to the callback as to `Aio::new`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/aio/for_context";
+	///
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// server.listen(ADDRESS)?;
+	///
+	/// // Echo every request back to the client.
+	/// let ctx = Context::new(&server)?;
+	/// let aio = Aio::for_context(&ctx, |aio, ctx, res| match res {
+	///     AioResult::RecvOk(m) => ctx.send(&aio, m).unwrap(),
+	///     AioResult::SendOk => ctx.recv(&aio).unwrap(),
+	///     _ => {},
+	/// })?;
+	/// ctx.recv(&aio)?;
+	///
+	/// let client = Socket::new(Protocol::Req0)?;
+	/// client.dial(ADDRESS)?;
+	/// for _ in 0..3 {
+	///     client.send(&b"echo"[..])?;
+	///     assert_eq!(&client.recv()?[..], b"echo");
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn for_context<F>(ctx: &Context, callback: F) -> Result<Self>
+	where
+		F: Fn(Aio, &Context, AioResult) + Sync + Send + 'static,
+	{
+		let ctx = ctx.clone();
+		Aio::new(move |aio, res| callback(aio, &ctx, res))
+	}
+
+	/// Creates a new asynchronous I/O handle without a callback.
+	///
+	/// Instead of being passed to a callback, the result of each operation is
+	/// kept inside of the AIO, where it can be read with `Aio::result` once
+	/// `Aio::wait` returns. Together with `Aio::set_timeout`, this allows
+	/// performing a single operation with a timeout from synchronous code
+	/// without touching the timeouts of the socket.
+	///
+	/// Nothing is notified when an operation completes, so this cannot be used
+	/// for event-driven designs, where the next operation is started from the
+	/// completion of the previous one. Use `Aio::new` for those.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::*;
+	///
+	/// let socket = Socket::new(Protocol::Pull0)?;
+	/// socket.listen("inproc://nng/aio/new_manual")?;
+	///
+	/// let aio = Aio::new_manual()?;
+	/// aio.set_timeout(Some(Duration::from_millis(50)))?;
+	/// socket.recv_async(&aio)?;
+	///
+	/// // Nobody is sending anything, so the receive times out.
+	/// aio.wait();
+	/// match aio.result() {
+	///     Some(AioResult::RecvErr(e)) => assert_eq!(e, Error::TimedOut),
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// }
+	/// assert!(aio.result().is_none());
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn new_manual() -> Result<Self>
+	{
+		Aio::new(|aio, res| *aio.inner.result.lock().unwrap() = Some(res))
+	}
+
+	/// Takes the result of the last completed operation of a manual AIO.
+	///
+	/// This returns `None` if no operation has completed since the result was
+	/// last taken, including while an operation is still running. Call
+	/// `Aio::wait` first to wait for the completion. Starting a new operation
+	/// before taking the result of the previous one throws that result away
+	/// once the new one completes.
+	///
+	/// AIOs created with `Aio::new` pass the results to their callback, so this
+	/// always returns `None` for them.
+	pub fn result(&self) -> Option<AioResult> { self.inner.result.lock().unwrap().take() }
+
+	/// Returns the number of bytes transferred by the last completed operation.
+	///
+	/// The count is recorded before the callback is called and stays the same
+	/// until the next operation completes, so it can be read from the callback
+	/// without racing with any operation the callback starts. For sends and
+	/// receives of messages, this is the length of the message body. Sleeps and
+	/// failed operations usually report zero.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "tcp://127.0.0.1:27183";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// let aio = Aio::new_manual()?;
+	/// push.send_async(&aio, Message::with_zeros(1000)?)?;
+	/// aio.wait();
+	/// match aio.result() {
+	///     Some(AioResult::SendOk) => assert_eq!(aio.last_count(), 1000),
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// }
+	///
+	/// pull.recv_async(&aio)?;
+	/// aio.wait();
+	/// match aio.result() {
+	///     Some(AioResult::RecvOk(_)) => assert_eq!(aio.last_count(), 1000),
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn last_count(&self) -> usize { self.inner.count.load(Ordering::Relaxed) }
+
+	/// Keeps a message with the AIO until it is taken back with
+	/// `Aio::take_stash`.
+	///
+	/// This lets a callback hold on to a message between operations without
+	/// any state of its own. The most common use is a server that has to do
+	/// some work before replying: by stashing the request when it is received,
+	/// the request can be cleared and reused as the reply instead of
+	/// allocating a new message.
+	///
+	/// Returns the message that was stashed before, if any. The stash is
+	/// shared by all handles to the AIO and a stashed message is dropped along
+	/// with the AIO.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{io::Write, time::Duration};
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/aio/stash";
+	///
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// server.listen(ADDRESS)?;
+	/// let ctx = Context::new(&server)?;
+	///
+	/// let aio = Aio::for_context(&ctx, |aio, ctx, res| match res {
+	///     // Keep the request around while "working".
+	///     AioResult::RecvOk(m) => {
+	///         assert!(aio.stash(m).is_none());
+	///         aio.sleep(Duration::from_millis(10)).unwrap();
+	///     },
+	///
+	///     // Turn the request into the reply.
+	///     AioResult::SleepOk => {
+	///         let mut msg = aio.take_stash().unwrap();
+	///         let name = String::from_utf8_lossy(&msg).into_owned();
+	///         msg.clear();
+	///         write!(msg, "Hello, {}!", name).unwrap();
+	///         ctx.send(&aio, msg).unwrap();
+	///     },
+	///
+	///     AioResult::SendOk => {},
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// })?;
+	/// ctx.recv(&aio)?;
+	///
+	/// let client = Socket::new(Protocol::Req0)?;
+	/// client.dial(ADDRESS)?;
+	/// client.send(&b"Ferris"[..])?;
+	/// assert_eq!(&client.recv()?[..], b"Hello, Ferris!");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn stash(&self, msg: Message) -> Option<Message>
+	{
+		self.inner.stash.lock().unwrap().replace(msg)
+	}
+
+	/// Takes back the message kept with `Aio::stash`, if any.
+	pub fn take_stash(&self) -> Option<Message> { self.inner.stash.lock().unwrap().take() }
+
+	/// Sets a filter that decides which received messages reach the callback.
+	///
+	/// The filter is called with every message successfully received by this
+	/// AIO. If it returns `FilterDecision::Drop`, the message is freed and the
+	/// receive is started again on the same socket or context without calling
+	/// the callback. This can be used for filtering that NNG doesn't provide,
+	/// such as matching the end of a topic, without waking up the rest of the
+	/// application for messages it doesn't want.
+	///
+	/// Like the callback, the filter runs on an NNG thread. It must not block
+	/// or perform any long-running work, and a panic inside of it aborts the
+	/// program. Each restarted receive is a new operation, so a timeout set
+	/// with `Aio::set_timeout` starts over after every dropped message.
+	///
+	/// The filter replaces any previous one and takes effect starting with the
+	/// next completed receive. Once the AIO has been stopped, messages are no
+	/// longer filtered so that the final receive still reaches the callback.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{sync::{mpsc, Mutex}, time::Duration};
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/aio/set_recv_filter";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	///
+	/// let (tx, rx) = mpsc::channel();
+	/// let tx = Mutex::new(tx);
+	/// let cb_pull = pull.clone();
+	/// let aio = Aio::new(move |aio, res| match res {
+	///     AioResult::RecvOk(m) => {
+	///         tx.lock().unwrap().send(m[0]).unwrap();
+	///         cb_pull.recv_async(&aio).unwrap();
+	///     },
+	///     AioResult::RecvErr(Error::Closed) => {},
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// })?;
+	///
+	/// // Only let the even-numbered messages through.
+	/// aio.set_recv_filter(|m| {
+	///     if m[0] % 2 == 0 { FilterDecision::Deliver } else { FilterDecision::Drop }
+	/// });
+	/// pull.recv_async(&aio)?;
+	///
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	/// for i in 0..10u8 {
+	///     push.send(&[i][..])?;
+	/// }
+	///
+	/// let timeout = Duration::from_secs(1);
+	/// let received = (0..5).map(|_| rx.recv_timeout(timeout).unwrap()).collect::<Vec<_>>();
+	/// assert_eq!(received, [0, 2, 4, 6, 8]);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn set_recv_filter<F>(&self, filter: F)
+	where
+		F: Fn(&Message) -> FilterDecision + Sync + Send + 'static,
+	{
+		let filter = RecvFilter(Box::new(filter));
+		*self.inner.recv_filter.lock().unwrap() = Some(Arc::new(filter));
+	}
+
+	/// Removes the filter set with `Aio::set_recv_filter`.
+	///
+	/// All received messages are delivered to the callback again, starting
+	/// with the next completed receive.
+	pub fn clear_recv_filter(&self) { *self.inner.recv_filter.lock().unwrap() = None; }
+
+	/// Binds the AIO to a single socket or context.
+	///
+	/// Once bound, sends and receives started on any other socket or context
+	/// fail with `Error::AioOwnershipViolation`, which names both objects,
+	/// instead of interfering with whatever the owner is doing with the AIO.
+	/// This makes it easier to track down an AIO that has accidentally been
+	/// shared between unrelated components. Sleeps are always allowed and
+	/// AIOs that were never bound accept any socket or context.
+	///
+	/// Binding again replaces the previous owner.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::*;
+	///
+	/// let a = Socket::new(Protocol::Rep0)?;
+	/// let b = Socket::new(Protocol::Rep0)?;
+	/// let ctx = Context::new(&a)?;
+	///
+	/// let aio = Aio::new(|_, _| {})?;
+	/// assert_eq!(aio.owner(), None);
+	/// aio.bind_to(&ctx);
+	/// assert_eq!(aio.owner(), Some(OwnerTag::from(&ctx)));
+	///
+	/// match b.recv_async(&aio) {
+	///     Err(Error::AioOwnershipViolation { expected, actual }) => {
+	///         assert_eq!(expected, OwnerTag::from(&ctx));
+	///         assert_eq!(actual, OwnerTag::from(&b));
+	///     },
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// }
+	///
+	/// // The owner itself can still use it.
+	/// ctx.recv(&aio)?;
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn bind_to<T: Into<OwnerTag>>(&self, owner: T)
+	{
+		self.inner.owner.store(owner.into().pack(), Ordering::Release);
+	}
+
+	/// Returns the socket or context that the AIO is bound to, if any.
+	pub fn owner(&self) -> Option<OwnerTag>
+	{
+		OwnerTag::unpack(self.inner.owner.load(Ordering::Acquire))
+	}
+
 	/// Set the timeout of asynchronous operations.
 	///
 	/// This causes a timer to be started when the operation is actually
@@ -245,6 +743,24 @@ impl Aio
 	/// It is only valid to try and set this when no operations are active.
 	pub fn set_timeout(&self, dur: Option<Duration>) -> Result<()>
 	{
+		self.set_timeout_ms(to_nng_ms(dur))
+	}
+
+	/// Makes operations use the timeout configured on the socket or context.
+	///
+	/// It is only valid to try and set this when no operations are active.
+	pub(crate) fn use_default_timeout(&self) -> Result<()>
+	{
+		self.set_timeout_ms(nng_sys::NNG_DURATION_DEFAULT)
+	}
+
+	/// Sets the timeout of asynchronous operations in NNG's representation.
+	fn set_timeout_ms(&self, ms: nng_sys::nng_duration) -> Result<()>
+	{
+		if self.inner.stopped.load(Ordering::Acquire) {
+			return Err(Error::Closed);
+		}
+
 		// We need to check that no operations are happening and then prevent them from
 		// happening while we set the timeout. Any state that isn't `Inactive` will do
 		// so the choice is arbitrary. That being said, `Sleeping` feels the most
@@ -254,13 +770,15 @@ impl Aio
 		let old_state = self.inner.state.compare_and_swap(inactive, sleeping, Ordering::Acquire);
 
 		if old_state == inactive {
-			let ms = duration_to_nng(dur);
 			let aiop = self.inner.handle.load(Ordering::Relaxed);
 			unsafe {
 				nng_sys::nng_aio_set_timeout(aiop, ms);
 			}
 
-			self.inner.state.store(inactive, Ordering::Release);
+			self.inner.state.store(inactive, Ordering::SeqCst);
+			if self.inner.waiting.load(Ordering::SeqCst) != 0 {
+				self.notify_waiters();
+			}
 			Ok(())
 		}
 		else {
@@ -280,12 +798,17 @@ impl Aio
 	/// operation in progress, this function will return `Error::TryAgain`.
 	pub fn sleep(&self, dur: Duration) -> Result<()>
 	{
+		if self.inner.stopped.load(Ordering::Acquire) {
+			return Err(Error::Closed);
+		}
+
 		let sleeping = State::Sleeping as usize;
 		let inactive = State::Inactive as usize;
 		let old_state = self.inner.state.compare_and_swap(inactive, sleeping, Ordering::AcqRel);
 
 		if old_state == inactive {
-			let ms = duration_to_nng(Some(dur));
+			let ms = to_nng_ms(Some(dur));
+			trace_event!(tracing::Level::TRACE, op = ?State::Sleeping, "aio.start");
 			let aiop = self.inner.handle.load(Ordering::Relaxed);
 			unsafe {
 				nng_sys::nng_sleep_aio(ms, aiop);
@@ -311,6 +834,93 @@ impl Aio
 		}
 	}
 
+	/// Blocks the current thread until any of the AIOs has no operation
+	/// running, returning its index.
+	///
+	/// An AIO that is idle when this is called is returned right away, as is
+	/// an AIO whose operation completes while waiting, even if its callback
+	/// starts a new operation. If several AIOs qualify, the lowest index is
+	/// returned. Returns `None` if the timeout expires first, or right away
+	/// if there are no AIOs. A timeout of `None` waits forever.
+	///
+	/// Waiting costs the AIOs nothing while no thread is waiting on them. The
+	/// callback of an AIO that was already idle may still be running when its
+	/// index is returned, so use `Aio::wait` before reading its
+	/// `Aio::result`. This function should **not** be called from within a
+	/// completion callback.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::*;
+	///
+	/// let aios = (0..8).map(|_| Aio::new_manual()).collect::<Result<Vec<_>>>()?;
+	/// for (i, aio) in aios.iter().enumerate() {
+	///     let ms = if i == 5 { 10 } else { 5_000 };
+	///     aio.sleep(Duration::from_millis(ms))?;
+	/// }
+	///
+	/// let refs: Vec<&Aio> = aios.iter().collect();
+	/// assert_eq!(Aio::wait_any(&refs, Some(Duration::from_secs(1))), Some(5));
+	///
+	/// for aio in &aios {
+	///     aio.cancel();
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn wait_any(aios: &[&Aio], timeout: Option<Duration>) -> Option<usize>
+	{
+		GroupWait::run(aios, timeout, |done| done.iter().position(|&d| d))
+	}
+
+	/// Blocks the current thread until every one of the AIOs has no operation
+	/// running, returning `false` if the timeout expired first.
+	///
+	/// Each AIO counts as done once it is idle or once the operation that was
+	/// running when this was called completes, so callbacks that keep starting
+	/// new operations do not stall the wait. This is meant for waiting out a
+	/// set of workers after canceling or stopping them. A timeout of `None`
+	/// waits forever.
+	///
+	/// The same caveats apply as to `Aio::wait_any`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::*;
+	///
+	/// let aios = (1..=4).map(|_| Aio::new_manual()).collect::<Result<Vec<_>>>()?;
+	/// for (i, aio) in aios.iter().enumerate() {
+	///     aio.sleep(Duration::from_millis(100 * i as u64))?;
+	/// }
+	///
+	/// // The longest sleep outlasts the timeout.
+	/// let refs: Vec<&Aio> = aios.iter().collect();
+	/// assert!(!Aio::wait_all(&refs, Some(Duration::from_millis(150))));
+	/// assert!(Aio::wait_all(&refs, None));
+	///
+	/// // No wakeup is missed while operations complete at random.
+	/// let mut seed = 0x2545_F491_u32;
+	/// for _ in 0..200 {
+	///     for aio in &aios {
+	///         seed ^= seed << 13;
+	///         seed ^= seed >> 17;
+	///         seed ^= seed << 5;
+	///         aio.sleep(Duration::from_micros(u64::from(seed % 2_000)))?;
+	///     }
+	///     assert!(Aio::wait_all(&refs, Some(Duration::from_secs(5))));
+	///     assert!(Aio::wait_any(&refs, Some(Duration::from_secs(5))).is_some());
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn wait_all(aios: &[&Aio], timeout: Option<Duration>) -> bool
+	{
+		GroupWait::run(aios, timeout, |done| if done.iter().all(|&d| d) { Some(()) } else { None })
+			.is_some()
+	}
+
 	/// Cancel the currently running I/O operation.
 	pub fn cancel(&self)
 	{
@@ -319,37 +929,118 @@ impl Aio
 		}
 	}
 
+	/// Stops the AIO, canceling the current operation and preventing any more
+	/// from starting.
+	///
+	/// This blocks until the callback for the current operation, if any, has
+	/// finished. Afterwards, every attempt to start an operation on this AIO
+	/// (with this or any other handle) returns `Error::Closed`, which means a
+	/// callback that restarts itself will stop doing so, provided it handles
+	/// that error instead of panicking. Stopping an AIO cannot be undone.
+	///
+	/// This function should **not** be called from within the completion
+	/// callback.
+	pub fn stop(&self)
+	{
+		self.inner.stopped.store(true, Ordering::Release);
+		unsafe {
+			nng_sys::nng_aio_stop(self.inner.handle.load(Ordering::Relaxed));
+		}
+	}
+
+	/// Makes every receive started from now on fail with `Error::Closed`,
+	/// while sends and sleeps still work.
+	///
+	/// This is how `AioGroup::drain` stops workers from accepting requests
+	/// without cutting off their replies. It cannot be undone.
+	pub(crate) fn close_recv(&self) { self.inner.recv_closed.store(true, Ordering::Release); }
+
+	/// Returns whether starting a receive fails with `Error::Closed`.
+	fn recv_is_closed(&self) -> bool
+	{
+		self.inner.stopped.load(Ordering::Acquire) || self.inner.recv_closed.load(Ordering::Acquire)
+	}
+
+	/// Returns whether a receive operation is running.
+	pub(crate) fn is_receiving(&self) -> bool
+	{
+		self.inner.state.load(Ordering::Acquire) == State::Receiving as usize
+	}
+
 	/// Send a message on the provided socket.
 	pub(crate) fn send_socket(&self, socket: &Socket, msg: Message) -> SendResult<()>
 	{
+		if self.inner.stopped.load(Ordering::Acquire) {
+			return Err(SendError { message: msg, error: Error::Closed });
+		}
+		if let Err(e) = self.check_owner(socket) {
+			return Err(SendError { message: msg, error: e });
+		}
+
+		self.send_handle(socket.handle(), msg)
+	}
+
+	/// Send a message on the socket with the given handle.
+	///
+	/// This skips the owner check, so it is only for AIOs of the crate that
+	/// are never bound. Unlike a `Socket`, the handle doesn't keep the socket
+	/// open, and the send fails with `Error::Closed` once it has been closed.
+	pub(crate) fn send_handle(&self, handle: nng_sys::nng_socket, msg: Message) -> SendResult<()>
+	{
+		if self.inner.stopped.load(Ordering::Acquire) {
+			return Err(SendError { message: msg, error: Error::Closed });
+		}
+
 		let inactive = State::Inactive as usize;
 		let sending = State::Sending as usize;
 
 		let old_state = self.inner.state.compare_and_swap(inactive, sending, Ordering::AcqRel);
 
 		if old_state == inactive {
+			trace_event!(tracing::Level::TRACE, op = ?State::Sending, "aio.start");
+			self.inner.send_len.store(msg.len(), Ordering::Relaxed);
 			let aiop = self.inner.handle.load(Ordering::Relaxed);
 			unsafe {
 				nng_sys::nng_aio_set_msg(aiop, msg.into_ptr().as_ptr());
-				nng_sys::nng_send_aio(socket.handle(), aiop);
+				nng_sys::nng_send_aio(handle, aiop);
 			}
 
 			Ok(())
 		}
 		else {
-			Err((msg, Error::TryAgain))
+			Err(SendError { message: msg, error: Error::TryAgain })
 		}
 	}
 
 	/// Receive a message on the provided socket.
 	pub(crate) fn recv_socket(&self, socket: &Socket) -> Result<()>
 	{
+		if self.recv_is_closed() {
+			return Err(Error::Closed);
+		}
+		self.check_owner(socket)?;
+
 		let inactive = State::Inactive as usize;
 		let receiving = State::Receiving as usize;
 		let old_state = self.inner.state.compare_and_swap(inactive, receiving, Ordering::AcqRel);
 
 		if old_state == inactive {
+			trace_event!(tracing::Level::TRACE, op = ?State::Receiving, "aio.start");
+			let target = RecvTarget::Socket(socket.handle());
+			self.inner.recv_target.store(target.pack(), Ordering::Release);
 			let aiop = self.inner.handle.load(Ordering::Relaxed);
+
+			#[cfg(feature = "async-api")]
+			{
+				*self.inner.recv_kept.lock().unwrap() = Some(Arc::clone(socket.pre_received()));
+				if let Some(msg) = socket.pre_received().pop_front() {
+					if let Err(msg) = unsafe { Aio::deliver_kept(aiop, msg) } {
+						socket.pre_received().push_front(msg);
+					}
+					return Ok(());
+				}
+			}
+
 			unsafe {
 				nng_sys::nng_recv_aio(socket.handle(), aiop);
 			}
@@ -363,12 +1054,21 @@ impl Aio
 	/// Send a message on the provided context.
 	pub(crate) fn send_ctx(&self, ctx: &Context, msg: Message) -> SendResult<()>
 	{
+		if self.inner.stopped.load(Ordering::Acquire) {
+			return Err(SendError { message: msg, error: Error::Closed });
+		}
+		if let Err(e) = self.check_owner(ctx) {
+			return Err(SendError { message: msg, error: e });
+		}
+
 		let inactive = State::Inactive as usize;
 		let sending = State::Sending as usize;
 
 		let old_state = self.inner.state.compare_and_swap(inactive, sending, Ordering::AcqRel);
 
 		if old_state == inactive {
+			trace_event!(tracing::Level::TRACE, op = ?State::Sending, "aio.start");
+			self.inner.send_len.store(msg.len(), Ordering::Relaxed);
 			let aiop = self.inner.handle.load(Ordering::Relaxed);
 			unsafe {
 				nng_sys::nng_aio_set_msg(aiop, msg.into_ptr().as_ptr());
@@ -378,19 +1078,39 @@ impl Aio
 			Ok(())
 		}
 		else {
-			Err((msg, Error::TryAgain))
+			Err(SendError { message: msg, error: Error::TryAgain })
 		}
 	}
 
 	/// Receive a message on the provided context.
 	pub(crate) fn recv_ctx(&self, ctx: &Context) -> Result<()>
 	{
+		if self.recv_is_closed() {
+			return Err(Error::Closed);
+		}
+		self.check_owner(ctx)?;
+
 		let inactive = State::Inactive as usize;
 		let receiving = State::Receiving as usize;
 		let old_state = self.inner.state.compare_and_swap(inactive, receiving, Ordering::AcqRel);
 
 		if old_state == inactive {
+			trace_event!(tracing::Level::TRACE, op = ?State::Receiving, "aio.start");
+			let target = RecvTarget::Context(ctx.handle());
+			self.inner.recv_target.store(target.pack(), Ordering::Release);
 			let aiop = self.inner.handle.load(Ordering::Relaxed);
+
+			#[cfg(feature = "async-api")]
+			{
+				*self.inner.recv_kept.lock().unwrap() = Some(Arc::clone(ctx.pre_received()));
+				if let Some(msg) = ctx.pre_received().pop_front() {
+					if let Err(msg) = unsafe { Aio::deliver_kept(aiop, msg) } {
+						ctx.pre_received().push_front(msg);
+					}
+					return Ok(());
+				}
+			}
+
 			unsafe {
 				nng_sys::nng_ctx_recv(ctx.handle(), aiop);
 			}
@@ -401,6 +1121,107 @@ impl Aio
 		}
 	}
 
+	/// Completes a receive with a message kept by a dropped `RecvFuture`.
+	///
+	/// The completion goes through NNG, so the callback runs on an NNG thread
+	/// as it does for any other receive. If the AIO is being stopped, NNG
+	/// completes it with `Error::Canceled` instead and the message is handed
+	/// back.
+	#[cfg(feature = "async-api")]
+	unsafe fn deliver_kept(
+		aiop: *mut nng_sys::nng_aio,
+		msg: Message,
+	) -> std::result::Result<(), Message>
+	{
+		if !nng_sys::nng_aio_begin(aiop) {
+			return Err(msg);
+		}
+
+		nng_sys::nng_aio_set_msg(aiop, msg.into_ptr().as_ptr());
+		nng_sys::nng_aio_finish(aiop, 0);
+		Ok(())
+	}
+
+	/// Checks that an operation on the given object is allowed by the owner.
+	///
+	/// The tag of the object is only looked up if the AIO is bound, so that
+	/// AIOs that never are don't pay for the check.
+	fn check_owner<T: Into<OwnerTag>>(&self, actual: T) -> Result<()>
+	{
+		let expected = match self.owner() {
+			Some(expected) => expected,
+			None => return Ok(()),
+		};
+
+		let actual = actual.into();
+		if expected == actual {
+			Ok(())
+		}
+		else {
+			Err(Error::AioOwnershipViolation { expected, actual })
+		}
+	}
+
+	/// Runs the receive filter over a message received by the AIO.
+	///
+	/// If the filter drops the message, the receive is started again on the
+	/// same target and this returns `true`. The callback must not be called in
+	/// that case, as the operation is still running.
+	unsafe fn filter_recv(&self, aiop: *mut nng_sys::nng_aio, msg: &Message) -> bool
+	{
+		// A stopped AIO, or one whose receives were closed, would fail the new receive, so
+		// there is no point in dropping the message.
+		if self.recv_is_closed() {
+			return false;
+		}
+
+		// The filter is cloned out of the lock so that it can replace itself.
+		let filter = match &*self.inner.recv_filter.lock().unwrap() {
+			Some(f) => Arc::clone(f),
+			None => return false,
+		};
+
+		if (filter.0)(msg) == FilterDecision::Deliver {
+			return false;
+		}
+
+		let target = RecvTarget::unpack(self.inner.recv_target.load(Ordering::Acquire));
+		trace_event!(tracing::Level::TRACE, op = ?State::Receiving, "aio.start");
+
+		// As with `recv_async` and `Context::recv`, the messages kept by dropped
+		// futures come before the ones NNG has yet to receive.
+		#[cfg(feature = "async-api")]
+		{
+			let kept = self.inner.recv_kept.lock().unwrap().clone();
+			if let Some(kept) = kept {
+				if let Some(msg) = kept.pop_front() {
+					match Aio::deliver_kept(aiop, msg) {
+						Ok(()) => return true,
+						Err(msg) => kept.push_front(msg),
+					}
+				}
+			}
+		}
+
+		match target {
+			Some(RecvTarget::Socket(s)) => nng_sys::nng_recv_aio(s, aiop),
+			Some(RecvTarget::Context(c)) => nng_sys::nng_ctx_recv(c, aiop),
+			None => return false,
+		}
+
+		true
+	}
+
+	/// Tells every `Aio::wait_any` and `Aio::wait_all` that includes this AIO
+	/// that its operation has completed.
+	fn notify_waiters(&self)
+	{
+		for (wait, index) in &*self.inner.waiters.lock().unwrap() {
+			wait.done.lock().unwrap()[*index] = true;
+			wait.cond.notify_all();
+		}
+	}
+
 	/// Trampoline function for calling a closure from C.
 	///
 	/// This is really unsafe because you have to be absolutely positive in that
@@ -422,7 +1243,7 @@ impl Aio
 		// See #6 for "discussion" about why we abort here.
 		if res.is_err() {
 			// No other useful information to relay to the user.
-			error!("Panic in AIO callback function.");
+			log_error!("Panic in AIO callback function.");
 			std::process::abort();
 		}
 	}
@@ -462,10 +1283,59 @@ struct Inner
 	/// The current state of the AIO object, represented as a `usize`.
 	state: AtomicUsize,
 
+	/// Whether or not the AIO has been stopped by the user.
+	stopped: AtomicBool,
+
+	/// Whether receives fail while sends are still allowed, see
+	/// `Aio::close_recv`.
+	recv_closed: AtomicBool,
+
+	/// The number of bytes transferred by the last completed operation.
+	count: AtomicUsize,
+
+	/// The length of the message being sent, in case NNG doesn't count it.
+	send_len: AtomicUsize,
+
 	/// The callback function.
 	///
 	/// We're OK with the extra layer of indirection because we never call it.
 	callback: AtomicPtr<Box<dyn Fn() + Sync + Send + 'static>>,
+
+	/// The result of the last operation, for AIOs created with `new_manual`.
+	result: Mutex<Option<AioResult>>,
+
+	/// The message kept with the AIO by `Aio::stash`.
+	stash: Mutex<Option<Message>>,
+
+	/// The filter set with `Aio::set_recv_filter`.
+	recv_filter: Mutex<Option<Arc<RecvFilter>>>,
+
+	/// What the last receive operation was started on.
+	///
+	/// This is the raw handle rather than the socket or context itself so that
+	/// a running receive doesn't keep them open. It is packed with
+	/// `RecvTarget::pack`, with zero meaning that nothing was received yet.
+	recv_target: AtomicU64,
+
+	/// The messages kept by dropped futures on the target of the last receive,
+	/// which a receive started again by the filter takes from first.
+	#[cfg(feature = "async-api")]
+	recv_kept: Mutex<Option<Arc<PreReceived>>>,
+
+	/// The object the AIO was bound to with `Aio::bind_to`.
+	///
+	/// It is packed with `OwnerTag::pack`, with zero meaning that the AIO is
+	/// not bound, so that unbound AIOs can be checked without a lock.
+	owner: AtomicU64,
+
+	/// The number of entries in `waiters`.
+	///
+	/// This is checked on every completion so that the list is only locked
+	/// while a group wait is active.
+	waiting: AtomicUsize,
+
+	/// The group waits that include this AIO, along with its index in each.
+	waiters: Mutex<Vec<(Arc<GroupWait>, usize)>>,
 }
 
 impl Drop for Inner
@@ -487,24 +1357,136 @@ impl Drop for Inner
 			// not be running an operation when free is called, and the source doesn't
 			// clearly (to my understanding of the code) show that it is being done. Plus,
 			// the manual does suggest cases where stopping first is good.
-			unsafe {
-				nng_sys::nng_aio_stop(aiop);
-				nng_sys::nng_aio_free(aiop);
+			let handles = Handles { aiop, callback: self.callback.load(Ordering::Relaxed) };
 
-				// Now that we know nothing is in the callback, we can free it.
-				let _ = Box::from_raw(self.callback.load(Ordering::Relaxed));
+			// Both of those calls wait for the callback to return, so they can't be made
+			// from within the callback itself, which is where the last handle goes away
+			// if the callback outlives everything else holding on to the AIO. The reaper
+			// thread waits for the callback instead.
+			if CALLBACK_AIO.with(Cell::get) == aiop {
+				reap(handles);
+			}
+			else {
+				unsafe { handles.free() }
 			}
 		}
 	}
 }
 
+/// Frees the AIO on the reaper thread, starting it if this is the first one.
+///
+/// The reaper frees the AIOs one at a time as their callbacks return, so a
+/// single thread is shared by all of the AIOs dropped from their own callback.
+fn reap(handles: Handles)
+{
+	static REAPER: OnceLock<Option<mpsc::Sender<Handles>>> = OnceLock::new();
+
+	let reaper = REAPER.get_or_init(|| {
+		let (tx, rx) = mpsc::channel::<Handles>();
+		thread::Builder::new()
+			.name("nng-aio-free".into())
+			.spawn(move || {
+				for handles in rx {
+					unsafe { handles.free() }
+				}
+			})
+			.ok()
+			.map(|_| tx)
+	});
+
+	// The handles are leaked if there is no reaper, as freeing them here would
+	// deadlock the callback.
+	if !reaper.as_ref().is_some_and(|tx| tx.send(handles).is_ok()) {
+		log_error!("Failed to start a thread to free an AIO, leaking it");
+	}
+}
+
+thread_local! {
+	/// The NNG AIO whose callback is running on this thread, or null.
+	static CALLBACK_AIO: Cell<*mut nng_sys::nng_aio> = const { Cell::new(ptr::null_mut()) };
+}
+
+/// Restores the AIO whose callback is running on this thread when dropped.
+///
+/// Restoring the previous value instead of clearing it keeps this correct
+/// should NNG ever run the callback of another AIO inline.
+struct CallbackGuard(*mut nng_sys::nng_aio);
+impl CallbackGuard
+{
+	/// Remembers the AIO whose callback was running before this one.
+	fn new() -> Self { CallbackGuard(CALLBACK_AIO.with(Cell::get)) }
+}
+
+impl Drop for CallbackGuard
+{
+	fn drop(&mut self) { CALLBACK_AIO.with(|c| c.set(self.0)) }
+}
+
+/// The parts of an AIO that are freed once it is no longer used.
+struct Handles
+{
+	/// The NNG AIO object.
+	aiop: *mut nng_sys::nng_aio,
+
+	/// The callback of the AIO.
+	callback: *mut Box<dyn Fn() + Sync + Send + 'static>,
+}
+impl Handles
+{
+	/// Stops and frees the AIO, followed by its callback.
+	///
+	/// This must not be called from within the callback of the AIO and nothing
+	/// can use either of them afterwards.
+	unsafe fn free(self)
+	{
+		nng_sys::nng_aio_stop(self.aiop);
+		nng_sys::nng_aio_free(self.aiop);
+
+		// Now that we know nothing is in the callback, we can free it.
+		let _ = Box::from_raw(self.callback);
+	}
+}
+
+// Nothing else holds on to the AIO or callback once they are being freed, so
+// they can be freed on any thread.
+unsafe impl Send for Handles {}
+
 /// The result of an AIO operation.
+///
+/// ## Example
+///
+/// Closing a socket while a send is in progress fails the send, but NNG may or
+/// may not hand the message back:
+///
+/// ```
+/// use std::sync::{mpsc, Mutex};
+/// use nng::*;
+///
+/// // A push socket with no peers holds on to the message until it is closed.
+/// let socket = Socket::new(Protocol::Push0)?;
+/// socket.listen("inproc://nng/aio/result/example")?;
+///
+/// let (tx, rx) = mpsc::channel();
+/// let tx = Mutex::new(tx);
+/// let aio = Aio::new(move |_, res| tx.lock().unwrap().send(res).unwrap())?;
+///
+/// socket.send_async(&aio, Message::new()?)?;
+/// socket.close();
+///
+/// match rx.recv().unwrap() {
+///     AioResult::SendErr(_msg, e) => assert_eq!(e, Error::Closed),
+///     AioResult::SendErrLost(e) => assert_eq!(e, Error::Closed),
+///     res => panic!("Unexpected result: {:?}", res),
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
 // There are no "Inactive" results as I don't think there is a valid way to get any type of callback
 // trigger when there are no operations running. All of the "user forced" errors, such as
 // cancellation or timeouts, don't happen if there are no running operations. If there are no
 // running operations, then no non-"user forced" errors can happen.
 #[derive(Clone, Debug)]
 #[must_use]
+#[non_exhaustive]
 pub enum AioResult
 {
 	/// The send operation was successful.
@@ -515,10 +1497,19 @@ pub enum AioResult
 	/// This contains the message that was being sent.
 	SendErr(Message, Error),
 
+	/// The send operation failed and NNG did not return the message.
+	///
+	/// This can happen on some error paths, such as when the socket is closed
+	/// while the message is being sent. The message has already been freed.
+	SendErrLost(Error),
+
 	/// The receive operation was successful.
 	RecvOk(Message),
 
 	/// The receive operation failed.
+	///
+	/// If NNG reports a successful receive but does not provide a message, this
+	/// will contain `Error::Internal`.
 	RecvErr(Error),
 
 	/// The sleep operation was successful.
@@ -531,6 +1522,96 @@ pub enum AioResult
 	SleepErr(Error),
 }
 
+impl AioResult
+{
+	/// Returns whether the operation succeeded, whatever it was.
+	pub fn is_ok(&self) -> bool
+	{
+		matches!(self, AioResult::SendOk | AioResult::RecvOk(_) | AioResult::SleepOk)
+	}
+
+	/// Returns the outcome of a receive operation.
+	///
+	/// This is only meant for the result of a receive. Calling it on the
+	/// result of any other operation is a programming error and panics, as it
+	/// would otherwise drop the message of a failed send.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{sync::{mpsc, Mutex}, time::Duration};
+	/// use nng::*;
+	///
+	/// let server = Socket::new(Protocol::Pull0)?;
+	/// server.listen("inproc://nng/aio/into_recv")?;
+	/// let client = Socket::new(Protocol::Push0)?;
+	/// client.dial("inproc://nng/aio/into_recv")?;
+	///
+	/// let (tx, rx) = mpsc::channel();
+	/// let tx = Mutex::new(tx);
+	/// let aio = Aio::new(move |_, res| tx.lock().unwrap().send(res.into_recv()).unwrap())?;
+	///
+	/// server.recv_async(&aio)?;
+	/// client.send(&b"hello"[..])?;
+	/// assert_eq!(&rx.recv().unwrap()?[..], b"hello");
+	///
+	/// aio.set_timeout(Some(Duration::from_millis(10)))?;
+	/// server.recv_async(&aio)?;
+	/// assert_eq!(rx.recv().unwrap().unwrap_err(), Error::TimedOut);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn into_recv(self) -> Result<Message>
+	{
+		match self {
+			AioResult::RecvOk(m) => Ok(m),
+			AioResult::RecvErr(e) => Err(e),
+			res => panic!("AioResult::into_recv called on the result of {:?}", res),
+		}
+	}
+
+	/// Returns the outcome of a send operation.
+	///
+	/// A failed send comes with the message when NNG gave it back, so that it
+	/// can be sent again, and with `None` when the message was lost, as for
+	/// `AioResult::SendErrLost`.
+	///
+	/// This is only meant for the result of a send. Calling it on the result
+	/// of any other operation is a programming error and panics, as it would
+	/// otherwise drop the message of a successful receive.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::*;
+	///
+	/// let socket = Socket::new(Protocol::Push0)?;
+	/// let aio = Aio::new_manual()?;
+	///
+	/// // A push socket without peers holds on to the message until it is closed.
+	/// socket.send_async(&aio, &b"hello"[..])?;
+	/// socket.close();
+	/// aio.wait();
+	///
+	/// let res = aio.result().unwrap();
+	/// assert!(!res.is_ok());
+	/// let (msg, err) = res.into_send().unwrap_err();
+	/// assert_eq!(err, Error::Closed);
+	/// if let Some(msg) = msg {
+	///     assert_eq!(&msg[..], b"hello");
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn into_send(self) -> std::result::Result<(), (Option<Message>, Error)>
+	{
+		match self {
+			AioResult::SendOk => Ok(()),
+			AioResult::SendErr(m, e) => Err((Some(m), e)),
+			AioResult::SendErrLost(e) => Err((None, e)),
+			res => panic!("AioResult::into_send called on the result of {:?}", res),
+		}
+	}
+}
+
 impl From<AioResult> for Result<Option<Message>>
 {
 	fn from(aio_res: AioResult) -> Result<Option<Message>>
@@ -539,12 +1620,221 @@ impl From<AioResult> for Result<Option<Message>>
 
 		match aio_res {
 			SendOk | SleepOk => Ok(None),
-			SendErr(_, e) | RecvErr(e) | SleepErr(e) => Err(e),
+			SendErr(_, e) | SendErrLost(e) | RecvErr(e) | SleepErr(e) => Err(e),
 			RecvOk(m) => Ok(Some(m)),
 		}
 	}
 }
 
+/// The decision of a receive filter about a received message.
+///
+/// See `Aio::set_recv_filter` for details.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FilterDecision
+{
+	/// The message is passed on to the callback.
+	Deliver,
+
+	/// The message is dropped and the receive is started again.
+	Drop,
+}
+
+/// Identifies the socket or context that owns an AIO.
+///
+/// See `Aio::bind_to` for details. Tags are created from references to the
+/// owner and are only equal when they refer to the same object.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OwnerTag
+{
+	/// The socket with the given ID.
+	Socket(i32),
+
+	/// The context with the given ID.
+	Context(i32),
+}
+
+impl OwnerTag
+{
+	/// Packs the tag into a single non-zero word.
+	fn pack(self) -> u64
+	{
+		match self {
+			OwnerTag::Socket(id) => pack_id(KIND_SOCKET, id as u32),
+			OwnerTag::Context(id) => pack_id(KIND_CONTEXT, id as u32),
+		}
+	}
+
+	/// Unpacks a tag packed with `OwnerTag::pack`, or `None` for zero.
+	fn unpack(packed: u64) -> Option<OwnerTag>
+	{
+		match unpack_id(packed) {
+			Some((KIND_SOCKET, id)) => Some(OwnerTag::Socket(id as i32)),
+			Some((KIND_CONTEXT, id)) => Some(OwnerTag::Context(id as i32)),
+			_ => None,
+		}
+	}
+}
+
+impl From<&Socket> for OwnerTag
+{
+	fn from(socket: &Socket) -> OwnerTag
+	{
+		OwnerTag::Socket(unsafe { nng_sys::nng_socket_id(socket.handle()) })
+	}
+}
+
+impl From<&Context> for OwnerTag
+{
+	fn from(ctx: &Context) -> OwnerTag
+	{
+		OwnerTag::Context(unsafe { nng_sys::nng_ctx_id(ctx.handle()) })
+	}
+}
+
+impl fmt::Display for OwnerTag
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match *self {
+			OwnerTag::Socket(id) => write!(f, "socket {}", id),
+			OwnerTag::Context(id) => write!(f, "context {}", id),
+		}
+	}
+}
+
+/// A receive filter set on an AIO.
+struct RecvFilter(Box<dyn Fn(&Message) -> FilterDecision + Sync + Send + 'static>);
+
+impl fmt::Debug for RecvFilter
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("RecvFilter") }
+}
+
+/// The socket or context that a receive operation was started on.
+#[derive(Clone, Copy, Debug)]
+enum RecvTarget
+{
+	/// The receive was started with `Socket::recv_async`.
+	Socket(nng_sys::nng_socket),
+
+	/// The receive was started with `Context::recv`.
+	Context(nng_sys::nng_ctx),
+}
+
+impl RecvTarget
+{
+	/// Packs the target into a single non-zero word.
+	fn pack(self) -> u64
+	{
+		match self {
+			RecvTarget::Socket(s) => pack_id(KIND_SOCKET, s._bindgen_opaque_blob),
+			RecvTarget::Context(c) => pack_id(KIND_CONTEXT, c._bindgen_opaque_blob),
+		}
+	}
+
+	/// Unpacks a target packed with `RecvTarget::pack`, or `None` for zero.
+	fn unpack(packed: u64) -> Option<RecvTarget>
+	{
+		match unpack_id(packed) {
+			Some((KIND_SOCKET, id)) => {
+				Some(RecvTarget::Socket(nng_sys::nng_socket { _bindgen_opaque_blob: id }))
+			},
+			Some((KIND_CONTEXT, id)) => {
+				Some(RecvTarget::Context(nng_sys::nng_ctx { _bindgen_opaque_blob: id }))
+			},
+			_ => None,
+		}
+	}
+}
+
+/// The kind of object packed by `pack_id` for a socket.
+const KIND_SOCKET: u32 = 1;
+
+/// The kind of object packed by `pack_id` for a context.
+const KIND_CONTEXT: u32 = 2;
+
+/// Packs the kind and ID of a socket or context into a single word.
+///
+/// The kind goes in the upper half, so the result is never zero and a socket
+/// never compares equal to a context with the same ID.
+fn pack_id(kind: u32, id: u32) -> u64 { u64::from(kind) << 32 | u64::from(id) }
+
+/// Splits a word packed by `pack_id`, or returns `None` for zero.
+fn unpack_id(packed: u64) -> Option<(u32, u32)>
+{
+	if packed == 0 {
+		None
+	}
+	else {
+		Some(((packed >> 32) as u32, packed as u32))
+	}
+}
+
+/// A thread blocked in `Aio::wait_any` or `Aio::wait_all`.
+#[derive(Debug)]
+struct GroupWait
+{
+	/// Whether each of the AIOs has completed or was found idle.
+	done: Mutex<Vec<bool>>,
+
+	/// Notified whenever one of the AIOs completes.
+	cond: Condvar,
+}
+
+impl GroupWait
+{
+	/// Waits until `ready` returns something for the completed AIOs, or until
+	/// the timeout expires.
+	fn run<T, F>(aios: &[&Aio], timeout: Option<Duration>, ready: F) -> Option<T>
+	where
+		F: Fn(&[bool]) -> Option<T>,
+	{
+		let done = Mutex::new(vec![false; aios.len()]);
+		let wait = Arc::new(GroupWait { done, cond: Condvar::new() });
+
+		for (i, aio) in aios.iter().enumerate() {
+			aio.inner.waiters.lock().unwrap().push((Arc::clone(&wait), i));
+			aio.inner.waiting.fetch_add(1, Ordering::SeqCst);
+		}
+
+		// Operations that completed before the registration didn't notify us, but they
+		// have left their AIO inactive, which is checked only after registering.
+		let res = {
+			let mut done = wait.done.lock().unwrap();
+			for (i, aio) in aios.iter().enumerate() {
+				if aio.inner.state.load(Ordering::SeqCst) == State::Inactive as usize {
+					done[i] = true;
+				}
+			}
+
+			let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+			loop {
+				if let Some(res) = ready(&done) {
+					break Some(res);
+				}
+
+				done = match deadline {
+					Some(d) => {
+						let now = Instant::now();
+						if now >= d {
+							break None;
+						}
+						wait.cond.wait_timeout(done, d - now).unwrap().0
+					},
+					None => wait.cond.wait(done).unwrap(),
+				};
+			}
+		};
+
+		for aio in aios {
+			aio.inner.waiters.lock().unwrap().retain(|(w, _)| !Arc::ptr_eq(w, &wait));
+			aio.inner.waiting.fetch_sub(1, Ordering::SeqCst);
+		}
+
+		res
+	}
+}
+
 /// Represents the state of the AIO object.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(usize)]
@@ -0,0 +1,385 @@
+//! Batching of many small published messages into fewer large ones.
+use std::{
+	convert::TryFrom,
+	str,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use crate::{
+	aio::{Aio, AioResult},
+	error::{Error, Result},
+	message::Message,
+	protocol::Protocol,
+	socket::Socket,
+};
+
+/// The magic value at the start of every batch message.
+const MAGIC: &[u8; 4] = b"NNGB";
+
+/// The version of the batch message format.
+const FORMAT_VERSION: u8 = 1;
+
+/// The length of the header at the start of every batch message.
+const HEADER_LEN: usize = MAGIC.len() + 1;
+
+/// Publishes records on a _pub_ socket in batches.
+///
+/// Every message sent on a socket has a fixed cost in system calls and
+/// framing, which dominates when publishing large numbers of tiny messages.
+/// This publisher appends each record to an in-progress batch message and only
+/// sends the batch once it holds at least `max_batch_bytes` bytes, or once
+/// `max_delay` has passed since the first record was added to it. Records are
+/// therefore delayed by at most `max_delay`. While the batch is empty, no timer
+/// is running.
+///
+/// Batches use their own wire format, so they must be received with
+/// `BatchReader` rather than a `Subscriber`. The _sub_ protocol filters on the
+/// start of each message, which is the same for every batch, so subscribers
+/// must subscribe to everything (the empty topic) and filter the records
+/// themselves.
+///
+/// Dropping the publisher sends the partial batch, logging any error. Use
+/// `BatchingPublisher::shutdown` to see the error instead.
+///
+/// ## Wire Format
+///
+/// Version 1 of the batch format is laid out as follows, with all integers
+/// big-endian:
+///
+/// | Bytes | Contents                                |
+/// |-------|-----------------------------------------|
+/// | 4     | The magic value `NNGB`                  |
+/// | 1     | The format version, currently `1`       |
+/// | Rest  | The records, one after the other        |
+///
+/// Each record is a 2 byte topic length, the UTF-8 topic, a 4 byte payload
+/// length, and the payload.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nng::options::{protocol::pubsub::Subscribe, Options};
+/// use nng::{BatchReader, BatchingPublisher, Protocol, Socket};
+///
+/// const ADDRESS: &str = "inproc://nng/batch/example";
+///
+/// let socket = Socket::new(Protocol::Pub0)?;
+/// socket.listen(ADDRESS)?;
+/// let publisher = BatchingPublisher::new(socket, 32, Duration::from_secs(60))?;
+///
+/// let subscriber = Socket::new(Protocol::Sub0)?;
+/// subscriber.set_opt::<Subscribe>(Vec::new())?;
+/// subscriber.dial(ADDRESS)?;
+/// # std::thread::sleep(Duration::from_millis(50));
+///
+/// // Each record takes 6 bytes plus the topic and payload, so the third one
+/// // pushes the batch over 32 bytes and sends it.
+/// publisher.publish("a", b"1111")?;
+/// publisher.publish("b", b"2222")?;
+/// publisher.publish("c", b"3333")?;
+///
+/// let batch = subscriber.recv()?;
+/// let records: Vec<_> = BatchReader::frames(&batch)?.collect();
+/// assert_eq!(records, [("a", &b"1111"[..]), ("b", b"2222"), ("c", b"3333")]);
+///
+/// // Shutting down sends the partial batch.
+/// publisher.publish("d", b"4444")?;
+/// publisher.shutdown()?;
+///
+/// let batch = subscriber.recv()?;
+/// let records: Vec<_> = BatchReader::frames(&batch)?.collect();
+/// assert_eq!(records, [("d", &b"4444"[..])]);
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct BatchingPublisher
+{
+	/// The state shared with the flush timer.
+	shared: Arc<Shared>,
+
+	/// The AIO used as the flush timer.
+	timer: Aio,
+}
+impl BatchingPublisher
+{
+	/// Creates a new batching publisher on top of the provided _pub_ socket.
+	///
+	/// Returns `Error::NotSupported` if the socket does not use the _pub_
+	/// protocol.
+	pub fn new(socket: Socket, max_batch_bytes: usize, max_delay: Duration) -> Result<Self>
+	{
+		socket.require_protocol(Protocol::Pub0)?;
+
+		let shared = Arc::new(Shared {
+			socket,
+			max_batch_bytes,
+			max_delay,
+			batch: Mutex::new(Batch { msg: None, timer_running: false }),
+		});
+
+		// The timer only ever sleeps and it is only stopped when the publisher is
+		// shutting down, so there is nothing to do unless it ran out.
+		let cb_shared = Arc::clone(&shared);
+		let timer = Aio::new(move |_, res| {
+			if let AioResult::SleepOk = res {
+				let mut batch = cb_shared.batch.lock().unwrap();
+				batch.timer_running = false;
+				let res = cb_shared.send(&mut batch);
+				drop(batch);
+
+				if let Err(e) = res {
+					log_error!("Unable to send the batch ({})", e);
+				}
+			}
+		})?;
+
+		Ok(BatchingPublisher { shared, timer })
+	}
+
+	/// Returns the underlying socket, used for dialing, listening, and
+	/// setting options.
+	pub fn socket(&self) -> &Socket { &self.shared.socket }
+
+	/// Adds a record to the current batch.
+	///
+	/// The batch is sent once it reaches the maximum batch size, in which case
+	/// any error from sending it is returned here. Errors from batches sent by
+	/// the timer are logged. Returns `Error::InvalidInput` if the topic is
+	/// longer than 65535 bytes or the payload is longer than 4 GiB.
+	///
+	/// ## Example
+	///
+	/// A batch that never fills up is sent once the delay has passed:
+	///
+	/// ```
+	/// use std::time::{Duration, Instant};
+	/// use nng::options::{protocol::pubsub::Subscribe, Options, RecvTimeout};
+	/// use nng::{BatchReader, BatchingPublisher, Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/batch/publish";
+	/// const DELAY: Duration = Duration::from_millis(50);
+	///
+	/// let socket = Socket::new(Protocol::Pub0)?;
+	/// socket.listen(ADDRESS)?;
+	/// let publisher = BatchingPublisher::new(socket, 64 * 1024, DELAY)?;
+	///
+	/// let subscriber = Socket::new(Protocol::Sub0)?;
+	/// subscriber.set_opt::<Subscribe>(Vec::new())?;
+	/// subscriber.set_opt::<RecvTimeout>(Some(Duration::from_secs(1)))?;
+	/// subscriber.dial(ADDRESS)?;
+	/// # std::thread::sleep(Duration::from_millis(50));
+	///
+	/// for round in 0..2 {
+	///     let start = Instant::now();
+	///     publisher.publish("tick", &[round])?;
+	///     publisher.publish("tock", &[round])?;
+	///
+	///     // NNG's timers only have millisecond resolution, so allow some slack.
+	///     let batch = subscriber.recv()?;
+	///     assert!(start.elapsed() >= DELAY / 2);
+	///     let records: Vec<_> = BatchReader::frames(&batch)?.collect();
+	///     assert_eq!(records, [("tick", &[round][..]), ("tock", &[round])]);
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn publish(&self, topic: &str, payload: &[u8]) -> Result<()>
+	{
+		let topic_len = u16::try_from(topic.len()).map_err(|_| Error::InvalidInput)?;
+		let payload_len = u32::try_from(payload.len()).map_err(|_| Error::InvalidInput)?;
+
+		let mut batch = self.shared.batch.lock().unwrap();
+		let mut msg = match batch.msg.take() {
+			Some(m) => m,
+			None => {
+				let mut msg = Message::with_capacity(self.shared.max_batch_bytes)?;
+				msg.push_back(MAGIC)?;
+				msg.push_back(&[FORMAT_VERSION])?;
+				msg
+			},
+		};
+
+		// A partially written record would corrupt the rest of the batch.
+		let old_len = msg.len();
+		let res = msg
+			.push_back(&topic_len.to_be_bytes())
+			.and_then(|_| msg.push_back(topic.as_bytes()))
+			.and_then(|_| msg.push_back(&payload_len.to_be_bytes()))
+			.and_then(|_| msg.push_back(payload));
+
+		if let Err(e) = res {
+			msg.truncate(old_len);
+			batch.msg = Some(msg);
+			return Err(e);
+		}
+
+		if msg.len() >= self.shared.max_batch_bytes {
+			return self.shared.socket.send(msg).map_err(|e| e.error);
+		}
+		batch.msg = Some(msg);
+
+		// The timer may still be running for an earlier batch that was sent
+		// because it was full. It will send this batch early, which is fine.
+		if !batch.timer_running {
+			match self.timer.sleep(self.shared.max_delay) {
+				Ok(()) => batch.timer_running = true,
+				Err(_) => return self.shared.send(&mut batch),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Sends the current batch immediately, if there is one.
+	pub fn flush(&self) -> Result<()>
+	{
+		let mut batch = self.shared.batch.lock().unwrap();
+		self.shared.send(&mut batch)
+	}
+
+	/// Stops the flush timer and sends the partial batch, if there is one.
+	pub fn shutdown(self) -> Result<()>
+	{
+		self.timer.stop();
+		self.flush()
+	}
+}
+
+impl Drop for BatchingPublisher
+{
+	fn drop(&mut self)
+	{
+		self.timer.stop();
+		if let Err(e) = self.flush() {
+			log_error!("Unable to send the final batch ({})", e);
+		}
+	}
+}
+
+/// The state of a `BatchingPublisher` that is shared with its flush timer.
+#[derive(Debug)]
+struct Shared
+{
+	/// The underlying _pub_ socket.
+	socket: Socket,
+
+	/// The size at which a batch is sent.
+	max_batch_bytes: usize,
+
+	/// The longest time a record waits in a batch.
+	max_delay: Duration,
+
+	/// The batch currently being built.
+	///
+	/// Batches are sent while holding the lock so that they go out in order.
+	batch: Mutex<Batch>,
+}
+impl Shared
+{
+	/// Sends the batch, if there is one.
+	fn send(&self, batch: &mut Batch) -> Result<()>
+	{
+		match batch.msg.take() {
+			Some(m) => self.socket.send(m).map_err(|e| e.error),
+			None => Ok(()),
+		}
+	}
+}
+
+/// A batch being built, along with the state of the flush timer.
+#[derive(Debug)]
+struct Batch
+{
+	/// The message holding the records, if any have been published.
+	msg: Option<Message>,
+
+	/// Whether or not the flush timer is running.
+	timer_running: bool,
+}
+
+/// Reads the records out of the batches sent by a `BatchingPublisher`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BatchReader;
+impl BatchReader
+{
+	/// Returns an iterator over the `(topic, payload)` records of a batch.
+	///
+	/// The whole batch is validated up front. Returns `Error::Protocol` if the
+	/// message is not a batch in a format this version of the crate
+	/// understands or if any of its records are malformed.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::{BatchReader, Error, Message};
+	///
+	/// let msg = Message::from(&b"not a batch"[..]);
+	/// assert_eq!(BatchReader::frames(&msg).unwrap_err(), Error::Protocol);
+	/// ```
+	pub fn frames(msg: &Message) -> Result<BatchFrames<'_>>
+	{
+		let data = msg.as_slice();
+		if data.len() < HEADER_LEN || &data[..4] != MAGIC || data[4] != FORMAT_VERSION {
+			return Err(Error::Protocol);
+		}
+
+		// Nothing can go wrong once the records are checked, which keeps the
+		// iterator simple.
+		let frames = BatchFrames { data: &data[HEADER_LEN..] };
+		let mut check = frames.clone();
+		while !check.data.is_empty() {
+			check.next_record().ok_or(Error::Protocol)?;
+		}
+
+		Ok(frames)
+	}
+}
+
+/// An iterator over the records of a batch.
+///
+/// Created by `BatchReader::frames`.
+#[derive(Clone, Debug)]
+pub struct BatchFrames<'a>
+{
+	/// The records that have not yet been returned.
+	data: &'a [u8],
+}
+impl<'a> BatchFrames<'a>
+{
+	/// Splits the next record off of the data.
+	///
+	/// Returns `None` if the record is malformed.
+	fn next_record(&mut self) -> Option<(&'a str, &'a [u8])>
+	{
+		let (topic, rest) = split_prefixed(self.data, 2)?;
+		let (payload, rest) = split_prefixed(rest, 4)?;
+		let topic = str::from_utf8(topic).ok()?;
+
+		self.data = rest;
+		Some((topic, payload))
+	}
+}
+impl<'a> Iterator for BatchFrames<'a>
+{
+	type Item = (&'a str, &'a [u8]);
+
+	fn next(&mut self) -> Option<Self::Item>
+	{
+		if self.data.is_empty() { None } else { self.next_record() }
+	}
+}
+
+/// Splits a big-endian length of `width` bytes and that many bytes off of the
+/// data.
+fn split_prefixed(data: &[u8], width: usize) -> Option<(&[u8], &[u8])>
+{
+	if data.len() < width {
+		return None;
+	}
+
+	let (len, rest) = data.split_at(width);
+	let len = len.iter().fold(0, |acc, &b| (acc << 8) | usize::from(b));
+
+	if rest.len() < len { None } else { Some(rest.split_at(len)) }
+}
@@ -0,0 +1,443 @@
+//! Channels that move values between threads over `inproc`.
+use std::{
+	collections::HashMap,
+	error, fmt, mem,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Condvar, Mutex,
+	},
+};
+
+use crate::{
+	aio::Aio,
+	error::{Error, Result},
+	message::Message,
+	protocol::Protocol,
+	shutdown::ShutdownToken,
+	socket::Socket,
+};
+
+/// The identifier of the message that ends a channel.
+const END_ID: u64 = 0;
+
+/// The generation of the next channel that is created.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Creates a channel that moves values between threads over an `inproc`
+/// socket.
+///
+/// Only an identifier travels through NNG. The values themselves wait in a
+/// table shared by both ends of the channel until they are received, so they
+/// are never serialized and can be anything that is `Send`, including types
+/// holding `Arc`s or file handles. The channel listens on `inproc://<name>`,
+/// which returns `Error::AddressInUse` if the name is already taken. As such,
+/// there is only ever a single receiver for each name.
+///
+/// Sends block until the receiver has room for the value, like a bounded
+/// `std::sync::mpsc` channel. Senders can be cloned and the channel stays
+/// open for as long as any of them is around. Once they have all been
+/// dropped, the receiver returns the values that are still on their way and
+/// then `Error::Closed`.
+///
+/// Dropping the receiver closes the channel: sends fail with `Error::Closed`
+/// and hand the value back, while values that were sent but not received are
+/// dropped along with the receiver. NNG discards the messages that referred
+/// to them, so this is what keeps them from leaking. Messages sent to the
+/// address by other sockets are ignored.
+///
+/// ## Example
+///
+/// ```
+/// use std::{fs::File, sync::Arc, thread};
+/// use nng::{channel, Error};
+///
+/// // Neither of these can be serialized.
+/// struct Job {
+///     shared: Arc<String>,
+///     log: Option<File>,
+/// }
+///
+/// let (tx, rx) = channel::<Job>("nng/channel/example")?;
+/// let shared = Arc::new(String::from("shared state"));
+///
+/// for _ in 0..2 {
+///     let (tx, shared) = (tx.clone(), Arc::clone(&shared));
+///     thread::spawn(move || {
+///         for _ in 0..5 {
+///             tx.send(Job { shared: Arc::clone(&shared), log: None }).unwrap();
+///         }
+///     });
+/// }
+/// drop(tx);
+///
+/// let mut received = 0;
+/// loop {
+///     match rx.recv() {
+///         Ok(job) => {
+///             assert!(Arc::ptr_eq(&job.shared, &shared));
+///             assert!(job.log.is_none());
+///             received += 1;
+///         },
+///         Err(Error::Closed) => break,
+///         Err(e) => panic!("Unexpected error: {}", e),
+///     }
+/// }
+/// assert_eq!(received, 10);
+///
+/// // Every clone made by the senders has been dropped again.
+/// assert_eq!(Arc::strong_count(&shared), 1);
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub fn channel<T: Send + 'static>(name: &str) -> Result<(TypedSender<T>, TypedReceiver<T>)>
+{
+	let url = format!("inproc://{}", name);
+
+	let socket = Socket::new(Protocol::Pull0)?;
+	socket.listen(&url)?;
+	let mut nonblocking = socket.clone();
+	nonblocking.set_nonblocking(true);
+
+	let push = Socket::new(Protocol::Push0)?;
+	push.dial(&url)?;
+
+	let shared = Arc::new(Shared {
+		url,
+		generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+		next_id: AtomicU64::new(END_ID + 1),
+		state: Mutex::new(State { values: HashMap::new(), sending: 0, closed: false }),
+		cvar: Condvar::new(),
+		push,
+		end_aio: Aio::new_manual()?,
+		ended: ShutdownToken::new(),
+	});
+
+	let sender = TypedSender { inner: Arc::new(SenderInner { shared: Arc::clone(&shared) }) };
+	let receiver = TypedReceiver { shared, socket, nonblocking };
+	Ok((sender, receiver))
+}
+
+/// The sending half of a channel created with `channel`.
+///
+/// Cloning the sender creates another handle to the same channel.
+pub struct TypedSender<T>
+{
+	/// The part shared between the clones of this sender.
+	inner: Arc<SenderInner<T>>,
+}
+impl<T: Send + 'static> TypedSender<T>
+{
+	/// Sends a value to the receiver.
+	///
+	/// This blocks until the receiver has room for the value. If the receiver
+	/// has been dropped, the value is handed back along with `Error::Closed`.
+	pub fn send(&self, value: T) -> std::result::Result<(), TypedSendError<T>>
+	{
+		let shared = &self.inner.shared;
+		let id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+
+		{
+			let mut state = shared.state.lock().unwrap();
+			if state.closed {
+				return Err(TypedSendError { value, error: Error::Closed });
+			}
+			state.values.insert(id, value);
+			state.sending += 1;
+		}
+
+		let res = shared.push.send(&frame(shared.generation, id)[..]);
+
+		let mut state = shared.state.lock().unwrap();
+		state.sending -= 1;
+		let res = res.map_err(|e| {
+			// The receiver waits for running sends before dropping the values, so a
+			// value whose message was not sent is still in the table.
+			let value = state.values.remove(&id).expect("Unsent value was removed");
+			TypedSendError { value, error: e.error }
+		});
+		drop(state);
+
+		shared.cvar.notify_all();
+		res
+	}
+}
+
+impl<T> Clone for TypedSender<T>
+{
+	fn clone(&self) -> Self { TypedSender { inner: Arc::clone(&self.inner) } }
+}
+
+impl<T> fmt::Debug for TypedSender<T>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("TypedSender").field("url", &self.inner.shared.url).finish()
+	}
+}
+
+/// The receiving half of a channel created with `channel`.
+///
+/// Dropping the receiver closes the channel and drops every value that was
+/// sent but not received.
+///
+/// ## Example
+///
+/// ```
+/// use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+/// use nng::{channel, Error};
+///
+/// // Counts the values that have been dropped.
+/// struct Tracked(Arc<AtomicUsize>);
+/// impl Drop for Tracked {
+///     fn drop(&mut self) { self.0.fetch_add(1, Ordering::SeqCst); }
+/// }
+///
+/// let drops = Arc::new(AtomicUsize::new(0));
+/// let (tx, rx) = channel::<Tracked>("nng/channel/receiver")?;
+///
+/// // A received value belongs to the caller.
+/// tx.send(Tracked(drops.clone())).unwrap();
+/// drop(rx.recv()?);
+/// assert_eq!(drops.load(Ordering::SeqCst), 1);
+///
+/// // Values that were never received are dropped along with the receiver.
+/// tx.send(Tracked(drops.clone())).unwrap();
+/// drop(rx);
+/// assert_eq!(drops.load(Ordering::SeqCst), 2);
+///
+/// // Afterwards, sends hand the value back.
+/// let err = tx.send(Tracked(drops.clone())).unwrap_err();
+/// assert_eq!(err.error, Error::Closed);
+/// assert_eq!(drops.load(Ordering::SeqCst), 2);
+/// drop(err);
+/// assert_eq!(drops.load(Ordering::SeqCst), 3);
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub struct TypedReceiver<T>
+{
+	/// The state shared with the senders.
+	shared: Arc<Shared<T>>,
+
+	/// The _pull_ socket the identifiers are received on.
+	socket: Socket,
+
+	/// A non-blocking handle to the same socket.
+	nonblocking: Socket,
+}
+impl<T: Send + 'static> TypedReceiver<T>
+{
+	/// Receives a value, waiting until one is sent.
+	///
+	/// Returns `Error::Closed` once every sender has been dropped and all of
+	/// the values they sent have been received. The receiver can be shared
+	/// between threads, in which case each value is received by only one of
+	/// them.
+	pub fn recv(&self) -> Result<T>
+	{
+		loop {
+			if self.shared.ended.is_shutdown() {
+				return Err(Error::Closed);
+			}
+
+			// The token is triggered by whichever thread receives the end of the
+			// channel, which cancels the receives of all of the others.
+			match self.socket.recv_cancellable(&self.shared.ended) {
+				Ok(msg) => {
+					if let Some(res) = self.take(&msg) {
+						return res;
+					}
+				},
+				Err(Error::Canceled) => {},
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Receives a value if one is available, without waiting.
+	///
+	/// Returns `Error::TryAgain` if no value is available right now and
+	/// `Error::Closed` if the channel has ended, as with `TypedReceiver::recv`.
+	pub fn try_recv(&self) -> Result<T>
+	{
+		loop {
+			if self.shared.ended.is_shutdown() {
+				return Err(Error::Closed);
+			}
+
+			let msg = self.nonblocking.recv()?;
+			if let Some(res) = self.take(&msg) {
+				return res;
+			}
+		}
+	}
+
+	/// Takes the value a message refers to out of the table.
+	///
+	/// Returns `None` for messages that did not come from this channel.
+	fn take(&self, msg: &Message) -> Option<Result<T>>
+	{
+		let (generation, id) = parse_frame(msg.as_slice())?;
+		if generation != self.shared.generation {
+			return None;
+		}
+
+		if id == END_ID {
+			self.shared.ended.trigger();
+			return Some(Err(Error::Closed));
+		}
+
+		self.shared.state.lock().unwrap().values.remove(&id).map(Ok)
+	}
+}
+
+impl<T> fmt::Debug for TypedReceiver<T>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("TypedReceiver").field("url", &self.shared.url).finish()
+	}
+}
+
+impl<T> Drop for TypedReceiver<T>
+{
+	fn drop(&mut self)
+	{
+		self.shared.state.lock().unwrap().closed = true;
+
+		// Closing the sockets fails the sends that are waiting for room, which then
+		// take their values back out of the table.
+		self.socket.close();
+		self.shared.push.close();
+
+		let values = {
+			let mut state = self.shared.state.lock().unwrap();
+			while state.sending > 0 {
+				state = self.shared.cvar.wait(state).unwrap();
+			}
+			mem::take(&mut state.values)
+		};
+
+		// The values might hold the other end of the channel, so they are dropped
+		// without holding the lock.
+		drop(values);
+	}
+}
+
+/// Error type for sends on a channel created with `channel`.
+///
+/// The value that could not be sent is handed back along with the reason.
+pub struct TypedSendError<T>
+{
+	/// The value that could not be sent.
+	pub value: T,
+
+	/// The reason the value could not be sent.
+	pub error: Error,
+}
+impl<T> TypedSendError<T>
+{
+	/// Splits the error into the value and the reason for the failure.
+	pub fn into_parts(self) -> (T, Error) { (self.value, self.error) }
+}
+
+impl<T> From<TypedSendError<T>> for Error
+{
+	fn from(e: TypedSendError<T>) -> Error { e.error }
+}
+
+impl<T> fmt::Debug for TypedSendError<T>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("TypedSendError").field("error", &self.error).finish()
+	}
+}
+
+impl<T> fmt::Display for TypedSendError<T>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(&self.error, f) }
+}
+
+impl<T> error::Error for TypedSendError<T> {}
+
+/// The part of a sender shared between its clones.
+struct SenderInner<T>
+{
+	/// The state shared with the receiver.
+	shared: Arc<Shared<T>>,
+}
+
+impl<T> Drop for SenderInner<T>
+{
+	fn drop(&mut self)
+	{
+		// Everything sent before this is received before the end of the channel,
+		// as there is only a single pipe. The send is asynchronous so that dropping
+		// the last sender doesn't wait for room. If the receiver is gone, it fails.
+		let end = frame(self.shared.generation, END_ID);
+		let _ = self.shared.push.send_async(&self.shared.end_aio, &end[..]);
+	}
+}
+
+/// The state shared between both ends of a channel.
+struct Shared<T>
+{
+	/// The address of the channel.
+	url: String,
+
+	/// Tells the messages of this channel apart from those of other sockets.
+	generation: u64,
+
+	/// The identifier of the next value that is sent.
+	next_id: AtomicU64,
+
+	/// The values that have been sent but not received.
+	state: Mutex<State<T>>,
+
+	/// Signaled whenever a send finishes.
+	cvar: Condvar,
+
+	/// The _push_ socket the identifiers are sent on.
+	push: Socket,
+
+	/// The AIO used to send the end of the channel.
+	end_aio: Aio,
+
+	/// Triggered once the end of the channel has been received.
+	ended: ShutdownToken,
+}
+
+/// The state protected by the mutex of a channel.
+struct State<T>
+{
+	/// The values that have been sent but not received, by identifier.
+	values: HashMap<u64, T>,
+
+	/// The number of sends that are waiting for NNG.
+	sending: usize,
+
+	/// Whether the receiver has been dropped.
+	closed: bool,
+}
+
+/// Creates the message body that refers to a value.
+fn frame(generation: u64, id: u64) -> [u8; 16]
+{
+	let mut frame = [0; 16];
+	frame[..8].copy_from_slice(&generation.to_be_bytes());
+	frame[8..].copy_from_slice(&id.to_be_bytes());
+	frame
+}
+
+/// Reads the generation and identifier out of a message body.
+fn parse_frame(body: &[u8]) -> Option<(u64, u64)>
+{
+	if body.len() != 16 {
+		return None;
+	}
+
+	let mut generation = [0; 8];
+	let mut id = [0; 8];
+	generation.copy_from_slice(&body[..8]);
+	id.copy_from_slice(&body[8..]);
+	Some((u64::from_be_bytes(generation), u64::from_be_bytes(id)))
+}
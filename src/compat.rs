@@ -0,0 +1,446 @@
+//! Detecting and avoiding incompatible peer configurations.
+//!
+//! Some misconfigurations are invisible until they cause messages to go
+//! missing. The most common one is a mismatched `RecvMaxSize`: NNG silently
+//! drops any message larger than the receiver allows, so a dialer using the
+//! default limit can appear perfectly healthy right up until the first large
+//! message is sent to it.
+//!
+//! Applications that control both ends of a connection can use this module to
+//! exchange their capabilities when the connection is first established. Each
+//! side calls `advertise` followed by `check`, which waits for the capabilities
+//! of the peer and returns a `CompatReport` listing any mismatches.
+//!
+//! This is an application level protocol that uses the normal send and receive
+//! path of the socket. It is therefore only usable with protocols where both
+//! sides can send to each other (such as _pair_ or _bus_) and the capability
+//! messages must be the first messages exchanged on the connection. Peers
+//! that use an older implementation of the protocols, and can't take part in
+//! the handshake, are covered by `nanomsg_mode` instead. This module is only
+//! available with the `compat` feature.
+//!
+//! ## Wire Format
+//!
+//! The capability message is versioned so that it can be extended in the
+//! future. Version 1 is laid out as follows, with all integers big-endian:
+//!
+//! | Bytes | Contents                                           |
+//! |-------|----------------------------------------------------|
+//! | 4     | The magic value `NNGC`                             |
+//! | 1     | The format version, currently `1`                  |
+//! | 8     | The `RecvMaxSize` of the sender, `0` for unlimited |
+//! | 4     | The application protocol version                   |
+//! | Rest  | The version of this crate used by the sender       |
+//!
+//! ## Legacy nanomsg Peers
+//!
+//! NNG speaks the same scalability protocols as nanomsg, its predecessor, as
+//! well as the bindings of other languages built on either of them, such as
+//! pynng. The two implementations differ in what they support and in the
+//! defaults of their options, however, which is summed up below:
+//!
+//! | Aspect             | nanomsg            | NNG 1.1                     | `nanomsg_mode`     |
+//! |--------------------|--------------------|-----------------------------|--------------------|
+//! | `tcp`, `ipc` wire  | SP framing         | Same framing                | -                  |
+//! | `ws` subprotocol   | See below          | `<protocol>.sp.nanomsg.org` | -                  |
+//! | _pair_ version 1   | Not available      | Available                   | Rejected           |
+//! | _sub_ topics       | Byte prefixes      | Byte prefixes               | -                  |
+//! | Request resend     | 60 s               | 60 s                        | 60 s               |
+//! | Survey deadline    | 1 s                | 1 s                         | 1 s                |
+//! | Receive limit      | 1 MiB              | 1 MiB                       | 1 MiB              |
+//! | Reconnect interval | 100 ms, no backoff | 1 s, no backoff             | 100 ms, no backoff |
+//! | Buffer sizes       | In bytes           | In messages                 | -                  |
+//!
+//! The transports frame messages identically, so a peer on `tcp` or `ipc`
+//! only has to use a matching protocol. Over websockets, an NNG dialer asks
+//! for the subprotocol named after the protocol of its _peer_, such as
+//! `rep.sp.nanomsg.org` for a _req_ socket, and an NNG listener only accepts
+//! the name of its own protocol. NNG 1.1 can't change the subprotocol, so a
+//! websocket peer that names it differently can't connect and must use `tcp`
+//! instead.
+//!
+//! Topics are compared byte for byte on both sides. Clients written in C
+//! commonly subscribe with `sizeof` of a string literal, which includes its
+//! terminating `NUL`, and then only match messages that have it as well.
+//! Likewise, a binding that encodes text topics, as pynng does with UTF-8,
+//! only matches publishers that use the same encoding.
+//!
+//! The receive limits and the resend and survey times have the same defaults,
+//! but `nanomsg_mode` sets them anyway, so that a socket configured elsewhere
+//! has the values legacy peers expect. Buffer sizes aren't translated, as a
+//! number of bytes says nothing about the number of messages.
+//!
+//! ## Example
+//!
+//! ```
+//! use std::{thread, time::Duration};
+//! use nng::{compat::{self, Capabilities, Mismatch}, options::{Options, RecvMaxSize}, *};
+//!
+//! const ADDRESS: &str = "inproc://nng/compat/example";
+//! const TIMEOUT: Duration = Duration::from_secs(1);
+//!
+//! let server = Socket::new(Protocol::Pair1)?;
+//! server.set_opt::<RecvMaxSize>(16 * 1024 * 1024)?;
+//! server.listen(ADDRESS)?;
+//!
+//! let client = Socket::new(Protocol::Pair1)?;
+//! client.dial(ADDRESS)?;
+//!
+//! // The server side runs in a separate thread, as a separate process would.
+//! let remote = thread::spawn(move || -> Result<_> {
+//!     let local = Capabilities::from_socket(&server, 1)?;
+//!     compat::advertise(&server, &local)?;
+//!     compat::check(&server, &local, TIMEOUT)
+//! });
+//!
+//! let local = Capabilities::from_socket(&client, 1)?;
+//! compat::advertise(&client, &local)?;
+//! let report = compat::check(&client, &local, TIMEOUT)?;
+//!
+//! // Both sides see the mismatch, from their own point of view.
+//! let expected = Mismatch::RecvMaxSize { local: 1024 * 1024, peer: 16 * 1024 * 1024 };
+//! assert_eq!(report.mismatches(), &[expected]);
+//! assert!(!report.is_compatible());
+//!
+//! let report = remote.join().unwrap()?;
+//! let expected = Mismatch::RecvMaxSize { local: 16 * 1024 * 1024, peer: 1024 * 1024 };
+//! assert_eq!(report.mismatches(), &[expected]);
+//! # Ok::<(), nng::Error>(())
+//! ```
+use std::{
+	convert::TryFrom,
+	fmt,
+	time::Duration,
+};
+
+use crate::{
+	aio::{Aio, AioResult},
+	error::{Error, Result},
+	message::Message,
+	options::{Options, RecvMaxSize, SocketConfig},
+	protocol::{Protocol, ProtocolKind},
+	socket::Socket,
+};
+
+/// The magic value at the start of every capability message.
+const MAGIC: &[u8; 4] = b"NNGC";
+
+/// The version of the capability message format.
+const FORMAT_VERSION: u8 = 1;
+
+/// The length of the fixed portion of a version 1 capability message.
+const HEADER_LEN: usize = 4 + 1 + 8 + 4;
+
+/// The default of `NN_RCVMAXSIZE` in nanomsg.
+const NANOMSG_RECV_MAX_SIZE: usize = 1024 * 1024;
+
+/// The default of `NN_RECONNECT_IVL` in nanomsg.
+const NANOMSG_RECONNECT: Duration = Duration::from_millis(100);
+
+/// The default of `NN_REQ_RESEND_IVL` in nanomsg.
+const NANOMSG_RESEND: Duration = Duration::from_secs(60);
+
+/// The default of `NN_SURVEYOR_DEADLINE` in nanomsg.
+const NANOMSG_SURVEY: Duration = Duration::from_secs(1);
+
+/// The capabilities of one side of a connection.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Capabilities
+{
+	/// The largest message the side is willing to receive, `0` if unlimited.
+	pub recv_max_size: usize,
+
+	/// The version of the application protocol, as chosen by the application.
+	pub protocol_version: u32,
+
+	/// The version of this crate used by the side.
+	pub crate_version: String,
+}
+impl Capabilities
+{
+	/// Creates the capabilities of the local side of the socket.
+	///
+	/// The receive limit is read from the `RecvMaxSize` option of the socket.
+	pub fn from_socket(socket: &Socket, protocol_version: u32) -> Result<Self>
+	{
+		Ok(Capabilities {
+			recv_max_size: socket.get_opt::<RecvMaxSize>()?,
+			protocol_version,
+			crate_version: env!("CARGO_PKG_VERSION").to_string(),
+		})
+	}
+
+	/// Encodes the capabilities into a capability message.
+	fn encode(&self) -> Message
+	{
+		let mut buf = Vec::with_capacity(HEADER_LEN + self.crate_version.len());
+		buf.extend_from_slice(MAGIC);
+		buf.push(FORMAT_VERSION);
+		buf.extend_from_slice(&(self.recv_max_size as u64).to_be_bytes());
+		buf.extend_from_slice(&self.protocol_version.to_be_bytes());
+		buf.extend_from_slice(self.crate_version.as_bytes());
+
+		Message::from(&buf[..])
+	}
+
+	/// Decodes a capability message.
+	///
+	/// Returns `None` if the message is not a capability message this version
+	/// of the crate understands.
+	fn decode(msg: &[u8]) -> Option<Self>
+	{
+		if msg.len() < HEADER_LEN || &msg[..4] != MAGIC || msg[4] != FORMAT_VERSION {
+			return None;
+		}
+
+		let mut size = [0; 8];
+		size.copy_from_slice(&msg[5..13]);
+		let mut version = [0; 4];
+		version.copy_from_slice(&msg[13..17]);
+
+		Some(Capabilities {
+			// A limit too large to represent locally is as good as unlimited.
+			recv_max_size: usize::try_from(u64::from_be_bytes(size)).unwrap_or(0),
+			protocol_version: u32::from_be_bytes(version),
+			crate_version: String::from_utf8_lossy(&msg[HEADER_LEN..]).into_owned(),
+		})
+	}
+}
+
+/// A single difference between the local and peer capabilities.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Mismatch
+{
+	/// The two sides accept different maximum message sizes.
+	///
+	/// Messages larger than the smaller of the two limits are silently dropped
+	/// when sent towards that side. A value of `0` means unlimited.
+	RecvMaxSize
+	{
+		/// The local `RecvMaxSize`.
+		local: usize,
+
+		/// The `RecvMaxSize` of the peer.
+		peer: usize,
+	},
+
+	/// The two sides use different versions of the application protocol.
+	ProtocolVersion
+	{
+		/// The local protocol version.
+		local: u32,
+
+		/// The protocol version of the peer.
+		peer: u32,
+	},
+}
+
+impl fmt::Display for Mismatch
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self {
+			Mismatch::RecvMaxSize { local, peer } => {
+				write!(f, "maximum receive size differs (local {}, peer {})", local, peer)
+			},
+			Mismatch::ProtocolVersion { local, peer } => {
+				write!(f, "protocol version differs (local {}, peer {})", local, peer)
+			},
+		}
+	}
+}
+
+/// The result of comparing the local capabilities against those of the peer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompatReport
+{
+	/// The capabilities of the peer, if they are known.
+	peer: Option<Capabilities>,
+
+	/// The differences between the two sides.
+	mismatches: Vec<Mismatch>,
+}
+impl CompatReport
+{
+	/// Returns the capabilities advertised by the peer.
+	///
+	/// This is `None` if the peer did not advertise its capabilities before
+	/// the timeout or sent something that is not a capability message. This
+	/// usually means that the peer does not take part in the handshake.
+	pub fn peer(&self) -> Option<&Capabilities> { self.peer.as_ref() }
+
+	/// Returns the differences between the local and peer capabilities.
+	pub fn mismatches(&self) -> &[Mismatch] { &self.mismatches }
+
+	/// Returns `true` if the peer capabilities are known and match.
+	pub fn is_compatible(&self) -> bool { self.peer.is_some() && self.mismatches.is_empty() }
+}
+
+impl fmt::Display for CompatReport
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		if self.peer.is_none() {
+			return write!(f, "unknown peer capabilities");
+		}
+
+		if self.mismatches.is_empty() {
+			return write!(f, "compatible");
+		}
+
+		for (i, m) in self.mismatches.iter().enumerate() {
+			if i != 0 {
+				write!(f, "; ")?;
+			}
+			write!(f, "{}", m)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Sends the local capabilities to the peer.
+///
+/// This is a normal, blocking send on the socket.
+pub fn advertise(socket: &Socket, local: &Capabilities) -> Result<()>
+{
+	socket.send(local.encode()).map_err(|e| e.error)
+}
+
+/// Waits for the capabilities of the peer and compares them to the local ones.
+///
+/// This receives a single message from the socket. If no message arrives
+/// before the timeout or the message is not a capability message, the
+/// returned report has no peer capabilities instead of failing, as that is
+/// the expected outcome when the peer does not take part in the handshake.
+/// Other errors, such as the socket being closed, are returned as usual.
+///
+/// A message that is not a capability message is consumed by this function.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nng::{compat::{self, Capabilities}, *};
+///
+/// const ADDRESS: &str = "inproc://nng/compat/unknown";
+///
+/// let server = Socket::new(Protocol::Pair1)?;
+/// server.listen(ADDRESS)?;
+///
+/// // The server never advertises its capabilities.
+/// let client = Socket::new(Protocol::Pair1)?;
+/// client.dial(ADDRESS)?;
+///
+/// let local = Capabilities::from_socket(&client, 1)?;
+/// compat::advertise(&client, &local)?;
+/// let report = compat::check(&client, &local, Duration::from_millis(100))?;
+///
+/// assert!(report.peer().is_none());
+/// assert!(!report.is_compatible());
+/// assert_eq!(report.to_string(), "unknown peer capabilities");
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub fn check(socket: &Socket, local: &Capabilities, timeout: Duration) -> Result<CompatReport>
+{
+	let peer = match recv_timeout(socket, timeout) {
+		Ok(m) => Capabilities::decode(&m),
+		Err(Error::TimedOut) => None,
+		Err(e) => return Err(e),
+	};
+
+	let mut mismatches = Vec::new();
+	if let Some(p) = &peer {
+		if p.recv_max_size != local.recv_max_size {
+			mismatches.push(Mismatch::RecvMaxSize { local: local.recv_max_size, peer: p.recv_max_size });
+		}
+
+		if p.protocol_version != local.protocol_version {
+			mismatches.push(Mismatch::ProtocolVersion {
+				local: local.protocol_version,
+				peer:  p.protocol_version,
+			});
+		}
+	}
+
+	Ok(CompatReport { peer, mismatches })
+}
+
+/// Returns the configuration matching the defaults of nanomsg for the protocol.
+///
+/// This sets the options listed in the module documentation, with a
+/// `reconnect_max_time` of zero, which disables the backoff as
+/// `NN_RECONNECT_IVL_MAX` does. The result can be adjusted further before it
+/// is applied with `Socket::apply_config`, which is what `nanomsg_mode` does.
+///
+/// This returns `Error::NotSupported` for _pair_ version 1, which nanomsg
+/// doesn't have, and for custom protocols.
+pub fn nanomsg_config<P: Into<ProtocolKind>>(protocol: P) -> Result<SocketConfig>
+{
+	let protocol = match protocol.into() {
+		ProtocolKind::Known(Protocol::Pair1) | ProtocolKind::Custom(_) => {
+			return Err(Error::NotSupported);
+		},
+		ProtocolKind::Known(p) => p,
+	};
+
+	let (resend_time, survey_time) = match protocol {
+		Protocol::Req0 => (Some(Some(NANOMSG_RESEND)), None),
+		Protocol::Surveyor0 => (None, Some(Some(NANOMSG_SURVEY))),
+		_ => (None, None),
+	};
+
+	Ok(SocketConfig {
+		recv_max_size: Some(NANOMSG_RECV_MAX_SIZE),
+		reconnect_min_time: Some(Some(NANOMSG_RECONNECT)),
+		reconnect_max_time: Some(Some(Duration::from_millis(0))),
+		resend_time,
+		survey_time,
+		..SocketConfig::default()
+	})
+}
+
+/// Configures the socket to talk to peers that use nanomsg.
+///
+/// This applies `nanomsg_config` for the protocol of the socket. NNG passes
+/// the reconnect times and the receive limit on to the dialers and listeners
+/// of the socket, including those that already exist. A _pair_ version 1
+/// socket fails with `Error::NotSupported` without being changed.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nng::{compat, options::{Options, ReconnectMinTime, protocol::reqrep::ResendTime}, *};
+///
+/// let req = Socket::new(Protocol::Req0)?;
+/// compat::nanomsg_mode(&req)?;
+/// assert_eq!(req.get_opt::<ReconnectMinTime>()?, Some(Duration::from_millis(100)));
+/// assert_eq!(req.get_opt::<ResendTime>()?, Some(Duration::from_secs(60)));
+///
+/// let pair = Socket::new(Protocol::Pair1)?;
+/// assert_eq!(compat::nanomsg_mode(&pair), Err(Error::NotSupported));
+/// assert_eq!(pair.get_opt::<ReconnectMinTime>()?, Some(Duration::from_secs(1)));
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub fn nanomsg_mode(socket: &Socket) -> Result<()>
+{
+	socket.apply_config(&nanomsg_config(socket.protocol())?)
+}
+
+/// Receives a message, giving up after the timeout.
+///
+/// This uses an AIO so that the receive timeout of the socket is left alone.
+fn recv_timeout(socket: &Socket, timeout: Duration) -> Result<Message>
+{
+	let aio = Aio::new_manual()?;
+	aio.set_timeout(Some(timeout))?;
+	socket.recv_async(&aio)?;
+	aio.wait();
+
+	match aio.result() {
+		Some(AioResult::RecvOk(m)) => Ok(m),
+		Some(AioResult::RecvErr(e)) => Err(e),
+		_ => Err(Error::Internal),
+	}
+}
@@ -0,0 +1,111 @@
+//! Compression of message bodies.
+// Without any of the algorithms enabled, large parts of this go unused.
+#![cfg_attr(not(any(feature = "compression-zstd", feature = "compression-lz4")), allow(unused))]
+use std::convert::TryFrom;
+
+use crate::error::{Error, Result};
+
+/// The magic value at the start of the compression header.
+const MAGIC: &[u8; 4] = b"NNGZ";
+
+/// The length of the compression header.
+const HEADER_LEN: usize = 9;
+
+/// The default limit on the size of a decompressed body.
+///
+/// This is the default `RecvMaxSize` of NNG, so that a compressed message can't
+/// expand to anything larger than what would have been accepted uncompressed.
+pub(crate) const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 1024 * 1024;
+
+/// A compression algorithm for message bodies.
+///
+/// Each algorithm is only available with its own feature, `compression-zstd`
+/// or `compression-lz4`. See `Message::compress` for how the compressed body
+/// is laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Compression
+{
+	/// Zstandard at its default level, stored as a standard Zstandard frame.
+	#[cfg(feature = "compression-zstd")]
+	Zstd,
+
+	/// LZ4, stored as a single LZ4 block without a frame.
+	#[cfg(feature = "compression-lz4")]
+	Lz4,
+}
+
+impl Compression
+{
+	/// Returns the identifier of the algorithm in the compression header.
+	const fn id(self) -> u8
+	{
+		match self {
+			#[cfg(feature = "compression-zstd")]
+			Compression::Zstd => 1,
+			#[cfg(feature = "compression-lz4")]
+			Compression::Lz4 => 2,
+		}
+	}
+}
+
+/// Returns whether the data starts with the compression magic.
+pub(crate) fn is_compressed(data: &[u8]) -> bool { data.starts_with(MAGIC) }
+
+/// Compresses the data, including the header.
+///
+/// Returns `None` if the result wouldn't be any smaller than the data.
+pub(crate) fn compress(data: &[u8], algo: Compression) -> Result<Option<Vec<u8>>>
+{
+	let len = u32::try_from(data.len()).map_err(|_| Error::MessageTooLarge)?;
+
+	let mut out = Vec::with_capacity(HEADER_LEN + data.len());
+	out.extend_from_slice(MAGIC);
+	out.push(algo.id());
+	out.extend_from_slice(&len.to_be_bytes());
+
+	match algo {
+		#[cfg(feature = "compression-zstd")]
+		Compression::Zstd => {
+			let compressed = zstd::bulk::compress(data, 0).map_err(|_| Error::Internal)?;
+			out.extend_from_slice(&compressed);
+		},
+		#[cfg(feature = "compression-lz4")]
+		Compression::Lz4 => out.extend_from_slice(&lz4_flex::block::compress(data)),
+	}
+
+	Ok(if out.len() < data.len() { Some(out) } else { None })
+}
+
+/// Decompresses data starting with a compression header.
+///
+/// The caller has to make sure that the data starts with the magic value.
+pub(crate) fn decompress(data: &[u8], max: usize) -> Result<Vec<u8>>
+{
+	if data.len() < HEADER_LEN {
+		return Err(Error::DecompressionFailed);
+	}
+
+	let mut len = [0; 4];
+	len.copy_from_slice(&data[5..HEADER_LEN]);
+	let len = u32::from_be_bytes(len) as usize;
+	if len > max {
+		return Err(Error::MessageExceedsLimit { size: len, limit: max });
+	}
+
+	// The buffer is only as large as the length in the header, so corrupt or
+	// malicious data can't make either algorithm write any more than that.
+	let mut out = vec![0; len];
+	let input = &data[HEADER_LEN..];
+	let written: Option<usize> = match data[4] {
+		#[cfg(feature = "compression-zstd")]
+		1 => zstd::bulk::decompress_to_buffer(input, &mut out[..]).ok(),
+		#[cfg(feature = "compression-lz4")]
+		2 => lz4_flex::block::decompress_into(input, &mut out).ok(),
+		_ => return Err(Error::NotSupported),
+	};
+
+	match written {
+		Some(n) if n == len => Ok(out),
+		_ => Err(Error::DecompressionFailed),
+	}
+}
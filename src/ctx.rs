@@ -2,12 +2,16 @@ use std::{
 	cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd},
 	hash::{Hash, Hasher},
 	sync::Arc,
+	time::{Duration, Instant},
 };
 
+#[cfg(feature = "async-api")]
+use crate::future::{PreReceived, RecvFuture, RequestFuture, SendFuture};
 use crate::{
-	aio::Aio,
-	error::{Result, SendResult},
+	aio::{Aio, AioResult},
+	error::{Error, Result, SendResult},
 	message::Message,
+	options::{protocol::reqrep::ResendTime, Options},
 	socket::Socket,
 };
 
@@ -24,6 +28,80 @@ use crate::{
 ///
 /// See the documentation of the `Aio` type for examples on how to use Socket
 /// Contexts.
+///
+/// ## Fairness
+///
+/// On a _rep_ socket, the contexts that are waiting to receive take turns in
+/// the order they started waiting: each request goes to the context that has
+/// been waiting the longest. Contexts have no queue of their own, so a request
+/// only ever goes to a context that is waiting for one, and a context that is
+/// still busy with a request doesn't hold up any of the others. When no
+/// context is waiting, every connection holds on to at most one request until
+/// one is. NNG has no options that change this.
+///
+/// NNG doesn't know how long a context takes to reply, though. A slow context
+/// gets its turn like any other whenever it is waiting, so at low load it gets
+/// as many requests as the fast ones and each of those takes its time. Under
+/// load it simply gets fewer, as it spends less time waiting. A context that
+/// doesn't want more work shouldn't receive until it does.
+///
+/// Three fast contexts and a slow one, serving four clients:
+///
+/// ```
+/// use std::{collections::HashMap, sync::{Arc, Mutex}, thread, time::Duration};
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/ctx/fairness";
+///
+/// let server = Socket::new(Protocol::Rep0)?;
+/// server.listen(ADDRESS)?;
+///
+/// let served = Arc::new(Mutex::new(HashMap::new()));
+/// for &(name, delay) in &[("a", 1), ("b", 1), ("c", 1), ("slow", 200)] {
+///     let ctx = Context::new(&server)?;
+///     let served = Arc::clone(&served);
+///     thread::spawn(move || -> Result<()> {
+///         let aio = Aio::new_manual()?;
+///         loop {
+///             ctx.recv(&aio)?;
+///             aio.wait();
+///             let msg = match aio.result() {
+///                 Some(AioResult::RecvOk(m)) => m,
+///                 _ => return Ok(()),
+///             };
+///
+///             thread::sleep(Duration::from_millis(delay));
+///             *served.lock().unwrap().entry(name).or_insert(0) += 1;
+///             ctx.send(&aio, msg)?;
+///             aio.wait();
+///         }
+///     });
+/// }
+/// # thread::sleep(Duration::from_millis(50));
+///
+/// let clients: Vec<_> = (0..4).map(|_| thread::spawn(|| -> Result<()> {
+///     let client = Socket::new(Protocol::Req0)?;
+///     client.dial(ADDRESS)?;
+///     for _ in 0..10 {
+///         client.send(&b"work"[..])?;
+///         client.recv()?;
+///     }
+///     Ok(())
+/// })).collect();
+/// for client in clients {
+///     client.join().unwrap()?;
+/// }
+///
+/// // The fast contexts share most of the work, the slow one still gets a few
+/// // turns.
+/// let served = served.lock().unwrap();
+/// assert_eq!(served.values().sum::<u32>(), 40);
+/// assert!((1..=5).contains(&served["slow"]));
+/// for name in &["a", "b", "c"] {
+///     assert!(served[name] >= 8, "{} served {}", name, served[name]);
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
 #[derive(Clone, Debug)]
 pub struct Context
 {
@@ -42,7 +120,13 @@ impl Context
 		let mut ctx = nng_sys::nng_ctx::NNG_CTX_INITIALIZER;
 		let rv = unsafe { nng_sys::nng_ctx_open(&mut ctx as _, socket.handle()) };
 
-		rv2res!(rv, Context { inner: Arc::new(Inner { ctx }) })
+		rv2res!(rv, Context {
+			inner: Arc::new(Inner {
+				ctx,
+				#[cfg(feature = "async-api")]
+				pre_received: Arc::default(),
+			}),
+		})
 	}
 
 	/// Send a message using the context asynchronously.
@@ -61,8 +145,160 @@ impl Context
 	/// This function will return immediately. If there is already an I/O
 	/// operation in progress that is _not_ a receive operation, this function
 	/// will return `ErrorKind::TryAgain`.
+	///
+	/// The message is always allocated by NNG, as described for
+	/// `Socket::recv_async`, so there is no way to receive into a supplied one.
 	pub fn recv(&self, aio: &Aio) -> Result<()> { aio.recv_ctx(self) }
 
+	/// Returns a future that receives a message on the context.
+	///
+	/// The receive starts when the future is first polled. See the `future`
+	/// module for how it interacts with executors.
+	#[cfg(feature = "async-api")]
+	pub fn recv_future(&self) -> RecvFuture { RecvFuture::context(self) }
+
+	/// Returns a future that sends the message on the context.
+	///
+	/// The send starts when the future is first polled. See `SendFuture` for
+	/// what happens to the message when the send fails.
+	#[cfg(feature = "async-api")]
+	pub fn send_future<M: Into<Message>>(&self, msg: M) -> SendFuture
+	{
+		SendFuture::context(self, msg.into())
+	}
+
+	/// Returns a future that sends the request on a _req_ context and
+	/// resolves with the reply.
+	///
+	/// The request is sent when the future is first polled. Dropping the
+	/// future abandons the request, see `RequestFuture`.
+	#[cfg(feature = "async-api")]
+	pub fn request<M: Into<Message>>(&self, msg: M) -> RequestFuture
+	{
+		RequestFuture::new(self, msg.into())
+	}
+
+	/// Sets the time after which a _req_ context resends its request, or turns
+	/// the resends off with `None`.
+	///
+	/// This is the `ResendTime` option of the context, which starts out with
+	/// the value of the socket when the context is created and can then be
+	/// changed without affecting the socket or any of its other contexts.
+	///
+	/// NNG 1.1 resends right away rather than never when the option is
+	/// infinite, so `None` sets the longest time NNG can represent instead, a
+	/// little under 25 days. A zero duration would resend constantly and
+	/// returns `Error::InvalidInput`. Returns `Error::NotSupported` if the
+	/// context does not belong to a _req_ socket.
+	pub fn set_resend_time(&self, time: Option<Duration>) -> Result<()>
+	{
+		match time {
+			Some(d) if d == Duration::from_millis(0) => Err(Error::InvalidInput),
+			Some(d) => self.set_opt::<ResendTime>(Some(d)),
+			None => self.set_opt::<ResendTime>(Some(Duration::from_millis(i32::MAX as u64))),
+		}
+	}
+
+	/// Sends a request on a _req_ context that is never resent and waits for
+	/// the reply until the timeout.
+	///
+	/// A _req_ socket resends requests that haven't been answered within the
+	/// `ResendTime`, so a request whose reply is merely slow is executed again
+	/// by the server. That is harmless for idempotent requests but not, say,
+	/// for a payment. This turns the resends of the context off with
+	/// `Context::set_resend_time` and then sends the request and receives the
+	/// reply like `Socket::request_with_deadline`, so the timeout covers both.
+	/// The setting stays on the context afterwards. Contexts are cheap, so
+	/// opening one for each such request, or keeping one around just for
+	/// them, leaves the other requests of the socket with their resends.
+	///
+	/// If this returns `Error::TimedOut`, the request may or may not have been
+	/// executed, and NNG discards a reply that arrives later. NNG 1.1 also
+	/// still resends a request if the connection it was sent on is lost before
+	/// the reply arrives, as it can't tell whether the server received it,
+	/// and there is no option to prevent that. Requests that must not be
+	/// executed twice even then need an identifier the server can recognize.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{sync::{Arc, Mutex}, thread, time::Duration};
+	/// use nng::{options::{protocol::reqrep::ResendTime, Options}, *};
+	///
+	/// const ADDRESS: &str = "inproc://nng/ctx/request_no_resend";
+	///
+	/// // The server counts how many times it executes every request and takes
+	/// // longer to reply than the client waits before resending.
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// server.listen(ADDRESS)?;
+	/// let executed = Arc::new(Mutex::new(Vec::new()));
+	/// let log = Arc::clone(&executed);
+	/// thread::spawn(move || while let Ok(msg) = server.recv() {
+	///     log.lock().unwrap().push(msg.as_slice().to_vec());
+	///     thread::sleep(Duration::from_millis(100));
+	///     let _ = server.send(msg);
+	/// });
+	/// let count = |body: &[u8]| executed.lock().unwrap().iter().filter(|b| *b == body).count();
+	///
+	/// let client = Socket::new(Protocol::Req0)?;
+	/// client.set_opt::<ResendTime>(Some(Duration::from_millis(20)))?;
+	/// client.dial(ADDRESS)?;
+	///
+	/// // Without resends, the slow reply arrives and the charge happens once.
+	/// let ctx = Context::new(&client)?;
+	/// let reply = ctx.request_no_resend(&b"charge"[..], Duration::from_secs(5))?;
+	/// assert_eq!(&reply[..], b"charge");
+	/// thread::sleep(Duration::from_millis(300));
+	/// assert_eq!(count(b"charge"), 1);
+	///
+	/// // A context with the resends of the socket has the server do it again.
+	/// let ctx = Context::new(&client)?;
+	/// let aio = Aio::new_manual()?;
+	/// ctx.send(&aio, &b"retry"[..]).unwrap();
+	/// aio.wait();
+	/// ctx.recv(&aio)?;
+	/// aio.wait();
+	/// thread::sleep(Duration::from_millis(300));
+	/// assert!(count(b"retry") > 1);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn request_no_resend<M: Into<Message>>(
+		&self,
+		msg: M,
+		timeout: Duration,
+	) -> Result<Message>
+	{
+		self.set_resend_time(None)?;
+		if timeout == Duration::from_millis(0) {
+			return Err(Error::TimedOut);
+		}
+		// A timeout too long for an `Instant` has no deadline.
+		let deadline = Instant::now().checked_add(timeout);
+
+		let aio = Aio::new_manual()?;
+		aio.set_timeout(Some(timeout))?;
+		self.send(&aio, msg).map_err(|e| e.error)?;
+		aio.wait();
+		match aio.result() {
+			Some(AioResult::SendOk) => {},
+			Some(AioResult::SendErr(_, e)) | Some(AioResult::SendErrLost(e)) => return Err(e),
+			res => unreachable!("Send AIO completed with a non-send result: {:?}", res),
+		}
+
+		// As in `Socket::request_with_deadline`, the extra millisecond keeps a
+		// receive that is about to time out from failing before it is queued,
+		// which would keep the request instead of abandoning it.
+		let remaining = deadline.map_or(timeout, |d| d.saturating_duration_since(Instant::now()));
+		aio.set_timeout(Some(remaining.saturating_add(Duration::from_millis(1))))?;
+		self.recv(&aio)?;
+		aio.wait();
+		match aio.result() {
+			Some(AioResult::RecvOk(m)) => Ok(m),
+			Some(AioResult::RecvErr(e)) => Err(e),
+			res => unreachable!("Receive AIO completed with a non-receive result: {:?}", res),
+		}
+	}
+
 	/// Closes the context.
 	///
 	/// Messages that have been submitted for sending may be flushed or
@@ -77,6 +313,10 @@ impl Context
 
 	/// Returns the inner `nng_ctx` object.
 	pub(crate) fn handle(&self) -> nng_sys::nng_ctx { self.inner.ctx }
+
+	/// Returns the messages received by dropped futures, see `RecvFuture`.
+	#[cfg(feature = "async-api")]
+	pub(crate) fn pre_received(&self) -> &Arc<PreReceived> { &self.inner.pre_received }
 }
 
 impl PartialEq for Context
@@ -144,6 +384,10 @@ expose_options!{
 struct Inner
 {
 	ctx: nng_sys::nng_ctx,
+
+	/// The messages received by dropped futures, see `RecvFuture`.
+	#[cfg(feature = "async-api")]
+	pre_received: Arc<PreReceived>,
 }
 impl Inner
 {
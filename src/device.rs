@@ -0,0 +1,72 @@
+//! Forwarding messages between raw sockets.
+use crate::{error::Result, socket::Socket};
+
+/// Forwards messages between two raw sockets until one of them is closed.
+///
+/// Every message received on one socket is sent on the other, in both
+/// directions, along with its header. This is how brokers are built: for
+/// example, a raw _rep_ socket that the clients dial and a raw _req_ socket
+/// that the workers dial make a broker that spreads the requests over the
+/// workers and routes each reply back to the client that sent the request.
+///
+/// The sockets must both be in raw mode, as created with `Socket::new_raw`,
+/// and use protocols that are each other's peers, such as _rep_ and _req_ or
+/// _pull_ and _push_. Otherwise, this returns `Error::InvalidInput`.
+///
+/// This blocks the calling thread and returns the error that stopped the
+/// forwarding, which is `Error::Closed` when one of the sockets was closed.
+/// Both sockets should be closed once this returns.
+///
+/// See the [nng documentation][1] for more information.
+///
+/// ## Example
+///
+/// A broker in front of three workers:
+///
+/// ```
+/// use std::{collections::HashMap, thread, time::Duration};
+/// use nng::*;
+///
+/// const FRONT: &str = "inproc://nng/device/front";
+/// const BACK: &str = "inproc://nng/device/back";
+///
+/// let front = Socket::new_raw(Protocol::Rep0)?;
+/// front.listen(FRONT)?;
+/// let back = Socket::new_raw(Protocol::Req0)?;
+/// back.listen(BACK)?;
+///
+/// let (f, b) = (front.clone(), back.clone());
+/// let broker = thread::spawn(move || forwarder(&f, &b));
+///
+/// // Each worker answers with its own name.
+/// for name in &["a", "b", "c"] {
+///     let worker = Socket::rep0()?;
+///     worker.dial(BACK)?;
+///     thread::spawn(move || {
+///         while let Ok(_) = worker.recv() {
+///             worker.send(name.as_bytes()).unwrap();
+///         }
+///     });
+/// }
+/// # thread::sleep(Duration::from_millis(50));
+///
+/// let client = Socket::req0()?;
+/// client.dial(FRONT)?;
+/// let mut served = HashMap::new();
+/// for _ in 0..30 {
+///     client.send(&b"work"[..])?;
+///     *served.entry(client.recv()?.as_slice().to_vec()).or_insert(0) += 1;
+/// }
+/// assert_eq!(served.len(), 3);
+///
+/// front.close();
+/// assert_eq!(broker.join().unwrap(), Err(Error::Closed));
+/// # Ok::<(), nng::Error>(())
+/// ```
+///
+/// [1]: https://nanomsg.github.io/nng/man/v1.1.0/nng_device.3.html
+pub fn forwarder(s1: &Socket, s2: &Socket) -> Result<()>
+{
+	let rv = unsafe { nng_sys::nng_device(s1.handle(), s2.handle()) };
+	rv2res!(rv)
+}
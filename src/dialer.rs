@@ -2,7 +2,8 @@
 //!
 //! A dialer is responsible for establishing and maintaining outgoing
 //! connections. If a connection is ever broken, or fails, the dialer object
-//! automatically attempts to reconnect.
+//! automatically attempts to reconnect, unless it was started with
+//! `DialerOptions::no_reconnect`.
 //!
 //! Directly creating a dialer object is only necessary when one wishes to
 //! configure the connection before opening it or if one wants to close the
@@ -20,13 +21,20 @@
 //! [1]: https://nanomsg.github.io/nng/man/v1.1.0/nng_dialer.5.html
 use std::{
 	cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd},
+	collections::HashMap,
 	ffi::CString,
+	fmt,
 	hash::{Hash, Hasher},
+	sync::{Arc, Mutex},
 };
 
 use crate::{
-	error::{Error, Result},
+	error::{Error, Result, TemplateError},
+	options::{private::OptOps, Options, SetOpt, Url},
+	pipe::{Pipe, PipeEvent},
 	socket::Socket,
+	tls::TlsConfig,
+	util::missing_is_closed,
 };
 
 /// A constructed and running dialer.
@@ -34,11 +42,14 @@ use crate::{
 /// This dialer has already been started on the socket and will continue
 /// serving the connection until either it is explicitly closed or the owning
 /// socket is closed.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone)]
 pub struct Dialer
 {
 	/// The handle to the underlying
 	handle: nng_sys::nng_dialer,
+
+	/// The URL the dialer was created with.
+	url: Arc<str>,
 }
 impl Dialer
 {
@@ -61,9 +72,19 @@ impl Dialer
 			nng_sys::nng_dial(socket.handle(), addr.as_ptr(), &mut handle as *mut _, flags as i32)
 		};
 
-		rv2res!(rv, Dialer { handle })
+		rv2res!(rv, Dialer { handle, url: url.into() })
 	}
 
+	/// Returns the ID of the dialer, which is unique among the dialers of all
+	/// sockets and stays the same after it is closed.
+	pub fn id(&self) -> i32 { unsafe { nng_sys::nng_dialer_id(self.handle) } }
+
+	/// Returns the URL the dialer was created with.
+	///
+	/// This is the URL as it was given and is kept after the dialer is
+	/// closed.
+	pub fn url(&self) -> &str { &self.url }
+
 	/// Closes the dialer.
 	///
 	/// This also closes any `Pipe` objects that have been created by the
@@ -72,18 +93,52 @@ impl Dialer
 	/// utilize the dialer (with this or any other handle) will result in
 	/// an error.
 	///
+	/// Closing a dialer that is already closed, including through another
+	/// handle or by closing its socket, does nothing and returns `Ok`.
+	///
 	/// Dialers are implicitly closed when the socket they are associated with
 	/// is closed. Dialers are _not_ closed when all handles are dropped.
-	pub fn close(self)
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::*;
+	///
+	/// let server = Socket::new(Protocol::Pull0)?;
+	/// server.listen("inproc://nng/dialer/close")?;
+	///
+	/// let client = Socket::new(Protocol::Push0)?;
+	/// let dialer = Dialer::new(&client, "inproc://nng/dialer/close", false)?;
+	/// assert_eq!(dialer.to_string(), format!("dialer#{} inproc://nng/dialer/close", dialer.id()));
+	///
+	/// dialer.close()?;
+	/// dialer.close()?;
+	/// assert!(dialer.is_closed());
+	/// assert_eq!(dialer.url(), "inproc://nng/dialer/close");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn close(&self) -> Result<()>
 	{
-		// Closing the dialer should only ever result in success or ECLOSED and
-		// both of those mean that the drop was successful.
+		// Closing the dialer should only ever result in success, ECLOSED, or ENOENT
+		// if it or its socket has already been closed. All of those mean that it is
+		// closed now.
 		let rv = unsafe { nng_sys::nng_dialer_close(self.handle) };
-		assert!(
-			rv == 0 || rv == nng_sys::NNG_ECLOSED as i32,
-			"Unexpected error code while closing dialer ({})",
-			rv
-		);
+		if rv == nng_sys::NNG_ECLOSED as i32 || rv == nng_sys::NNG_ENOENT as i32 {
+			return Ok(());
+		}
+
+		rv2res!(rv)
+	}
+
+	/// Returns whether the dialer has been closed, through any handle or by
+	/// closing its socket.
+	pub fn is_closed(&self) -> bool
+	{
+		// Any option will do, as a dialer that is closed can no longer be found.
+		let mut raw = false;
+		let name = nng_sys::NNG_OPT_RAW as *const _ as *const _;
+		let rv = unsafe { nng_sys::nng_dialer_getopt_bool(self.handle, name, &mut raw as *mut _) };
+		rv == nng_sys::NNG_ECLOSED as i32 || rv == nng_sys::NNG_ENOENT as i32
 	}
 
 	/// Create a new Dialer handle from a libnng handle.
@@ -92,7 +147,13 @@ impl Dialer
 	pub(crate) fn from_nng_sys(handle: nng_sys::nng_dialer) -> Self
 	{
 		assert!(unsafe { nng_sys::nng_dialer_id(handle) > 0 }, "Dialer handle is not initialized");
-		Dialer { handle }
+
+		// These are found through their pipes, so the URL has to be looked up.
+		let mut dialer = Dialer { handle, url: Arc::from("") };
+		if let Ok(url) = dialer.get_opt::<Url>() {
+			dialer.url = url.into();
+		}
+		dialer
 	}
 }
 
@@ -132,9 +193,26 @@ impl Hash for Dialer
 	}
 }
 
+impl fmt::Debug for Dialer
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("Dialer").field("id", &self.id()).field("url", &self.url).finish()
+	}
+}
+
+impl fmt::Display for Dialer
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "dialer#{} {}", self.id(), self.url)
+	}
+}
+
 #[rustfmt::skip]
 expose_options!{
 	Dialer :: handle -> nng_sys::nng_dialer;
+	MISSING_IS_CLOSED = true;
 
 	GETOPT_BOOL = nng_sys::nng_dialer_getopt_bool;
 	GETOPT_INT = nng_sys::nng_dialer_getopt_int;
@@ -175,6 +253,15 @@ pub struct DialerOptions
 {
 	/// The underlying dialer object that we are configuring
 	handle: nng_sys::nng_dialer,
+
+	/// The URL the dialer was created with.
+	url: String,
+
+	/// The socket the dialer belongs to.
+	socket: Socket,
+
+	/// Whether the dialer is closed instead of reconnecting.
+	no_reconnect: bool,
 }
 impl DialerOptions
 {
@@ -194,7 +281,97 @@ impl DialerOptions
 			nng_sys::nng_dialer_create(&mut handle as *mut _, socket.handle(), addr.as_ptr())
 		};
 
-		rv2res!(rv, DialerOptions { handle })
+		rv2res!(rv, DialerOptions {
+			handle,
+			url: url.to_string(),
+			socket: socket.clone(),
+			no_reconnect: false,
+		})
+	}
+
+	/// Sets whether the dialer is closed once its connection is lost, rather
+	/// than redialing.
+	///
+	/// NNG has no option to turn reconnecting off, and a `ReconnectMinTime` of
+	/// zero makes it redial right away rather than never. Instead, the crate
+	/// follows the pipe events of the socket and closes the dialer as soon as
+	/// the pipe it established is removed. This happens before NNG would
+	/// schedule the redial, so the dialer never connects a second time and
+	/// `Dialer::is_closed` returns `true` from then on. Pipe events are
+	/// followed alongside any callback set with `Socket::pipe_notify`.
+	///
+	/// This only concerns connections that were established. A dialer started
+	/// in nonblocking mode still retries until its first connection succeeds.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{sync::{mpsc, Mutex}, thread, time::Duration};
+	/// use nng::{options::{Options, ReconnectMinTime}, *};
+	///
+	/// const ADDRESS: &str = "inproc://nng/dialer/no_reconnect";
+	/// let retry = Some(Duration::from_millis(10));
+	///
+	/// let server = Socket::new(Protocol::Pull0)?;
+	/// let (tx, added) = mpsc::channel();
+	/// let tx = Mutex::new(tx);
+	/// server.pipe_notify(move |_, ev| if ev == PipeEvent::AddPost {
+	///     tx.lock().unwrap().send(()).unwrap();
+	/// })?;
+	/// let listener = Listener::new(&server, ADDRESS, false)?;
+	///
+	/// let client = Socket::new(Protocol::Push0)?;
+	/// let options = DialerOptions::new(&client, ADDRESS)?.no_reconnect(true);
+	/// options.set_opt::<ReconnectMinTime>(retry)?;
+	/// let dialer = options.start(false).map_err(|(_, e)| e)?;
+	/// added.recv_timeout(Duration::from_secs(1)).unwrap();
+	///
+	/// // Losing the connection closes the dialer, which stays away once the
+	/// // listener is back.
+	/// listener.close()?;
+	/// let listener = Listener::new(&server, ADDRESS, false)?;
+	/// thread::sleep(Duration::from_millis(100));
+	/// assert!(dialer.is_closed());
+	/// assert!(added.try_recv().is_err());
+	///
+	/// // Other dialers reconnect as usual.
+	/// let options = DialerOptions::new(&client, ADDRESS)?;
+	/// options.set_opt::<ReconnectMinTime>(retry)?;
+	/// let dialer = options.start(false).map_err(|(_, e)| e)?;
+	/// added.recv_timeout(Duration::from_secs(1)).unwrap();
+	///
+	/// listener.close()?;
+	/// Listener::new(&server, ADDRESS, false)?;
+	/// added.recv_timeout(Duration::from_secs(1)).unwrap();
+	/// assert!(!dialer.is_closed());
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn no_reconnect(mut self, enabled: bool) -> Self
+	{
+		self.no_reconnect = enabled;
+		self
+	}
+
+	/// Sets the TLS configuration the dialer connects with.
+	///
+	/// This is the client side of `ListenerOptions::set_tls_config`, for the
+	/// certificate authorities the server is checked against, the name it is
+	/// checked for, and the certificate of the client if the server requires
+	/// one. The dialer keeps its own reference to the configuration, and the
+	/// `CaFile` and `CertKeyFile` options set afterwards modify it. Dialers
+	/// that do not use TLS return `Error::NotSupported`.
+	///
+	/// NNG checks the certificate of the server against the host of the URL by
+	/// setting it on the configuration the dialer starts out with, which this
+	/// replaces. The configuration has to name the server with
+	/// `TlsConfig::server_name` instead, or the handshake fails with
+	/// `Error::Crypto`.
+	pub fn set_tls_config(&self, config: &TlsConfig) -> Result<()>
+	{
+		let name = nng_sys::NNG_OPT_TLS_CONFIG.as_ptr() as *const _;
+		let rv = unsafe { nng_sys::nng_dialer_setopt_ptr(self.handle, name, config.as_ptr()) };
+
+		rv2res!(rv).map_err(missing_is_closed)
 	}
 
 	/// Cause the dialer to start connecting to the address with which it was
@@ -209,7 +386,8 @@ impl DialerOptions
 	/// made asynchronously.
 	///
 	/// Furthermore, if the connection was closed for a synchronously dialed
-	/// connection, the dialer will still attempt to redial asynchronously.
+	/// connection, the dialer will still attempt to redial asynchronously,
+	/// unless `DialerOptions::no_reconnect` was set.
 	///
 	/// The returned handle controls the life of the dialer. If it is dropped,
 	/// the dialer is shut down and no more messages will be received on it.
@@ -217,25 +395,53 @@ impl DialerOptions
 	{
 		let flags = if nonblocking { nng_sys::NNG_FLAG_NONBLOCK } else { 0 };
 
+		// The dialer has to be known before it starts, as its pipe may be added before
+		// the start returns.
+		if self.no_reconnect {
+			if let Err(e) = self.socket.add_one_shot_dialer(self.to_dialer()) {
+				return Err((self, e));
+			}
+		}
+
 		// If there is an error starting the dialer, we don't want to consume
 		// it. Instead, we'll return it to the user and they can decide what to
 		// do.
 		let rv = unsafe { nng_sys::nng_dialer_start(self.handle, flags as i32) };
 
 		match rv {
-			0 => {
-				let handle = Dialer { handle: self.handle };
-				std::mem::forget(self);
-				Ok(handle)
+			0 => Ok(self.into_dialer()),
+			e => {
+				if self.no_reconnect {
+					self.socket.remove_one_shot_dialer(&self.to_dialer());
+				}
+				Err((self, missing_is_closed(Error::from_code(e as u32))))
 			},
-			e => Err((self, Error::from_code(e as u32))),
 		}
 	}
+
+	/// Returns the underlying `nng_dialer`.
+	pub(crate) fn handle(&self) -> nng_sys::nng_dialer { self.handle }
+
+	/// Returns a handle to the dialer, which is only valid after it is started.
+	pub(crate) fn to_dialer(&self) -> Dialer
+	{
+		Dialer { handle: self.handle, url: Arc::from(self.url.as_str()) }
+	}
+
+	/// Turns this into a handle to the started dialer without closing it.
+	pub(crate) fn into_dialer(mut self) -> Dialer
+	{
+		// The options are left without a dialer, which the drop skips.
+		let handle =
+			std::mem::replace(&mut self.handle, nng_sys::nng_dialer::NNG_DIALER_INITIALIZER);
+		Dialer { handle, url: std::mem::take(&mut self.url).into() }
+	}
 }
 
 #[rustfmt::skip]
 expose_options!{
 	DialerOptions :: handle -> nng_sys::nng_dialer;
+	MISSING_IS_CLOSED = true;
 
 	GETOPT_BOOL = nng_sys::nng_dialer_getopt_bool;
 	GETOPT_INT = nng_sys::nng_dialer_getopt_int;
@@ -274,13 +480,180 @@ impl Drop for DialerOptions
 {
 	fn drop(&mut self)
 	{
-		// Closing the dialer should only ever result in success or ECLOSED and
-		// both of those mean that the drop was successful.
+		if unsafe { nng_sys::nng_dialer_id(self.handle) } <= 0 {
+			return;
+		}
+
+		// Closing the dialer should only ever result in success, ECLOSED, or
+		// ENOENT if the socket has already closed and freed it. All of those mean
+		// that the drop was successful.
 		let rv = unsafe { nng_sys::nng_dialer_close(self.handle) };
 		assert!(
-			rv == 0 || rv == nng_sys::NNG_ECLOSED as i32,
+			rv == 0 || rv == nng_sys::NNG_ECLOSED as i32 || rv == nng_sys::NNG_ENOENT as i32,
 			"Unexpected error code while closing dialer ({})",
 			rv
 		);
 	}
 }
+
+/// The dialers of a socket that are closed once their connection is lost, see
+/// `DialerOptions::no_reconnect`.
+#[derive(Debug, Default)]
+pub(crate) struct OneShotDialers
+{
+	/// The registered dialers and the pipes they established.
+	state: Mutex<OneShotState>,
+}
+
+/// The mutable state of `OneShotDialers`.
+#[derive(Debug, Default)]
+struct OneShotState
+{
+	/// The dialers that have not been closed by the registry yet.
+	dialers: Vec<Dialer>,
+
+	/// The dialers of the open pipes that belong to one of them, by pipe ID.
+	///
+	/// The dialer of a pipe can no longer be looked up once the pipe has been
+	/// removed, so it is recorded when the pipe is added.
+	pipes: HashMap<i32, Dialer>,
+}
+
+impl OneShotDialers
+{
+	/// Registers a dialer that is about to be started.
+	pub(crate) fn add(&self, dialer: Dialer)
+	{
+		let mut state = self.state.lock().unwrap();
+		state.dialers.retain(|d| !d.is_closed());
+		state.dialers.push(dialer);
+	}
+
+	/// Unregisters a dialer that failed to start.
+	pub(crate) fn remove(&self, dialer: &Dialer)
+	{
+		self.state.lock().unwrap().dialers.retain(|d| d != dialer);
+	}
+
+	/// Follows the pipe events of the socket.
+	pub(crate) fn pipe_event(&self, pipe: Pipe, event: PipeEvent)
+	{
+		match event {
+			PipeEvent::AddPre => {
+				if let Some(dialer) = pipe.dialer() {
+					let mut state = self.state.lock().unwrap();
+					if state.dialers.contains(&dialer) {
+						state.pipes.insert(pipe.id(), dialer);
+					}
+				}
+			},
+			PipeEvent::RemovePost => {
+				let dialer = {
+					let mut state = self.state.lock().unwrap();
+					let dialer = state.pipes.remove(&pipe.id());
+					if let Some(d) = &dialer {
+						state.dialers.retain(|other| other != d);
+					}
+					dialer
+				};
+
+				// NNG only schedules the redial after this event, and not at all once the
+				// dialer is closed.
+				if let Some(d) = dialer {
+					if let Err(e) = d.close() {
+						log_error!("Failed to close dialer after its connection was lost: {}", e);
+					}
+				}
+			},
+			_ => {},
+		}
+	}
+}
+
+/// A reusable set of dialer options.
+///
+/// This is the dialer counterpart of `ListenerTemplate`: the options are
+/// recorded on the Rust side and applied, in the order in which they were
+/// added, to every dialer created from the template.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nng::{options::{Options, ReconnectMinTime}, *};
+///
+/// let server = Socket::new(Protocol::Pull0)?;
+/// server.listen("inproc://nng/dialer/template/a")?;
+/// server.listen("inproc://nng/dialer/template/b")?;
+///
+/// let min = Some(Duration::from_millis(250));
+/// let template = DialerTemplate::new().with_opt::<ReconnectMinTime>(min);
+///
+/// let client = Socket::new(Protocol::Push0)?;
+/// for url in &["inproc://nng/dialer/template/a", "inproc://nng/dialer/template/b"] {
+///     let dialer = template.dial_on(&client, url, false)?;
+///     assert_eq!(dialer.get_opt::<ReconnectMinTime>()?, min);
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct DialerTemplate
+{
+	/// The names of the stored options and the functions applying them.
+	options: Vec<(&'static str, Arc<ApplyFn>)>,
+}
+impl DialerTemplate
+{
+	/// Creates a template without any options.
+	pub fn new() -> Self { DialerTemplate::default() }
+
+	/// Adds an option to apply to every dialer created from the template.
+	///
+	/// The value is not checked until it is applied, so an invalid one is
+	/// only reported by `DialerTemplate::dial_on`.
+	pub fn with_opt<T>(mut self, val: T::OptType) -> Self
+	where
+		T: OptOps,
+		T::OptType: Clone + Send + Sync + 'static,
+		DialerOptions: SetOpt<T>,
+	{
+		let apply = move |options: &DialerOptions| options.set_opt::<T>(val.clone());
+		self.options.push((T::NAME, Arc::new(apply)));
+		self
+	}
+
+	/// Creates a dialer on the socket with the stored options and starts it.
+	///
+	/// The meaning of `nonblocking` is the same as for `DialerOptions::start`.
+	/// If anything fails, the error names the URL and, if applicable, the
+	/// stored option that could not be applied. No dialer is left behind in
+	/// that case.
+	pub fn dial_on(
+		&self,
+		socket: &Socket,
+		url: &str,
+		nonblocking: bool,
+	) -> std::result::Result<Dialer, TemplateError>
+	{
+		let fail = |option, error| TemplateError { url: url.to_string(), option, error };
+
+		let options = DialerOptions::new(socket, url).map_err(|e| fail(None, e))?;
+		for (name, apply) in &self.options {
+			apply(&options).map_err(|e| fail(Some(*name), e))?;
+		}
+
+		options.start(nonblocking).map_err(|(_, e)| fail(None, e))
+	}
+}
+
+impl fmt::Debug for DialerTemplate
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let names: Vec<_> = self.options.iter().map(|(name, _)| *name).collect();
+		f.debug_struct("DialerTemplate").field("options", &names).finish()
+	}
+}
+
+/// Applies a stored option to a dialer.
+type ApplyFn = dyn Fn(&DialerOptions) -> Result<()> + Send + Sync;
@@ -1,18 +1,231 @@
-use std::{error, fmt, io};
+use std::{error, ffi::CStr, fmt, io};
 
-use crate::message::Message;
+use crate::{aio::OwnerTag, message::Message, protocol::ProtocolKind};
 
 /// Specialized `Result` type for use with nng.
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Specialized `Result` type for use with send operations.
-pub(crate) type SendResult<T> = std::result::Result<T, SendError>;
+pub type SendResult<T> = std::result::Result<T, SendError>;
 
 /// Error type for send operations.
-pub(crate) type SendError = (Message, Error);
+///
+/// A failed send does not consume the message, so it is handed back along
+/// with the reason for the failure and can be sent again. Converting this into
+/// an `Error`, such as with the `?` operator, drops the message.
+///
+/// Sending on a socket or context always hands back the message when the
+/// send fails. Asynchronous sends may not be able to, which
+/// `AioResult::SendErrLost` reports.
+///
+/// ## Example
+///
+/// ```
+/// use nng::{Error, Protocol, Socket};
+///
+/// let mut socket = Socket::new(Protocol::Push0)?;
+/// socket.set_nonblocking(true);
+///
+/// // Nobody is connected, so there is nowhere to send the message.
+/// match socket.send(&b"retry me"[..]) {
+///     Err(e) if e.error == Error::TryAgain => assert_eq!(&e.message[..], b"retry me"),
+///     res => panic!("Unexpected result: {:?}", res),
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct SendError
+{
+	/// The message that could not be sent.
+	pub message: Message,
+
+	/// The reason the message could not be sent.
+	pub error: Error,
+}
+/// Extension methods for the results of send operations.
+///
+/// A `SendResult` can already be propagated with `?` from a function that
+/// returns a plain `Result`, which drops the message. These cover the other
+/// common cases without destructuring the `SendError` at every call site.
+///
+/// ## Example
+///
+/// ```
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/error/send_result_ext";
+///
+/// // `?` works on send results in functions returning a plain `Result`.
+/// fn greet(socket: &Socket) -> Result<()> {
+///     socket.send(&b"world"[..])?;
+///     Ok(())
+/// }
+///
+/// let mut push = Socket::new(Protocol::Push0)?;
+/// push.set_nonblocking(true);
+///
+/// // Nobody is connected, so the retry fails as well and hands the message back.
+/// let err = push.send(&b"hello"[..]).retry_with(|msg| push.send(msg)).unwrap_err();
+/// assert_eq!(err.error, Error::TryAgain);
+/// assert_eq!(&err.message[..], b"hello");
+///
+/// let pull = Socket::new(Protocol::Pull0)?;
+/// pull.listen(ADDRESS)?;
+/// push.dial(ADDRESS)?;
+/// push.set_nonblocking(false);
+/// push.send(err.message).discard_msg()?;
+/// greet(&push)?;
+///
+/// assert_eq!(&pull.recv()?[..], b"hello");
+/// assert_eq!(&pull.recv()?[..], b"world");
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub trait SendResultExt<T>
+{
+	/// Drops the message of a failed send, keeping only the reason.
+	fn discard_msg(self) -> Result<T>;
+
+	/// Retries a failed send by handing the message to the closure.
+	///
+	/// The closure is only called if the send failed, regardless of the
+	/// reason. Successful results are returned as they are.
+	fn retry_with<F>(self, f: F) -> SendResult<T>
+	where
+		F: FnOnce(Message) -> SendResult<T>;
+
+	/// Turns the error into the `(Message, Error)` tuple that send operations
+	/// failed with before `SendError`.
+	///
+	/// This is for code that still matches on the tuple and will be removed
+	/// in the next major release. Such code moves to `SendError` by matching
+	/// on its `message` and `error` fields instead, such as with
+	/// `Err(SendError { message, error })`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// # #![allow(deprecated)]
+	/// use nng::*;
+	///
+	/// let mut push = Socket::new(Protocol::Push0)?;
+	/// push.set_nonblocking(true);
+	///
+	/// match push.send(&b"hello"[..]).into_tuple() {
+	///     Err((msg, Error::TryAgain)) => assert_eq!(&msg[..], b"hello"),
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	#[deprecated(note = "match on the `message` and `error` fields of `SendError` instead")]
+	fn into_tuple(self) -> std::result::Result<T, (Message, Error)>;
+}
+impl<T> SendResultExt<T> for SendResult<T>
+{
+	fn discard_msg(self) -> Result<T> { self.map_err(Error::from) }
+
+	fn retry_with<F>(self, f: F) -> SendResult<T>
+	where
+		F: FnOnce(Message) -> SendResult<T>,
+	{
+		self.or_else(|e| f(e.message))
+	}
+
+	fn into_tuple(self) -> std::result::Result<T, (Message, Error)>
+	{
+		self.map_err(<(Message, Error)>::from)
+	}
+}
+
+/// Error type for starting an endpoint from a template.
+///
+/// See `ListenerTemplate` and `DialerTemplate`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TemplateError
+{
+	/// The URL of the endpoint that could not be started.
+	pub url: String,
+
+	/// The name of the stored option that could not be applied, or `None` if
+	/// creating or starting the endpoint failed.
+	pub option: Option<&'static str>,
+
+	/// The reason the endpoint could not be started.
+	pub error: Error,
+}
+
+/// Error type for dialing several URLs at once.
+///
+/// See `Socket::dial_any`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DialAnyError
+{
+	/// The URLs that could not be dialed along with the reasons, in the order
+	/// in which they were given.
+	pub errors: Vec<(String, Error)>,
+}
+
+/// Error type for setting a batch of options all at once.
+///
+/// See `Socket::apply_options_atomic`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OptionApplyError
+{
+	/// The position of the option that failed in the batch.
+	pub index: usize,
+
+	/// The name of the option that failed.
+	pub option: String,
+
+	/// The reason the option could not be set.
+	pub error: Error,
+
+	/// The options that could not be restored to their previous values,
+	/// along with the reasons.
+	pub rollback_errors: Vec<(String, Error)>,
+}
+
+impl OptionApplyError
+{
+	/// Returns whether every option that was set before the failure was
+	/// restored, leaving the options as they were before the batch.
+	pub fn rolled_back(&self) -> bool { self.rollback_errors.is_empty() }
+}
 
 /// Errors potentially returned by NNG operations.
+///
+/// This is a plain, `Copy` enumeration so that errors can be matched on
+/// directly and cheaply passed around, including out of AIO callbacks. It
+/// implements `std::error::Error`, so it can be propagated with `?` into
+/// `Box<dyn std::error::Error>` or any of the error-handling crates built on
+/// it and recovered again by downcasting. The code NNG reported for the error
+/// is available from `Error::raw_code`.
+///
+/// Errors converted into an `io::Error` keep the original error as their inner
+/// error, except for `Error::SystemErr`, which turns into the matching OS
+/// error instead.
+///
+/// ## Example
+///
+/// ```
+/// use std::{error, io};
+/// use nng::*;
+///
+/// fn dial() -> std::result::Result<Socket, Box<dyn error::Error + Send + Sync>> {
+///     let socket = Socket::new(Protocol::Req0)?;
+///     socket.dial("inproc://nng/error/nobody")?;
+///     Ok(socket)
+/// }
+///
+/// let err = dial().unwrap_err();
+/// assert_eq!(err.downcast_ref::<Error>(), Some(&Error::ConnectionRefused));
+///
+/// let io_err = io::Error::from(Error::ConnectionRefused);
+/// assert_eq!(io_err.kind(), io::ErrorKind::ConnectionRefused);
+/// let inner = io_err.get_ref().and_then(|e| e.downcast_ref::<Error>());
+/// assert_eq!(inner, Some(&Error::ConnectionRefused));
+/// ```
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
 #[rustfmt::skip]
 pub enum Error
 {
@@ -109,6 +322,63 @@ pub enum Error
 	/// An internal error occurred.
 	Internal,
 
+	/// A sealed message failed its integrity check.
+	///
+	/// This is produced by this crate rather than NNG, see `Message::seal`.
+	IntegrityCheckFailed,
+
+	/// A received message was larger than the limit given by the caller.
+	///
+	/// This is produced by this crate rather than NNG, see
+	/// `Socket::recv_limited`. Messages exceeding `RecvMaxSize` are dropped by
+	/// NNG without an error.
+	MessageExceedsLimit
+	{
+		/// The size of the message body, in bytes.
+		size: usize,
+
+		/// The limit that was exceeded, in bytes.
+		limit: usize,
+	},
+
+	/// A compressed message body was damaged.
+	///
+	/// This is produced by this crate rather than NNG, see
+	/// `Message::decompress`.
+	DecompressionFailed,
+
+	/// A received message did not have the exact length expected by the caller.
+	///
+	/// This is produced by this crate rather than NNG, see
+	/// `Socket::recv_array`.
+	MessageLengthMismatch
+	{
+		/// The size of the message body, in bytes.
+		size: usize,
+
+		/// The expected size, in bytes.
+		expected: usize,
+	},
+
+	/// An AIO was used with a socket or context other than the one it is
+	/// bound to.
+	///
+	/// This is produced by this crate rather than NNG, see `Aio::bind_to`.
+	AioOwnershipViolation
+	{
+		/// The owner the AIO is bound to.
+		expected: OwnerTag,
+
+		/// The socket or context the operation was started on.
+		actual: OwnerTag,
+	},
+
+	/// The protocol of a new socket is not available in the NNG library.
+	///
+	/// This is what `Error::NotSupported` becomes when it is returned while
+	/// opening a socket, see `Socket::new`.
+	ProtocolUnavailable(ProtocolKind),
+
 	/// An unknown system error occurred.
 	SystemErr(u32),
 
@@ -171,11 +441,261 @@ impl Error
 			_ => Error::Unknown(code),
 		}
 	}
+
+	/// Returns the NNG error code of the error.
+	///
+	/// System and transport errors are returned with the `NNG_ESYSERR` and
+	/// `NNG_ETRANERR` bits set, the way NNG reports them. Errors that are
+	/// produced by this crate rather than NNG, such as
+	/// `Error::IntegrityCheckFailed`, have no code and return `None`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::Error;
+	///
+	/// let plain = Error::TimedOut;
+	/// let system = Error::SystemErr(1); // EPERM
+	/// let transport = Error::TransportErr(3);
+	///
+	/// assert_eq!(plain.raw_code(), Some(nng_sys::NNG_ETIMEDOUT));
+	/// assert_eq!(system.raw_code(), Some(nng_sys::NNG_ESYSERR | 1));
+	/// assert_eq!(transport.raw_code(), Some(nng_sys::NNG_ETRANERR | 3));
+	/// assert_eq!(Error::IntegrityCheckFailed.raw_code(), None);
+	///
+	/// assert!(system.is_system() && !system.is_transport());
+	/// assert!(transport.is_transport() && !transport.is_system());
+	/// assert!(!plain.is_system() && !plain.is_transport());
+	///
+	/// // The messages are the ones NNG would show for the codes.
+	/// let messages = [plain.to_string(), system.to_string(), transport.to_string()];
+	/// assert_eq!(messages[0], "Timed out");
+	/// assert_eq!(messages[2], "Transport error #3");
+	/// assert!(messages.iter().all(|m| !m.is_empty()));
+	/// assert!(messages[0] != messages[1] && messages[1] != messages[2]);
+	/// ```
+	#[rustfmt::skip]
+	#[allow(clippy::match_same_arms)]
+	pub fn raw_code(&self) -> Option<u32>
+	{
+		let code = match *self {
+			Error::Interrupted       => nng_sys::NNG_EINTR,
+			Error::OutOfMemory       => nng_sys::NNG_ENOMEM,
+			Error::InvalidInput      => nng_sys::NNG_EINVAL,
+			Error::Busy              => nng_sys::NNG_EBUSY,
+			Error::TimedOut          => nng_sys::NNG_ETIMEDOUT,
+			Error::ConnectionRefused => nng_sys::NNG_ECONNREFUSED,
+			Error::Closed            => nng_sys::NNG_ECLOSED,
+			Error::TryAgain          => nng_sys::NNG_EAGAIN,
+			Error::NotSupported      => nng_sys::NNG_ENOTSUP,
+			Error::AddressInUse      => nng_sys::NNG_EADDRINUSE,
+			Error::IncorrectState    => nng_sys::NNG_ESTATE,
+			Error::EntryNotFound     => nng_sys::NNG_ENOENT,
+			Error::Protocol          => nng_sys::NNG_EPROTO,
+			Error::DestUnreachable   => nng_sys::NNG_EUNREACHABLE,
+			Error::AddressInvalid    => nng_sys::NNG_EADDRINVAL,
+			Error::PermissionDenied  => nng_sys::NNG_EPERM,
+			Error::MessageTooLarge   => nng_sys::NNG_EMSGSIZE,
+			Error::ConnectionAborted => nng_sys::NNG_ECONNABORTED,
+			Error::ConnectionReset   => nng_sys::NNG_ECONNRESET,
+			Error::Canceled          => nng_sys::NNG_ECANCELED,
+			Error::OutOfFiles        => nng_sys::NNG_ENOFILES,
+			Error::OutOfSpace        => nng_sys::NNG_ENOSPC,
+			Error::ResourceExists    => nng_sys::NNG_EEXIST,
+			Error::ReadOnly          => nng_sys::NNG_EREADONLY,
+			Error::WriteOnly         => nng_sys::NNG_EWRITEONLY,
+			Error::Crypto            => nng_sys::NNG_ECRYPTO,
+			Error::PeerAuth          => nng_sys::NNG_EPEERAUTH,
+			Error::NoArgument        => nng_sys::NNG_ENOARG,
+			Error::Ambiguous         => nng_sys::NNG_EAMBIGUOUS,
+			Error::BadType           => nng_sys::NNG_EBADTYPE,
+			Error::Internal          => nng_sys::NNG_EINTERNAL,
+			Error::SystemErr(c)      => nng_sys::NNG_ESYSERR | c,
+			Error::TransportErr(c)   => nng_sys::NNG_ETRANERR | c,
+			Error::Unknown(c)        => c,
+
+			// NNG only reports that the protocol is not supported.
+			Error::ProtocolUnavailable(_) => nng_sys::NNG_ENOTSUP,
+
+			Error::IntegrityCheckFailed
+			| Error::MessageExceedsLimit { .. }
+			| Error::DecompressionFailed
+			| Error::MessageLengthMismatch { .. }
+			| Error::AioOwnershipViolation { .. } => return None,
+		};
+
+		Some(code)
+	}
+
+	/// Returns whether retrying the operation that failed with this error may
+	/// succeed.
+	///
+	/// Transient errors come from the state of the peers, the network, or the
+	/// system at the time, which can change without the application doing
+	/// anything different:
+	///
+	/// * `TryAgain`, `TimedOut`, `Interrupted`, and `Busy`, where the
+	///   operation could not complete yet.
+	/// * `ConnectionRefused`, `ConnectionReset`, `ConnectionAborted`, and
+	///   `DestUnreachable`, where the peer or the network may come back.
+	/// * `OutOfMemory`, `OutOfFiles`, and `OutOfSpace`, where the resources
+	///   may be freed again.
+	/// * `SystemErr` for the operating system errors of the same kinds,
+	///   according to `io::ErrorKind`.
+	///
+	/// Every other error is permanent: retrying the same operation fails the
+	/// same way. This includes `Closed`, as a closed socket or endpoint stays
+	/// closed, `Canceled`, as the application asked for the operation to
+	/// stop, errors in the arguments or configuration such as `InvalidInput`,
+	/// `NotSupported`, `AddressInvalid`, or `AddressInUse`, failed security
+	/// checks such as `PeerAuth` and `Crypto`, protocol errors such as
+	/// `IncorrectState` and `Protocol`, and the errors this crate produces
+	/// about a particular message, such as `IntegrityCheckFailed`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::*;
+	///
+	/// fn dial_with_retries(socket: &Socket, url: &str, attempts: u32) -> Result<()> {
+	///     let mut attempt = 1;
+	///     loop {
+	///         match socket.dial(url) {
+	///             Err(e) if e.is_transient() && attempt < attempts => attempt += 1,
+	///             res => return res,
+	///         }
+	///         thread::sleep(Duration::from_millis(10));
+	///     }
+	/// }
+	///
+	/// // Nobody is listening yet, which a retry can fix.
+	/// let url = "inproc://nng/error/is_transient";
+	/// let socket = Socket::new(Protocol::Req0)?;
+	/// assert_eq!(dial_with_retries(&socket, url, 2), Err(Error::ConnectionRefused));
+	///
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// server.listen(url)?;
+	/// dial_with_retries(&socket, url, 2)?;
+	///
+	/// // A closed socket stays closed, so there is no point in retrying.
+	/// socket.close();
+	/// let err = dial_with_retries(&socket, url, 1000).unwrap_err();
+	/// assert!(err.is_closed() && !err.is_transient());
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	#[rustfmt::skip]
+	#[allow(clippy::match_same_arms)]
+	pub fn is_transient(&self) -> bool
+	{
+		match *self {
+			Error::TryAgain
+			| Error::TimedOut
+			| Error::Interrupted
+			| Error::Busy
+			| Error::ConnectionRefused
+			| Error::ConnectionReset
+			| Error::ConnectionAborted
+			| Error::DestUnreachable
+			| Error::OutOfMemory
+			| Error::OutOfFiles
+			| Error::OutOfSpace => true,
+
+			Error::SystemErr(c) => matches!(
+				io::Error::from_raw_os_error(c as i32).kind(),
+				io::ErrorKind::WouldBlock
+					| io::ErrorKind::TimedOut
+					| io::ErrorKind::Interrupted
+					| io::ErrorKind::ConnectionRefused
+					| io::ErrorKind::ConnectionReset
+					| io::ErrorKind::ConnectionAborted
+					| io::ErrorKind::OutOfMemory
+			),
+
+			Error::Closed
+			| Error::Canceled
+			| Error::InvalidInput
+			| Error::NotSupported
+			| Error::AddressInUse
+			| Error::IncorrectState
+			| Error::EntryNotFound
+			| Error::Protocol
+			| Error::AddressInvalid
+			| Error::PermissionDenied
+			| Error::MessageTooLarge
+			| Error::ResourceExists
+			| Error::ReadOnly
+			| Error::WriteOnly
+			| Error::Crypto
+			| Error::PeerAuth
+			| Error::NoArgument
+			| Error::Ambiguous
+			| Error::BadType
+			| Error::Internal
+			| Error::IntegrityCheckFailed
+			| Error::MessageExceedsLimit { .. }
+			| Error::DecompressionFailed
+			| Error::MessageLengthMismatch { .. }
+			| Error::AioOwnershipViolation { .. }
+			| Error::ProtocolUnavailable(_)
+			| Error::TransportErr(_)
+			| Error::Unknown(_) => false,
+		}
+	}
+
+	/// Returns whether the error says that the socket, context, or endpoint
+	/// the operation was started on has been closed.
+	///
+	/// No operation on the same object can succeed after this, so it is the
+	/// usual signal for a loop receiving or sending on it to stop.
+	pub fn is_closed(&self) -> bool { matches!(self, Error::Closed) }
+
+	/// Returns whether this is an error of the operating system, reported
+	/// through NNG.
+	pub fn is_system(&self) -> bool { matches!(self, Error::SystemErr(_)) }
+
+	/// Returns whether this is an error specific to a transport.
+	pub fn is_transport(&self) -> bool { matches!(self, Error::TransportErr(_)) }
 }
 
 impl From<SendError> for Error
 {
-	fn from((_, e): SendError) -> Error { e }
+	fn from(e: SendError) -> Error { e.error }
+}
+
+impl From<SendError> for (Message, Error)
+{
+	fn from(e: SendError) -> (Message, Error) { (e.message, e.error) }
+}
+
+impl From<(Message, Error)> for SendError
+{
+	fn from((message, error): (Message, Error)) -> SendError { SendError { message, error } }
+}
+
+impl From<SendError> for io::Error
+{
+	fn from(e: SendError) -> io::Error { e.error.into() }
+}
+
+impl From<TemplateError> for Error
+{
+	fn from(e: TemplateError) -> Error { e.error }
+}
+
+impl From<DialAnyError> for Error
+{
+	/// Returns the error of the first URL, or `Error::AddressInvalid` if there
+	/// were no URLs at all.
+	fn from(e: DialAnyError) -> Error
+	{
+		e.errors.first().map_or(Error::AddressInvalid, |(_, error)| *error)
+	}
+}
+
+impl From<OptionApplyError> for Error
+{
+	fn from(e: OptionApplyError) -> Error { e.error }
 }
 
 impl From<Error> for io::Error
@@ -198,7 +718,10 @@ impl From<Error> for io::Error
 				Error::ConnectionAborted => io::ErrorKind::ConnectionAborted,
 				Error::ConnectionReset => io::ErrorKind::ConnectionReset,
 				Error::ResourceExists => io::ErrorKind::AlreadyExists,
-				Error::BadType => io::ErrorKind::InvalidData,
+				Error::BadType
+				| Error::IntegrityCheckFailed
+				| Error::DecompressionFailed
+				| Error::MessageLengthMismatch { .. } => io::ErrorKind::InvalidData,
 				_ => io::ErrorKind::Other,
 			};
 
@@ -209,55 +732,92 @@ impl From<Error> for io::Error
 
 impl error::Error for Error {}
 
+impl error::Error for SendError {}
+
+impl fmt::Display for SendError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(&self.error, f) }
+}
+
+impl error::Error for TemplateError {}
+
+impl fmt::Display for TemplateError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self.option {
+			Some(option) => write!(f, "Cannot apply {} to {}: {}", option, self.url, self.error),
+			None => write!(f, "Cannot start endpoint for {}: {}", self.url, self.error),
+		}
+	}
+}
+
+impl error::Error for DialAnyError {}
+
+impl fmt::Display for DialAnyError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		if self.errors.is_empty() {
+			return write!(f, "No URLs to dial");
+		}
+
+		write!(f, "Cannot dial any of the URLs")?;
+		for (i, (url, error)) in self.errors.iter().enumerate() {
+			write!(f, "{} {}: {}", if i == 0 { ":" } else { ";" }, url, error)?;
+		}
+		Ok(())
+	}
+}
+
+impl error::Error for OptionApplyError {}
+
+impl fmt::Display for OptionApplyError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "Cannot apply {}: {}", self.option, self.error)?;
+		for (i, (option, error)) in self.rollback_errors.iter().enumerate() {
+			let sep = if i == 0 { ", and cannot restore" } else { ";" };
+			write!(f, "{} {}: {}", sep, option, error)?;
+		}
+		Ok(())
+	}
+}
+
 impl fmt::Display for Error
 {
-	#[rustfmt::skip]
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
 	{
-		// Now, we could do a call into NNG for this but I think that adds
-		// unnecessary complication since we would have to deal with c-strings
-		// and unsafe code. We also couldn't do that for anything that wasn't a
-		// "standard" error since that code is technically not thread-safe. It
-		// really is just easier to hard-code the strings here.
-		//
-		// For the system error, we are going to lean on the standard library
-		// to produce the output message for us. I am fairly certain that
-		// creating one is not a heavy operation, so this should be fine.
 		match *self {
-			Error::Interrupted       => write!(f, "Interrupted"),
-			Error::OutOfMemory       => write!(f, "Out of memory"),
-			Error::InvalidInput      => write!(f, "Invalid argument"),
-			Error::Busy              => write!(f, "Resource busy"),
-			Error::TimedOut          => write!(f, "Timed out"),
-			Error::ConnectionRefused => write!(f, "Connection refused"),
-			Error::Closed            => write!(f, "Object closed"),
-			Error::TryAgain          => write!(f, "Try again"),
-			Error::NotSupported      => write!(f, "Not supported"),
-			Error::AddressInUse      => write!(f, "Address in use"),
-			Error::IncorrectState    => write!(f, "Incorrect state"),
-			Error::EntryNotFound     => write!(f, "Entry not found"),
-			Error::Protocol          => write!(f, "Protocol error"),
-			Error::DestUnreachable   => write!(f, "Destination unreachable"),
-			Error::AddressInvalid    => write!(f, "Address invalid"),
-			Error::PermissionDenied  => write!(f, "Permission denied"),
-			Error::MessageTooLarge   => write!(f, "Message too large"),
-			Error::ConnectionReset   => write!(f, "Connection reset"),
-			Error::ConnectionAborted => write!(f, "Connection aborted"),
-			Error::Canceled          => write!(f, "Operation canceled"),
-			Error::OutOfFiles        => write!(f, "Out of files"),
-			Error::OutOfSpace        => write!(f, "Out of space"),
-			Error::ResourceExists    => write!(f, "Resource already exists"),
-			Error::ReadOnly          => write!(f, "Read only resource"),
-			Error::WriteOnly         => write!(f, "Write only resource"),
-			Error::Crypto            => write!(f, "Cryptographic error"),
-			Error::PeerAuth          => write!(f, "Peer could not be authenticated"),
-			Error::NoArgument        => write!(f, "Option requires argument"),
-			Error::Ambiguous         => write!(f, "Ambiguous option"),
-			Error::BadType           => write!(f, "Incorrect type"),
-			Error::Internal          => write!(f, "Internal error detected"),
-			Error::SystemErr(c)      => write!(f, "{}", io::Error::from_raw_os_error(c as i32)),
-			Error::TransportErr(c)   => write!(f, "Transport error #{}", c),
-			Error::Unknown(c)        => write!(f, "Unknown error code #{}", c),
+			Error::IntegrityCheckFailed => write!(f, "Message integrity check failed"),
+			Error::MessageExceedsLimit { size, limit } => {
+				write!(f, "Message of {} bytes exceeds the limit of {} bytes", size, limit)
+			},
+			Error::DecompressionFailed => write!(f, "Compressed message is damaged"),
+			Error::MessageLengthMismatch { size, expected } => {
+				write!(f, "Message of {} bytes does not have the expected {} bytes", size, expected)
+			},
+			Error::AioOwnershipViolation { expected, actual } => {
+				write!(f, "AIO bound to {} was used with {}", expected, actual)
+			},
+			Error::ProtocolUnavailable(p) => {
+				write!(f, "Protocol {} is not available in this build of NNG", p)
+			},
+
+			// NNG formats these into static buffers, which isn't thread-safe, so they are
+			// rendered here the same way. The standard library produces the same message
+			// for system errors as NNG, without the static buffer.
+			Error::SystemErr(c) => write!(f, "{}", io::Error::from_raw_os_error(c as i32)),
+			Error::TransportErr(c) => write!(f, "Transport error #{}", c),
+			Error::Unknown(c) => write!(f, "Unknown error #{}", c),
+
+			// Everything else is in the table of NNG, which only holds static strings.
+			_ => {
+				let code = self.raw_code().expect("NNG error without a code");
+				let msg = unsafe { CStr::from_ptr(nng_sys::nng_strerror(code as _)) };
+				f.write_str(&msg.to_string_lossy())
+			},
 		}
 	}
 }
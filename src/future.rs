@@ -0,0 +1,828 @@
+//! Futures for sending and receiving that work with any executor.
+//!
+//! These are plain `std::future::Future`s, available with the `async-api`
+//! feature. They don't depend on any runtime: each future runs its operation
+//! on an AIO of its own and the completion callback of the AIO wakes the task
+//! that last polled the future. Nothing but the waker is needed, so they can
+//! be driven by async-std, smol, Tokio, or a hand-rolled executor alike.
+//!
+//! The operation starts the first time the future is polled. Dropping a
+//! future whose operation is still running cancels the operation and waits
+//! for its callback to return. Receives are safe to drop, such as when
+//! another branch of a `select!` wins: a receive that is canceled never takes
+//! a message off the socket, and if it completed in the meantime, its message
+//! is kept by the socket or context and returned by the next receive on it.
+//! See `RecvFuture` for the caveat of _req_ sockets. The message of a send is
+//! dropped with the future, see `SendFuture` for how to get it back.
+//! `RequestFuture` sends a request on a context and receives its reply in
+//! one go.
+//!
+//! The waker is called from an NNG thread, from within the callback of the
+//! AIO. It must not poll the future right away on that thread, which no
+//! executor does, as the future can't be dropped from within the callback.
+//!
+//! ## Examples
+//!
+//! This is a complete executor, enough to drive a _rep_ server with a context
+//! per thread and a few _req_ clients, which all run at the same time:
+//!
+//! ```
+//! use std::{future::Future, sync::Arc, task::{self, Poll, Wake, Waker}, thread};
+//! use nng::*;
+//!
+//! const ADDRESS: &str = "inproc://nng/future/block_on";
+//!
+//! struct Unpark(thread::Thread);
+//! impl Wake for Unpark {
+//!     fn wake(self: Arc<Self>) { self.0.unpark(); }
+//! }
+//!
+//! fn block_on<F: Future>(fut: F) -> F::Output {
+//!     let mut fut = Box::pin(fut);
+//!     let waker = Waker::from(Arc::new(Unpark(thread::current())));
+//!     let mut cx = task::Context::from_waker(&waker);
+//!     loop {
+//!         match fut.as_mut().poll(&mut cx) {
+//!             Poll::Ready(v) => return v,
+//!             Poll::Pending => thread::park(),
+//!         }
+//!     }
+//! }
+//!
+//! let server = Socket::rep0()?;
+//! server.listen(ADDRESS)?;
+//! for _ in 0..2 {
+//!     let ctx = Context::new(&server)?;
+//!     thread::spawn(move || block_on(async {
+//!         while let Ok(mut msg) = ctx.recv_future().await {
+//!             msg.push_back(b" world")?;
+//!             ctx.send_future(msg).await?;
+//!         }
+//!         Ok::<(), Error>(())
+//!     }));
+//! }
+//!
+//! let clients: Vec<_> = (0..4).map(|i| thread::spawn(move || block_on(async move {
+//!     let client = Socket::req0()?;
+//!     client.dial(ADDRESS)?;
+//!     let request = format!("hello {}", i);
+//!     client.send_future(request.as_bytes()).await?;
+//!     let reply = client.recv_future().await?;
+//!     assert_eq!(&reply[..], format!("hello {} world", i).as_bytes());
+//!     Ok::<(), Error>(())
+//! }))).collect();
+//!
+//! for client in clients {
+//!     client.join().unwrap()?;
+//! }
+//! # Ok::<(), nng::Error>(())
+//! ```
+//!
+//! The same exchange, with the tasks of async-std:
+//!
+//! ```
+//! use async_std::task;
+//! use nng::*;
+//!
+//! const ADDRESS: &str = "inproc://nng/future/async_std";
+//!
+//! task::block_on(async {
+//!     let server = Socket::rep0()?;
+//!     server.listen(ADDRESS)?;
+//!     for _ in 0..2 {
+//!         let ctx = Context::new(&server)?;
+//!         task::spawn(async move {
+//!             while let Ok(mut msg) = ctx.recv_future().await {
+//!                 msg.push_back(b" world").unwrap();
+//!                 ctx.send_future(msg).await.unwrap();
+//!             }
+//!         });
+//!     }
+//!
+//!     let clients: Vec<_> = (0..4).map(|i| task::spawn(async move {
+//!         let client = Socket::req0()?;
+//!         client.dial(ADDRESS)?;
+//!         client.send_future(format!("hello {}", i).as_bytes()).await?;
+//!         let reply = client.recv_future().await?;
+//!         assert_eq!(&reply[..], format!("hello {} world", i).as_bytes());
+//!         Ok::<(), Error>(())
+//!     })).collect();
+//!
+//!     for client in clients {
+//!         client.await?;
+//!     }
+//!     Ok::<(), Error>(())
+//! })?;
+//! # Ok::<(), nng::Error>(())
+//! ```
+use std::{
+	collections::VecDeque,
+	future::Future,
+	mem,
+	pin::Pin,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, Mutex, PoisonError,
+	},
+	task::{self, Poll, Waker},
+};
+
+use crate::{
+	aio::{Aio, AioResult},
+	ctx::Context,
+	error::{Result, SendError, SendResult},
+	message::Message,
+	socket::Socket,
+};
+
+/// A future that receives a message.
+///
+/// This is created by `Socket::recv_future` or `Context::recv_future`.
+///
+/// Dropping the future never loses a message. If the receive completed before
+/// the future was dropped, but after it was last polled, the message is kept
+/// by the socket or context it was received on. The next receive there, be it
+/// another future, `Socket::recv`, or an AIO, returns it before taking any new
+/// message from NNG. Messages kept by a context are only returned by receives
+/// on that context, as the reply to a request has to go out on the context
+/// that received it.
+///
+/// Dropping a running receive on a _req_ socket or context is different, as
+/// NNG treats canceling it as abandoning the request: the reply is discarded
+/// when it arrives and the request has to be sent again. Only a reply that
+/// arrived before the receive was canceled is kept.
+///
+/// ## Example
+///
+/// The future can be moved between tasks while it is waiting. Only the waker
+/// of the last poll is woken:
+///
+/// ```
+/// use std::{future::Future, pin::Pin, task::{self, Wake, Waker}};
+/// use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/future/recv_future";
+///
+/// #[derive(Default)]
+/// struct Count(AtomicUsize);
+/// impl Wake for Count {
+///     fn wake(self: Arc<Self>) { self.0.fetch_add(1, Ordering::SeqCst); }
+/// }
+///
+/// let pull = Socket::pull0()?;
+/// pull.listen(ADDRESS)?;
+/// let mut fut = pull.recv_future();
+///
+/// // Poll it from one task, then hand it over to async-std.
+/// let first = Arc::new(Count::default());
+/// let waker = Waker::from(Arc::clone(&first));
+/// assert!(Pin::new(&mut fut).poll(&mut task::Context::from_waker(&waker)).is_pending());
+///
+/// let task = async_std::task::spawn(fut);
+/// # std::thread::sleep(std::time::Duration::from_millis(50));
+///
+/// let push = Socket::push0()?;
+/// push.dial(ADDRESS)?;
+/// push.send(&b"moved"[..])?;
+/// let msg = async_std::task::block_on(task)?;
+///
+/// assert_eq!(&msg[..], b"moved");
+/// assert_eq!(first.0.load(Ordering::SeqCst), 0);
+/// # Ok::<(), nng::Error>(())
+/// ```
+///
+/// Futures that are dropped right after their first poll race the messages
+/// coming in, yet every message is received exactly once and in order:
+///
+/// ```
+/// use std::{future::Future, pin::Pin, sync::Arc, task::{self, Poll, Wake, Waker}, thread};
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/future/recv_future/drop";
+/// const COUNT: u32 = 10_000;
+///
+/// struct Noop;
+/// impl Wake for Noop {
+///     fn wake(self: Arc<Self>) {}
+/// }
+///
+/// let pull = Socket::pull0()?;
+/// pull.listen(ADDRESS)?;
+/// let push = Socket::push0()?;
+/// push.dial(ADDRESS)?;
+/// // The sender hands the socket back, as closing it would drop queued messages.
+/// let sender = thread::spawn(move || {
+///     for i in 0..COUNT {
+///         push.send(&i.to_le_bytes()[..]).unwrap();
+///     }
+///     push
+/// });
+///
+/// let waker = Waker::from(Arc::new(Noop));
+/// let mut cx = task::Context::from_waker(&waker);
+/// let mut received = Vec::new();
+/// for _ in 0..COUNT {
+///     let mut fut = pull.recv_future();
+///     if let Poll::Ready(msg) = Pin::new(&mut fut).poll(&mut cx) {
+///         received.push(msg?);
+///     }
+/// }
+///
+/// while received.len() < COUNT as usize {
+///     received.push(pull.recv()?);
+/// }
+/// let _push = sender.join().unwrap();
+///
+/// for (i, msg) in received.iter().enumerate() {
+///     assert_eq!(msg[..], (i as u32).to_le_bytes());
+/// }
+/// let mut nonblocking = pull.clone();
+/// nonblocking.set_nonblocking(true);
+/// assert_eq!(nonblocking.recv().unwrap_err(), Error::TryAgain);
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct RecvFuture
+{
+	/// What the message is received on.
+	target: Target,
+
+	/// How far along the receive is.
+	stage: Stage<()>,
+}
+impl RecvFuture
+{
+	/// Creates a future that receives on the socket.
+	pub(crate) fn socket(socket: &Socket) -> Self
+	{
+		RecvFuture { target: Target::Socket(socket.clone()), stage: Stage::Idle(()) }
+	}
+
+	/// Creates a future that receives on the context.
+	pub(crate) fn context(ctx: &Context) -> Self
+	{
+		RecvFuture { target: Target::Context(ctx.clone()), stage: Stage::Idle(()) }
+	}
+
+	/// Cancels the receive, if it is running.
+	///
+	/// The future then completes with `Error::Canceled`, unless a message
+	/// arrived first. This has no effect before the future is first polled.
+	pub fn cancel(&self)
+	{
+		if let Stage::Running(op) = &self.stage {
+			op.aio.cancel();
+		}
+	}
+}
+
+impl Drop for RecvFuture
+{
+	fn drop(&mut self)
+	{
+		if let Stage::Running(op) = mem::replace(&mut self.stage, Stage::Done) {
+			if let Some(AioResult::RecvOk(msg)) = op.finish() {
+				self.target.keep(msg);
+			}
+		}
+	}
+}
+
+impl Future for RecvFuture
+{
+	type Output = Result<Message>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output>
+	{
+		let this = &mut *self;
+		if let Stage::Idle(()) = this.stage {
+			let op = match Operation::new() {
+				Ok(op) => op,
+				Err(e) => {
+					this.stage = Stage::Done;
+					return Poll::Ready(Err(e));
+				},
+			};
+
+			if let Err(e) = this.target.recv(&op.aio) {
+				this.stage = Stage::Done;
+				return Poll::Ready(Err(e));
+			}
+			this.stage = Stage::Running(op);
+		}
+
+		let res = match &this.stage {
+			Stage::Running(op) => match op.poll(cx) {
+				Poll::Ready(res) => res,
+				Poll::Pending => return Poll::Pending,
+			},
+			_ => panic!("`RecvFuture` polled after completion"),
+		};
+
+		this.stage = Stage::Done;
+		match res {
+			AioResult::RecvOk(m) => Poll::Ready(Ok(m)),
+			AioResult::RecvErr(e) => Poll::Ready(Err(e)),
+			res => unreachable!("Receive AIO completed with a non-receive result: {:?}", res),
+		}
+	}
+}
+
+/// A future that sends a message.
+///
+/// This is created by `Socket::send_future` or `Context::send_future`.
+///
+/// A failed send does not consume the message. The future completes with the
+/// error and keeps the message, if NNG handed it back, for
+/// `SendFuture::take_message`. Awaiting a mutable reference to the future
+/// keeps it around for that. Dropping the future drops the message, including
+/// when the send is still running: cancel it with `SendFuture::cancel` and
+/// await it instead to get the message back.
+///
+/// ## Example
+///
+/// ```
+/// use nng::*;
+///
+/// // Without any peers, the send waits for one to show up.
+/// let push = Socket::push0()?;
+/// push.listen("inproc://nng/future/send_future")?;
+///
+/// async_std::task::block_on(async {
+///     let mut fut = push.send_future(&b"undelivered"[..]);
+///     let polled = async_std::future::timeout(std::time::Duration::from_millis(50), &mut fut);
+///     assert!(polled.await.is_err());
+///
+///     fut.cancel();
+///     assert_eq!((&mut fut).await, Err(Error::Canceled));
+///     assert_eq!(&fut.take_message().unwrap()[..], b"undelivered");
+/// });
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct SendFuture
+{
+	/// What the message is sent on.
+	target: Target,
+
+	/// How far along the send is.
+	stage: Stage<Message>,
+
+	/// The message of a send that failed.
+	unsent: Option<Message>,
+}
+impl SendFuture
+{
+	/// Creates a future that sends the message on the socket.
+	pub(crate) fn socket(socket: &Socket, msg: Message) -> Self
+	{
+		SendFuture {
+			target: Target::Socket(socket.clone()),
+			stage:  Stage::Idle(msg),
+			unsent: None,
+		}
+	}
+
+	/// Creates a future that sends the message on the context.
+	pub(crate) fn context(ctx: &Context, msg: Message) -> Self
+	{
+		SendFuture { target: Target::Context(ctx.clone()), stage: Stage::Idle(msg), unsent: None }
+	}
+
+	/// Cancels the send, if it is running.
+	///
+	/// The future then completes with `Error::Canceled`, unless the message
+	/// was sent first. This has no effect before the future is first polled.
+	pub fn cancel(&self)
+	{
+		if let Stage::Running(op) = &self.stage {
+			op.aio.cancel();
+		}
+	}
+
+	/// Takes the message of a send that failed.
+	///
+	/// This returns `None` until the future has completed with an error, and
+	/// also afterwards if NNG did not hand the message back.
+	pub fn take_message(&mut self) -> Option<Message> { self.unsent.take() }
+}
+
+impl Future for SendFuture
+{
+	type Output = Result<()>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output>
+	{
+		let this = &mut *self;
+		if let Stage::Idle(_) = this.stage {
+			let msg = match mem::replace(&mut this.stage, Stage::Done) {
+				Stage::Idle(msg) => msg,
+				_ => unreachable!(),
+			};
+
+			let op = match Operation::new() {
+				Ok(op) => op,
+				Err(e) => {
+					this.unsent = Some(msg);
+					return Poll::Ready(Err(e));
+				},
+			};
+
+			if let Err(SendError { message, error }) = this.target.send(&op.aio, msg) {
+				this.unsent = Some(message);
+				return Poll::Ready(Err(error));
+			}
+			this.stage = Stage::Running(op);
+		}
+
+		let res = match &this.stage {
+			Stage::Running(op) => match op.poll(cx) {
+				Poll::Ready(res) => res,
+				Poll::Pending => return Poll::Pending,
+			},
+			_ => panic!("`SendFuture` polled after completion"),
+		};
+
+		this.stage = Stage::Done;
+		match res {
+			AioResult::SendOk => Poll::Ready(Ok(())),
+			AioResult::SendErr(m, e) => {
+				this.unsent = Some(m);
+				Poll::Ready(Err(e))
+			},
+			AioResult::SendErrLost(e) => Poll::Ready(Err(e)),
+			res => unreachable!("Send AIO completed with a non-send result: {:?}", res),
+		}
+	}
+}
+
+/// A future that sends a request on a context and receives its reply.
+///
+/// This is created by `Context::request`. The request is sent when the future
+/// is first polled, and the reply is received on the same AIO as soon as the
+/// send has completed, so the future resolves with the reply or with the
+/// error of whichever step failed. The message is dropped if sending it
+/// fails. A _req_ context abandons its previous request when it sends a new
+/// one, so a reply to that one kept by a dropped `RecvFuture` is discarded.
+///
+/// Dropping the future aborts the request. If it is still being sent, the
+/// send is canceled, and otherwise the receive is, which makes a _req_
+/// context abandon the request: the reply is discarded when it arrives, even
+/// if it did so after the future was last polled, and the next request on the
+/// context is unaffected. The same happens when a timeout wrapping the future
+/// expires.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use async_std::{future::timeout, task};
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/future/request";
+///
+/// task::block_on(async {
+///     // The server echoes the requests, slowly so for some.
+///     let server = Socket::rep0()?;
+///     server.listen(ADDRESS)?;
+///     let ctx = Context::new(&server)?;
+///     task::spawn(async move {
+///         while let Ok(msg) = ctx.recv_future().await {
+///             if msg.starts_with(b"slow") {
+///                 task::sleep(Duration::from_millis(200)).await;
+///             }
+///             ctx.send_future(msg).await.unwrap();
+///         }
+///     });
+///
+///     // Every context has a request of its own in flight.
+///     let client = Socket::req0()?;
+///     client.dial(ADDRESS)?;
+///     let requests: Vec<_> = (0..4).map(|i| {
+///         let ctx = Context::new(&client)?;
+///         Ok(task::spawn(async move {
+///             let reply = ctx.request(format!("request {}", i).as_bytes()).await?;
+///             assert_eq!(&reply[..], format!("request {}", i).as_bytes());
+///             Ok::<(), Error>(())
+///         }))
+///     }).collect::<Result<_>>()?;
+///     for request in requests {
+///         request.await?;
+///     }
+///
+///     // A request that takes too long is abandoned, and its late reply doesn't
+///     // reach the next one.
+///     let ctx = Context::new(&client)?;
+///     let slow = timeout(Duration::from_millis(50), ctx.request(&b"slow"[..])).await;
+///     assert!(slow.is_err());
+///     assert_eq!(&ctx.request(&b"fast"[..]).await?[..], b"fast");
+///     Ok::<(), Error>(())
+/// })?;
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct RequestFuture
+{
+	/// The context the request is sent on.
+	ctx: Context,
+
+	/// How far along the request is.
+	stage: Exchange,
+}
+impl RequestFuture
+{
+	/// Creates a future that sends the request on the context.
+	pub(crate) fn new(ctx: &Context, msg: Message) -> Self
+	{
+		RequestFuture { ctx: ctx.clone(), stage: Exchange::Idle(msg) }
+	}
+
+	/// Cancels the send or receive, whichever is running.
+	///
+	/// The future then completes with `Error::Canceled`, unless the step that
+	/// was running completed first. This has no effect before the future is
+	/// first polled.
+	pub fn cancel(&self)
+	{
+		match &self.stage {
+			Exchange::Sending(op) => op.aio.cancel(),
+			Exchange::Receiving(op) => op.aio.cancel(),
+			_ => {},
+		}
+	}
+}
+
+impl Future for RequestFuture
+{
+	type Output = Result<Message>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output>
+	{
+		let this = &mut *self;
+		loop {
+			match mem::replace(&mut this.stage, Exchange::Done) {
+				Exchange::Idle(msg) => {
+					let op = match Operation::new() {
+						Ok(op) => op,
+						Err(e) => return Poll::Ready(Err(e)),
+					};
+
+					this.ctx.pre_received().clear();
+					if let Err(SendError { error, .. }) = this.ctx.send(&op.aio, msg) {
+						return Poll::Ready(Err(error));
+					}
+					this.stage = Exchange::Sending(op);
+				},
+				Exchange::Sending(op) => match op.poll(cx) {
+					Poll::Ready(AioResult::SendOk) => {
+						if let Err(e) = this.ctx.recv(&op.aio) {
+							return Poll::Ready(Err(e));
+						}
+						this.stage = Exchange::Receiving(op);
+					},
+					Poll::Ready(AioResult::SendErr(_, e))
+					| Poll::Ready(AioResult::SendErrLost(e)) => {
+						return Poll::Ready(Err(e));
+					},
+					Poll::Ready(res) => {
+						unreachable!("Send AIO completed with a non-send result: {:?}", res)
+					},
+					Poll::Pending => {
+						this.stage = Exchange::Sending(op);
+						return Poll::Pending;
+					},
+				},
+				Exchange::Receiving(op) => match op.poll(cx) {
+					Poll::Ready(AioResult::RecvOk(m)) => return Poll::Ready(Ok(m)),
+					Poll::Ready(AioResult::RecvErr(e)) => return Poll::Ready(Err(e)),
+					Poll::Ready(res) => {
+						unreachable!("Receive AIO completed with a non-receive result: {:?}", res)
+					},
+					Poll::Pending => {
+						this.stage = Exchange::Receiving(op);
+						return Poll::Pending;
+					},
+				},
+				Exchange::Done => panic!("`RequestFuture` polled after completion"),
+			}
+		}
+	}
+}
+
+/// How far along the request of a `RequestFuture` is.
+#[derive(Debug)]
+enum Exchange
+{
+	/// The future has not been polled yet and holds on to the request.
+	Idle(Message),
+
+	/// The request is being sent.
+	Sending(Operation),
+
+	/// The reply is being received, on the AIO that sent the request.
+	Receiving(Operation),
+
+	/// The future has completed.
+	Done,
+}
+
+/// What an operation is started on.
+#[derive(Debug)]
+enum Target
+{
+	/// A socket, for `Socket::recv_future` and `Socket::send_future`.
+	Socket(Socket),
+
+	/// A context, for `Context::recv_future` and `Context::send_future`.
+	Context(Context),
+}
+impl Target
+{
+	/// Starts a receive on the target.
+	fn recv(&self, aio: &Aio) -> Result<()>
+	{
+		match self {
+			Target::Socket(s) => s.recv_async(aio),
+			Target::Context(c) => c.recv(aio),
+		}
+	}
+
+	/// Starts a send on the target.
+	fn send(&self, aio: &Aio, msg: Message) -> SendResult<()>
+	{
+		match self {
+			Target::Socket(s) => s.send_async(aio, msg),
+			Target::Context(c) => c.send(aio, msg),
+		}
+	}
+
+	/// Keeps a message received by a dropped future for the next receive.
+	fn keep(&self, msg: Message)
+	{
+		match self {
+			Target::Socket(s) => s.pre_received().push_back(msg),
+			Target::Context(c) => c.pre_received().push_back(msg),
+		}
+	}
+}
+
+/// How far along the operation of a future is.
+#[derive(Debug)]
+enum Stage<T>
+{
+	/// The future has not been polled yet and holds on to what it needs to
+	/// start the operation.
+	Idle(T),
+
+	/// The operation is running.
+	Running(Operation),
+
+	/// The future has completed.
+	Done,
+}
+
+/// An operation on an AIO of its own, which wakes the future when it is done.
+#[derive(Debug)]
+struct Operation
+{
+	/// The AIO running the operation.
+	aio: Aio,
+
+	/// The state shared with the callback of the AIO.
+	slot: Arc<Mutex<Slot>>,
+}
+impl Operation
+{
+	/// Creates the AIO for a new operation.
+	fn new() -> Result<Self>
+	{
+		let slot = Arc::new(Mutex::new(Slot { result: None, waker: None }));
+		let cb_slot = Arc::clone(&slot);
+		let aio = Aio::new(move |_, res| {
+			// The result has to be in place before the waker is taken, as the future checks
+			// for it under the same lock before it stores a waker. The waker itself is
+			// called outside of the lock.
+			let waker = {
+				let mut slot = cb_slot.lock().unwrap_or_else(PoisonError::into_inner);
+				slot.result = Some(res);
+				slot.waker.take()
+			};
+
+			if let Some(waker) = waker {
+				waker.wake();
+			}
+		})?;
+
+		Ok(Operation { aio, slot })
+	}
+
+	/// Stops the operation and returns its result, if it completed.
+	fn finish(self) -> Option<AioResult>
+	{
+		self.aio.stop();
+		let res = self.slot.lock().unwrap_or_else(PoisonError::into_inner).result.take();
+		res
+	}
+
+	/// Returns the result of the operation or stores the waker of the task to
+	/// wake once there is one.
+	fn poll(&self, cx: &mut task::Context<'_>) -> Poll<AioResult>
+	{
+		let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+		if let Some(res) = slot.result.take() {
+			return Poll::Ready(res);
+		}
+
+		// The future may have moved to another task since the last poll, in which case
+		// the old waker would wake the wrong one.
+		match &slot.waker {
+			Some(w) if w.will_wake(cx.waker()) => {},
+			_ => slot.waker = Some(cx.waker().clone()),
+		}
+
+		Poll::Pending
+	}
+}
+
+impl Drop for Operation
+{
+	fn drop(&mut self)
+	{
+		// Stopping the AIO cancels the operation if it is still running and waits for
+		// the callback. Otherwise, the callback could end up with the last handle to
+		// the AIO and try to stop it from within itself.
+		self.aio.stop();
+	}
+}
+
+/// The state shared between a future and the callback of its AIO.
+#[derive(Debug)]
+struct Slot
+{
+	/// The result of the operation, once it has completed.
+	result: Option<AioResult>,
+
+	/// The waker of the task that last polled the future.
+	waker: Option<Waker>,
+}
+
+/// The messages received by futures that were dropped before returning them.
+///
+/// Every socket and context has one of these, which its receives take from
+/// before asking NNG for a new message.
+#[derive(Debug, Default)]
+pub(crate) struct PreReceived
+{
+	/// The number of kept messages, so that receives can skip the lock.
+	len: AtomicUsize,
+
+	/// The kept messages, oldest first.
+	messages: Mutex<VecDeque<Message>>,
+}
+impl PreReceived
+{
+	/// Keeps a message after the ones already kept.
+	pub(crate) fn push_back(&self, msg: Message)
+	{
+		let mut messages = self.messages.lock().unwrap_or_else(PoisonError::into_inner);
+		messages.push_back(msg);
+		self.len.store(messages.len(), Ordering::Release);
+	}
+
+	/// Puts a message that was taken but could not be delivered back in front.
+	pub(crate) fn push_front(&self, msg: Message)
+	{
+		let mut messages = self.messages.lock().unwrap_or_else(PoisonError::into_inner);
+		messages.push_front(msg);
+		self.len.store(messages.len(), Ordering::Release);
+	}
+
+	/// Drops all of the kept messages.
+	pub(crate) fn clear(&self)
+	{
+		if self.len.load(Ordering::Acquire) == 0 {
+			return;
+		}
+
+		let mut messages = self.messages.lock().unwrap_or_else(PoisonError::into_inner);
+		messages.clear();
+		self.len.store(messages.len(), Ordering::Release);
+	}
+
+	/// Takes the oldest kept message, if there is one.
+	pub(crate) fn pop_front(&self) -> Option<Message>
+	{
+		if self.len.load(Ordering::Acquire) == 0 {
+			return None;
+		}
+
+		let mut messages = self.messages.lock().unwrap_or_else(PoisonError::into_inner);
+		let msg = messages.pop_front();
+		self.len.store(messages.len(), Ordering::Release);
+		msg
+	}
+}
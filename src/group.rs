@@ -0,0 +1,358 @@
+//! Groups of AIO workers sharing a socket.
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::{
+	aio::{Aio, AioResult},
+	ctx::Context,
+	error::{Error, Result},
+	message::Message,
+	socket::Socket,
+};
+
+/// A collection of AIO workers, each with their own socket context.
+///
+/// This is the usual structure of an asynchronous server: a number of
+/// `(Aio, Context)` pairs that all run the same callback. The group owns the
+/// pairs and knows how to tear them down safely.
+///
+/// ## Teardown Order
+///
+/// Shutting down a set of AIO workers must happen in a specific order:
+///
+/// 1. Stop every AIO. This cancels the outstanding operations, waits for any
+///    running callback to finish, and makes future operations fail with
+///    `Error::Closed` so that the callbacks can no longer restart themselves.
+/// 2. Close every context. No callback can be using them anymore.
+/// 3. Close (or drop) the socket.
+///
+/// Closing the contexts or the socket first means a callback may still be
+/// running, or may be about to start a new operation, on an object that is
+/// being torn down. `AioGroup::shutdown` performs the first two steps in the
+/// correct order and dropping the group does the same. The socket is left
+/// alone, so a new group can be started on it afterwards.
+///
+/// Stopping the AIOs cancels replies that are still being sent. To answer the
+/// requests that were already received first, use `AioGroup::drain` instead.
+///
+/// Callbacks must handle errors from starting operations instead of
+/// panicking, as they may see `Error::Closed` during the shutdown.
+///
+/// ## Example
+///
+/// ```
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/group/example";
+///
+/// let server = Socket::new(Protocol::Rep0)?;
+/// let workers = AioGroup::new(&server, 4, |aio, ctx, res| {
+///     let _ = match res {
+///         // Echo the request back and then wait for the next one.
+///         AioResult::RecvOk(m) => ctx.send(&aio, m).map_err(|e| e.error),
+///         AioResult::SendOk => ctx.recv(&aio),
+///
+///         // Canceled or stopped, so there is nothing left to do.
+///         _ => Ok(()),
+///     };
+/// })?;
+///
+/// server.listen(ADDRESS)?;
+/// workers.start_recv()?;
+///
+/// let client = Socket::new(Protocol::Req0)?;
+/// client.dial(ADDRESS)?;
+/// client.send(&b"hello"[..])?;
+/// assert_eq!(&client.recv()?[..], b"hello");
+///
+/// workers.shutdown();
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct AioGroup
+{
+	/// The AIO objects and the contexts they operate on.
+	workers: Vec<(Aio, Context)>,
+
+	/// Held by each worker while its callback runs or a receive is started
+	/// on it, so that `AioGroup::drain` never cancels anything but a receive.
+	running: Vec<Arc<Mutex<()>>>,
+}
+impl AioGroup
+{
+	/// Creates `count` workers on the socket, all running the same callback.
+	///
+	/// The callback receives the context of the worker whose operation
+	/// completed. No operations are started, see `AioGroup::start_recv`.
+	///
+	/// The same panicking rules apply to the callback as to `Aio::new`.
+	pub fn new<F>(socket: &Socket, count: usize, callback: F) -> Result<Self>
+	where
+		F: Fn(Aio, &Context, AioResult) + Sync + Send + 'static,
+	{
+		let callback = Arc::new(callback);
+
+		let mut running = Vec::with_capacity(count);
+		let workers = (0..count)
+			.map(|_| {
+				let ctx = Context::new(socket)?;
+				let cb = Arc::clone(&callback);
+				let lock = Arc::new(Mutex::new(()));
+				let cb_lock = Arc::clone(&lock);
+				let aio = Aio::for_context(&ctx, move |aio, ctx, res| {
+					let _running = lock_running(&cb_lock);
+					cb(aio, ctx, res);
+				})?;
+
+				running.push(lock);
+				Ok((aio, ctx))
+			})
+			.collect::<Result<_>>()?;
+
+		Ok(AioGroup { workers, running })
+	}
+
+	/// Starts a receive operation on every worker.
+	pub fn start_recv(&self) -> Result<()>
+	{
+		self.workers.iter().zip(&self.running).try_for_each(|((a, c), r)| {
+			let _running = lock_running(r);
+			c.recv(a)
+		})
+	}
+
+	/// Returns the workers in the group.
+	pub fn workers(&self) -> &[(Aio, Context)] { &self.workers }
+
+	/// Cancels the current operation of every worker.
+	///
+	/// Unlike `AioGroup::shutdown`, the callbacks are free to start new
+	/// operations afterwards.
+	pub fn cancel(&self)
+	{
+		for (aio, _) in &self.workers {
+			aio.cancel();
+		}
+	}
+
+	/// Stops all of the workers and then closes their contexts.
+	///
+	/// This blocks until every running callback has finished. The socket is
+	/// not closed. This is the same as dropping the group, but more explicit.
+	pub fn shutdown(self) { drop(self) }
+
+	/// Shuts down the group once the workers have finished the requests they
+	/// already received.
+	///
+	/// From now on, starting a receive on any of the workers fails with
+	/// `Error::Closed`, and the workers that are waiting for a request are
+	/// canceled, so their callbacks see `Error::Canceled`. The ones in the
+	/// middle of a request keep going until they try to receive the next one,
+	/// which means that they can still sleep, send their reply, and retry a
+	/// failed send. This blocks until no worker has an operation left and then
+	/// shuts down the group as `AioGroup::shutdown` does.
+	///
+	/// Together with a `ShutdownToken`, this quiesces a server: wait for the
+	/// token, drain the group, and then close the socket. Closing the socket
+	/// still discards what the transports have yet to write, see
+	/// `Socket::close`.
+	///
+	/// Receives have to be started by the callbacks or `AioGroup::start_recv`
+	/// for this to work. Like `Aio::stop`, this must not be called from within
+	/// one of the callbacks.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/group/drain";
+	///
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// let workers = AioGroup::new(&server, 2, |aio, ctx, res| {
+	///     let _ = match res {
+	///         // Take a while to answer, so that the request is still being
+	///         // worked on when the group is drained.
+	///         AioResult::RecvOk(m) => {
+	///             aio.stash(m);
+	///             aio.sleep(Duration::from_millis(50))
+	///         },
+	///         AioResult::SleepOk => {
+	///             ctx.send(&aio, aio.take_stash().unwrap()).map_err(|e| e.error)
+	///         },
+	///
+	///         // Fails with `Error::Closed` once the group is draining.
+	///         AioResult::SendOk => ctx.recv(&aio),
+	///         _ => Ok(()),
+	///     };
+	/// })?;
+	/// server.listen(ADDRESS)?;
+	/// workers.start_recv()?;
+	///
+	/// let client = Socket::new(Protocol::Req0)?;
+	/// client.dial(ADDRESS)?;
+	/// client.send(&b"hello"[..])?;
+	/// thread::sleep(Duration::from_millis(10));
+	/// workers.drain();
+	///
+	/// // The request was answered before the group was shut down.
+	/// assert_eq!(&client.recv()?[..], b"hello");
+	/// server.close();
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn drain(self)
+	{
+		for (aio, _) in &self.workers {
+			aio.close_recv();
+		}
+
+		// A worker that isn't running its callback can only be waiting for a
+		// request or be in the middle of one, so canceling it while holding its
+		// lock never cuts off a reply.
+		for ((aio, _), running) in self.workers.iter().zip(&self.running) {
+			let _running = lock_running(running);
+			if aio.is_receiving() {
+				aio.cancel();
+			}
+		}
+
+		for (aio, _) in &self.workers {
+			aio.wait();
+		}
+	}
+}
+
+/// Locks the mutex that keeps a worker of an `AioGroup` from being drained.
+///
+/// The lock guards no data, so a callback that panicked leaves nothing
+/// inconsistent behind.
+fn lock_running(running: &Mutex<()>) -> MutexGuard<'_, ()>
+{
+	running.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Drop for AioGroup
+{
+	fn drop(&mut self)
+	{
+		// The order here matters, see the type level documentation.
+		for (aio, _) in &self.workers {
+			aio.stop();
+		}
+
+		for (_, ctx) in &self.workers {
+			ctx.close();
+		}
+	}
+}
+
+/// An AIO worker that receives messages in batches.
+///
+/// Every time a message arrives, the receiver also collects the messages that
+/// are already queued behind it, as with `Socket::recv_drain`, and hands all
+/// of them to the callback at once, up to `max` messages per call. Under load
+/// this amortizes the cost of a callback over many messages, while a single
+/// message is still delivered without delay.
+///
+/// Unlike `AioGroup`, this works on the socket itself rather than on
+/// contexts, so it can be used with protocols that have no contexts, such as
+/// _pull_. The receiver keeps the socket open and restarts itself after every
+/// callback until it is dropped. Dropping it blocks until any running
+/// callback has finished.
+///
+/// ## Example
+///
+/// ```
+/// use std::sync::mpsc;
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/group/drain_receiver";
+///
+/// let pull = Socket::new(Protocol::Pull0)?;
+/// pull.listen(ADDRESS)?;
+///
+/// let (tx, rx) = mpsc::channel();
+/// let tx = std::sync::Mutex::new(tx);
+/// let receiver = DrainReceiver::new(&pull, 64, move |res| {
+///     let _ = tx.lock().unwrap().send(res.map_err(|(_, e)| e));
+/// })?;
+///
+/// let push = Socket::new(Protocol::Push0)?;
+/// push.dial(ADDRESS)?;
+/// for i in 0..10u8 {
+///     push.send(&[i][..])?;
+/// }
+///
+/// let mut received = Vec::new();
+/// while received.len() < 10 {
+///     let batch = rx.recv().unwrap()?;
+///     assert!(!batch.is_empty() && batch.len() <= 64);
+///     received.extend(batch.iter().map(|m| m[0]));
+/// }
+/// assert_eq!(received, (0..10).collect::<Vec<_>>());
+///
+/// drop(receiver);
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct DrainReceiver
+{
+	/// The AIO performing the receives.
+	aio: Aio,
+}
+impl DrainReceiver
+{
+	/// Creates the receiver and starts receiving on the socket.
+	///
+	/// Each call of the callback receives between one and `max` messages. If
+	/// a receive fails, the callback is given the error along with whichever
+	/// messages were received before it, which may be none, and the receiver
+	/// carries on afterwards. Returns `Error::InvalidInput` if `max` is zero.
+	///
+	/// The same panicking rules apply to the callback as to `Aio::new`.
+	pub fn new<F>(socket: &Socket, max: usize, callback: F) -> Result<Self>
+	where
+		F: Fn(std::result::Result<Vec<Message>, (Vec<Message>, Error)>) + Sync + Send + 'static,
+	{
+		if max == 0 {
+			return Err(Error::InvalidInput);
+		}
+
+		let sock = socket.clone();
+		let aio = Aio::new(move |aio, res| {
+			let batch = match res {
+				AioResult::RecvOk(m) => {
+					let mut batch = vec![m];
+					match sock.recv_drain(max - 1) {
+						Ok(rest) => {
+							batch.extend(rest);
+							Ok(batch)
+						},
+						Err((rest, e)) => {
+							batch.extend(rest);
+							Err((batch, e))
+						},
+					}
+				},
+
+				// Stopped, as the receiver is being dropped.
+				AioResult::RecvErr(Error::Closed) | AioResult::RecvErr(Error::Canceled) => return,
+				AioResult::RecvErr(e) => Err((Vec::new(), e)),
+				_ => return,
+			};
+
+			callback(batch);
+
+			// This only fails once the AIO is stopped or the socket is closed.
+			let _ = sock.recv_async(&aio);
+		})?;
+
+		socket.recv_async(&aio)?;
+		Ok(DrainReceiver { aio })
+	}
+}
+
+impl Drop for DrainReceiver
+{
+	fn drop(&mut self) { self.aio.stop(); }
+}
@@ -0,0 +1,339 @@
+//! Detection of connections whose peer has silently gone away.
+use std::{
+	collections::HashMap,
+	fmt,
+	sync::{Arc, Condvar, Mutex},
+	thread::{self, JoinHandle},
+	time::{Duration, Instant},
+};
+
+use crate::{
+	error::{Error, Result},
+	message::Message,
+	options::{protocol::pair::Polyamorous, Options},
+	pipe::{Pipe, PipeEvent},
+	protocol::{Protocol, ProtocolKind},
+	socket::{PipeNotifyFn, Socket},
+};
+
+/// Sends heartbeats to the peers of a socket and closes the connections of
+/// peers that stop sending anything.
+///
+/// A peer that disappears without closing its connection, say because its
+/// machine lost power or a firewall dropped the connection, leaves a
+/// half-open connection behind. Nothing arrives on it any more, but TCP only
+/// notices once it tries to send and runs out of retransmissions, which can
+/// take many minutes. `tcp::KeepAlive` makes the operating system probe idle
+/// connections, but NNG 1.1 has no options for the keep-alive timers, so the
+/// system defaults apply, which are usually two hours. NNG has no heartbeat
+/// or idle timeout of its own either.
+///
+/// This fills the gap in the application. Every `interval`, a short
+/// heartbeat message, `Heartbeat::MESSAGE`, is sent to every peer, and a
+/// connection on which nothing has been received for `timeout` is closed.
+/// This removes its pipe as usual, which `Socket::pipe_notify` reports as
+/// `PipeEvent::RemovePost`, and a dialer then reconnects on its own.
+///
+/// Both ends have to run a heartbeat, with an interval well below the
+/// timeout of the other end. Received messages only count as signs of life
+/// if they go through `Heartbeat::recv` or `Heartbeat::observe`, which also
+/// filter out the heartbeats of the peer so that the application never sees
+/// them. Both ends therefore have to keep receiving, as heartbeats waiting in
+/// the receive buffer don't count until they have been received, and the
+/// application protocol must never send a message that is the same as a
+/// heartbeat.
+///
+/// Heartbeats need a way to send to every peer, so this only supports the
+/// _pair_ protocols, including polyamorous _pair_ version 1, and _bus_. They
+/// are sent without blocking and skipped for peers whose queue is full, as
+/// such a peer is busy rather than gone. Connections are followed from when
+/// the heartbeat starts, which should happen before the socket dials or
+/// listens. Connections that were already there are picked up once a message
+/// from them is observed.
+///
+/// The heartbeat stops when it is dropped or the socket is closed. It holds
+/// a handle to the socket until then.
+///
+/// ## Example
+///
+/// ```
+/// use std::{sync::{mpsc, Mutex}, thread, time::Duration};
+/// use nng::{options::{protocol::pair::Polyamorous, Options}, *};
+///
+/// const ADDRESS: &str = "inproc://nng/heartbeat/example";
+/// let interval = Duration::from_millis(10);
+/// let timeout = Duration::from_millis(200);
+///
+/// let server = Socket::new(Protocol::Pair1)?;
+/// server.set_opt::<Polyamorous>(true)?;
+/// let (tx, removed) = mpsc::channel();
+/// let tx = Mutex::new(tx);
+/// server.pipe_notify(move |_, ev| if ev == PipeEvent::RemovePost {
+///     let _ = tx.lock().unwrap().send(());
+/// })?;
+/// let heartbeat = Heartbeat::new(&server, interval, timeout)?;
+/// server.listen(ADDRESS)?;
+///
+/// let (tx, received) = mpsc::channel();
+/// thread::spawn(move || while let Ok(msg) = heartbeat.recv() {
+///     let _ = tx.send(msg);
+/// });
+///
+/// // While both ends send heartbeats and receive, the connection stays up.
+/// let client = Socket::new(Protocol::Pair1)?;
+/// let client_heartbeat = Heartbeat::new(&client, interval, timeout)?;
+/// client.dial(ADDRESS)?;
+/// thread::spawn(move || while client_heartbeat.recv().is_ok() {});
+///
+/// thread::sleep(timeout * 2);
+/// assert!(removed.try_recv().is_err());
+///
+/// // A peer that is connected but never sends anything is cut off.
+/// let silent = Socket::new(Protocol::Pair1)?;
+/// silent.dial(ADDRESS)?;
+/// removed.recv_timeout(Duration::from_secs(5)).expect("Quiet connection wasn't closed");
+///
+/// // The application only sees its own messages.
+/// client.send(&b"hello"[..])?;
+/// assert_eq!(&received.recv_timeout(Duration::from_secs(5)).unwrap()[..], b"hello");
+///
+/// client.close();
+/// server.close();
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub struct Heartbeat
+{
+	/// The state shared with the background thread and the pipe watcher.
+	shared: Arc<Shared>,
+
+	/// The pipe watcher, which is unregistered once this is dropped.
+	_watcher: Arc<PipeNotifyFn>,
+
+	/// The background thread sending the heartbeats.
+	thread: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat
+{
+	/// The body of every heartbeat message.
+	pub const MESSAGE: &'static [u8] = b"\0nng-rs heartbeat\0";
+
+	/// Starts sending heartbeats on the socket every `interval` and closing
+	/// connections that are quiet for `timeout`.
+	///
+	/// Returns `Error::NotSupported` if the socket uses a protocol other than
+	/// _pair_ or _bus_, or `Error::InvalidInput` if either duration is zero.
+	pub fn new(socket: &Socket, interval: Duration, timeout: Duration) -> Result<Self>
+	{
+		let polyamorous = match socket.protocol() {
+			ProtocolKind::Known(Protocol::Pair1) => socket.get_opt::<Polyamorous>()?,
+			ProtocolKind::Known(Protocol::Pair0) | ProtocolKind::Known(Protocol::Bus0) => false,
+			_ => return Err(Error::NotSupported),
+		};
+		if interval == Duration::from_secs(0) || timeout == Duration::from_secs(0) {
+			return Err(Error::InvalidInput);
+		}
+
+		let shared = Arc::new(Shared {
+			socket: socket.clone(),
+			interval,
+			timeout,
+			polyamorous,
+			state: Mutex::new(State { stopped: false, last_seen: HashMap::new() }),
+			cond: Condvar::new(),
+		});
+
+		let watcher_shared = Arc::clone(&shared);
+		let watcher: Arc<PipeNotifyFn> =
+			Arc::new(move |pipe, ev| watcher_shared.pipe_event(pipe, ev));
+		socket.watch_pipes(&watcher)?;
+
+		let thread_shared = Arc::clone(&shared);
+		let thread = thread::spawn(move || thread_shared.run());
+
+		Ok(Heartbeat { shared, _watcher: watcher, thread: Some(thread) })
+	}
+
+	/// Receives the next message that isn't a heartbeat.
+	///
+	/// This blocks like `Socket::recv` on the socket of the heartbeat and
+	/// observes every message it receives.
+	pub fn recv(&self) -> Result<Message>
+	{
+		loop {
+			let mut msg = self.shared.socket.recv()?;
+			if !self.observe(&mut msg) {
+				return Ok(msg);
+			}
+		}
+	}
+
+	/// Records that the connection the message came from is alive and
+	/// returns whether the message is a heartbeat.
+	///
+	/// Applications that receive messages in some other way, such as with an
+	/// `Aio`, have to pass every one of them through this and drop the
+	/// heartbeats.
+	pub fn observe(&self, msg: &mut Message) -> bool
+	{
+		if let Some(pipe) = msg.pipe() {
+			self.shared.state.lock().unwrap().last_seen.insert(pipe, Instant::now());
+		}
+
+		msg.as_slice() == Heartbeat::MESSAGE
+	}
+
+	/// Returns the number of connections being followed.
+	pub fn connections(&self) -> usize { self.shared.state.lock().unwrap().last_seen.len() }
+}
+
+impl fmt::Debug for Heartbeat
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("Heartbeat")
+			.field("interval", &self.shared.interval)
+			.field("timeout", &self.shared.timeout)
+			.field("connections", &self.connections())
+			.finish()
+	}
+}
+
+impl Drop for Heartbeat
+{
+	fn drop(&mut self)
+	{
+		self.shared.state.lock().unwrap().stopped = true;
+		self.shared.cond.notify_all();
+
+		if let Some(thread) = self.thread.take() {
+			if thread.join().is_err() {
+				log_error!("Heartbeat thread panicked");
+			}
+		}
+	}
+}
+
+/// The state shared between a `Heartbeat` and its thread.
+struct Shared
+{
+	/// The socket the heartbeats are sent on.
+	socket: Socket,
+
+	/// The time between heartbeats.
+	interval: Duration,
+
+	/// The time after which a quiet connection is closed.
+	timeout: Duration,
+
+	/// Whether the socket has to be told the pipe of every heartbeat.
+	polyamorous: bool,
+
+	/// The mutable state.
+	state: Mutex<State>,
+
+	/// Signalled when the heartbeat is stopped.
+	cond: Condvar,
+}
+
+impl Shared
+{
+	/// The body of the background thread.
+	fn run(&self)
+	{
+		// An interval too long for an `Instant` never comes to an end.
+		let mut next = Instant::now().checked_add(self.interval);
+		let mut state = self.state.lock().unwrap();
+		loop {
+			let now = Instant::now();
+			if state.stopped {
+				return;
+			}
+			match next {
+				Some(n) if now >= n => {},
+				Some(n) => {
+					state = self.cond.wait_timeout(state, n - now).unwrap().0;
+					continue;
+				},
+				None => {
+					state = self.cond.wait(state).unwrap();
+					continue;
+				},
+			}
+			next = now.checked_add(self.interval);
+
+			let timeout = self.timeout;
+			let quiet: Vec<Pipe> = state
+				.last_seen
+				.iter()
+				.filter(|&(_, &seen)| now.duration_since(seen) >= timeout)
+				.map(|(&pipe, _)| pipe)
+				.collect();
+			for pipe in &quiet {
+				state.last_seen.remove(pipe);
+			}
+			let pipes: Vec<Pipe> = state.last_seen.keys().copied().collect();
+			drop(state);
+
+			for pipe in quiet {
+				log_warn!("Closing pipe {} after {:?} without traffic", pipe.id(), timeout);
+				pipe.close();
+			}
+
+			if self.beat(&pipes) == Err(Error::Closed) {
+				return;
+			}
+			state = self.state.lock().unwrap();
+		}
+	}
+
+	/// Sends a heartbeat to each of the pipes.
+	fn beat(&self, pipes: &[Pipe]) -> Result<()>
+	{
+		if pipes.is_empty() {
+			return Ok(());
+		}
+
+		// Without polyamorous mode, a single message reaches every peer.
+		let targets = if self.polyamorous { pipes } else { &pipes[..1] };
+		for &pipe in targets {
+			let mut msg = Message::from(Heartbeat::MESSAGE);
+			if self.polyamorous {
+				msg.set_pipe(pipe);
+			}
+
+			match self.socket.send_blocking(msg, false) {
+				Ok(()) => {},
+				Err(e) if e.error == Error::Closed => return Err(Error::Closed),
+				Err(_) => {},
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Follows the pipes of the socket.
+	fn pipe_event(&self, pipe: Pipe, ev: PipeEvent)
+	{
+		let mut state = self.state.lock().unwrap();
+		match ev {
+			PipeEvent::AddPost => {
+				state.last_seen.insert(pipe, Instant::now());
+			},
+			PipeEvent::RemovePost => {
+				state.last_seen.remove(&pipe);
+			},
+			_ => {},
+		}
+	}
+}
+
+/// The mutable state of a `Heartbeat`.
+struct State
+{
+	/// Whether the heartbeat has been stopped.
+	stopped: bool,
+
+	/// The time something was last received on each connection.
+	last_seen: HashMap<Pipe, Instant>,
+}
@@ -77,6 +77,111 @@
 //!
 //! Additional examples are in the `examples` directory.
 //!
+//! ### Tracing
+//!
+//! Errors and warnings are always reported through the `log` crate. With the
+//! `tracing` feature they are also emitted as `tracing` events, along with the
+//! following events describing the life of sockets and operations:
+//!
+//! | Event           | Level   | Fields                                 |
+//! |-----------------|---------|----------------------------------------|
+//! | `socket.dial`   | `DEBUG` | `url`, `rv` or `result`                |
+//! | `socket.listen` | `DEBUG` | `url`, `rv`                            |
+//! | `pipe.event`    | `DEBUG` | `pipe`, `event`, `remote`              |
+//! | `aio.start`     | `TRACE` | `op`                                   |
+//! | `aio.complete`  | `TRACE` | `op`, `rv`                             |
+//!
+//! Pipe events are only reported for sockets with a pipe notification
+//! callback. The remote address is only looked up when the `DEBUG` level is
+//! enabled. When routing `log` records into `tracing`, the errors and warnings
+//! will show up twice.
+//!
+//! ```
+//! # #[cfg(feature = "tracing")] {
+//! use std::{io, sync::{Arc, Mutex}};
+//! use nng::*;
+//!
+//! #[derive(Clone, Default)]
+//! struct Capture(Arc<Mutex<Vec<u8>>>);
+//! impl io::Write for Capture {
+//!     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+//!         self.0.lock().unwrap().write(buf)
+//!     }
+//!     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+//! }
+//!
+//! let output = Capture::default();
+//! let writer = output.clone();
+//! tracing_subscriber::fmt()
+//!     .with_max_level(tracing::Level::TRACE)
+//!     .with_ansi(false)
+//!     .with_writer(move || writer.clone())
+//!     .init();
+//!
+//! const ADDRESS: &str = "inproc://nng/tracing";
+//! let server = Socket::new(Protocol::Rep0)?;
+//! server.pipe_notify(|_, _| {})?;
+//! server.listen(ADDRESS)?;
+//!
+//! let client = Socket::new(Protocol::Req0)?;
+//! client.dial(ADDRESS)?;
+//! client.send(&b"ping"[..])?;
+//!
+//! // Answer with an AIO so that the operation events show up.
+//! let (tx, rx) = std::sync::mpsc::sync_channel(1);
+//! let tx = Mutex::new(tx);
+//! let aio = Aio::new(move |_, res| tx.lock().unwrap().send(res).unwrap())?;
+//! server.recv_async(&aio)?;
+//! let request = match rx.recv().unwrap() {
+//!     AioResult::RecvOk(m) => m,
+//!     res => panic!("Unexpected result: {:?}", res),
+//! };
+//! server.send_async(&aio, request)?;
+//! rx.recv().unwrap();
+//! client.recv()?;
+//!
+//! let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+//! for expected in &[
+//!     "socket.listen url=\"inproc://nng/tracing\" rv=0",
+//!     "socket.dial url=\"inproc://nng/tracing\" rv=0",
+//!     "pipe.event pipe=",
+//!     "aio.start op=Receiving",
+//!     "aio.complete op=Receiving rv=0",
+//!     "aio.start op=Sending",
+//!     "aio.complete op=Sending rv=0",
+//! ] {
+//!     assert!(output.contains(expected), "missing {:?} in:\n{}", expected, output);
+//! }
+//! # }
+//! # Ok::<(), nng::Error>(())
+//! ```
+//!
+//! ### Futures
+//!
+//! With the `async-api` feature, sockets and contexts can also send and
+//! receive through futures, which work with any executor and pull in no async
+//! runtime. See the `future` module for details.
+//!
+//! ### Testing
+//!
+//! Code that only sends and receives can be written against the
+//! `MessageTransport` trait, which sockets and contexts implement. With the
+//! `testing` feature, the `testing` module provides a scripted mock of it for
+//! unit tests that don't need any real sockets.
+//!
+//! ### Configuration
+//!
+//! `options::SocketConfig` holds the common socket options, which
+//! `Socket::apply_config` checks and sets in one call. With the
+//! `config-serde` feature, it can be read from configuration files through
+//! `serde`.
+//!
+//! ### Memory statistics
+//!
+//! With the `memory-stats` feature, `memory_stats` reports how many messages
+//! are currently owned by Rust code and the size of their bodies, which helps
+//! tell messages held by the application apart from memory used elsewhere.
+//!
 //! [1]: https://github.com/nanomsg/nng
 //! [2]: https://nanomsg.github.io/nng/man/v1.1.0/nng_inproc.7
 //! [3]: https://nanomsg.github.io/nng/man/v1.1.0/nng_req.7
@@ -122,26 +227,85 @@ mod util;
 
 mod addr;
 mod aio;
+mod batch;
+mod channel;
+#[cfg(feature = "compression")]
+mod compression;
 mod ctx;
+mod device;
 mod dialer;
 mod error;
+mod group;
+mod heartbeat;
 mod listener;
+#[cfg(feature = "memory-stats")]
+mod memory;
 mod message;
+#[cfg(any(test, feature = "testing"))]
+mod ownership;
 mod pipe;
 mod protocol;
+mod pubsub;
+mod queue;
+mod reconnect;
+mod respondent;
+mod shutdown;
+mod split;
 mod socket;
+mod survey;
+mod tls;
+mod tracked;
+mod transport;
+mod typed;
+mod url;
+mod version;
 
+#[cfg(feature = "compat")]
+pub mod compat;
+#[cfg(feature = "async-api")]
+pub mod future;
 pub mod options;
+pub mod panic_policy;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod time;
+pub mod topics;
 
 pub use crate::{
-	addr::SocketAddr,
-	aio::{Aio, AioResult},
+	addr::{RawSocketAddr, SocketAddr},
+	aio::{Aio, AioResult, FilterDecision, OwnerTag},
+	batch::{BatchFrames, BatchReader, BatchingPublisher},
+	channel::{channel, TypedReceiver, TypedSendError, TypedSender},
 	ctx::Context,
-	dialer::{Dialer, DialerOptions},
-	error::{Error, Result},
-	listener::{Listener, ListenerOptions},
+	device::forwarder,
+	dialer::{Dialer, DialerOptions, DialerTemplate},
+	error::{
+		DialAnyError, Error, OptionApplyError, Result, SendError, SendResult, SendResultExt,
+		TemplateError,
+	},
+	group::{AioGroup, DrainReceiver},
+	heartbeat::Heartbeat,
+	listener::{bind_ephemeral, Listener, ListenerOptions, ListenerTemplate},
 	message::{Header, Message},
-	pipe::{Pipe, PipeEvent},
-	protocol::Protocol,
+	pipe::{Pipe, PipeEvent, PipeStats},
+	protocol::{available_protocols, Protocol, ProtocolKind},
+	pubsub::{LastValueCache, Publisher, Subscriber, SubscriberIter},
+	queue::PushQueue,
+	reconnect::ReconnectingDialer,
+	respondent::RespondentWorker,
+	shutdown::ShutdownToken,
 	socket::Socket,
+	split::{RecvHalf, SendHalf},
+	survey::{SurveyPeers, SurveyResponses},
+	tls::{tls_backend, TlsAuthMode, TlsConfig, TlsMode},
+	tracked::SendToken,
+	transport::MessageTransport,
+	typed::{Pair0Socket, Pair1Socket, PubSocket, ReqSocket, SubSocket, SurveyorSocket},
+	url::ParsedUrl,
+	version::nng_version,
 };
+
+#[cfg(feature = "compression")]
+pub use crate::compression::Compression;
+#[cfg(feature = "memory-stats")]
+pub use crate::memory::{memory_stats, MessageMemoryStats};
@@ -20,12 +20,23 @@
 use std::{
 	cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd},
 	ffi::CString,
+	fmt,
 	hash::{Hash, Hasher},
+	process,
+	sync::{
+		atomic::{AtomicU64, Ordering as AtomicOrdering},
+		Arc,
+	},
 };
 
 use crate::{
-	error::{Error, Result},
+	addr::SocketAddr,
+	error::{Error, Result, TemplateError},
+	options::{private::OptOps, LocalAddr, Options, SetOpt, Url},
 	socket::Socket,
+	tls::TlsConfig,
+	url::ParsedUrl,
+	util::missing_is_closed,
 };
 
 /// A constructed and running listener.
@@ -33,11 +44,14 @@ use crate::{
 /// This listener has already been started on the socket and will continue
 /// serving the connection until either it is explicitly close or the owning
 /// socket is closed.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone)]
 pub struct Listener
 {
 	/// The handle to the underlying
 	handle: nng_sys::nng_listener,
+
+	/// The URL the listener was created with.
+	url: Arc<str>,
 }
 impl Listener
 {
@@ -60,9 +74,136 @@ impl Listener
 			nng_sys::nng_listen(socket.handle(), addr.as_ptr(), &mut handle as *mut _, flags as i32)
 		};
 
-		rv2res!(rv, Listener { handle })
+		rv2res!(rv, Listener { handle, url: url.into() })
 	}
 
+	/// Creates and starts a listener on an already listening socket.
+	///
+	/// This is the same as creating `ListenerOptions` for the URL, handing
+	/// them the descriptor with `ListenerOptions::listen_fd` and starting
+	/// them, which is what socket activation by systemd or similar supervisors
+	/// needs. The URL should match the socket, such as a `tcp://` URL with its
+	/// address and port.
+	///
+	/// If NNG does not support listening on a descriptor, which is the case for
+	/// NNG v1.1, this returns `Error::NotSupported` and the descriptor is left
+	/// untouched. Once NNG has accepted the descriptor it owns it, so it is
+	/// closed along with the listener if the listener then fails to start.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{net::TcpListener, os::unix::io::{FromRawFd, IntoRawFd}};
+	/// use nng::*;
+	///
+	/// // Stand-in for the socket passed by the service manager.
+	/// let std_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	/// let url = format!("tcp://{}", std_listener.local_addr().unwrap());
+	/// let fd = std_listener.into_raw_fd();
+	///
+	/// let server = Socket::new(Protocol::Pull0)?;
+	/// match Listener::from_raw_fd(&server, &url, fd) {
+	///     Ok(listener) => {
+	///         let client = Socket::new(Protocol::Push0)?;
+	///         client.dial(&url)?;
+	///         client.send(&b"activated"[..])?;
+	///         assert_eq!(&server.recv()?[..], b"activated");
+	///         listener.close()?;
+	///     },
+	///     Err(Error::NotSupported) => drop(unsafe { TcpListener::from_raw_fd(fd) }),
+	///     Err(e) => return Err(e),
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	#[cfg(unix)]
+	pub fn from_raw_fd(socket: &Socket, url: &str, fd: std::os::unix::io::RawFd) -> Result<Self>
+	{
+		let options = ListenerOptions::new(socket, url)?;
+		options.listen_fd(fd)?;
+		options.start(false).map_err(|(_, e)| e)
+	}
+
+	/// Replaces the TLS configuration of the running listener.
+	///
+	/// Connections accepted afterwards use the new configuration, such as a
+	/// renewed certificate, while established connections keep the one they
+	/// were accepted with. The listener stays bound the whole time, so there
+	/// is no gap in which clients are refused. Replacing the listener instead
+	/// is not an option, as closing a listener also closes every connection it
+	/// accepted.
+	///
+	/// The listener keeps its own reference to the configuration, which can
+	/// be dropped afterwards. If this fails, the previous configuration stays
+	/// in place. Listeners that do not use TLS return `Error::NotSupported`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::{options::{transport::tls::CertKeyFile, Options}, *};
+	///
+	/// let config = |cert| -> Result<TlsConfig> {
+	///     let config = TlsConfig::new(TlsMode::Server)?;
+	///     config.cert_key_file(cert, None)?;
+	///     Ok(config)
+	/// };
+	///
+	/// // The TLS support of NNG is optional.
+	/// match config("examples/certs/server.pem") {
+	///     Err(Error::NotSupported) => return Ok(()),
+	///     res => res?,
+	/// };
+	///
+	/// let server = Socket::new(Protocol::Pair1)?;
+	/// server.set_opt::<options::protocol::pair::Polyamorous>(true)?;
+	/// let options = ListenerOptions::new(&server, "tls+tcp://127.0.0.1:0")?;
+	/// options.set_opt::<CertKeyFile>("examples/certs/server.pem".to_string())?;
+	/// let listener = options.start(false).map_err(|(_, e)| e)?;
+	/// let url = match listener.get_opt::<options::LocalAddr>()? {
+	///     SocketAddr::Inet(addr) => format!("tls+tcp://localhost:{}", addr.port()),
+	///     addr => panic!("Unexpected address: {:?}", addr),
+	/// };
+	///
+	/// let connect = || -> Result<Socket> {
+	///     let client = Socket::new(Protocol::Pair1)?;
+	///     let options = DialerOptions::new(&client, &url)?;
+	///     options.set_opt::<options::transport::tls::CaFile>("examples/certs/ca.pem".into())?;
+	///     options.start(false).map_err(|(_, e)| e)?;
+	///     Ok(client)
+	/// };
+	///
+	/// let before = connect()?;
+	/// before.send(&b"before"[..])?;
+	/// assert_eq!(&server.recv()?[..], b"before");
+	///
+	/// listener.rebind_tls(&config("examples/certs/server-renewed.pem")?)?;
+	///
+	/// // The established connection is unaffected, new ones get the new certificate.
+	/// before.send(&b"still connected"[..])?;
+	/// assert_eq!(&server.recv()?[..], b"still connected");
+	/// let after = connect()?;
+	/// after.send(&b"after"[..])?;
+	/// assert_eq!(&server.recv()?[..], b"after");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn rebind_tls(&self, config: &TlsConfig) -> Result<()>
+	{
+		let name = nng_sys::NNG_OPT_TLS_CONFIG.as_ptr() as *const _;
+		let rv = unsafe { nng_sys::nng_listener_setopt_ptr(self.handle, name, config.as_ptr()) };
+
+		rv2res!(rv).map_err(missing_is_closed)
+	}
+
+	/// Returns the ID of the listener, which is unique among the listeners of
+	/// all sockets and stays the same after it is closed.
+	pub fn id(&self) -> i32 { unsafe { nng_sys::nng_listener_id(self.handle) } }
+
+	/// Returns the URL the listener was created with.
+	///
+	/// This is the URL as it was given and is kept after the listener is
+	/// closed. It can differ from the `Url` option, which reports the port
+	/// that was bound in place of port zero.
+	pub fn url(&self) -> &str { &self.url }
+
 	/// Closes the listener.
 	///
 	/// This also closes any `Pipe` objects that have been created by the
@@ -71,18 +212,65 @@ impl Listener
 	/// utilize the listener (with this or any other handle) will result in an
 	/// error.
 	///
+	/// Closing a listener that is already closed, including through another
+	/// handle or by closing its socket, does nothing and returns `Ok`.
+	///
 	/// Listeners are implicitly closed when the socket they are associated with
 	/// is closed. Listeners are _not_ closed when all handles are dropped.
-	pub fn close(self)
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::*;
+	///
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// let listener = Listener::new(&server, "tcp://127.0.0.1:0", false)?;
+	/// let copy = listener.clone();
+	///
+	/// let debug = format!("{:?}", listener);
+	/// assert!(debug.contains(&format!("id: {}", listener.id())));
+	/// assert!(debug.contains("url: \"tcp://127.0.0.1:0\""));
+	/// assert_eq!(listener.to_string(), format!("listener#{} tcp://127.0.0.1:0", listener.id()));
+	///
+	/// // Closing twice, or through another handle, is fine.
+	/// assert!(!copy.is_closed());
+	/// listener.close()?;
+	/// copy.close()?;
+	/// assert!(copy.is_closed());
+	///
+	/// // The URL outlives the listener.
+	/// assert_eq!(listener.url(), "tcp://127.0.0.1:0");
+	///
+	/// // So does the closing of the socket.
+	/// let other = Listener::new(&server, "tcp://127.0.0.1:0", false)?;
+	/// server.close();
+	/// assert!(other.is_closed());
+	/// other.close()?;
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn close(&self) -> Result<()>
 	{
-		// Closing the listener should only ever result in success or ECLOSED
-		// and both of those mean that the drop was successful.
+		// Closing the listener should only ever result in success, ECLOSED, or ENOENT
+		// if it or its socket has already been closed. All of those mean that it is
+		// closed now.
 		let rv = unsafe { nng_sys::nng_listener_close(self.handle) };
-		assert!(
-			rv == 0 || rv == nng_sys::NNG_ECLOSED as i32,
-			"Unexpected error code while closing listener ({})",
-			rv
-		);
+		if rv == nng_sys::NNG_ECLOSED as i32 || rv == nng_sys::NNG_ENOENT as i32 {
+			return Ok(());
+		}
+
+		rv2res!(rv)
+	}
+
+	/// Returns whether the listener has been closed, through any handle or by
+	/// closing its socket.
+	pub fn is_closed(&self) -> bool
+	{
+		// Any option will do, as a listener that is closed can no longer be found.
+		let mut raw = false;
+		let name = nng_sys::NNG_OPT_RAW as *const _ as *const _;
+		let rv =
+			unsafe { nng_sys::nng_listener_getopt_bool(self.handle, name, &mut raw as *mut _) };
+		rv == nng_sys::NNG_ECLOSED as i32 || rv == nng_sys::NNG_ENOENT as i32
 	}
 
 	/// Create a new Listener handle from a libnng handle.
@@ -94,7 +282,13 @@ impl Listener
 			unsafe { nng_sys::nng_listener_id(handle) > 0 },
 			"Listener handle is not initialized"
 		);
-		Listener { handle }
+
+		// These are found through their pipes, so the URL has to be looked up.
+		let mut listener = Listener { handle, url: Arc::from("") };
+		if let Ok(url) = listener.get_opt::<Url>() {
+			listener.url = url.into();
+		}
+		listener
 	}
 }
 
@@ -134,9 +328,26 @@ impl Hash for Listener
 	}
 }
 
+impl fmt::Debug for Listener
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("Listener").field("id", &self.id()).field("url", &self.url).finish()
+	}
+}
+
+impl fmt::Display for Listener
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "listener#{} {}", self.id(), self.url)
+	}
+}
+
 #[rustfmt::skip]
 expose_options!{
 	Listener :: handle -> nng_sys::nng_listener;
+	MISSING_IS_CLOSED = true;
 
 	GETOPT_BOOL = nng_sys::nng_listener_getopt_bool;
 	GETOPT_INT = nng_sys::nng_listener_getopt_int;
@@ -155,7 +366,8 @@ expose_options!{
 	SETOPT_STRING = nng_sys::nng_listener_setopt_string;
 
 	Gets -> [LocalAddr, Raw, RecvBufferSize,
-	         RecvTimeout, SendBufferSize, Url,
+	         RecvMaxSize, RecvTimeout,
+	         SendBufferSize, Url,
 	         SendTimeout, SocketName, MaxTtl,
 	         protocol::reqrep::ResendTime,
 	         protocol::survey::SurveyTime,
@@ -170,11 +382,17 @@ expose_options!{
 /// started. If it is not necessary to change listener settings or to close the
 /// listener without closing the socket, then `Socket::listen` provides a
 /// simpler interface and does not require tracking an object.
+///
+/// TLS listeners always need this, as they must have a certificate before
+/// they start accepting connections, see `ListenerOptions::set_tls_config`.
 #[derive(Debug)]
 pub struct ListenerOptions
 {
 	/// The underlying listener object that we are configuring
 	handle: nng_sys::nng_listener,
+
+	/// The URL the listener was created with.
+	url: String,
 }
 impl ListenerOptions
 {
@@ -194,7 +412,7 @@ impl ListenerOptions
 			nng_sys::nng_listener_create(&mut handle as *mut _, socket.handle(), addr.as_ptr())
 		};
 
-		rv2res!(rv, ListenerOptions { handle })
+		rv2res!(rv, ListenerOptions { handle, url: url.to_string() })
 	}
 
 	/// Cause the listener to start listening on the address with which it was
@@ -210,7 +428,7 @@ impl ListenerOptions
 	/// The returned handle controls the life of the listener. If it is
 	/// dropped, the listener is shut down and no more messages will be
 	/// received on it.
-	pub fn start(self, nonblocking: bool) -> std::result::Result<Listener, (Self, Error)>
+	pub fn start(mut self, nonblocking: bool) -> std::result::Result<Listener, (Self, Error)>
 	{
 		let flags = if nonblocking { nng_sys::NNG_FLAG_NONBLOCK } else { 0 };
 
@@ -221,18 +439,145 @@ impl ListenerOptions
 
 		match rv {
 			0 => {
-				let handle = Listener { handle: self.handle };
+				let handle =
+					Listener { handle: self.handle, url: std::mem::take(&mut self.url).into() };
 				std::mem::forget(self);
 				Ok(handle)
 			},
-			e => Err((self, Error::from_code(e as u32))),
+			e => Err((self, missing_is_closed(Error::from_code(e as u32)))),
 		}
 	}
+
+	/// Makes the listener accept connections on an already listening socket.
+	///
+	/// This is meant for socket activation, such as by systemd, where the
+	/// service is handed a socket that is already bound and listening and
+	/// must not bind the address itself. The listener should be created with
+	/// a URL matching the socket, such as a `tcp://` URL with its address and
+	/// port, and started as usual after this succeeds. From then on, NNG owns
+	/// the descriptor and the application must not use or close it.
+	///
+	/// This relies on the `listen-fd` option of newer NNG versions. NNG v1.1
+	/// does not have it, in which case this returns `Error::NotSupported` and
+	/// the descriptor is left untouched.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{net::TcpListener, os::unix::io::{FromRawFd, IntoRawFd}};
+	/// use nng::*;
+	///
+	/// // Stand-in for the socket passed by the service manager.
+	/// let std_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	/// let url = format!("tcp://{}", std_listener.local_addr().unwrap());
+	/// let fd = std_listener.into_raw_fd();
+	///
+	/// let server = Socket::new(Protocol::Pull0)?;
+	/// let options = ListenerOptions::new(&server, &url)?;
+	/// match options.listen_fd(fd) {
+	///     Ok(()) => {
+	///         let _listener = options.start(false).map_err(|(_, e)| e)?;
+	///
+	///         let client = Socket::new(Protocol::Push0)?;
+	///         client.dial(&url)?;
+	///         client.send(&b"activated"[..])?;
+	///         assert_eq!(&server.recv()?[..], b"activated");
+	///     },
+	///     Err(Error::NotSupported) => drop(unsafe { TcpListener::from_raw_fd(fd) }),
+	///     Err(e) => return Err(e),
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	#[cfg(unix)]
+	pub fn listen_fd(&self, fd: std::os::unix::io::RawFd) -> Result<()>
+	{
+		let name = b"listen-fd\0";
+		let rv = unsafe {
+			nng_sys::nng_listener_setopt_int(self.handle, name.as_ptr() as *const _, fd)
+		};
+
+		rv2res!(rv).map_err(missing_is_closed)
+	}
+
+	/// Sets the TLS configuration the listener accepts connections with.
+	///
+	/// This is how a TLS server is set up before it starts accepting, with
+	/// its own certificate, the certificate authorities its clients are
+	/// checked against, and whether they must present a certificate at all. A
+	/// listener started with a URL only, such as by `Socket::listen`, has none
+	/// of that and cannot complete a single handshake. Every listener can be
+	/// given a configuration of its own, while listeners that are handed the
+	/// same one share it. See `examples/mtls.rs` for a server that requires
+	/// certificates from its clients.
+	///
+	/// The listener keeps its own reference to the configuration, replacing
+	/// the one it had. The `CaFile` and `CertKeyFile` options modify the
+	/// configuration of the listener, so setting them afterwards changes the
+	/// shared configuration for everyone using it. Once started, the
+	/// configuration can still be replaced with `Listener::rebind_tls`.
+	/// Listeners that do not use TLS return `Error::NotSupported`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::{options::{transport::tls::Verified, Options}, *};
+	///
+	/// let server_config = || -> Result<TlsConfig> {
+	///     let config = TlsConfig::new(TlsMode::Server)?;
+	///     config.cert_key_file("examples/certs/server.pem", None)?;
+	///     config.ca_file("examples/certs/ca.pem")?;
+	///     config.auth_mode(TlsAuthMode::Required)?;
+	///     Ok(config)
+	/// };
+	///
+	/// // The TLS support of NNG is optional.
+	/// let server_config = match server_config() {
+	///     Err(Error::NotSupported) => return Ok(()),
+	///     res => res?,
+	/// };
+	///
+	/// let server = Socket::new(Protocol::Pair0)?;
+	/// let options = ListenerOptions::new(&server, "tls+tcp://127.0.0.1:0")?;
+	/// options.set_tls_config(&server_config)?;
+	/// let listener = options.start(false).map_err(|(_, e)| e)?;
+	/// let url = match listener.get_opt::<options::LocalAddr>()? {
+	///     SocketAddr::Inet(addr) => format!("tls+tcp://localhost:{}", addr.port()),
+	///     addr => panic!("Unexpected address: {:?}", addr),
+	/// };
+	///
+	/// let client_config = TlsConfig::new(TlsMode::Client)?;
+	/// client_config.ca_file("examples/certs/ca.pem")?;
+	/// client_config.server_name("localhost")?;
+	/// client_config.cert_key_file("examples/certs/client.pem", None)?;
+	///
+	/// let client = Socket::new(Protocol::Pair0)?;
+	/// let options = DialerOptions::new(&client, &url)?;
+	/// options.set_tls_config(&client_config)?;
+	/// options.start(false).map_err(|(_, e)| e)?;
+	///
+	/// client.send(&b"hello"[..])?;
+	/// let mut msg = server.recv()?;
+	/// assert_eq!(&msg[..], b"hello");
+	/// assert!(msg.pipe().unwrap().get_opt::<Verified>()?);
+	///
+	/// // Plain listeners have no use for a TLS configuration.
+	/// let options = ListenerOptions::new(&server, "tcp://127.0.0.1:0")?;
+	/// assert_eq!(options.set_tls_config(&server_config), Err(Error::NotSupported));
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn set_tls_config(&self, config: &TlsConfig) -> Result<()>
+	{
+		let name = nng_sys::NNG_OPT_TLS_CONFIG.as_ptr() as *const _;
+		let rv = unsafe { nng_sys::nng_listener_setopt_ptr(self.handle, name, config.as_ptr()) };
+
+		rv2res!(rv).map_err(missing_is_closed)
+	}
 }
 
 #[rustfmt::skip]
 expose_options!{
 	ListenerOptions :: handle -> nng_sys::nng_listener;
+	MISSING_IS_CLOSED = true;
 
 	GETOPT_BOOL = nng_sys::nng_listener_getopt_bool;
 	GETOPT_INT = nng_sys::nng_listener_getopt_int;
@@ -251,7 +596,8 @@ expose_options!{
 	SETOPT_STRING = nng_sys::nng_listener_setopt_string;
 
 	Gets -> [LocalAddr, Raw, RecvBufferSize,
-	         RecvTimeout, SendBufferSize, Url,
+	         RecvMaxSize, RecvTimeout,
+	         SendBufferSize, Url,
 	         SendTimeout, SocketName, MaxTtl,
 	         protocol::reqrep::ResendTime,
 	         protocol::survey::SurveyTime,
@@ -286,13 +632,214 @@ impl Drop for ListenerOptions
 {
 	fn drop(&mut self)
 	{
-		// Closing the listener should only ever result in success or ECLOSED
-		// and both of those mean that the drop was successful.
+		// Closing the listener should only ever result in success, ECLOSED, or
+		// ENOENT if the socket has already closed and freed it. All of those mean
+		// that the drop was successful.
 		let rv = unsafe { nng_sys::nng_listener_close(self.handle) };
 		assert!(
-			rv == 0 || rv == nng_sys::NNG_ECLOSED as i32,
+			rv == 0 || rv == nng_sys::NNG_ECLOSED as i32 || rv == nng_sys::NNG_ENOENT as i32,
 			"Unexpected error code while closing listener ({})",
 			rv
 		);
 	}
 }
+
+/// A reusable set of listener options.
+///
+/// An NNG listener is tied to its URL from the moment it is created, so a
+/// `ListenerOptions` can only ever start a single listener. A template instead
+/// records the options on the Rust side and applies them, in the order in
+/// which they were added, to every listener it creates. This is useful for
+/// starting many listeners that only differ in their URL, such as one per
+/// port. Templates can be cloned to derive variations of them.
+///
+/// ## Example
+///
+/// ```
+/// use nng::{options::{LocalAddr, Options, RecvMaxSize}, *};
+///
+/// let template = ListenerTemplate::new().with_opt::<RecvMaxSize>(4096);
+/// let socket = Socket::new(Protocol::Pull0)?;
+///
+/// let mut ports = Vec::new();
+/// for _ in 0..3 {
+///     let listener = template.listen_on(&socket, "tcp://127.0.0.1:0")?;
+///     assert_eq!(listener.get_opt::<RecvMaxSize>()?, 4096);
+///     match listener.get_opt::<LocalAddr>()? {
+///         SocketAddr::Inet(addr) => ports.push(addr.port()),
+///         addr => panic!("Unexpected address: {:?}", addr),
+///     }
+/// }
+///
+/// ports.sort();
+/// ports.dedup();
+/// assert_eq!(ports.len(), 3);
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct ListenerTemplate
+{
+	/// The names of the stored options and the functions applying them.
+	options: Vec<(&'static str, Arc<ApplyFn>)>,
+}
+impl ListenerTemplate
+{
+	/// Creates a template without any options.
+	pub fn new() -> Self { ListenerTemplate::default() }
+
+	/// Adds an option to apply to every listener created from the template.
+	///
+	/// The value is not checked until it is applied, so an invalid one is
+	/// only reported by `ListenerTemplate::listen_on`.
+	pub fn with_opt<T>(mut self, val: T::OptType) -> Self
+	where
+		T: OptOps,
+		T::OptType: Clone + Send + Sync + 'static,
+		ListenerOptions: SetOpt<T>,
+	{
+		let apply = move |options: &ListenerOptions| options.set_opt::<T>(val.clone());
+		self.options.push((T::NAME, Arc::new(apply)));
+		self
+	}
+
+	/// Creates a listener on the socket with the stored options and starts
+	/// it.
+	///
+	/// The listener is started synchronously, as with
+	/// `ListenerOptions::start`. If anything fails, the error names the URL
+	/// and, if applicable, the stored option that could not be applied. No
+	/// listener is left behind in that case.
+	pub fn listen_on(
+		&self,
+		socket: &Socket,
+		url: &str,
+	) -> std::result::Result<Listener, TemplateError>
+	{
+		let fail = |option, error| TemplateError { url: url.to_string(), option, error };
+
+		let options = ListenerOptions::new(socket, url).map_err(|e| fail(None, e))?;
+		for (name, apply) in &self.options {
+			apply(&options).map_err(|e| fail(Some(*name), e))?;
+		}
+
+		options.start(false).map_err(|(_, e)| fail(None, e))
+	}
+}
+
+impl fmt::Debug for ListenerTemplate
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let names: Vec<_> = self.options.iter().map(|(name, _)| *name).collect();
+		f.debug_struct("ListenerTemplate").field("options", &names).finish()
+	}
+}
+
+/// Applies a stored option to a listener.
+type ApplyFn = dyn Fn(&ListenerOptions) -> Result<()> + Send + Sync;
+
+/// The number of names tried by `bind_ephemeral` for transports without
+/// ephemeral ports.
+const EPHEMERAL_ATTEMPTS: usize = 16;
+
+/// The counter making the names created by `bind_ephemeral` unique within the
+/// process.
+static EPHEMERAL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Starts a listener on an address picked at random from the given base URL.
+///
+/// This is meant for tests and other places that need a listener nobody else
+/// uses, without having to coordinate the choice of its address. The base URL
+/// is completed depending on its transport:
+///
+/// * For `tcp`, `tcp4`, `tcp6` and `tls+tcp`, the base must only have a host,
+///   such as `tcp://127.0.0.1`. The port `0` is appended, so that the system
+///   picks a free one, and the actual port is read back from the listener.
+/// * For `ipc` and `inproc`, which have no such thing, the process ID and a
+///   counter are appended to the path or name, such as
+///   `ipc:///tmp/test-1234-0`. If the result is already in use, by another
+///   process for example, the next value of the counter is tried, giving up
+///   after a few attempts with `Error::AddressInUse`.
+///
+/// This returns the listener, its local address, and a URL with which it can
+/// be dialed. Other transports are not supported and a base that already has
+/// a port is not a valid address. Note that a wildcard host, such as
+/// `0.0.0.0`, is kept in the returned URL, which is not dialable everywhere.
+///
+/// ## Example
+///
+/// ```
+/// use std::{collections::HashSet, thread};
+/// use nng::*;
+///
+/// let ipc = format!("ipc://{}/nng-ephemeral", std::env::temp_dir().display());
+/// let workers: Vec<_> = (0..100)
+///     .map(|_| {
+///         let ipc = ipc.clone();
+///         thread::spawn(move || -> Result<_> {
+///             let socket = Socket::new(Protocol::Pull0)?;
+///             let (_, _, tcp) = bind_ephemeral(&socket, "tcp://127.0.0.1")?;
+///             let (_, _, ipc) = bind_ephemeral(&socket, &ipc)?;
+///             Ok((socket, tcp, ipc))
+///         })
+///     })
+///     .collect();
+///
+/// let mut urls = HashSet::new();
+/// let mut sockets = Vec::new();
+/// for worker in workers {
+///     let (socket, tcp, ipc) = worker.join().unwrap()?;
+///     assert!(urls.insert(tcp.clone()) && urls.insert(ipc.clone()));
+///     sockets.push((socket, tcp, ipc));
+/// }
+///
+/// for (socket, tcp, ipc) in &sockets {
+///     for url in &[tcp, ipc] {
+///         let push = Socket::new(Protocol::Push0)?;
+///         push.dial(url)?;
+///         push.send(url.as_bytes())?;
+///         assert_eq!(&socket.recv()?[..], url.as_bytes());
+///     }
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub fn bind_ephemeral(socket: &Socket, base: &str) -> Result<(Listener, SocketAddr, String)>
+{
+	let parsed = ParsedUrl::parse(base).map_err(|_| Error::AddressInvalid)?;
+	match parsed.scheme() {
+		"tcp" | "tcp4" | "tcp6" | "tls+tcp" => {
+			if parsed.port().is_some() || !parsed.path().is_empty() {
+				return Err(Error::AddressInvalid);
+			}
+
+			let listener = Listener::new(socket, &format!("{}:0", base), false)?;
+			let addr = listener.get_opt::<LocalAddr>()?;
+			let url = match &addr {
+				SocketAddr::Inet(a) => format!("{}://{}", parsed.scheme(), a),
+				SocketAddr::Inet6(a) => format!("{}://{}", parsed.scheme(), a),
+				_ => return Err(Error::AddressInvalid),
+			};
+			Ok((listener, addr, url))
+		},
+		"ipc" | "inproc" => {
+			for _ in 0..EPHEMERAL_ATTEMPTS {
+				let count = EPHEMERAL_COUNTER.fetch_add(1, AtomicOrdering::Relaxed);
+				let url = format!("{}-{}-{}", base, process::id(), count);
+				let listener = match Listener::new(socket, &url, false) {
+					Err(Error::AddressInUse) => continue,
+					res => res?,
+				};
+
+				// The inproc transport has no addresses on its listeners.
+				let addr = match listener.get_opt::<LocalAddr>() {
+					Err(Error::NotSupported) => SocketAddr::InProc(url["inproc://".len()..].into()),
+					res => res?,
+				};
+				return Ok((listener, addr, url));
+			}
+
+			Err(Error::AddressInUse)
+		},
+		_ => Err(Error::NotSupported),
+	}
+}
@@ -0,0 +1,96 @@
+//! Accounting of the memory held in messages owned by Rust code.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of live messages owned by Rust code.
+static MESSAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// The total size of the bodies of those messages.
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// The messages currently owned by Rust code, as counted with the
+/// `memory-stats` feature.
+///
+/// A message is counted from when it is created, received, or cloned until
+/// it is dropped or handed to NNG, such as by sending it. Messages queued
+/// inside NNG, waiting to be sent or received, are not counted. Their bodies
+/// are counted with their current size, so growing or shrinking the body
+/// changes the total. Headers and the fixed overhead of every message are not
+/// included.
+///
+/// NNG 1.1 does not report how many messages are waiting in its queues, not
+/// even in its statistics. The messages it can hold are bounded by the
+/// `RecvBufferSize` and `SendBufferSize` of every socket, together with the
+/// queues of the individual pipes, which hold at most a few messages each.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct MessageMemoryStats
+{
+	/// The number of live messages.
+	pub messages: usize,
+
+	/// The total size of their bodies, in bytes.
+	pub bytes: usize,
+}
+
+/// Returns the number of messages currently owned by Rust code and the size
+/// of their bodies.
+///
+/// This is only available with the `memory-stats` feature. Without it, the
+/// accounting is compiled out and messages carry no overhead for it. With it,
+/// creating and dropping a message both take two relaxed atomic operations,
+/// and changing the size of its body takes one.
+///
+/// The two counters are read one after the other, so while other threads are
+/// creating or dropping messages they may not describe the same moment.
+///
+/// ## Example
+///
+/// ```
+/// use nng::{memory_stats, Message};
+///
+/// let baseline = memory_stats();
+/// for _ in 0..10_000 {
+///     drop(Message::from(&b"short-lived"[..]));
+/// }
+/// assert_eq!(memory_stats(), baseline);
+///
+/// let held: Vec<Message> = (0..1000).map(|i| Message::from(&vec![0; i % 100][..])).collect();
+/// let payload: usize = (0..1000).map(|i| i % 100).sum();
+/// let stats = memory_stats();
+/// assert_eq!(stats.messages, baseline.messages + 1000);
+/// assert_eq!(stats.bytes, baseline.bytes + payload);
+///
+/// drop(held);
+/// assert_eq!(memory_stats(), baseline);
+/// ```
+pub fn memory_stats() -> MessageMemoryStats
+{
+	MessageMemoryStats {
+		messages: MESSAGES.load(Ordering::Relaxed),
+		bytes:    BYTES.load(Ordering::Relaxed),
+	}
+}
+
+/// Counts a message with a body of the given size that Rust code took.
+pub(crate) fn acquired(bytes: usize)
+{
+	MESSAGES.fetch_add(1, Ordering::Relaxed);
+	BYTES.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Stops counting a message that was dropped or handed to NNG.
+pub(crate) fn released(bytes: usize)
+{
+	MESSAGES.fetch_sub(1, Ordering::Relaxed);
+	BYTES.fetch_sub(bytes, Ordering::Relaxed);
+}
+
+/// Accounts for a change in the size of the body of a counted message.
+pub(crate) fn resized(old: usize, new: usize)
+{
+	if new > old {
+		BYTES.fetch_add(new - old, Ordering::Relaxed);
+	}
+	else {
+		BYTES.fetch_sub(old - new, Ordering::Relaxed);
+	}
+}
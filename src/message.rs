@@ -1,5 +1,6 @@
 //! Message handling utilities
 use std::{
+	fmt,
 	io::{self, Write},
 	iter::FromIterator,
 	ops::{Deref, DerefMut, Index, IndexMut},
@@ -7,7 +8,17 @@ use std::{
 	slice::{self, SliceIndex},
 };
 
-use crate::{error::Result, pipe::Pipe, util::validate_ptr};
+#[cfg(feature = "compression")]
+use crate::compression::{self, Compression};
+#[cfg(feature = "memory-stats")]
+use crate::memory;
+#[cfg(any(test, feature = "testing"))]
+use crate::ownership;
+use crate::{
+	error::{Error, Result},
+	pipe::Pipe,
+	util::validate_ptr,
+};
 
 /// An `nng` message type.
 ///
@@ -18,10 +29,29 @@ use crate::{error::Result, pipe::Pipe, util::validate_ptr};
 /// In addition to the regular portion of the message there is a header that
 /// carries protocol specific header information. Most applications will not
 /// need to touch the header and will only interact with the regular message.
+///
+/// ## Byte Slice Access
+///
+/// A message dereferences to its body as a `[u8]` and also implements
+/// `AsRef<[u8]>` and `AsMut<[u8]>`, so it can be used anywhere a byte slice is
+/// expected. These only ever cover the body: the header is excluded and must
+/// be accessed explicitly through `Message::as_header`.
+///
+/// ```
+/// use nng::Message;
+///
+/// let mut msg = Message::from(&b"body"[..]);
+/// msg.as_mut_header().push_back(b"header")?;
+///
+/// assert_eq!(&*msg, b"body");
+/// assert_eq!(msg.as_ref(), b"body");
+/// assert_eq!(String::from_utf8_lossy(&msg), "body");
+/// assert_eq!(msg.as_header().as_slice(), b"header");
+/// # Ok::<(), nng::Error>(())
+/// ```
 // TODO(#29): We could implement many other common traits, we just have to figure out if the header
 // should be included in those or not. Maybe sometimes people will care about that. Also, make sure
 // those changes also get applied to `Header`.
-#[derive(Debug)]
 pub struct Message
 {
 	/// The pointer to the actual message.
@@ -37,6 +67,10 @@ pub struct Message
 	/// the message) but that requires both a `Header` and `HeaderMut` type
 	/// which seems like it would just end with duplicate code.
 	header: Header,
+
+	/// The size of the body as last counted by `memory_stats`.
+	#[cfg(feature = "memory-stats")]
+	counted: usize,
 }
 impl Message
 {
@@ -54,6 +88,7 @@ impl Message
 	///
 	/// The returned buffer will have a capacity equal to `cap` but a length of
 	/// zero. To get a `Message` with a specified length, use `Message::zeros`.
+	/// See `Message::reserve` for how the body grows beyond that.
 	pub fn with_capacity(cap: usize) -> Result<Self>
 	{
 		let mut msgp: *mut nng_sys::nng_msg = ptr::null_mut();
@@ -112,6 +147,7 @@ impl Message
 
 		// We are guarding against this, so this should never happen
 		debug_assert_eq!(rv, 0, "Message was too short to truncate");
+		self.recount();
 	}
 
 	/// Remove the first `len` bytes from the front of the message body.
@@ -126,6 +162,7 @@ impl Message
 		};
 
 		debug_assert_eq!(rv, 0, "Message was too short to trim");
+		self.recount();
 	}
 
 	/// Returns a slice that contains the contents of the message body.
@@ -150,6 +187,16 @@ impl Message
 		}
 	}
 
+	/// Returns a slice that contains the contents of the message body.
+	///
+	/// This is an alias of `Message::as_slice`.
+	pub fn as_bytes(&self) -> &[u8] { self.as_slice() }
+
+	/// Returns a mutable slice that contains the contents of the message body.
+	///
+	/// This is an alias of `Message::as_mut_slice`.
+	pub fn as_bytes_mut(&mut self) -> &mut [u8] { self.as_mut_slice() }
+
 	/// Returns a reference to the message header.
 	pub const fn as_header(&self) -> &Header { &self.header }
 
@@ -163,18 +210,67 @@ impl Message
 	pub fn is_empty(&self) -> bool { self.len() == 0 }
 
 	/// Clears the message body.
+	///
+	/// The header is left alone, see `Message::clear_header`.
 	pub fn clear(&mut self)
 	{
 		unsafe {
 			nng_sys::nng_msg_clear(self.msgp.as_ptr());
 		}
+		self.recount();
 	}
 
+	/// Clears the message header.
+	///
+	/// The body is left alone, see `Message::clear`. This is the same as
+	/// calling `Header::clear`. Messages that are reused for sending on raw
+	/// sockets usually need both cleared, as a new header is built for every
+	/// send.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/message/clear_header";
+	///
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// server.listen(ADDRESS)?;
+	/// let client = unsafe { Socket::open_with("req0", nng_sys::nng_req0_open_raw)? };
+	/// client.dial(ADDRESS)?;
+	///
+	/// // Raw requests carry their ID, with the high bit set, in the header and every
+	/// // reply is reused as the next request.
+	/// let mut msg = Message::new()?;
+	/// for id in 1..=3u32 {
+	///     let id = (0x8000_0000 | id).to_be_bytes();
+	///     msg.clear();
+	///     msg.clear_header();
+	///     msg.as_mut_header().push_back(&id)?;
+	///     msg.push_back(b"ping")?;
+	///     client.send(msg)?;
+	///
+	///     let req = server.recv()?;
+	///     server.send(req)?;
+	///
+	///     msg = client.recv()?;
+	///     msg.clear();
+	///     assert!(msg.is_empty());
+	///     assert_eq!(msg.as_header().as_slice(), &id[..]);
+	/// }
+	///
+	/// msg.clear_header();
+	/// assert!(msg.as_header().is_empty());
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn clear_header(&mut self) { self.header.clear() }
+
 	/// Prepends the data to the message body.
 	pub fn push_front(&mut self, data: &[u8]) -> Result<()>
 	{
 		let rv =
 			unsafe { nng_sys::nng_msg_insert(self.msgp.as_ptr(), data.as_ptr() as _, data.len()) };
+		self.recount();
 
 		rv2res!(rv)
 	}
@@ -184,10 +280,85 @@ impl Message
 	{
 		let rv =
 			unsafe { nng_sys::nng_msg_append(self.msgp.as_ptr(), data.as_ptr() as _, data.len()) };
+		self.recount();
 
 		rv2res!(rv)
 	}
 
+	/// Appends the data to the back of the message body.
+	///
+	/// This is equivalent to `Message::push_back` but matches the naming used
+	/// by `Vec<u8>`.
+	pub fn extend_from_slice(&mut self, data: &[u8]) -> Result<()> { self.push_back(data) }
+
+	/// Makes room for at least `additional` more bytes at the back of the body.
+	///
+	/// NNG grows a body to exactly the size it needs whenever data is
+	/// appended past the end of its buffer, copying the body every time. It
+	/// only allocates a little slack when it creates the message and never
+	/// over-allocates after that, so appending many small pieces past the
+	/// end, such as with the `io::Write` implementation, reallocates for
+	/// every one of them. After this returns, appending up to `additional`
+	/// bytes with `Message::push_back` or `io::Write` is guaranteed not to
+	/// reallocate. Nothing is allocated if there is room already, so this is
+	/// cheap to call before every message when reusing one.
+	///
+	/// The room is at the back of the body, so `Message::clear` and
+	/// `Message::truncate` keep it, while the space removed with
+	/// `Message::trim` can't be appended to until the body is reallocated.
+	/// The header has a buffer of its own and isn't affected.
+	///
+	/// Returns `Error::OutOfMemory` if the memory can't be allocated or the
+	/// body would be larger than `isize::MAX` bytes.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::io::Write;
+	/// use byteorder::{BigEndian, WriteBytesExt};
+	/// use nng::Message;
+	///
+	/// // The message is reused, so only the first round allocates.
+	/// let mut msg = Message::new()?;
+	/// for round in 0..3u64 {
+	///     msg.clear();
+	///     msg.reserve(1000 * 8)?;
+	///     let buffer = msg.as_slice().as_ptr();
+	///
+	///     // None of the writes move the body while they fit.
+	///     for i in 0..1000 {
+	///         msg.write_u64::<BigEndian>(round * 1000 + i).unwrap();
+	///         assert_eq!(msg.as_slice().as_ptr(), buffer);
+	///     }
+	///     assert_eq!(msg.len(), 8000);
+	/// }
+	///
+	/// // Writing past the reserved room reallocates the body.
+	/// let buffer = msg.as_slice().as_ptr();
+	/// msg.write_all(&[0; 4096]).unwrap();
+	/// assert_ne!(msg.as_slice().as_ptr(), buffer);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn reserve(&mut self, additional: usize) -> Result<()>
+	{
+		// NNG doesn't check its sizes for overflow, so this has the same limit
+		// as a `Vec<u8>`.
+		let wanted = match self.len().checked_add(additional) {
+			Some(wanted) if wanted <= isize::MAX as usize => wanted,
+			_ => return Err(Error::OutOfMemory),
+		};
+
+		// NNG has no way to ask for capacity directly, but growing the body
+		// only reallocates if it doesn't fit, and shrinking it back keeps the
+		// buffer.
+		let rv = unsafe { nng_sys::nng_msg_realloc(self.msgp.as_ptr(), wanted) };
+		rv2res!(rv)?;
+
+		let rv = unsafe { nng_sys::nng_msg_chop(self.msgp.as_ptr(), additional) };
+		debug_assert_eq!(rv, 0, "Message was too short to chop");
+		Ok(())
+	}
+
 	/// Attempts to duplicate the message.
 	///
 	/// This is functionally equivalent to calling `Clone` but allows the user
@@ -202,6 +373,260 @@ impl Message
 		Ok(Message::from_ptr(msgp))
 	}
 
+	/// Converts the message into a `Bytes` without copying the body.
+	///
+	/// The returned buffer shares the memory of the message, which is freed
+	/// once the last clone of the `Bytes` is dropped. The header is not part of
+	/// the buffer but stays allocated until then as well.
+	///
+	/// Going the other way, with `Message::from`, always copies, as NNG can
+	/// only send messages that it allocated itself.
+	///
+	/// ```
+	/// # #[cfg(feature = "bytes")] {
+	/// use nng::Message;
+	///
+	/// let msg = Message::from(&b"hello"[..]);
+	/// let body = msg.as_slice().as_ptr();
+	///
+	/// let bytes = msg.into_bytes();
+	/// assert_eq!(&bytes[..], b"hello");
+	/// assert_eq!(bytes.as_ptr(), body);
+	///
+	/// let msg = Message::from(bytes.slice(1..));
+	/// assert_eq!(&msg[..], b"ello");
+	/// # }
+	/// ```
+	#[cfg(feature = "bytes")]
+	pub fn into_bytes(self) -> bytes::Bytes { bytes::Bytes::from_owner(self) }
+
+	/// Appends an integrity trailer to the message body.
+	///
+	/// This guards against corruption introduced outside of NNG, such as by a
+	/// faulty bridge between two networks, which the transports cannot detect.
+	/// The receiver removes the trailer with `Message::verify_and_unseal`. The
+	/// header is owned by the protocol and is neither covered nor modified.
+	///
+	/// ## Layout
+	///
+	/// The trailer is 8 bytes long and is placed directly after the body, so a
+	/// sealed message is laid out as follows:
+	///
+	/// | Bytes | Contents                                               |
+	/// |-------|--------------------------------------------------------|
+	/// | `n`   | The original body                                      |
+	/// | 4     | The magic value `NNGS` (`0x4E 0x4E 0x47 0x53`)         |
+	/// | 4     | The CRC-32 of the original body, big-endian            |
+	///
+	/// The checksum is the common CRC-32 used by zlib, Ethernet and PNG
+	/// (reflected polynomial `0xEDB88320`, initial value and final XOR of
+	/// `0xFFFFFFFF`), so peers in other languages can use any standard
+	/// implementation.
+	///
+	/// Returns `Error::IncorrectState` if the message is already sealed.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::{Error, Message};
+	///
+	/// let mut msg = Message::from(&b"payload"[..]);
+	/// msg.seal()?;
+	/// assert_eq!(msg.len(), 7 + 8);
+	/// assert_eq!(msg.seal(), Err(Error::IncorrectState));
+	///
+	/// // Flipping a single bit of the body or of the trailer is detected.
+	/// for &i in &[0, 12] {
+	///     let mut corrupt = msg.clone();
+	///     corrupt[i] ^= 0x01;
+	///     assert_eq!(corrupt.verify_and_unseal(), Err(Error::IntegrityCheckFailed));
+	/// }
+	///
+	/// msg.verify_and_unseal()?;
+	/// assert_eq!(msg.as_slice(), b"payload");
+	/// assert_eq!(msg.verify_and_unseal(), Err(Error::IntegrityCheckFailed));
+	///
+	/// // The trailer of the standard CRC-32 check input.
+	/// let mut check = Message::from(&b"123456789"[..]);
+	/// check.seal()?;
+	/// assert_eq!(&check[9..], b"NNGS\xCB\xF4\x39\x26");
+	///
+	/// // Empty messages work as well.
+	/// let mut empty = Message::new()?;
+	/// empty.seal()?;
+	/// assert_eq!(&empty[..], &[0x4E, 0x4E, 0x47, 0x53, 0x00, 0x00, 0x00, 0x00]);
+	/// empty.verify_and_unseal()?;
+	/// assert!(empty.is_empty());
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn seal(&mut self) -> Result<()>
+	{
+		if seal_is_valid(self.as_slice()) {
+			return Err(Error::IncorrectState);
+		}
+
+		let crc = crc32(self.as_slice());
+		let mut trailer = [0; SEAL_LEN];
+		trailer[..4].copy_from_slice(SEAL_MAGIC);
+		trailer[4..].copy_from_slice(&crc.to_be_bytes());
+
+		self.push_back(&trailer)
+	}
+
+	/// Checks and removes the integrity trailer added by `Message::seal`.
+	///
+	/// Returns `Error::IntegrityCheckFailed` if the body was modified, if the
+	/// trailer is damaged, or if the message was not sealed at all (including
+	/// when it has already been unsealed). The message is left untouched when
+	/// an error is returned.
+	pub fn verify_and_unseal(&mut self) -> Result<()>
+	{
+		if !seal_is_valid(self.as_slice()) {
+			return Err(Error::IntegrityCheckFailed);
+		}
+
+		let len = self.len() - SEAL_LEN;
+		self.truncate(len);
+		Ok(())
+	}
+
+	/// Compresses the message body.
+	///
+	/// The compressed body starts with a small header identifying it, so the
+	/// receiver can use `Message::decompress` without knowing whether or how
+	/// the sender compressed it. If compressing doesn't make the body any
+	/// smaller, it is left uncompressed. This always applies to empty and very
+	/// short bodies, which are smaller than the compression header alone, so
+	/// they are sent as they are and come out of `Message::decompress`
+	/// unchanged. The header is owned by the protocol and is never touched.
+	///
+	/// ## Layout
+	///
+	/// A compressed body is laid out as follows:
+	///
+	/// | Bytes | Contents                                               |
+	/// |-------|--------------------------------------------------------|
+	/// | 4     | The magic value `NNGZ` (`0x4E 0x4E 0x47 0x5A`)         |
+	/// | 1     | The algorithm: 1 for Zstandard, 2 for LZ4              |
+	/// | 4     | The length of the original body, big-endian            |
+	/// | `n`   | The compressed body                                    |
+	///
+	/// Returns `Error::IncorrectState` if the body already starts with the
+	/// magic value, as it would be mistaken for a compressed one, and
+	/// `Error::MessageTooLarge` if it is larger than 4 GiB. The message is left
+	/// untouched when an error is returned.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// # #[cfg(feature = "compression-zstd")] {
+	/// use nng::{Compression, Error, Message};
+	///
+	/// let mut msg = Message::from(&[b'a'; 1000][..]);
+	/// msg.compress(Compression::Zstd)?;
+	/// assert!(msg.len() < 100);
+	/// assert_eq!(&msg[..5], b"NNGZ\x01");
+	/// assert_eq!(msg.compress(Compression::Zstd), Err(Error::IncorrectState));
+	///
+	/// msg.decompress()?;
+	/// assert_eq!(&msg[..], &[b'a'; 1000][..]);
+	///
+	/// // Data that doesn't get any smaller is left as it is.
+	/// let mut short = Message::from(&b"abc"[..]);
+	/// short.compress(Compression::Zstd)?;
+	/// assert_eq!(&short[..], b"abc");
+	/// short.decompress()?;
+	/// assert_eq!(&short[..], b"abc");
+	///
+	/// let mut empty = Message::new()?;
+	/// empty.compress(Compression::Zstd)?;
+	/// empty.decompress()?;
+	/// assert!(empty.is_empty());
+	/// # }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	#[cfg(feature = "compression")]
+	pub fn compress(&mut self, algo: Compression) -> Result<()>
+	{
+		if compression::is_compressed(self.as_slice()) {
+			return Err(Error::IncorrectState);
+		}
+
+		if let Some(data) = compression::compress(self.as_slice(), algo)? {
+			self.replace_body(&data)?;
+		}
+
+		Ok(())
+	}
+
+	/// Decompresses a body compressed by `Message::compress`.
+	///
+	/// The decompressed body may be at most 1 MiB, which is the default
+	/// `RecvMaxSize` of NNG. This is the same as
+	/// `Message::decompress_limited`, which can use any other limit.
+	#[cfg(feature = "compression")]
+	pub fn decompress(&mut self) -> Result<()>
+	{
+		self.decompress_limited(compression::DEFAULT_MAX_DECOMPRESSED_SIZE)
+	}
+
+	/// Decompresses a body compressed by `Message::compress`, rejecting bodies
+	/// that would be larger than the limit once decompressed.
+	///
+	/// A body without the compression header is left as it is, so this can be
+	/// used on every message from a peer that only sometimes compresses. The
+	/// limit protects against bodies that decompress to far more than the
+	/// size of the message, and is checked against the original length in the
+	/// compression header before anything is decompressed.
+	///
+	/// Returns `Error::MessageExceedsLimit` if the body would be too large,
+	/// `Error::NotSupported` if it uses an algorithm whose feature isn't
+	/// enabled, and `Error::DecompressionFailed` if it is damaged. The message
+	/// is left untouched when an error is returned.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// # #[cfg(feature = "compression-lz4")] {
+	/// use nng::{Compression, Error, Message};
+	///
+	/// let mut msg = Message::with_zeros(64 * 1024)?;
+	/// msg.compress(Compression::Lz4)?;
+	/// let compressed = msg.clone();
+	///
+	/// // The limit applies to the decompressed body, not the compressed one.
+	/// assert_eq!(
+	///     msg.decompress_limited(1024),
+	///     Err(Error::MessageExceedsLimit { size: 64 * 1024, limit: 1024 })
+	/// );
+	/// assert_eq!(&msg[..], &compressed[..]);
+	///
+	/// // Lying about the length in the compression header is detected.
+	/// let mut forged = compressed.clone();
+	/// forged[5..9].copy_from_slice(&(1024u32).to_be_bytes());
+	/// assert_eq!(forged.decompress_limited(1024), Err(Error::DecompressionFailed));
+	///
+	/// let mut truncated = compressed.clone();
+	/// truncated.truncate(compressed.len() - 1);
+	/// assert_eq!(truncated.decompress(), Err(Error::DecompressionFailed));
+	///
+	/// msg.decompress_limited(64 * 1024)?;
+	/// assert_eq!(msg.len(), 64 * 1024);
+	/// assert!(msg.iter().all(|&b| b == 0));
+	/// # }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	#[cfg(feature = "compression")]
+	pub fn decompress_limited(&mut self, max: usize) -> Result<()>
+	{
+		if compression::is_compressed(self.as_slice()) {
+			let data = compression::decompress(self.as_slice(), max)?;
+			self.replace_body(&data)?;
+		}
+
+		Ok(())
+	}
+
 	/// Returns the pipe object associated with the message.
 	///
 	/// On receive, this is the pipe from which the message was received. On
@@ -229,30 +654,178 @@ impl Message
 	/// This is most useful when used with protocols that support directing a
 	/// message to a specific peer. For example, the _pair_ version 1 protocol
 	/// can do this when in polyamorous mode. Not all protocols support this.
+	///
+	/// Raw _rep_ and _respondent_ sockets ignore the pipe and route by the
+	/// message header instead, as described in `Message::push_route`.
 	pub fn set_pipe(&mut self, pipe: Pipe)
 	{
 		unsafe { nng_sys::nng_msg_set_pipe(self.msgp.as_ptr(), pipe.handle()) }
 	}
 
+	/// Prepends the routing information for the pipe to the message header.
+	///
+	/// Raw _rep_ and _respondent_ sockets deliver each message to the pipe
+	/// named by the first four bytes of the header, which hold the pipe ID in
+	/// big-endian order. That word is removed on delivery and the rest of the
+	/// header, the backtrace of the request that is being answered, goes out in
+	/// front of the body. A message whose pipe no longer exists is dropped.
+	/// `Message::set_pipe` has no effect on these sockets.
+	///
+	/// Received messages already start with the route of the pipe they came
+	/// from, so replying on the pipe that sent a request needs nothing but the
+	/// header that came with it. This is for everything else, such as user
+	/// space routers that pick the pipe themselves: take the route off with
+	/// `Message::take_route`, keep the rest of the header, and push the route
+	/// of the chosen pipe before sending.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/message/push_route";
+	///
+	/// let router = Socket::new_raw(Protocol::Rep0)?;
+	/// router.listen(ADDRESS)?;
+	///
+	/// let clients = [Socket::req0()?, Socket::req0()?];
+	/// for (i, client) in clients.iter().enumerate() {
+	///     client.dial(ADDRESS)?;
+	///     client.send(&[i as u8][..])?;
+	/// }
+	///
+	/// // Answer each request with a brand new message, routed by hand.
+	/// for _ in 0..clients.len() {
+	///     let mut request = router.recv()?;
+	///     let pipe = request.take_route().unwrap();
+	///     assert_eq!(Some(pipe), request.pipe());
+	///
+	///     let mut reply = Message::from(&[request[0] + 10][..]);
+	///     reply.as_mut_header().push_back(request.as_header().as_slice())?;
+	///     reply.push_route(pipe)?;
+	///     router.send(reply)?;
+	/// }
+	///
+	/// for (i, client) in clients.iter().enumerate() {
+	///     assert_eq!(&client.recv()?[..], &[i as u8 + 10]);
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn push_route(&mut self, pipe: Pipe) -> Result<()>
+	{
+		let rv =
+			unsafe { nng_sys::nng_msg_header_insert_u32(self.msgp.as_ptr(), pipe.id() as u32) };
+		rv2res!(rv)
+	}
+
+	/// Removes the routing information from the front of the message header
+	/// and returns the pipe it names.
+	///
+	/// This is the counterpart of `Message::push_route`. It returns `None`,
+	/// leaving the header as it was, if the header is too short to hold a
+	/// route or does not start with a pipe ID. Messages received on a raw
+	/// _bus_ socket also start with the ID of the pipe they came from, which
+	/// makes the socket skip that pipe when the message is sent again.
+	pub fn take_route(&mut self) -> Option<Pipe>
+	{
+		let id = match self.header.as_slice() {
+			[a, b, c, d, ..] => u32::from_be_bytes([*a, *b, *c, *d]),
+			_ => return None,
+		};
+
+		if id == 0 || id > i32::MAX as u32 {
+			return None;
+		}
+
+		self.header.trim(4);
+		Some(Pipe::from_nng_sys(nng_sys::nng_pipe { _bindgen_opaque_blob: id }))
+	}
+
+	/// Replaces the body with the data, leaving it untouched on failure.
+	#[cfg(feature = "compression")]
+	fn replace_body(&mut self, data: &[u8]) -> Result<()>
+	{
+		let rv = unsafe { nng_sys::nng_msg_realloc(self.msgp.as_ptr(), data.len()) };
+		rv2res!(rv)?;
+		self.recount();
+
+		self.as_mut_slice().copy_from_slice(data);
+		Ok(())
+	}
+
 	/// Creates a new message from the given pointer.
-	pub(crate) const fn from_ptr(msgp: NonNull<nng_sys::nng_msg>) -> Self
+	pub(crate) fn from_ptr(msgp: NonNull<nng_sys::nng_msg>) -> Self
 	{
+		#[cfg(any(test, feature = "testing"))]
+		ownership::claim(msgp);
+
+		#[cfg(feature = "memory-stats")]
+		{
+			let counted = unsafe { nng_sys::nng_msg_len(msgp.as_ptr()) };
+			memory::acquired(counted);
+			Message { msgp, header: Header { msgp }, counted }
+		}
+
+		#[cfg(not(feature = "memory-stats"))]
 		Message { msgp, header: Header { msgp } }
 	}
 
 	/// Consumes the message and returns the `nng_msg` pointer.
 	pub(crate) fn into_ptr(self) -> NonNull<nng_sys::nng_msg>
 	{
+		#[cfg(any(test, feature = "testing"))]
+		ownership::release(self.msgp);
+		#[cfg(feature = "memory-stats")]
+		memory::released(self.counted);
+
 		let ptr = self.msgp;
 		std::mem::forget(self);
 
 		ptr
 	}
+
+	/// Appends the data for `Extend`, with `spare` the room known to be
+	/// reserved at the back of the body.
+	fn push_reserved(&mut self, data: &[u8], spare: &mut usize)
+	{
+		if data.len() > *spare {
+			*spare = data.len().max(self.len());
+			self.reserve(*spare).expect("Failed to allocate memory");
+		}
+
+		self.push_back(data).expect("Failed to push to Message");
+		*spare -= data.len();
+	}
+
+	/// Updates `memory_stats` after the size of the body may have changed.
+	#[cfg(feature = "memory-stats")]
+	fn recount(&mut self)
+	{
+		let len = self.len();
+		if len != self.counted {
+			memory::resized(self.counted, len);
+			self.counted = len;
+		}
+	}
+
+	/// Does nothing, as messages are not counted without `memory-stats`.
+	// This has the same signature as the counting version, so that the callers
+	// don't need to care about the feature.
+	#[cfg(not(feature = "memory-stats"))]
+	#[inline(always)]
+	#[allow(clippy::unused_self, clippy::needless_pass_by_ref_mut)]
+	fn recount(&mut self) {}
 }
 impl Drop for Message
 {
 	fn drop(&mut self)
 	{
+		#[cfg(any(test, feature = "testing"))]
+		ownership::release(self.msgp);
+		#[cfg(feature = "memory-stats")]
+		memory::released(self.counted);
+
 		unsafe {
 			nng_sys::nng_msg_free(self.msgp.as_ptr());
 		}
@@ -279,6 +852,41 @@ impl Default for Message
 	fn default() -> Message { Message::new().unwrap() }
 }
 
+/// Shows the lengths and a preview of the contents of the message.
+///
+/// The preview is laid out like `hexdump -C` and is limited to the first 64
+/// bytes of the body and of the header, so even huge messages produce short
+/// output.
+///
+/// ## Example
+///
+/// ```
+/// use nng::Message;
+///
+/// let mut msg = Message::from(&b"hello"[..]);
+/// msg.as_mut_header().push_back(&[0x80, 0, 0, 1])?;
+/// assert_eq!(
+///     format!("{:?}", msg),
+///     "Message { len: 5, body: 68 65 6c 6c 6f |hello|, \
+///      header: Header { len: 4, data: 80 00 00 01 |....| } }",
+/// );
+///
+/// let big = Message::from(&[b'x'; 1000][..]);
+/// assert!(format!("{:?}", big).contains("xxx...(936 more bytes)|"));
+/// # Ok::<(), nng::Error>(())
+/// ```
+impl fmt::Debug for Message
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("Message")
+			.field("len", &self.len())
+			.field("body", &Preview(self.as_slice()))
+			.field("header", &self.header)
+			.finish()
+	}
+}
+
 impl<'a> From<&'a [u8]> for Message
 {
 	fn from(s: &[u8]) -> Message
@@ -296,6 +904,16 @@ impl<'a> From<&'a Vec<u8>> for Message
 	fn from(s: &Vec<u8>) -> Message { s.as_slice().into() }
 }
 
+#[cfg(feature = "bytes")]
+impl From<bytes::Bytes> for Message
+{
+	/// Copies the buffer into a new message.
+	///
+	/// NNG can't take ownership of memory it did not allocate, so this can't
+	/// reuse the buffer, even if it was created by `Message::into_bytes`.
+	fn from(b: bytes::Bytes) -> Message { b.as_ref().into() }
+}
+
 impl FromIterator<u8> for Message
 {
 	fn from_iter<T>(iter: T) -> Message
@@ -335,6 +953,19 @@ impl DerefMut for Message
 	fn deref_mut(&mut self) -> &mut [u8] { self.as_mut_slice() }
 }
 
+impl AsRef<[u8]> for Message
+{
+	fn as_ref(&self) -> &[u8] { self.as_slice() }
+}
+impl AsMut<[u8]> for Message
+{
+	fn as_mut(&mut self) -> &mut [u8] { self.as_mut_slice() }
+}
+
+/// Appends to the body.
+///
+/// Every write is a single copy into the body, which is only reallocated if
+/// the data doesn't fit. See `Message::reserve` for making sure it does.
 impl Write for Message
 {
 	#[inline]
@@ -359,9 +990,24 @@ impl Extend<u8> for Message
 {
 	fn extend<I: IntoIterator<Item = u8>>(&mut self, iter: I)
 	{
+		let iter = iter.into_iter();
+		let mut spare = iter.size_hint().0;
+		self.reserve(spare).expect("Failed to allocate memory");
+
+		// Bytes are pushed in chunks, and the body at least doubles whenever
+		// the reserved room runs out, so iterators that don't know their
+		// length don't reallocate for every byte.
+		let mut chunk = [0; 64];
+		let mut filled = 0;
 		for byte in iter {
-			self.push_back(slice::from_ref(&byte)).expect("Failed to push to Message");
+			chunk[filled] = byte;
+			filled += 1;
+			if filled == chunk.len() {
+				self.push_reserved(&chunk, &mut spare);
+				filled = 0;
+			}
 		}
+		self.push_reserved(&chunk[..filled], &mut spare);
 	}
 }
 
@@ -369,9 +1015,7 @@ impl<'a> Extend<&'a u8> for Message
 {
 	fn extend<I: IntoIterator<Item = &'a u8>>(&mut self, iter: I)
 	{
-		for byte in iter {
-			self.push_back(slice::from_ref(byte)).expect("Failed to push to Message");
-		}
+		self.extend(iter.into_iter().copied())
 	}
 }
 
@@ -389,11 +1033,81 @@ impl<I: SliceIndex<[u8]>> IndexMut<I> for Message
 	fn index_mut(&mut self, index: I) -> &mut Self::Output { self.as_mut_slice().index_mut(index) }
 }
 
+/// The number of bytes shown by the `Debug` implementations.
+const PREVIEW_LEN: usize = 64;
+
+/// Formats the start of a byte slice as hex and ASCII, like `hexdump -C`.
+struct Preview<'a>(&'a [u8]);
+impl fmt::Debug for Preview<'_>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		if self.0.is_empty() {
+			return write!(f, "||");
+		}
+
+		let shown = &self.0[..self.0.len().min(PREVIEW_LEN)];
+		for b in shown {
+			write!(f, "{:02x} ", b)?;
+		}
+
+		write!(f, "|")?;
+		for &b in shown {
+			let c = if b.is_ascii_graphic() || b == b' ' { char::from(b) } else { '.' };
+			write!(f, "{}", c)?;
+		}
+
+		let hidden = self.0.len() - shown.len();
+		if hidden > 0 {
+			write!(f, "...({} more bytes)", hidden)?;
+		}
+
+		write!(f, "|")
+	}
+}
+
+/// The magic value at the start of the integrity trailer.
+const SEAL_MAGIC: &[u8; 4] = b"NNGS";
+
+/// The length of the integrity trailer.
+const SEAL_LEN: usize = 8;
+
+/// Returns whether the data ends with a matching integrity trailer.
+fn seal_is_valid(data: &[u8]) -> bool
+{
+	if data.len() < SEAL_LEN {
+		return false;
+	}
+
+	let (body, trailer) = data.split_at(data.len() - SEAL_LEN);
+	let mut crc = [0; 4];
+	crc.copy_from_slice(&trailer[4..]);
+
+	&trailer[..4] == SEAL_MAGIC && u32::from_be_bytes(crc) == crc32(body)
+}
+
+/// Computes the standard (zlib) CRC-32 of the data.
+///
+/// Sealing is meant for the occasional flaky link rather than the hot path,
+/// so the bitwise version is used instead of carrying a lookup table.
+fn crc32(data: &[u8]) -> u32
+{
+	let mut crc = !0u32;
+	for &byte in data {
+		crc ^= u32::from(byte);
+		for _ in 0..8 {
+			let mask = (crc & 1).wrapping_neg();
+			crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+		}
+	}
+
+	!crc
+}
+
 /// The header of a `Message`.
 ///
 /// Most normal applications will never have to touch the message header. The
 /// only time it will be necessary is if the socket is in "raw" mode.
-#[derive(Debug)]
 pub struct Header
 {
 	msgp: NonNull<nng_sys::nng_msg>,
@@ -489,6 +1203,20 @@ impl Header
 unsafe impl Send for Header {}
 unsafe impl Sync for Header {}
 
+/// Shows the length and a preview of the contents of the header.
+///
+/// See the `Debug` implementation of `Message` for details.
+impl fmt::Debug for Header
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("Header")
+			.field("len", &self.len())
+			.field("data", &Preview(self.as_slice()))
+			.finish()
+	}
+}
+
 impl Deref for Header
 {
 	type Target = [u8];
@@ -500,6 +1228,15 @@ impl DerefMut for Header
 	fn deref_mut(&mut self) -> &mut [u8] { self.as_mut_slice() }
 }
 
+impl AsRef<[u8]> for Header
+{
+	fn as_ref(&self) -> &[u8] { self.as_slice() }
+}
+impl AsMut<[u8]> for Header
+{
+	fn as_mut(&mut self) -> &mut [u8] { self.as_mut_slice() }
+}
+
 impl Write for Header
 {
 	#[inline]
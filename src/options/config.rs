@@ -0,0 +1,291 @@
+//! Socket options gathered in a single configuration value.
+use std::time::Duration;
+
+use super::registry::{self, OptionDescriptor, OptionOp, OptionValue};
+use crate::{
+	error::{Error, Result},
+	protocol::ProtocolKind,
+};
+
+/// The largest buffer size NNG accepts for `RecvBufferSize` and
+/// `SendBufferSize`.
+const MAX_BUFFER_SIZE: i32 = 8192;
+
+/// The longest socket name NNG accepts, in bytes.
+const MAX_SOCKET_NAME: usize = 63;
+
+/// The tuning of a socket, as it might be read from a configuration file.
+///
+/// Every field is optional and only the fields that are set are applied with
+/// `Socket::apply_config`, so that a configuration only has to mention the
+/// options it changes. The fields map to the following options:
+///
+/// | Field                | Option               | Protocols            |
+/// |----------------------|----------------------|----------------------|
+/// | `recv_timeout`       | `RecvTimeout`        | All                  |
+/// | `send_timeout`       | `SendTimeout`        | All                  |
+/// | `recv_buffer_size`   | `RecvBufferSize`     | All                  |
+/// | `send_buffer_size`   | `SendBufferSize`     | All                  |
+/// | `recv_max_size`      | `RecvMaxSize`        | All                  |
+/// | `reconnect_min_time` | `ReconnectMinTime`   | All                  |
+/// | `reconnect_max_time` | `ReconnectMaxTime`   | All                  |
+/// | `socket_name`        | `SocketName`         | All                  |
+/// | `max_ttl`            | `MaxTtl`             | See `MaxTtl`         |
+/// | `resend_time`        | `reqrep::ResendTime` | _req_ version 0      |
+/// | `survey_time`        | `survey::SurveyTime` | _surveyor_ version 0 |
+/// | `tcp_no_delay`       | `tcp::NoDelay`       | All                  |
+/// | `tcp_keep_alive`     | `tcp::KeepAlive`     | All                  |
+///
+/// The durations are doubly optional: the outer `Option` says whether the
+/// field is set, and the inner one is the value of the option, where `None`
+/// means infinite as for the options themselves.
+///
+/// ## Serialization
+///
+/// With the `config-serde` feature, this implements `serde::Deserialize` and
+/// `serde::Serialize`. Fields that are missing are left unset and unknown
+/// fields are rejected, so that a misspelled option is an error rather than
+/// silently ignored. Durations are given in milliseconds, with `-1` meaning
+/// infinite, which is how NNG represents them. In TOML, a configuration could
+/// look like this:
+///
+/// ```toml
+/// recv_timeout = 250
+/// send_timeout = -1
+/// recv_buffer_size = 64
+/// reconnect_min_time = 100
+/// reconnect_max_time = 5000
+/// ```
+///
+/// Which reads the same in JSON:
+///
+/// ```
+/// # #[cfg(feature = "config-serde")] {
+/// use std::time::Duration;
+/// use nng::options::SocketConfig;
+///
+/// let json = r#"{ "recv_timeout": 250, "send_timeout": -1, "recv_buffer_size": 64 }"#;
+/// let config: SocketConfig = serde_json::from_str(json).unwrap();
+/// assert_eq!(config.recv_timeout, Some(Some(Duration::from_millis(250))));
+/// assert_eq!(config.send_timeout, Some(None));
+/// assert_eq!(config.recv_buffer_size, Some(64));
+/// assert_eq!(config.send_buffer_size, None);
+/// assert_eq!(serde_json::to_string(&config).unwrap(), json.replace(' ', ""));
+///
+/// // Misspelled options and negative durations are errors.
+/// assert!(serde_json::from_str::<SocketConfig>(r#"{ "recv_timout": 250 }"#).is_err());
+/// assert!(serde_json::from_str::<SocketConfig>(r#"{ "recv_timeout": -2 }"#).is_err());
+/// # }
+/// ```
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nng::{options::{Options, RecvBufferSize, RecvTimeout, SendTimeout, SocketConfig}, *};
+///
+/// let config = SocketConfig {
+///     recv_timeout: Some(Some(Duration::from_millis(250))),
+///     send_timeout: Some(None),
+///     recv_buffer_size: Some(64),
+///     ..SocketConfig::default()
+/// };
+///
+/// let socket = Socket::new(Protocol::Rep0)?;
+/// socket.apply_config(&config)?;
+/// assert_eq!(socket.get_opt::<RecvTimeout>()?, Some(Duration::from_millis(250)));
+/// assert_eq!(socket.get_opt::<SendTimeout>()?, None);
+/// assert_eq!(socket.get_opt::<RecvBufferSize>()?, 64);
+///
+/// // Invalid configurations are rejected before anything is set.
+/// let invalid = SocketConfig {
+///     recv_timeout: Some(Some(Duration::from_secs(1))),
+///     resend_time: Some(Some(Duration::from_secs(5))),
+///     ..SocketConfig::default()
+/// };
+/// assert_eq!(socket.apply_config(&invalid), Err(Error::NotSupported));
+/// assert_eq!(socket.get_opt::<RecvTimeout>()?, Some(Duration::from_millis(250)));
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(
+	feature = "config-serde",
+	derive(serde::Deserialize, serde::Serialize),
+	serde(default, deny_unknown_fields)
+)]
+pub struct SocketConfig
+{
+	/// The value of `RecvTimeout`.
+	#[cfg_attr(
+		feature = "config-serde",
+		serde(with = "millis", skip_serializing_if = "Option::is_none")
+	)]
+	pub recv_timeout: Option<Option<Duration>>,
+
+	/// The value of `SendTimeout`.
+	#[cfg_attr(
+		feature = "config-serde",
+		serde(with = "millis", skip_serializing_if = "Option::is_none")
+	)]
+	pub send_timeout: Option<Option<Duration>>,
+
+	/// The value of `RecvBufferSize`, between 0 and 8192.
+	#[cfg_attr(feature = "config-serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub recv_buffer_size: Option<i32>,
+
+	/// The value of `SendBufferSize`, between 0 and 8192.
+	#[cfg_attr(feature = "config-serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub send_buffer_size: Option<i32>,
+
+	/// The value of `RecvMaxSize`, in bytes.
+	#[cfg_attr(feature = "config-serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub recv_max_size: Option<usize>,
+
+	/// The value of `ReconnectMinTime`.
+	#[cfg_attr(
+		feature = "config-serde",
+		serde(with = "millis", skip_serializing_if = "Option::is_none")
+	)]
+	pub reconnect_min_time: Option<Option<Duration>>,
+
+	/// The value of `ReconnectMaxTime`.
+	#[cfg_attr(
+		feature = "config-serde",
+		serde(with = "millis", skip_serializing_if = "Option::is_none")
+	)]
+	pub reconnect_max_time: Option<Option<Duration>>,
+
+	/// The value of `SocketName`, of at most 63 bytes.
+	#[cfg_attr(feature = "config-serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub socket_name: Option<String>,
+
+	/// The value of `MaxTtl`, between 1 and 255.
+	#[cfg_attr(feature = "config-serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub max_ttl: Option<u8>,
+
+	/// The value of `reqrep::ResendTime`.
+	#[cfg_attr(
+		feature = "config-serde",
+		serde(with = "millis", skip_serializing_if = "Option::is_none")
+	)]
+	pub resend_time: Option<Option<Duration>>,
+
+	/// The value of `survey::SurveyTime`.
+	#[cfg_attr(
+		feature = "config-serde",
+		serde(with = "millis", skip_serializing_if = "Option::is_none")
+	)]
+	pub survey_time: Option<Option<Duration>>,
+
+	/// The value of `tcp::NoDelay`, the default for new dialers and
+	/// listeners.
+	#[cfg_attr(feature = "config-serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub tcp_no_delay: Option<bool>,
+
+	/// The value of `tcp::KeepAlive`, the default for new dialers and
+	/// listeners.
+	#[cfg_attr(feature = "config-serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub tcp_keep_alive: Option<bool>,
+}
+
+impl SocketConfig
+{
+	/// Returns the options that are set, by name, in the order they are
+	/// applied.
+	///
+	/// These can be passed to `Socket::apply_options`, which reports the
+	/// result of every option instead of the first failure.
+	pub fn options(&self) -> Vec<OptionOp<'static>>
+	{
+		let fields: [(&'static str, Option<OptionValue>); 13] = [
+			("RecvTimeout", self.recv_timeout.map(OptionValue::from)),
+			("SendTimeout", self.send_timeout.map(OptionValue::from)),
+			("RecvBufferSize", self.recv_buffer_size.map(OptionValue::from)),
+			("SendBufferSize", self.send_buffer_size.map(OptionValue::from)),
+			("RecvMaxSize", self.recv_max_size.map(OptionValue::from)),
+			("ReconnectMinTime", self.reconnect_min_time.map(OptionValue::from)),
+			("ReconnectMaxTime", self.reconnect_max_time.map(OptionValue::from)),
+			("SocketName", self.socket_name.clone().map(OptionValue::from)),
+			("MaxTtl", self.max_ttl.map(|v| OptionValue::Int(v.into()))),
+			("ResendTime", self.resend_time.map(OptionValue::from)),
+			("SurveyTime", self.survey_time.map(OptionValue::from)),
+			("NoDelay", self.tcp_no_delay.map(OptionValue::from)),
+			("KeepAlive", self.tcp_keep_alive.map(OptionValue::from)),
+		];
+
+		fields.iter().filter_map(|(name, value)| value.clone().map(|v| (*name, v))).collect()
+	}
+
+	/// Checks that the configuration can be applied to a socket of the
+	/// protocol.
+	///
+	/// This returns `Error::NotSupported` if a protocol option, such as
+	/// `resend_time`, is set for a protocol that doesn't have it, and
+	/// `Error::InvalidInput` if a value is outside of the range NNG accepts.
+	/// `Socket::apply_config` runs these checks before setting anything.
+	pub fn validate<P: Into<ProtocolKind>>(&self, protocol: P) -> Result<()>
+	{
+		let protocol = protocol.into();
+		for (name, _) in self.options() {
+			let descriptor = registry::all().iter().find(|o| o.name() == name);
+			let supported = match (descriptor.and_then(OptionDescriptor::protocols), protocol) {
+				(None, _) => true,
+				(Some(protocols), ProtocolKind::Known(p)) => protocols.contains(&p),
+				(Some(_), ProtocolKind::Custom(_)) => false,
+			};
+
+			if !supported {
+				return Err(Error::NotSupported);
+			}
+		}
+
+		let buffer = |size: Option<i32>| match size {
+			Some(s) => (0..=MAX_BUFFER_SIZE).contains(&s),
+			None => true,
+		};
+		let name = |name: &Option<String>| match name {
+			Some(n) => n.len() <= MAX_SOCKET_NAME && !n.contains('\0'),
+			None => true,
+		};
+		let valid = buffer(self.recv_buffer_size)
+			&& buffer(self.send_buffer_size)
+			&& name(&self.socket_name)
+			&& self.max_ttl != Some(0);
+
+		if valid { Ok(()) } else { Err(Error::InvalidInput) }
+	}
+}
+
+/// Serializes the durations of a `SocketConfig` as milliseconds.
+#[cfg(feature = "config-serde")]
+mod millis
+{
+	use std::time::Duration;
+
+	use serde::{Deserialize, Deserializer, Serializer, de::Error as _};
+
+	use crate::time::{from_nng_ms, to_nng_ms};
+
+	/// Writes the duration in milliseconds, or `-1` for infinite.
+	///
+	/// Unset durations are skipped before they get here.
+	#[allow(clippy::ref_option)]
+	pub(super) fn serialize<S: Serializer>(
+		dur: &Option<Option<Duration>>,
+		serializer: S,
+	) -> Result<S::Ok, S::Error>
+	{
+		serializer.serialize_i32(to_nng_ms(dur.flatten()))
+	}
+
+	/// Reads a duration in milliseconds, where `-1` is infinite.
+	pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<Option<Option<Duration>>, D::Error>
+	{
+		match i32::deserialize(deserializer)? {
+			ms if ms >= -1 => Ok(from_nng_ms(ms)),
+			ms => Err(D::Error::custom(format!("invalid duration of {} ms", ms))),
+		}
+	}
+}
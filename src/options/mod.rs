@@ -7,12 +7,26 @@
 //! Additionally, a Dialer or Listener is able to read options from the
 //! underlying Socket but they are unable to write options unless they are
 //! directly supported.
+//!
+//! For options that are only known at runtime, such as those read from a
+//! configuration file, `all` and `applicable_to` describe the options by name
+//! and `Socket::apply_options` sets them. `SocketConfig` gathers the common
+//! socket options in a struct, which `Socket::apply_config` sets in one call.
 use crate::error::Result;
 
+mod config;
+mod registry;
 mod types;
-pub use self::types::*;
+pub use self::{
+	config::SocketConfig,
+	registry::{
+		all, applicable_to, Applicability, OptionDescriptor, OptionOp, OptionValue, ValueType,
+	},
+	types::*,
+};
 
 pub(crate) mod private;
+pub(crate) use self::registry::{apply, apply_atomic};
 
 /// Trait for getting and setting options.
 ///
@@ -39,6 +53,52 @@ pub(crate) mod private;
 /// let socket = Socket::new(Protocol::Pub0).unwrap();
 /// socket.set_opt::<Raw>(true).unwrap(); // Won't compile
 /// ```
+///
+/// ## Thread Safety
+///
+/// All option operations take `&self` because NNG synchronizes option access
+/// internally. Options can therefore be read and written from any thread while
+/// other threads are using the same socket, dialer, listener, context, or
+/// pipe. This includes `Subscribe` and `Unsubscribe`, which only affect
+/// messages that arrive after the change is made.
+///
+/// For example, a monitoring thread can read the buffer sizes while the
+/// socket is in use:
+///
+/// ```
+/// use std::thread;
+/// use nng::options::{Options, RecvBufferSize};
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/options/thread_safety";
+///
+/// let pull = Socket::new(Protocol::Pull0)?;
+/// pull.listen(ADDRESS)?;
+/// let push = Socket::new(Protocol::Push0)?;
+/// push.dial(ADDRESS)?;
+///
+/// let monitors: Vec<_> = (0..4)
+///     .map(|_| {
+///         let pull = pull.clone();
+///         thread::spawn(move || {
+///             for _ in 0..1000 {
+///                 let size = pull.get_opt::<RecvBufferSize>().unwrap();
+///                 assert!(size >= 0);
+///             }
+///         })
+///     })
+///     .collect();
+///
+/// for i in 0..1000u32 {
+///     push.send(&i.to_le_bytes()[..])?;
+///     assert_eq!(&pull.recv()?[..], &i.to_le_bytes()[..]);
+/// }
+///
+/// for m in monitors {
+///     m.join().unwrap();
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
 pub trait Options: private::HasOpts
 {
 	/// Reads the specified option from the object.
@@ -46,7 +106,7 @@ pub trait Options: private::HasOpts
 	where
 		Self: GetOpt<T>,
 	{
-		T::get(self)
+		T::get(self).map_err(Self::opt_error)
 	}
 
 	/// Writes the specified option to the object.
@@ -54,7 +114,7 @@ pub trait Options: private::HasOpts
 	where
 		Self: SetOpt<T>,
 	{
-		T::set(self, val)
+		T::set(self, val).map_err(Self::opt_error)
 	}
 
 	/// Reads the specified option from the object.
@@ -69,7 +129,7 @@ pub trait Options: private::HasOpts
 	where
 		Self: UnsafeGetOpt<T>,
 	{
-		T::get(self)
+		T::get(self).map_err(Self::opt_error)
 	}
 
 	/// Writes the specified option to the object.
@@ -86,7 +146,7 @@ pub trait Options: private::HasOpts
 	where
 		Self: UnsafeGetOpt<T>,
 	{
-		T::set(self, val)
+		T::set(self, val).map_err(Self::opt_error)
 	}
 }
 impl<T: private::HasOpts> Options for T {}
@@ -9,6 +9,7 @@ use std::{
 use crate::{
 	addr::SocketAddr,
 	error::{Error, Result},
+	options::{OptionValue, ValueType},
 	util::validate_ptr,
 };
 
@@ -19,6 +20,18 @@ use crate::{
 /// returning an error code.
 pub trait OptOps: super::Opt
 {
+	/// The name of the option, which is the name of its type.
+	const NAME: &'static str;
+
+	/// The kind of value the option takes.
+	const VALUE_TYPE: ValueType;
+
+	/// Whether the option can be read.
+	const READABLE: bool;
+
+	/// Whether the option can be written.
+	const WRITABLE: bool;
+
 	/// Get the value of the option using the specified type.
 	fn get<T: HasOpts>(s: &T) -> Result<Self::OptType>;
 
@@ -78,9 +91,26 @@ pub trait HasOpts: Sized
 	/// Raw `nng` function to set a string value.
 	const SETOPT_STRING: unsafe extern "C" fn(Self::Handle, *const c_char, *const c_char) -> c_int;
 
+	/// Whether NNG reports the object as missing, rather than closed, once it
+	/// has been closed.
+	const MISSING_IS_CLOSED: bool = false;
+
 	/// Returns the underlying `nng` type.
 	fn handle(&self) -> Self::Handle;
 
+	/// Sets the option with the given name, if this type can set it at all.
+	fn set_named(&self, name: &str, value: OptionValue) -> Option<Result<()>>;
+
+	/// Reads the option with the given name, if this type can read it at all.
+	fn get_named(&self, name: &str) -> Option<Result<OptionValue>>;
+
+	/// Converts an error from an option operation into the one given to the
+	/// user.
+	fn opt_error(e: Error) -> Error
+	{
+		if Self::MISSING_IS_CLOSED { crate::util::missing_is_closed(e) } else { e }
+	}
+
 	/// Get the boolean option.
 	fn getopt_bool(&self, opt: *const c_char) -> Result<bool>
 	{
@@ -105,7 +135,9 @@ pub trait HasOpts: Sized
 		let mut dur: nng_sys::nng_duration = 0;
 		let rv = unsafe { (Self::GETOPT_MS)(self.handle(), opt, &mut dur as _) };
 
-		rv2res!(rv, crate::util::nng_to_duration(dur))
+		// Options hold the default that NNG applied, so they are never reported as
+		// `NNG_DURATION_DEFAULT`.
+		rv2res!(rv, crate::time::from_nng_ms(dur).flatten())
 	}
 
 	/// Get the `size_t` option.
@@ -179,7 +211,7 @@ pub trait HasOpts: Sized
 	/// Set the duration to the option.
 	fn setopt_ms(&self, opt: *const c_char, dur: Option<Duration>) -> Result<()>
 	{
-		let ms = crate::util::duration_to_nng(dur);
+		let ms = crate::time::to_nng_ms(dur);
 
 		let rv = unsafe { (Self::SETOPT_MS)(self.handle(), opt, ms) };
 		rv2res!(rv)
@@ -209,3 +241,129 @@ pub trait HasOpts: Sized
 		rv2res!(rv)
 	}
 }
+
+/// A type that options can take as their value.
+pub(crate) trait OptValue: Sized
+{
+	/// The kind of value this is.
+	const VALUE_TYPE: ValueType;
+
+	/// Converts the dynamically typed value, if it is of the right kind.
+	fn from_value(_: OptionValue) -> Result<Self> { Err(Error::BadType) }
+
+	/// Converts the value into a dynamically typed one, if there is a kind
+	/// for it.
+	fn into_value(self) -> Result<OptionValue> { Err(Error::BadType) }
+}
+
+impl OptValue for bool
+{
+	const VALUE_TYPE: ValueType = ValueType::Bool;
+
+	fn from_value(val: OptionValue) -> Result<Self>
+	{
+		if let OptionValue::Bool(v) = val { Ok(v) } else { Err(Error::BadType) }
+	}
+
+	fn into_value(self) -> Result<OptionValue> { Ok(OptionValue::Bool(self)) }
+}
+
+impl OptValue for i32
+{
+	const VALUE_TYPE: ValueType = ValueType::Int;
+
+	fn from_value(val: OptionValue) -> Result<Self>
+	{
+		if let OptionValue::Int(v) = val { Ok(v) } else { Err(Error::BadType) }
+	}
+
+	fn into_value(self) -> Result<OptionValue> { Ok(OptionValue::Int(self)) }
+}
+
+impl OptValue for u8
+{
+	const VALUE_TYPE: ValueType = ValueType::Int;
+
+	fn from_value(val: OptionValue) -> Result<Self>
+	{
+		use std::convert::TryFrom;
+		u8::try_from(i32::from_value(val)?).map_err(|_| Error::InvalidInput)
+	}
+
+	fn into_value(self) -> Result<OptionValue> { Ok(OptionValue::Int(self.into())) }
+}
+
+impl OptValue for u32
+{
+	const VALUE_TYPE: ValueType = ValueType::Int;
+
+	fn from_value(val: OptionValue) -> Result<Self>
+	{
+		use std::convert::TryFrom;
+		u32::try_from(i32::from_value(val)?).map_err(|_| Error::InvalidInput)
+	}
+}
+
+impl OptValue for u64
+{
+	const VALUE_TYPE: ValueType = ValueType::Uint64;
+}
+
+impl OptValue for usize
+{
+	const VALUE_TYPE: ValueType = ValueType::Size;
+
+	fn from_value(val: OptionValue) -> Result<Self>
+	{
+		if let OptionValue::Size(v) = val { Ok(v) } else { Err(Error::BadType) }
+	}
+
+	fn into_value(self) -> Result<OptionValue> { Ok(OptionValue::Size(self)) }
+}
+
+impl OptValue for Option<Duration>
+{
+	const VALUE_TYPE: ValueType = ValueType::Duration;
+
+	fn from_value(val: OptionValue) -> Result<Self>
+	{
+		if let OptionValue::Duration(v) = val { Ok(v) } else { Err(Error::BadType) }
+	}
+
+	fn into_value(self) -> Result<OptionValue> { Ok(OptionValue::Duration(self)) }
+}
+
+impl OptValue for String
+{
+	const VALUE_TYPE: ValueType = ValueType::String;
+
+	fn from_value(val: OptionValue) -> Result<Self>
+	{
+		if let OptionValue::String(v) = val { Ok(v) } else { Err(Error::BadType) }
+	}
+
+	fn into_value(self) -> Result<OptionValue> { Ok(OptionValue::String(self)) }
+}
+
+impl OptValue for Vec<u8>
+{
+	const VALUE_TYPE: ValueType = ValueType::Bytes;
+
+	fn from_value(val: OptionValue) -> Result<Self>
+	{
+		if let OptionValue::Bytes(v) = val { Ok(v) } else { Err(Error::BadType) }
+	}
+
+	fn into_value(self) -> Result<OptionValue> { Ok(OptionValue::Bytes(self)) }
+}
+
+impl OptValue for SocketAddr
+{
+	const VALUE_TYPE: ValueType = ValueType::SocketAddr;
+}
+
+#[cfg(windows)]
+impl OptValue for winapi::um::winnt::PSECURITY_DESCRIPTOR
+{
+	const VALUE_TYPE: ValueType = ValueType::Pointer;
+}
@@ -0,0 +1,376 @@
+//! Descriptions of the options, for looking them up by name at runtime.
+use std::time::Duration;
+
+#[cfg(windows)]
+use super::transport::ipc::SecurityDescriptor;
+#[cfg(unix)]
+use super::{
+	transport::ipc::{PeerGid, PeerUid, Permissions},
+	RecvFd, SendFd,
+};
+use super::{
+	private::{HasOpts, OptOps},
+	protocol::{
+		pair::Polyamorous,
+		pubsub::{Subscribe, Unsubscribe},
+		reqrep::ResendTime,
+		survey::SurveyTime,
+	},
+	transport::{
+		ipc::PeerPid,
+		tcp::{KeepAlive, NoDelay},
+		tls::{CaFile, CertKeyFile, Verified},
+		websocket::{RequestHeaders, ResponseHeaders},
+	},
+	LocalAddr, MaxTtl, Raw, ReconnectMaxTime, ReconnectMinTime, RecvBufferSize, RecvMaxSize,
+	RecvTimeout, RemAddr, SendBufferSize, SendTimeout, SocketName, Url,
+};
+use crate::{
+	error::{Error, OptionApplyError, Result},
+	protocol::Protocol,
+};
+
+/// The protocols supporting `MaxTtl`.
+const MAX_TTL_PROTOCOLS: &[Protocol] =
+	&[Protocol::Pair1, Protocol::Rep0, Protocol::Req0, Protocol::Surveyor0, Protocol::Respondent0];
+
+/// Every option of this crate, along with the protocols it is restricted to.
+static OPTIONS: &[OptionDescriptor] = &[
+	OptionDescriptor::of::<LocalAddr>(None),
+	OptionDescriptor::of::<RemAddr>(None),
+	OptionDescriptor::of::<Raw>(None),
+	OptionDescriptor::of::<ReconnectMinTime>(None),
+	OptionDescriptor::of::<ReconnectMaxTime>(None),
+	OptionDescriptor::of::<RecvBufferSize>(None),
+	#[cfg(unix)]
+	OptionDescriptor::of::<RecvFd>(None),
+	OptionDescriptor::of::<RecvMaxSize>(None),
+	OptionDescriptor::of::<RecvTimeout>(None),
+	OptionDescriptor::of::<SendBufferSize>(None),
+	#[cfg(unix)]
+	OptionDescriptor::of::<SendFd>(None),
+	OptionDescriptor::of::<SendTimeout>(None),
+	OptionDescriptor::of::<SocketName>(None),
+	OptionDescriptor::of::<MaxTtl>(Some(MAX_TTL_PROTOCOLS)),
+	OptionDescriptor::of::<Url>(None),
+	OptionDescriptor::of::<Polyamorous>(Some(&[Protocol::Pair1])),
+	OptionDescriptor::of::<Subscribe>(Some(&[Protocol::Sub0])),
+	OptionDescriptor::of::<Unsubscribe>(Some(&[Protocol::Sub0])),
+	OptionDescriptor::of::<ResendTime>(Some(&[Protocol::Req0])),
+	OptionDescriptor::of::<SurveyTime>(Some(&[Protocol::Surveyor0])),
+	#[cfg(unix)]
+	OptionDescriptor::of::<Permissions>(None),
+	#[cfg(windows)]
+	OptionDescriptor::of::<SecurityDescriptor>(None),
+	#[cfg(unix)]
+	OptionDescriptor::of::<PeerUid>(None),
+	#[cfg(unix)]
+	OptionDescriptor::of::<PeerGid>(None),
+	OptionDescriptor::of::<PeerPid>(None),
+	OptionDescriptor::of::<NoDelay>(None),
+	OptionDescriptor::of::<KeepAlive>(None),
+	OptionDescriptor::of::<CaFile>(None),
+	OptionDescriptor::of::<CertKeyFile>(None),
+	OptionDescriptor::of::<Verified>(None),
+	OptionDescriptor::of::<RequestHeaders>(None),
+	OptionDescriptor::of::<ResponseHeaders>(None),
+];
+
+/// Returns the descriptions of all options known to this crate.
+///
+/// This allows applications to validate options given by name, such as from a
+/// configuration file, before applying them with `Socket::apply_options`.
+///
+/// ## Example
+///
+/// ```
+/// use nng::options::{self, ValueType};
+///
+/// let timeout = options::all().iter().find(|o| o.name() == "RecvTimeout").unwrap();
+/// assert_eq!(timeout.value_type(), ValueType::Duration);
+/// assert!(timeout.is_readable() && timeout.is_writable());
+///
+/// let raw = options::all().iter().find(|o| o.name() == "Raw").unwrap();
+/// assert_eq!(raw.value_type(), ValueType::Bool);
+/// assert!(!raw.is_writable());
+/// ```
+pub fn all() -> &'static [OptionDescriptor] { OPTIONS }
+
+/// Returns whether the named option applies to sockets of the protocol.
+///
+/// Most options apply to every protocol, but protocol options such as
+/// `Subscribe` are only understood by the protocols they belong to. This does
+/// not say whether the option can be used on a socket as opposed to, say, a
+/// pipe, or with a certain transport.
+///
+/// ## Example
+///
+/// ```
+/// use nng::{options::{self, Applicability}, Protocol};
+///
+/// assert_eq!(options::applicable_to("Subscribe", Protocol::Sub0), Applicability::Yes);
+/// assert_eq!(options::applicable_to("Subscribe", Protocol::Req0), Applicability::No);
+/// assert_eq!(options::applicable_to("RecvTimeout", Protocol::Req0), Applicability::Yes);
+/// assert_eq!(options::applicable_to("Bogus", Protocol::Req0), Applicability::Unknown);
+/// ```
+pub fn applicable_to(option_name: &str, protocol: Protocol) -> Applicability
+{
+	match find(option_name) {
+		Some(o) if o.applies_to(protocol) => Applicability::Yes,
+		Some(_) => Applicability::No,
+		None => Applicability::Unknown,
+	}
+}
+
+/// Sets each of the named options, returning the result of each.
+pub(crate) fn apply<T: HasOpts>(target: &T, options: &[OptionOp]) -> Vec<Result<()>>
+{
+	options.iter().map(|(name, value)| set(target, name, value.clone())).collect()
+}
+
+/// Sets all of the named options or, if one of them fails, restores the ones
+/// that were already set.
+pub(crate) fn apply_atomic<T: HasOpts>(
+	target: &T,
+	options: &[OptionOp],
+) -> std::result::Result<(), OptionApplyError>
+{
+	let fail = |index: usize, error| OptionApplyError {
+		index,
+		option: options[index].0.to_string(),
+		error,
+		rollback_errors: Vec::new(),
+	};
+
+	// Every option is read before anything is set, so that a batch with an
+	// option that can't be restored fails without changing anything.
+	let mut saved: Vec<(usize, &str, OptionValue)> = Vec::new();
+	for (i, &(name, _)) in options.iter().enumerate() {
+		if saved.iter().any(|&(_, n, _)| n == name) {
+			continue;
+		}
+
+		match target.get_named(name) {
+			Some(Ok(value)) => saved.push((i, name, value)),
+			Some(Err(e)) => return Err(fail(i, e)),
+			None if find(name).is_some() => return Err(fail(i, Error::NotSupported)),
+			None => return Err(fail(i, Error::EntryNotFound)),
+		}
+	}
+
+	for (i, (name, value)) in options.iter().enumerate() {
+		if let Err(e) = set(target, name, value.clone()) {
+			let mut err = fail(i, e);
+			for (_, name, value) in saved.iter().rev().filter(|&&(first, ..)| first < i) {
+				if let Err(e) = set(target, name, value.clone()) {
+					err.rollback_errors.push(((*name).to_string(), e));
+				}
+			}
+
+			return Err(err);
+		}
+	}
+
+	Ok(())
+}
+
+/// Sets the named option, telling options this crate doesn't know apart from
+/// those the target can't set.
+fn set<T: HasOpts>(target: &T, name: &str, value: OptionValue) -> Result<()>
+{
+	match target.set_named(name, value) {
+		Some(res) => res,
+		None if find(name).is_some() => Err(Error::NotSupported),
+		None => Err(Error::EntryNotFound),
+	}
+}
+
+/// Looks up the description of the named option.
+fn find(name: &str) -> Option<&'static OptionDescriptor> { OPTIONS.iter().find(|o| o.name == name) }
+
+/// The description of an option.
+///
+/// See `options::all` for the list of all options.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct OptionDescriptor
+{
+	/// The name of the option type.
+	name: &'static str,
+
+	/// The kind of value the option takes.
+	value_type: ValueType,
+
+	/// Whether the option can be read.
+	readable: bool,
+
+	/// Whether the option can be written.
+	writable: bool,
+
+	/// The protocols the option is restricted to, if any.
+	protocols: Option<&'static [Protocol]>,
+}
+
+impl OptionDescriptor
+{
+	/// Describes the option type.
+	const fn of<T: OptOps>(protocols: Option<&'static [Protocol]>) -> Self
+	{
+		OptionDescriptor {
+			name: T::NAME,
+			value_type: T::VALUE_TYPE,
+			readable: T::READABLE,
+			writable: T::WRITABLE,
+			protocols,
+		}
+	}
+
+	/// Returns the name of the option, which is the name of its type.
+	pub const fn name(&self) -> &'static str { self.name }
+
+	/// Returns the kind of value the option takes.
+	pub const fn value_type(&self) -> ValueType { self.value_type }
+
+	/// Returns whether the option can be read by anything.
+	pub const fn is_readable(&self) -> bool { self.readable }
+
+	/// Returns whether the option can be written by anything.
+	pub const fn is_writable(&self) -> bool { self.writable }
+
+	/// Returns the protocols the option is restricted to, or `None` if it
+	/// applies to every protocol.
+	pub const fn protocols(&self) -> Option<&'static [Protocol]> { self.protocols }
+
+	/// Returns whether the option applies to the protocol.
+	fn applies_to(&self, protocol: Protocol) -> bool
+	{
+		match self.protocols {
+			Some(p) => p.contains(&protocol),
+			None => true,
+		}
+	}
+}
+
+/// Whether an option applies to a protocol.
+///
+/// See `options::applicable_to`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Applicability
+{
+	/// The protocol supports the option.
+	Yes,
+
+	/// The protocol does not support the option.
+	No,
+
+	/// The option is not known to this crate.
+	Unknown,
+}
+
+/// The kind of value an option takes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ValueType
+{
+	/// A boolean, given as `OptionValue::Bool`.
+	Bool,
+
+	/// An integer, given as `OptionValue::Int`.
+	///
+	/// Some options only take a smaller range of integers, such as `MaxTtl`.
+	Int,
+
+	/// A size in bytes, given as `OptionValue::Size`.
+	Size,
+
+	/// An optional duration, given as `OptionValue::Duration`.
+	Duration,
+
+	/// A string, given as `OptionValue::String`.
+	String,
+
+	/// Arbitrary bytes, given as `OptionValue::Bytes`.
+	Bytes,
+
+	/// An unsigned 64-bit integer, which is only ever read.
+	Uint64,
+
+	/// A socket address, which is only ever read.
+	SocketAddr,
+
+	/// A raw pointer, which cannot be set by name.
+	Pointer,
+}
+
+/// An option given by name along with the value to set it to.
+///
+/// See `Socket::apply_options` and `Socket::apply_options_atomic`.
+pub type OptionOp<'a> = (&'a str, OptionValue);
+
+/// The value of an option given by name.
+///
+/// See `Socket::apply_options`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum OptionValue
+{
+	/// A boolean value.
+	Bool(bool),
+
+	/// An integer value.
+	Int(i32),
+
+	/// A size in bytes.
+	Size(usize),
+
+	/// A duration, where `None` means infinite.
+	Duration(Option<Duration>),
+
+	/// A string value.
+	String(String),
+
+	/// A value of arbitrary bytes.
+	Bytes(Vec<u8>),
+}
+
+impl From<bool> for OptionValue
+{
+	fn from(v: bool) -> Self { OptionValue::Bool(v) }
+}
+
+impl From<i32> for OptionValue
+{
+	fn from(v: i32) -> Self { OptionValue::Int(v) }
+}
+
+impl From<usize> for OptionValue
+{
+	fn from(v: usize) -> Self { OptionValue::Size(v) }
+}
+
+impl From<Duration> for OptionValue
+{
+	fn from(v: Duration) -> Self { OptionValue::Duration(Some(v)) }
+}
+
+impl From<Option<Duration>> for OptionValue
+{
+	fn from(v: Option<Duration>) -> Self { OptionValue::Duration(v) }
+}
+
+impl From<&str> for OptionValue
+{
+	fn from(v: &str) -> Self { OptionValue::String(v.to_string()) }
+}
+
+impl From<String> for OptionValue
+{
+	fn from(v: String) -> Self { OptionValue::String(v) }
+}
+
+impl From<&[u8]> for OptionValue
+{
+	fn from(v: &[u8]) -> Self { OptionValue::Bytes(v.to_vec()) }
+}
+
+impl From<Vec<u8>> for OptionValue
+{
+	fn from(v: Vec<u8>) -> Self { OptionValue::Bytes(v) }
+}
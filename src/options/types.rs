@@ -77,7 +77,27 @@ create_option! {
 	/// ## Support
 	///
 	/// * Dialers can use this option.
-	/// * Sockets can use this option to create a new default value.
+	/// * Sockets can use this option to create a new default value and read
+	///   it back.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::{options::{Options, ReconnectMaxTime, ReconnectMinTime}, *};
+	///
+	/// let socket = Socket::new(Protocol::Push0)?;
+	/// socket.set_opt::<ReconnectMinTime>(Some(Duration::from_millis(250)))?;
+	/// socket.set_opt::<ReconnectMaxTime>(Some(Duration::from_secs(4)))?;
+	/// assert_eq!(socket.get_opt::<ReconnectMinTime>()?, Some(Duration::from_millis(250)));
+	/// assert_eq!(socket.get_opt::<ReconnectMaxTime>()?, Some(Duration::from_secs(4)));
+	///
+	/// // New dialers start out with the values of the socket.
+	/// let dialer = DialerOptions::new(&socket, "inproc://nng/options/reconnect")?;
+	/// assert_eq!(dialer.get_opt::<ReconnectMinTime>()?, Some(Duration::from_millis(250)));
+	/// assert_eq!(dialer.get_opt::<ReconnectMaxTime>()?, Some(Duration::from_secs(4)));
+	/// # Ok::<(), nng::Error>(())
+	/// ```
 	ReconnectMinTime -> Option<Duration>:
 	Get s = s.getopt_ms(nng_sys::NNG_OPT_RECONNMINT as *const _ as _);
 	Set s val = s.setopt_ms(nng_sys::NNG_OPT_RECONNMINT as *const _ as _, val);
@@ -97,7 +117,8 @@ create_option! {
 	/// ## Support
 	///
 	/// * Dialers can use this option.
-	/// * Sockets can use this option to create a new default value.
+	/// * Sockets can use this option to create a new default value and read
+	///   it back.
 	ReconnectMaxTime -> Option<Duration>:
 	Get s = s.getopt_ms(nng_sys::NNG_OPT_RECONNMAXT as *const _ as _);
 	Set s val = s.setopt_ms(nng_sys::NNG_OPT_RECONNMAXT as *const _ as _, val);
@@ -107,7 +128,13 @@ create_option! {
 	/// The depth of the socket's receive buffer as a number of messages.
 	///
 	/// Messages received by the transport may be buffered until the
-	/// application has accepted them for delivery.
+	/// application has accepted them for delivery. The depth counts messages
+	/// of any size and must be between 0 and 8192, inclusive. Once the buffer
+	/// is full, the socket stops reading from its pipes, which pushes back on
+	/// the peers as described for `SendBufferSize`. Receives take messages
+	/// from the buffer, and nonblocking ones fail with `Error::TryAgain` if it
+	/// is empty. Shrinking the buffer below the number of messages it holds
+	/// drops the oldest ones.
 	///
 	/// ## Support
 	///
@@ -151,10 +178,12 @@ create_option! {
 	/// per-dialer or per-listener basis.
 	///
 	/// Note that some transports may have further message size restrictions.
+	/// See `Socket::set_recv_max_size` for how the limit is enforced.
 	///
 	/// ## Support
 	///
 	/// * Dialers and Listeners can use this with the following transports:
+	///     * inproc
 	///     * TCP
 	///     * ZeroTier
 	///     * IPC
@@ -188,12 +217,86 @@ create_option! {
 	///
 	/// Messages sent by an application may be buffered by the socket until a
 	/// transport is ready to accept them for delivery. This value must be an
-	/// integer between 0 and 8192, inclusive.
+	/// integer between 0 and 8192, inclusive. It counts messages of any size.
+	///
+	/// ## Backpressure
+	///
+	/// Protocols that don't drop messages, such as _push_ and _pull_, stop
+	/// accepting new ones once the buffers between the sockets are full.
+	/// Blocking sends then wait until the receiver catches up, or until the
+	/// `SendTimeout` expires with `Error::TimedOut`. The sends of a socket
+	/// made nonblocking with `Socket::set_nonblocking` fail right away with
+	/// `Error::TryAgain` instead, and hand the message back.
+	///
+	/// The _inproc_ transport has no buffers of its own, so these options
+	/// are the whole queue. A _push_ socket with a single _pull_ peer accepts
+	/// up to its `SendBufferSize` plus the `RecvBufferSize` of the peer
+	/// before it pushes back, along with the three messages that are in
+	/// transit in the pipe. Every other peer adds room for a few more, and
+	/// other transports add their own buffering in the operating system. As
+	/// the messages move between the buffers in the background, a sender
+	/// that fills them very quickly may see `Error::TryAgain` a little before
+	/// they are full. Without a send buffer, a nonblocking send only succeeds
+	/// while a pipe is waiting for its next message.
+	///
+	/// NNG 1.1 releases the pressure in bursts: a pipe that is waiting for
+	/// room in a full receive buffer only delivers again once the receiver
+	/// has emptied the buffer. Until then, receiving a message from it makes
+	/// no room for the sender, after which the whole queue frees up at once.
 	///
 	/// ## Support
 	///
 	/// * Sockets can utilize this value.
 	/// * Dialers and Listeners can retrieve it from their owning Socket.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::{options::{Options, RecvBufferSize, SendBufferSize, SendTimeout}, *};
+	///
+	/// const ADDRESS: &str = "inproc://nng/options/backpressure";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.set_opt::<RecvBufferSize>(4)?;
+	/// pull.listen(ADDRESS)?;
+	///
+	/// let mut push = Socket::new(Protocol::Push0)?;
+	/// push.set_opt::<SendBufferSize>(2)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// // Fill the queue without a receiver, giving the messages time to move on.
+	/// push.set_nonblocking(true);
+	/// let mut accepted = 0;
+	/// loop {
+	///     let before = accepted;
+	///     while push.send(&[accepted as u8][..]).is_ok() {
+	///         accepted += 1;
+	///     }
+	///     thread::sleep(Duration::from_millis(20));
+	///     if accepted == before {
+	///         break;
+	///     }
+	/// }
+	/// assert_eq!(accepted, 2 + 4 + 3);
+	/// let err = push.send(&b"overflow"[..]).unwrap_err();
+	/// assert_eq!(err.error, Error::TryAgain);
+	///
+	/// // Blocking sends wait for the receiver to make room, up to the timeout.
+	/// push.set_nonblocking(false);
+	/// push.set_opt::<SendTimeout>(Some(Duration::from_millis(50)))?;
+	/// let err = push.send(&b"overflow"[..]).unwrap_err();
+	/// assert_eq!(err.error, Error::TimedOut);
+	///
+	/// // Once the receiver has caught up, there is room again.
+	/// for i in 0..accepted {
+	///     assert_eq!(&pull.recv()?[..], &[i as u8]);
+	/// }
+	/// push.set_opt::<SendTimeout>(Some(Duration::from_secs(5)))?;
+	/// push.send(&[accepted as u8][..])?;
+	/// assert_eq!(&pull.recv()?[..], &[accepted as u8]);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
 	SendBufferSize -> i32:
 	Get s = s.getopt_int(nng_sys::NNG_OPT_SENDBUF as *const _ as _);
 	Set s val = s.setopt_int(nng_sys::NNG_OPT_SENDBUF as *const _ as _, val);
@@ -264,6 +367,9 @@ create_option! {
 	/// message. Therefore it is helpful if all nodes in the topology use the
 	/// same value for this option.
 	///
+	/// The value must be between 1 and 255, inclusive. NNG rejects zero with
+	/// `Error::InvalidInput`.
+	///
 	/// ## Support
 	///
 	/// * Sockets can use this with the following protocols:
@@ -284,7 +390,7 @@ create_option! {
 	/// The URL with which a listener or dialer was configured.
 	///
 	/// Note that some transports will canonify URLs before returning them to
-	/// the application.
+	/// the application. `ParsedUrl` splits the value into its components.
 	///
 	/// ## Support
 	///
@@ -294,6 +400,22 @@ create_option! {
 }
 
 /// Options relating to the socket protocol.
+///
+/// Each of these options only works with some of the protocols:
+///
+/// | Option | Protocols |
+/// |--------|-----------|
+/// | `Polyamorous` | `Pair1` |
+/// | `Subscribe`, `Unsubscribe` | `Sub0` |
+/// | `ResendTime` | `Req0` |
+/// | `SurveyTime` | `Surveyor0` |
+/// | `MaxTtl` | `Pair1`, `Rep0`, `Req0`, `Respondent0`, `Surveyor0` |
+///
+/// A plain `Socket` accepts all of them and fails at runtime when the
+/// protocol doesn't support the option. The typed sockets, such as
+/// `SubSocket` and `ReqSocket`, only accept the options of their protocol, so
+/// using the wrong one is a compile error. The same mapping is available at
+/// runtime from `options::applicable_to`.
 pub mod protocol
 {
 	/// Options dealing with the PAIR protocol.
@@ -380,6 +502,11 @@ pub mod protocol
 			/// was sent disconnects, or if a peer becomes available while the
 			/// requester is waiting for an available peer.)
 			///
+			/// NNG 1.1 adds this to the current time without checking for
+			/// special values, so with `None` or a zero duration requests are
+			/// resent right away. `Context::set_resend_time` turns resending
+			/// off for a single context.
+			///
 			/// ## Support
 			///
 			/// * Sockets can read and write this value when using the following protocols:
@@ -410,7 +537,33 @@ pub mod protocol
 			///
 			/// * Sockets can read and write this value when using the following protocols:
 			///     * Surveyor v0
+			/// * Contexts can read and write this value when using the following protocols:
+			///     * Surveyor v0
 			/// * Dialers and Listeners can retrieve it from their owning Socket, if applicable.
+			///
+			/// ## Example
+			///
+			/// NNG keeps the survey time in whole milliseconds, which read back
+			/// unchanged. A fractional millisecond is rounded up when it is set.
+			///
+			/// ```
+			/// use std::time::Duration;
+			/// use nng::{options::{protocol::survey::SurveyTime, Options}, *};
+			///
+			/// let surveyor = Socket::new(Protocol::Surveyor0)?;
+			/// surveyor.set_opt::<SurveyTime>(Some(Duration::from_millis(1500)))?;
+			/// assert_eq!(surveyor.get_opt::<SurveyTime>()?, Some(Duration::from_millis(1500)));
+			///
+			/// surveyor.set_opt::<SurveyTime>(Some(Duration::from_micros(2500)))?;
+			/// assert_eq!(surveyor.get_opt::<SurveyTime>()?, Some(Duration::from_millis(3)));
+			///
+			/// // Contexts start out with the value of the socket.
+			/// let ctx = Context::new(&surveyor)?;
+			/// assert_eq!(ctx.get_opt::<SurveyTime>()?, Some(Duration::from_millis(3)));
+			/// ctx.set_opt::<SurveyTime>(Some(Duration::from_millis(20)))?;
+			/// assert_eq!(ctx.get_opt::<SurveyTime>()?, Some(Duration::from_millis(20)));
+			/// # Ok::<(), nng::Error>(())
+			/// ```
 			SurveyTime -> Option<Duration>:
 			Get s = s.getopt_ms(nng_sys::NNG_OPT_SURVEYOR_SURVEYTIME as *const _ as _);
 			Set s val = s.setopt_ms(nng_sys::NNG_OPT_SURVEYOR_SURVEYTIME as *const _ as _, val);
@@ -544,6 +697,13 @@ pub mod transport
 			/// useful on low-bandwidth connections to reduce overhead, but it
 			/// comes at a cost to latency.
 			///
+			/// Each pipe takes the value of its dialer or listener when it is
+			/// created and keeps it for as long as it exists. Setting this on a
+			/// socket also updates its existing dialers and listeners, so
+			/// connections made afterwards use the new value, but pipes that are
+			/// already connected keep the old one. Reading the option from a pipe
+			/// reports the value that was applied to its connection.
+			///
 			/// ## Support
 			///
 			/// * Dialers and Listeners can use this option with the following transports:
@@ -553,6 +713,38 @@ pub mod transport
 			///     * TCP
 			///     * TLS
 			/// * Sockets can use this to set a default value.
+			///
+			/// ## Example
+			///
+			/// ```
+			/// use std::{sync::{mpsc, Mutex}, time::Duration};
+			/// use nng::{options::{transport::tcp::NoDelay, Options, Url}, *};
+			///
+			/// let server = Socket::new(Protocol::Pull0)?;
+			/// let listener = Listener::new(&server, "tcp://127.0.0.1:0", false)?;
+			/// let url = listener.get_opt::<Url>()?;
+			///
+			/// let client = Socket::new(Protocol::Push0)?;
+			/// let (tx, pipes) = mpsc::channel();
+			/// let tx = Mutex::new(tx);
+			/// client.pipe_notify(move |pipe, ev| if ev == PipeEvent::AddPost {
+			///     tx.lock().unwrap().send(pipe).unwrap();
+			/// })?;
+			/// let timeout = Duration::from_secs(1);
+			///
+			/// client.set_opt::<NoDelay>(false)?;
+			/// client.dial(&url)?;
+			/// let old = pipes.recv_timeout(timeout).unwrap();
+			///
+			/// // Only the connections made from now on use the new value.
+			/// client.set_opt::<NoDelay>(true)?;
+			/// client.dial(&url)?;
+			/// let new = pipes.recv_timeout(timeout).unwrap();
+			///
+			/// assert!(!old.get_opt::<NoDelay>()?);
+			/// assert!(new.get_opt::<NoDelay>()?);
+			/// # Ok::<(), nng::Error>(())
+			/// ```
 			NoDelay -> bool:
 			Get s = s.getopt_bool(nng_sys::NNG_OPT_TCP_NODELAY as *const _ as _);
 			Set s val = s.setopt_bool(nng_sys::NNG_OPT_TCP_NODELAY as *const _ as _, val);
@@ -573,6 +765,14 @@ pub mod transport
 			/// keep connection table entries in NAT and other middleware from
 			/// being expiring due to lack of activity.
 			///
+			/// NNG offers no control over the timers of the probes, so it takes
+			/// as long as the operating system defaults, often hours, to notice
+			/// a dead peer. `Heartbeat` detects them at the application level in
+			/// a configurable time.
+			///
+			/// As with `NoDelay`, pipes keep the value they were created with,
+			/// which they report when the option is read from them.
+			///
 			/// ## Support
 			///
 			/// * Dialers and Listeners can use this option with the following transports:
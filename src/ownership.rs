@@ -0,0 +1,46 @@
+//! Checks, in test builds, that every message has a single owner in Rust.
+//!
+//! A message is a plain pointer to NNG, so nothing stops the same one from
+//! being wrapped in two `Message`s, such as by taking back a message after a
+//! send that NNG had already accepted, which frees it twice once both are
+//! dropped. With the `testing` feature, every live `Message` has its pointer
+//! recorded and a panic is raised when a pointer is wrapped while it already
+//! has an owner, or given up when it has none, so that such a mistake fails
+//! the tests that reach it instead of corrupting the heap.
+//!
+//! Every message then takes a global lock when it is created and freed,
+//! which is why this isn't part of ordinary builds. A mistake found on an NNG
+//! thread, such as in an AIO callback, aborts the process, as any panic there
+//! does.
+use std::{
+	collections::BTreeSet,
+	ptr::NonNull,
+	sync::{Mutex, PoisonError},
+	thread,
+};
+
+/// The pointers of the messages currently owned by a `Message`.
+static OWNED: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+
+/// Records that a `Message` took ownership of the message.
+pub(crate) fn claim(msgp: NonNull<nng_sys::nng_msg>)
+{
+	let mut owned = OWNED.lock().unwrap_or_else(PoisonError::into_inner);
+	let fresh = owned.insert(msgp.as_ptr() as usize);
+	drop(owned);
+
+	assert!(fresh, "Message {:p} was wrapped while it already had an owner", msgp);
+}
+
+/// Records that a `Message` gave up the message, by freeing it or handing it
+/// to NNG.
+pub(crate) fn release(msgp: NonNull<nng_sys::nng_msg>)
+{
+	let mut owned = OWNED.lock().unwrap_or_else(PoisonError::into_inner);
+	let known = owned.remove(&(msgp.as_ptr() as usize));
+	drop(owned);
+
+	// Panicking again while a `Message` is dropped during unwinding would only
+	// abort and hide the first panic.
+	assert!(known || thread::panicking(), "Message {:p} was given up without an owner", msgp);
+}
@@ -0,0 +1,217 @@
+//! What happens when a callback panics.
+//!
+//! The callbacks of AIOs and the pipe notification callbacks of sockets are
+//! called from NNG threads, through an `extern "C"` function. A panic cannot
+//! unwind into NNG, so by default a panicking callback aborts the program, as
+//! described in `Aio::new` and `Socket::pipe_notify`.
+//!
+//! That is the right behavior for applications, but it gets in the way of
+//! test harnesses: a single failing assertion inside of a callback aborts the
+//! whole test binary, including the output of every other test. For those
+//! cases, the panics can instead be captured and handed to a handler, after
+//! which NNG carries on as if the callback had returned normally.
+//!
+//! Setting the policy is `unsafe` because NNG and this crate were not
+//! designed with callbacks that stop halfway in mind. Capturing panics is only
+//! sound when the handler and every callback are unwind safe: whatever state a
+//! callback shares with the rest of the program must still be valid if it
+//! stops at the panic, and an operation the callback would have started never
+//! happens, which can leave a worker waiting forever. A panic inside the
+//! handler itself still aborts the program.
+//!
+//! ## Example
+//!
+//! ```
+//! use std::{sync::{mpsc, Arc, Mutex}, time::Duration};
+//! use nng::{panic_policy::{self, PanicPolicy, PanicSource}, *};
+//!
+//! let (tx, rx) = mpsc::channel();
+//! let tx = Mutex::new(tx);
+//! let policy = PanicPolicy::CapturePanics(Arc::new(move |payload, source| {
+//!     let msg = payload.downcast_ref::<&str>().unwrap().to_string();
+//!     tx.lock().unwrap().send((msg, source)).unwrap();
+//! }));
+//! let previous = unsafe { panic_policy::set(policy) };
+//! assert!(previous.is_abort());
+//!
+//! let (res_tx, res_rx) = mpsc::channel();
+//! let res_tx = Mutex::new(res_tx);
+//! let aio = Aio::new(move |_, res| match res {
+//!     AioResult::SleepOk => panic!("Oh no"),
+//!     res => res_tx.lock().unwrap().send(res).unwrap(),
+//! })?;
+//!
+//! // The panic reaches the handler along with the AIO that it happened in.
+//! aio.sleep(Duration::from_millis(10))?;
+//! match rx.recv_timeout(Duration::from_secs(1)).unwrap() {
+//!     (msg, PanicSource::Aio(a)) => {
+//!         assert_eq!(msg, "Oh no");
+//!         assert_eq!(a, aio);
+//!     },
+//!     res => panic!("Unexpected result: {:?}", res),
+//! }
+//!
+//! // The AIO keeps working afterwards.
+//! aio.set_timeout(Some(Duration::from_millis(10)))?;
+//! aio.sleep(Duration::from_secs(1))?;
+//! match res_rx.recv_timeout(Duration::from_secs(1)).unwrap() {
+//!     AioResult::SleepErr(Error::TimedOut) => {},
+//!     res => panic!("Unexpected result: {:?}", res),
+//! }
+//! # Ok::<(), nng::Error>(())
+//! ```
+//!
+//! Pipe notification callbacks are covered as well:
+//!
+//! ```
+//! use std::{sync::{mpsc, Arc, Mutex}, time::Duration};
+//! use nng::{panic_policy::{self, PanicPolicy, PanicSource}, *};
+//!
+//! const ADDRESS: &str = "inproc://nng/panic_policy/pipe_notify";
+//!
+//! let (tx, rx) = mpsc::channel();
+//! let tx = Mutex::new(tx);
+//! let policy = PanicPolicy::CapturePanics(Arc::new(move |_, source| {
+//!     tx.lock().unwrap().send(source).unwrap();
+//! }));
+//! unsafe { panic_policy::set(policy) };
+//!
+//! let server = Socket::new(Protocol::Rep0)?;
+//! server.pipe_notify(|_, ev| {
+//!     if ev == PipeEvent::AddPost {
+//!         panic!("Not today");
+//!     }
+//! })?;
+//! server.listen(ADDRESS)?;
+//!
+//! let client = Socket::new(Protocol::Req0)?;
+//! client.dial(ADDRESS)?;
+//! match rx.recv_timeout(Duration::from_secs(1)).unwrap() {
+//!     PanicSource::PipeNotify(_, PipeEvent::AddPost) => {},
+//!     res => panic!("Unexpected result: {:?}", res),
+//! }
+//!
+//! // The connection is still usable.
+//! client.send(&b"ping"[..])?;
+//! let msg = server.recv()?;
+//! server.send(msg)?;
+//! assert_eq!(&client.recv()?[..], b"ping");
+//! # Ok::<(), nng::Error>(())
+//! ```
+use std::{
+	any::Any,
+	fmt,
+	panic::{catch_unwind, AssertUnwindSafe},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, PoisonError, RwLock,
+	},
+};
+
+use crate::{
+	aio::Aio,
+	pipe::{Pipe, PipeEvent},
+};
+
+/// The function that captured panics are handed to.
+///
+/// It is called on the NNG thread that ran the callback, with the payload of
+/// the panic and the callback it came from.
+pub type PanicHandler = dyn Fn(Box<dyn Any + Send>, PanicSource) + Send + Sync + 'static;
+
+/// The policy for panicking callbacks.
+#[derive(Clone)]
+pub enum PanicPolicy
+{
+	/// Abort the program. This is the default.
+	Abort,
+
+	/// Hand the panic to the handler and carry on.
+	CapturePanics(Arc<PanicHandler>),
+}
+impl PanicPolicy
+{
+	/// Returns whether this is `PanicPolicy::Abort`.
+	pub fn is_abort(&self) -> bool { matches!(self, PanicPolicy::Abort) }
+}
+
+impl fmt::Debug for PanicPolicy
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self {
+			PanicPolicy::Abort => f.write_str("Abort"),
+			PanicPolicy::CapturePanics(_) => f.write_str("CapturePanics(..)"),
+		}
+	}
+}
+
+/// The callback that a captured panic came from.
+#[derive(Clone, Debug)]
+pub enum PanicSource
+{
+	/// The callback of the AIO.
+	Aio(Aio),
+
+	/// A pipe notification callback, called for the pipe and event.
+	PipeNotify(Pipe, PipeEvent),
+}
+
+/// The current policy.
+static POLICY: RwLock<PanicPolicy> = RwLock::new(PanicPolicy::Abort);
+
+/// Whether the current policy captures panics.
+///
+/// This lets the callbacks skip the lock as long as the policy is the default.
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+/// Sets the policy for callbacks that panic, returning the previous one.
+///
+/// The policy applies to every callback of every AIO and socket, starting
+/// with the next call of a callback.
+///
+/// ## Safety
+///
+/// Capturing panics is only sound if the handler and all callbacks are unwind
+/// safe, as described in the module documentation. Setting the policy back
+/// to `PanicPolicy::Abort` is always safe.
+pub unsafe fn set(policy: PanicPolicy) -> PanicPolicy
+{
+	let mut current = POLICY.write().unwrap_or_else(PoisonError::into_inner);
+	CAPTURING.store(!policy.is_abort(), Ordering::Release);
+	std::mem::replace(&mut *current, policy)
+}
+
+/// Returns the current policy for callbacks that panic.
+pub fn get() -> PanicPolicy { POLICY.read().unwrap_or_else(PoisonError::into_inner).clone() }
+
+/// Returns the handler of the current policy, if it captures panics.
+pub(crate) fn handler() -> Option<Arc<PanicHandler>>
+{
+	if !CAPTURING.load(Ordering::Acquire) {
+		return None;
+	}
+
+	match &*POLICY.read().unwrap_or_else(PoisonError::into_inner) {
+		PanicPolicy::Abort => None,
+		PanicPolicy::CapturePanics(h) => Some(Arc::clone(h)),
+	}
+}
+
+/// Runs a callback, handing a panic to the handler if there is one.
+///
+/// Without a handler, the panic continues on to the trampoline, which aborts.
+pub(crate) fn run<F, S>(handler: Option<&PanicHandler>, callback: F, source: S)
+where
+	F: FnOnce(),
+	S: FnOnce() -> PanicSource,
+{
+	match handler {
+		None => callback(),
+		Some(h) => {
+			if let Err(payload) = catch_unwind(AssertUnwindSafe(callback)) {
+				h(payload, source());
+			}
+		},
+	}
+}
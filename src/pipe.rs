@@ -1,9 +1,17 @@
 use std::{
 	cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd},
+	ffi::CStr,
 	hash::{Hash, Hasher},
+	ptr,
 };
 
-use crate::{dialer::Dialer, listener::Listener};
+use crate::{
+	addr::SocketAddr,
+	dialer::Dialer,
+	error::Result,
+	listener::Listener,
+	options::{Options, RemAddr},
+};
 
 /// A nanomsg-next-generation pipe.
 ///
@@ -51,6 +59,19 @@ impl Pipe
 		if id > 0 { Some(Listener::from_nng_sys(listener)) } else { None }
 	}
 
+	/// Returns the positive ID of the pipe.
+	///
+	/// The ID stays the same for the lifetime of the pipe and can be handed to
+	/// `Socket::close_pipe` later on.
+	pub fn id(self) -> i32 { unsafe { nng_sys::nng_pipe_id(self.handle) } }
+
+	/// Returns the address of the remote peer.
+	///
+	/// This is the same as reading the `RemAddr` option of the pipe. For TCP
+	/// based transports, this tells apart the connections of peers on the same
+	/// host by their port.
+	pub fn remote_addr(self) -> Result<SocketAddr> { self.get_opt::<RemAddr>() }
+
 	/// Closes the pipe.
 	///
 	/// Messages that have been submitted for sending may be flushed or
@@ -64,12 +85,80 @@ impl Pipe
 		// care about the return value.
 		let rv = unsafe { nng_sys::nng_pipe_close(self.handle) };
 		assert!(
-			rv == 0 || rv == nng_sys::NNG_ECLOSED as i32,
+			rv == 0 || rv == nng_sys::NNG_ECLOSED as i32 || rv == nng_sys::NNG_ENOENT as i32,
 			"Unexpected error code while closing pipe ({})",
 			rv
 		);
 	}
 
+	/// Returns the traffic counters of the pipe, if NNG keeps any.
+	///
+	/// The counters are read from the NNG statistics tree, which requires NNG
+	/// to be built with statistics enabled (the `nng-stats` feature). NNG v1.1
+	/// is the first version to have per-pipe statistics, but only the
+	/// _inproc_ transport publishes traffic counters there. For other
+	/// transports the individual counters are `None`.
+	///
+	/// Returns `None` if statistics are not available at all or if the pipe is
+	/// no longer part of the statistics tree, such as after it was closed.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::sync::{Arc, Mutex};
+	/// use nng::{PipeEvent, Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/pipe/stats";
+	///
+	/// let server = Socket::new(Protocol::Pull0)?;
+	/// let pipe = Arc::new(Mutex::new(None));
+	/// let cb_pipe = Arc::clone(&pipe);
+	/// server.pipe_notify(move |p, ev| if ev == PipeEvent::AddPost {
+	///     *cb_pipe.lock().unwrap() = Some(p);
+	/// })?;
+	/// server.listen(ADDRESS)?;
+	///
+	/// let client = Socket::new(Protocol::Push0)?;
+	/// client.dial(ADDRESS)?;
+	/// client.send(&b"hello"[..])?;
+	/// server.recv()?;
+	///
+	/// let pipe = pipe.lock().unwrap().expect("No pipe was added");
+	/// # // The statistics tree is empty when NNG is built without it.
+	/// if let Some(stats) = pipe.stats() {
+	///     assert_eq!(stats.messages_received, Some(1));
+	///     assert_eq!(stats.bytes_received, Some(5));
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn stats(self) -> Option<PipeStats>
+	{
+		let mut root = ptr::null_mut();
+		let rv = unsafe { nng_sys::nng_stats_get(&mut root as _) };
+		if rv != 0 {
+			return None;
+		}
+
+		let id = unsafe { nng_sys::nng_pipe_id(self.handle) };
+		let scope = format!("pipe{}", id);
+
+		// The snapshot is owned by us until it is freed, so walking it is safe.
+		let stats = unsafe {
+			let mut node = nng_sys::nng_stat_child(root);
+			while !node.is_null() {
+				if CStr::from_ptr(nng_sys::nng_stat_name(node)).to_bytes() == scope.as_bytes() {
+					break;
+				}
+				node = nng_sys::nng_stat_next(node);
+			}
+
+			if node.is_null() { None } else { Some(PipeStats::from_scope(node)) }
+		};
+
+		unsafe { nng_sys::nng_stats_free(root) };
+		stats
+	}
+
 	/// Returns the underlying nng handle for the pipe.
 	pub(crate) const fn handle(self) -> nng_sys::nng_pipe { self.handle }
 
@@ -119,9 +208,55 @@ impl Hash for Pipe
 	}
 }
 
+/// Traffic counters of a single pipe.
+///
+/// See `Pipe::stats` for when these are available.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PipeStats
+{
+	/// The number of messages sent on the pipe.
+	pub messages_sent: Option<u64>,
+
+	/// The number of messages received on the pipe.
+	pub messages_received: Option<u64>,
+
+	/// The number of bytes sent on the pipe, including headers.
+	pub bytes_sent: Option<u64>,
+
+	/// The number of bytes received on the pipe, including headers.
+	pub bytes_received: Option<u64>,
+}
+impl PipeStats
+{
+	/// Reads the counters from the statistics scope of a pipe.
+	///
+	/// The scope must point into a live statistics snapshot.
+	unsafe fn from_scope(scope: *mut nng_sys::nng_stat) -> Self
+	{
+		let mut stats = PipeStats::default();
+
+		let mut node = nng_sys::nng_stat_child(scope);
+		while !node.is_null() {
+			let value = Some(nng_sys::nng_stat_value(node));
+			match CStr::from_ptr(nng_sys::nng_stat_name(node)).to_bytes() {
+				b"txmsgs" => stats.messages_sent = value,
+				b"rxmsgs" => stats.messages_received = value,
+				b"txbytes" => stats.bytes_sent = value,
+				b"rxbytes" => stats.bytes_received = value,
+				_ => {},
+			}
+
+			node = nng_sys::nng_stat_next(node);
+		}
+
+		stats
+	}
+}
+
 #[rustfmt::skip]
 expose_options!{
 	Pipe :: handle -> nng_sys::nng_pipe;
+	MISSING_IS_CLOSED = true;
 
 	GETOPT_BOOL = nng_sys::nng_pipe_getopt_bool;
 	GETOPT_INT = nng_sys::nng_pipe_getopt_int;
@@ -1,5 +1,53 @@
 /// Protocols available for use by sockets.
-use std::fmt;
+use std::{
+	convert::TryFrom,
+	fmt,
+	sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::{
+	error::{Error, Result},
+	socket::Socket,
+};
+
+/// Every protocol, in the order they are declared in.
+const ALL: [Protocol; 11] = [
+	Protocol::Bus0,
+	Protocol::Pair0,
+	Protocol::Pair1,
+	Protocol::Pub0,
+	Protocol::Pull0,
+	Protocol::Push0,
+	Protocol::Rep0,
+	Protocol::Req0,
+	Protocol::Respondent0,
+	Protocol::Sub0,
+	Protocol::Surveyor0,
+];
+
+/// The protocol has not been probed yet.
+const UNPROBED: u8 = 0;
+
+/// The protocol was found to be available.
+const AVAILABLE: u8 = 1;
+
+/// The protocol was found to be missing.
+const UNAVAILABLE: u8 = 2;
+
+/// The result of probing each protocol, indexed like `ALL`.
+static PROBED: [AtomicU8; 11] = [
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+	AtomicU8::new(UNPROBED),
+];
 
 /// Describes a relationship between a socket and all sockets to which it is
 /// connected.
@@ -19,8 +67,11 @@ pub enum Protocol
 	/// Version 0 of the pair protocol.
 	///
 	/// The _pair_ protocol implements a peer-to-peer pattern, where
-	/// relationships between peers are one-to-one. See the
-	/// [pair documentation][1] for more information.
+	/// relationships between peers are one-to-one. Version 0 has no options
+	/// and is the version spoken by the legacy _nanomsg_ library, so it is
+	/// the one to use when talking to applications built on it. Otherwise,
+	/// `Protocol::pair` recommends version 1. The two versions cannot talk to
+	/// each other. See the [pair documentation][1] for more information.
 	///
 	/// [1]: https://nanomsg.github.io/nng/man/v1.1.0/nng_pair.7.html
 	Pair0,
@@ -32,6 +83,15 @@ pub enum Protocol
 	/// supports and optional _polyamorous_ mode. See the [pair
 	/// documentation][1] for more information.
 	///
+	/// Compared to version 0, messages carry a hop count, which `MaxTtl`
+	/// limits, so that messages forwarded around a loop of devices are
+	/// eventually dropped. With the `Polyamorous` option, which has to be set
+	/// before the socket connects to anything, a single socket accepts any
+	/// number of peers instead of refusing all but the first. Received
+	/// messages then tell their peer through `Message::pipe`, and sent ones
+	/// go to the peer set with `Message::set_pipe`, or to the first connected
+	/// one if none was set. `Pair1Socket` wraps these rules.
+	///
 	/// [1]: https://nanomsg.github.io/nng/man/v1.1.0/nng_pair.7.html
 	Pair1,
 
@@ -137,8 +197,206 @@ pub enum Protocol
 	Surveyor0,
 }
 
+impl Protocol
+{
+	/// Returns the recommended version of the _pair_ protocol.
+	///
+	/// This is `Protocol::Pair1`, which does everything version 0 does and
+	/// protects against loops of devices. The one reason to pick
+	/// `Protocol::Pair0` instead is to talk to peers that only speak version
+	/// 0, such as applications using the legacy _nanomsg_ library, as the two
+	/// versions refuse to connect to each other.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::{Protocol, Socket};
+	///
+	/// let server = Socket::new(Protocol::pair())?;
+	/// server.listen("inproc://nng/protocol/pair")?;
+	/// let client = Socket::new(Protocol::pair())?;
+	/// client.dial("inproc://nng/protocol/pair")?;
+	///
+	/// client.send(&b"hello"[..])?;
+	/// assert_eq!(&server.recv()?[..], b"hello");
+	/// assert_eq!(Protocol::pair(), Protocol::Pair1);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub const fn pair() -> Protocol { Protocol::Pair1 }
+
+	/// Returns whether the NNG library supports the protocol.
+	///
+	/// Every protocol is available when NNG is built by this crate, but a
+	/// system library may report some of them as not supported, which makes
+	/// `Socket::new` fail with `Error::ProtocolUnavailable`. This tells ahead
+	/// of time, so that an application can fall back to something else.
+	/// Libraries that leave the functions of a protocol out altogether fail to
+	/// link instead, as described for `Socket::new`.
+	///
+	/// The first call for a protocol finds out by opening a socket with it and
+	/// closing it right away. The answer is kept for as long as the process
+	/// runs, so later calls don't open any sockets. A socket that fails to open
+	/// for another reason, such as running out of memory, counts as missing
+	/// but is probed again on the next call.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::Protocol;
+	///
+	/// let discovery = if Protocol::Surveyor0.is_available() { "survey" } else { "static" };
+	/// println!("Discovering peers with {} configuration", discovery);
+	/// ```
+	pub fn is_available(self) -> bool
+	{
+		let probed = &PROBED[self as usize];
+		match probed.load(Ordering::Relaxed) {
+			AVAILABLE => return true,
+			UNAVAILABLE => return false,
+			_ => {},
+		}
+
+		// Threads that get here at the same time each probe, which is harmless.
+		match Socket::new(self) {
+			Ok(socket) => {
+				socket.close();
+				probed.store(AVAILABLE, Ordering::Relaxed);
+				true
+			},
+			Err(Error::ProtocolUnavailable(_)) => {
+				probed.store(UNAVAILABLE, Ordering::Relaxed);
+				false
+			},
+			Err(_) => false,
+		}
+	}
+}
+
+/// Returns the protocols that the NNG library supports.
+///
+/// This checks every protocol with `Protocol::is_available`, so only the
+/// first call opens any sockets.
+///
+/// ## Example
+///
+/// ```
+/// use nng::{available_protocols, Protocol};
+///
+/// // NNG hands out socket IDs in order, so the ID of a new socket shows how
+/// // many sockets were opened before it.
+/// fn next_socket_id() -> i32
+/// {
+///     let mut socket = nng_sys::nng_socket::NNG_SOCKET_INITIALIZER;
+///     unsafe {
+///         assert_eq!(nng_sys::nng_pair1_open(&mut socket), 0);
+///         let id = nng_sys::nng_socket_id(socket);
+///         nng_sys::nng_close(socket);
+///         id
+///     }
+/// }
+///
+/// let protocols = available_protocols();
+/// for common in &[Protocol::Pair1, Protocol::Pub0, Protocol::Sub0, Protocol::Req0, Protocol::Rep0] {
+///     assert!(protocols.contains(common));
+///     assert!(common.is_available());
+/// }
+///
+/// // The answers are cached, so asking again doesn't open more sockets.
+/// let before = next_socket_id();
+/// for _ in 0..100 {
+///     assert_eq!(available_protocols(), protocols);
+/// }
+/// assert_eq!(next_socket_id(), before + 1);
+/// ```
+pub fn available_protocols() -> Vec<Protocol>
+{
+	ALL.iter().copied().filter(|p| p.is_available()).collect()
+}
+
 #[allow(clippy::use_debug)]
 impl fmt::Display for Protocol
 {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{:?}", self) }
 }
+
+/// Converts a raw NNG protocol number into a protocol.
+///
+/// These are the numbers NNG reports through the `NNG_OPT_PROTO` and
+/// `NNG_OPT_PEER` options. Numbers that do not correspond to one of the
+/// protocols supported by this crate result in `Error::NotSupported`.
+///
+/// ## Example
+///
+/// ```
+/// use std::convert::TryFrom;
+/// use nng::{Error, Protocol};
+///
+/// // The protocol numbers are defined by NNG as `(major << 4) | minor`.
+/// assert_eq!(Protocol::try_from(0x70), Ok(Protocol::Bus0));
+/// assert_eq!(Protocol::try_from(0x10), Ok(Protocol::Pair0));
+/// assert_eq!(Protocol::try_from(0x11), Ok(Protocol::Pair1));
+/// assert_eq!(Protocol::try_from(0x20), Ok(Protocol::Pub0));
+/// assert_eq!(Protocol::try_from(0x21), Ok(Protocol::Sub0));
+/// assert_eq!(Protocol::try_from(0x30), Ok(Protocol::Req0));
+/// assert_eq!(Protocol::try_from(0x31), Ok(Protocol::Rep0));
+/// assert_eq!(Protocol::try_from(0x50), Ok(Protocol::Push0));
+/// assert_eq!(Protocol::try_from(0x51), Ok(Protocol::Pull0));
+/// assert_eq!(Protocol::try_from(0x62), Ok(Protocol::Surveyor0));
+/// assert_eq!(Protocol::try_from(0x63), Ok(Protocol::Respondent0));
+///
+/// assert_eq!(Protocol::try_from(0x12), Err(Error::NotSupported));
+/// assert_eq!(Protocol::try_from(-1), Err(Error::NotSupported));
+/// ```
+impl TryFrom<i32> for Protocol
+{
+	type Error = Error;
+
+	fn try_from(number: i32) -> Result<Self>
+	{
+		match number {
+			0x70 => Ok(Protocol::Bus0),
+			0x10 => Ok(Protocol::Pair0),
+			0x11 => Ok(Protocol::Pair1),
+			0x20 => Ok(Protocol::Pub0),
+			0x21 => Ok(Protocol::Sub0),
+			0x30 => Ok(Protocol::Req0),
+			0x31 => Ok(Protocol::Rep0),
+			0x50 => Ok(Protocol::Push0),
+			0x51 => Ok(Protocol::Pull0),
+			0x62 => Ok(Protocol::Surveyor0),
+			0x63 => Ok(Protocol::Respondent0),
+			_ => Err(Error::NotSupported),
+		}
+	}
+}
+
+/// The protocol of an open socket.
+///
+/// Sockets are usually opened with one of the protocols described by
+/// `Protocol`, but `Socket::open_with` allows for protocols that are unknown
+/// to this crate.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ProtocolKind
+{
+	/// One of the protocols supported by this crate.
+	Known(Protocol),
+
+	/// A protocol opened via `Socket::open_with`, identified by name.
+	Custom(&'static str),
+}
+
+impl From<Protocol> for ProtocolKind
+{
+	fn from(p: Protocol) -> ProtocolKind { ProtocolKind::Known(p) }
+}
+
+impl fmt::Display for ProtocolKind
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self {
+			ProtocolKind::Known(p) => p.fmt(f),
+			ProtocolKind::Custom(name) => write!(f, "{}", name),
+		}
+	}
+}
@@ -0,0 +1,515 @@
+//! Topic-based publish/subscribe helpers.
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use crate::{
+	aio::{Aio, AioResult},
+	error::{Error, Result},
+	message::Message,
+	options::{
+		protocol::pubsub::{Subscribe, Unsubscribe},
+		Options,
+	},
+	pipe::PipeEvent,
+	protocol::Protocol,
+	socket::Socket,
+	topics::TopicMatcher,
+};
+
+/// The byte that separates the topic from the payload.
+const SEPARATOR: u8 = 0;
+
+/// Publishes messages on a _pub_ socket, tagged with a topic.
+///
+/// The _sub_ protocol filters messages by checking whether the start of the
+/// message body matches any of the subscribed byte strings. This is powerful
+/// but easy to get wrong: subscribing to `sensors/temp` also receives messages
+/// for `sensors/temperature`, and the subscriber has to know where the topic
+/// ends and the payload begins.
+///
+/// `Publisher` and `Subscriber` encapsulate those semantics by
+/// sending every message as the topic, a single null byte, and then the
+/// payload. Because of the separator, `Subscriber::subscribe` performs prefix
+/// matching on topics while `Subscriber::subscribe_exact` only matches a
+/// single topic. Topics may not contain null bytes.
+///
+/// With the `pubsub-serde` feature, payloads can also be serialized values
+/// using JSON.
+///
+/// As with any _pub_/_sub_ pair, delivery is best-effort: messages published
+/// before the subscriber connects, or while its receive buffer is full, are
+/// dropped.
+///
+/// ## Example
+///
+/// ```
+/// use nng::options::{Options, RecvBufferSize};
+/// use nng::{Publisher, Subscriber};
+///
+/// const ADDRESS: &str = "inproc://nng/pubsub/example";
+///
+/// let publisher = Publisher::new()?;
+/// publisher.socket().listen(ADDRESS)?;
+///
+/// // Buffer a few messages so that none are dropped before we read them.
+/// let subscriber = Subscriber::new()?;
+/// subscriber.socket().set_opt::<RecvBufferSize>(8)?;
+/// subscriber.subscribe("sensors/")?;
+/// subscriber.socket().dial(ADDRESS)?;
+/// # std::thread::sleep(std::time::Duration::from_millis(50));
+///
+/// publisher.publish("sensors/temp", b"21.5")?;
+/// publisher.publish("alerts/fire", b"none")?;
+/// publisher.publish("sensors/humidity", b"40")?;
+///
+/// let (topic, payload) = subscriber.recv()?;
+/// assert_eq!(topic, "sensors/temp");
+/// assert_eq!(&payload[..], b"21.5");
+///
+/// let (topic, _) = subscriber.recv()?;
+/// assert_eq!(topic, "sensors/humidity");
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Publisher
+{
+	/// The underlying _pub_ socket.
+	socket: Socket,
+}
+impl Publisher
+{
+	/// Creates a new publisher on a new _pub_ socket.
+	pub fn new() -> Result<Self>
+	{
+		Ok(Publisher { socket: Socket::new(Protocol::Pub0)? })
+	}
+
+	/// Returns the underlying socket, used for dialing, listening, and
+	/// setting options.
+	pub fn socket(&self) -> &Socket { &self.socket }
+
+	/// Publishes the payload under the given topic.
+	///
+	/// Returns `Error::InvalidInput` if the topic contains a null byte.
+	pub fn publish(&self, topic: &str, payload: &[u8]) -> Result<()>
+	{
+		let mut msg = encode_topic(topic, payload.len())?;
+		msg.push_back(payload)?;
+
+		self.socket.send(msg).map_err(|e| e.error)
+	}
+
+	/// Publishes a value under the given topic, encoded as JSON.
+	///
+	/// Returns `Error::InvalidInput` if the topic contains a null byte or if
+	/// the value can't be serialized.
+	#[cfg(feature = "pubsub-serde")]
+	pub fn publish_value<T: serde::Serialize>(&self, topic: &str, value: &T) -> Result<()>
+	{
+		let payload = serde_json::to_vec(value).map_err(|_| Error::InvalidInput)?;
+		self.publish(topic, &payload)
+	}
+}
+
+/// A publisher that replays the latest message of each topic to new
+/// subscribers.
+///
+/// Subscribers normally miss everything that was published before they
+/// connected. The cache retains the most recent message published on each
+/// topic and sends all of them again whenever a new subscriber connects, so a
+/// late subscriber immediately learns the current value of every topic it is
+/// subscribed to. Messages use the same encoding as `Publisher`, so they are
+/// received with a `Subscriber`.
+///
+/// The _pub_ protocol cannot send a message to a single peer, so the replay
+/// goes to every connected subscriber. Existing subscribers will see the
+/// retained values again, which is harmless as long as they only care about
+/// the latest value. The replay starts as soon as the connection is
+/// established, so subscribers should subscribe before dialing and have a
+/// receive buffer large enough for all of the retained topics. It is sent in
+/// the background, one message at a time, and a value published in the
+/// meantime replaces the one waiting to be replayed.
+///
+/// The cache takes over the pipe notification callback of the socket.
+///
+/// ## Example
+///
+/// ```
+/// use nng::options::{Options, RecvBufferSize};
+/// use nng::{LastValueCache, Protocol, Socket, Subscriber};
+///
+/// const ADDRESS: &str = "inproc://nng/pubsub/lvc";
+///
+/// let cache = LastValueCache::new(Socket::new(Protocol::Pub0)?)?;
+/// cache.socket().listen(ADDRESS)?;
+///
+/// // Nobody is listening yet, but the latest values are remembered.
+/// cache.publish("sensors/temp", b"20.0")?;
+/// cache.publish("sensors/temp", b"21.5")?;
+/// cache.publish("sensors/humidity", b"40")?;
+///
+/// let subscriber = Subscriber::new()?;
+/// subscriber.socket().set_opt::<RecvBufferSize>(8)?;
+/// subscriber.subscribe("sensors/")?;
+/// subscriber.socket().dial(ADDRESS)?;
+///
+/// let mut values: Vec<_> = (0..2)
+///     .map(|_| subscriber.recv().map(|(t, p)| (t, p.as_slice().to_vec())))
+///     .collect::<Result<_, _>>()?;
+/// values.sort();
+///
+/// assert_eq!(values, vec![
+///     ("sensors/humidity".to_string(), b"40".to_vec()),
+///     ("sensors/temp".to_string(), b"21.5".to_vec()),
+/// ]);
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct LastValueCache
+{
+	/// The underlying _pub_ socket.
+	socket: Socket,
+
+	/// The most recent message of each topic.
+	///
+	/// Published messages are sent while holding the lock, and the replay
+	/// only copies them while holding it, so that a replay can never send an
+	/// older value after a newer one.
+	retained: Arc<Mutex<HashMap<String, Message>>>,
+
+	/// The progress of the replay to new subscribers.
+	replay: Arc<Mutex<Replay>>,
+}
+impl LastValueCache
+{
+	/// Creates a new cache on top of the provided _pub_ socket.
+	///
+	/// Returns `Error::NotSupported` if the socket does not use the _pub_
+	/// protocol.
+	pub fn new(socket: Socket) -> Result<Self>
+	{
+		socket.require_protocol(Protocol::Pub0)?;
+
+		let retained = Arc::new(Mutex::new(HashMap::new()));
+		let replay = Arc::new(Mutex::new(Replay::default()));
+
+		// The callbacks are stored inside of the socket, so they can't hold on to
+		// a `Socket` without keeping it open forever. The raw handle is enough.
+		let handle = socket.handle();
+		let cb_retained = Arc::clone(&retained);
+		let cb_replay = Arc::clone(&replay);
+		let aio = Aio::new(move |aio, res| {
+			replay_next(&aio, &res, handle, &cb_retained, &cb_replay);
+		})?;
+
+		let cb_replay = Arc::clone(&replay);
+		socket.pipe_notify(move |_, ev| {
+			if ev == PipeEvent::AddPost {
+				request_replay(&aio, &cb_replay);
+			}
+		})?;
+
+		Ok(LastValueCache { socket, retained, replay })
+	}
+
+	/// Returns the underlying socket, used for dialing, listening, and
+	/// setting options.
+	pub fn socket(&self) -> &Socket { &self.socket }
+
+	/// Publishes the payload under the given topic and retains it as the
+	/// latest value of that topic.
+	///
+	/// Returns `Error::InvalidInput` if the topic contains a null byte.
+	pub fn publish(&self, topic: &str, payload: &[u8]) -> Result<()>
+	{
+		let mut msg = encode_topic(topic, payload.len())?;
+		msg.push_back(payload)?;
+		let copy = msg.try_clone()?;
+
+		// The older value waiting to be replayed is dropped before the new one is
+		// sent, as it would otherwise arrive after it.
+		let mut retained = self.retained.lock().unwrap();
+		self.replay.lock().unwrap().forget(topic);
+		self.socket.send(msg)?;
+		retained.insert(topic.to_owned(), copy);
+		drop(retained);
+
+		Ok(())
+	}
+
+	/// Stops retaining the latest value of the topic.
+	///
+	/// Returns whether or not there was a retained value.
+	pub fn forget(&self, topic: &str) -> bool
+	{
+		let mut retained = self.retained.lock().unwrap();
+		self.replay.lock().unwrap().forget(topic);
+		retained.remove(topic).is_some()
+	}
+}
+
+/// The progress of the replay of a `LastValueCache`.
+///
+/// The replay is sent by a single AIO, one message at a time, with the next
+/// one sent from the callback of the previous. That keeps the sends off of the
+/// thread running the pipe notification and, as the _pub_ protocol only takes
+/// a message once it has distributed the previous one, the messages are
+/// queued by the AIO instead of being dropped by a non-blocking send.
+#[derive(Debug, Default)]
+struct Replay
+{
+	/// The retained messages still to be sent, along with their topic.
+	pending: VecDeque<(String, Message)>,
+
+	/// Whether a subscriber connected since the retained messages were last
+	/// copied into `pending`.
+	requested: bool,
+
+	/// Whether the AIO is busy with the replay.
+	running: bool,
+}
+impl Replay
+{
+	/// Drops the message of the topic waiting to be replayed, if any.
+	fn forget(&mut self, topic: &str) { self.pending.retain(|(t, _)| t != topic); }
+}
+
+/// Asks for the retained messages to be replayed, starting the AIO if it is
+/// idle.
+fn request_replay(aio: &Aio, replay: &Mutex<Replay>)
+{
+	let mut state = replay.lock().unwrap();
+	state.requested = true;
+
+	// The sleep completes right away, and its callback starts the replay.
+	if !state.running {
+		match aio.sleep(Duration::from_millis(0)) {
+			Ok(()) => state.running = true,
+			Err(e) => log_error!("Unable to start replaying the retained messages ({})", e),
+		}
+	}
+}
+
+/// Sends the next message of the replay, from the callback of the AIO.
+fn replay_next(
+	aio: &Aio,
+	res: &AioResult,
+	handle: nng_sys::nng_socket,
+	retained: &Mutex<HashMap<String, Message>>,
+	replay: &Mutex<Replay>,
+)
+{
+	match res {
+		AioResult::SleepOk | AioResult::SendOk => {},
+		AioResult::SendErr(_, e) | AioResult::SendErrLost(e) if *e != Error::Closed => {
+			log_error!("Unable to replay a retained message ({})", e);
+		},
+
+		// The socket was closed or the AIO stopped, so there is nobody left to
+		// replay to.
+		_ => {
+			*replay.lock().unwrap() = Replay::default();
+			return;
+		},
+	}
+
+	let mut state = replay.lock().unwrap();
+	if state.requested {
+		state.requested = false;
+		drop(state);
+
+		// The retained messages are locked first, like `LastValueCache::publish`
+		// does, and only for as long as it takes to copy them.
+		let retained = retained.lock().unwrap();
+		state = replay.lock().unwrap();
+		state.pending = retained
+			.iter()
+			.filter_map(|(topic, msg)| match msg.try_clone() {
+				Ok(m) => Some((topic.clone(), m)),
+				Err(e) => {
+					log_error!("Unable to copy the retained message of \"{}\" ({})", topic, e);
+					None
+				},
+			})
+			.collect();
+		drop(retained);
+	}
+
+	// The send only starts the AIO, so holding the lock through it keeps a new
+	// value from being published in between taking the message and sending it.
+	while let Some((topic, msg)) = state.pending.pop_front() {
+		match aio.send_handle(handle, msg) {
+			Ok(()) => return,
+			Err(e) => log_error!("Unable to replay \"{}\" ({})", topic, e.error),
+		}
+	}
+	state.running = false;
+}
+
+/// Receives messages on a _sub_ socket, split into topic and payload.
+///
+/// A new subscriber receives nothing until it subscribes to at least one
+/// topic. See `Publisher` for how topics are encoded and matched.
+#[derive(Clone, Debug)]
+pub struct Subscriber
+{
+	/// The underlying _sub_ socket.
+	socket: Socket,
+}
+impl Subscriber
+{
+	/// Creates a new subscriber on a new _sub_ socket.
+	pub fn new() -> Result<Self>
+	{
+		Ok(Subscriber { socket: Socket::new(Protocol::Sub0)? })
+	}
+
+	/// Returns the underlying socket, used for dialing, listening, and
+	/// setting options.
+	pub fn socket(&self) -> &Socket { &self.socket }
+
+	/// Subscribes to all topics that start with the given prefix.
+	///
+	/// An empty prefix subscribes to every topic. Returns
+	/// `Error::InvalidInput` if the prefix contains a null byte.
+	pub fn subscribe(&self, prefix: &str) -> Result<()>
+	{
+		self.socket.set_opt::<Subscribe>(topic_bytes(prefix)?.to_vec())
+	}
+
+	/// Subscribes to exactly the given topic.
+	///
+	/// Returns `Error::InvalidInput` if the topic contains a null byte.
+	pub fn subscribe_exact(&self, topic: &str) -> Result<()>
+	{
+		self.socket.set_opt::<Subscribe>(encode_topic(topic, 0)?.as_slice().to_vec())
+	}
+
+	/// Subscribes to the prefixes of the patterns, as given by
+	/// `TopicMatcher::nng_prefixes`.
+	///
+	/// This lets through every topic the patterns match, along with some that
+	/// they don't, which `Subscriber::recv_matching` skips. Returns
+	/// `Error::InvalidInput` if a pattern contains a null byte, in which case
+	/// the prefixes before it stay subscribed.
+	pub fn subscribe_patterns(&self, matcher: &TopicMatcher) -> Result<()>
+	{
+		for prefix in matcher.nng_prefixes() {
+			if prefix.contains(&SEPARATOR) {
+				return Err(Error::InvalidInput);
+			}
+			self.socket.set_opt::<Subscribe>(prefix.clone())?;
+		}
+
+		Ok(())
+	}
+
+	/// Removes a subscription made with `Subscriber::subscribe`.
+	///
+	/// Returns `Error::EntryNotFound` if there was no such subscription.
+	pub fn unsubscribe(&self, prefix: &str) -> Result<()>
+	{
+		self.socket.set_opt::<Unsubscribe>(topic_bytes(prefix)?.to_vec())
+	}
+
+	/// Removes a subscription made with `Subscriber::subscribe_exact`.
+	///
+	/// Returns `Error::EntryNotFound` if there was no such subscription.
+	pub fn unsubscribe_exact(&self, topic: &str) -> Result<()>
+	{
+		self.socket.set_opt::<Unsubscribe>(encode_topic(topic, 0)?.as_slice().to_vec())
+	}
+
+	/// Receives the next message, returning its topic and payload.
+	///
+	/// Messages that were not sent by a `Publisher` result in
+	/// `Error::Protocol`.
+	pub fn recv(&self) -> Result<(String, Message)>
+	{
+		let mut msg = self.socket.recv()?;
+
+		let end = msg.as_slice().iter().position(|&b| b == SEPARATOR).ok_or(Error::Protocol)?;
+		let topic = String::from_utf8(msg.as_slice()[..end].to_vec()).map_err(|_| Error::Protocol)?;
+		msg.trim(end + 1);
+
+		Ok((topic, msg))
+	}
+
+	/// Receives the next message whose topic matches the patterns, returning
+	/// its topic and payload.
+	///
+	/// Messages on other topics are dropped, so this blocks until a matching
+	/// one arrives or receiving fails.
+	pub fn recv_matching(&self, matcher: &TopicMatcher) -> Result<(String, Message)>
+	{
+		loop {
+			let (topic, msg) = self.recv()?;
+			if matcher.matches(topic.as_bytes()) {
+				return Ok((topic, msg));
+			}
+		}
+	}
+
+	/// Receives the next message and decodes its payload from JSON.
+	///
+	/// Payloads that can't be decoded as `T` result in `Error::BadType`.
+	#[cfg(feature = "pubsub-serde")]
+	pub fn recv_value<T: serde::de::DeserializeOwned>(&self) -> Result<(String, T)>
+	{
+		let (topic, payload) = self.recv()?;
+		let value = serde_json::from_slice(payload.as_slice()).map_err(|_| Error::BadType)?;
+
+		Ok((topic, value))
+	}
+
+	/// Returns an iterator that blocks to receive each message.
+	///
+	/// The iterator never ends on its own. It keeps returning errors once the
+	/// socket has been closed, so callers usually stop at the first error.
+	pub fn iter(&self) -> SubscriberIter<'_> { SubscriberIter { subscriber: self } }
+}
+
+/// A blocking iterator over the messages of a `Subscriber`.
+///
+/// Created by `Subscriber::iter`.
+#[derive(Debug)]
+pub struct SubscriberIter<'a>
+{
+	/// The subscriber receiving the messages.
+	subscriber: &'a Subscriber,
+}
+impl Iterator for SubscriberIter<'_>
+{
+	type Item = Result<(String, Message)>;
+
+	fn next(&mut self) -> Option<Self::Item> { Some(self.subscriber.recv()) }
+}
+
+impl<'a> IntoIterator for &'a Subscriber
+{
+	type IntoIter = SubscriberIter<'a>;
+	type Item = Result<(String, Message)>;
+
+	fn into_iter(self) -> Self::IntoIter { self.iter() }
+}
+
+/// Validates the topic and returns its bytes.
+fn topic_bytes(topic: &str) -> Result<&[u8]>
+{
+	if topic.as_bytes().contains(&SEPARATOR) { Err(Error::InvalidInput) } else { Ok(topic.as_bytes()) }
+}
+
+/// Creates a message containing the topic and the separator, with room for
+/// `extra` more bytes.
+fn encode_topic(topic: &str, extra: usize) -> Result<Message>
+{
+	let bytes = topic_bytes(topic)?;
+	let mut msg = Message::with_capacity(bytes.len() + 1 + extra)?;
+	msg.push_back(bytes)?;
+	msg.push_back(&[SEPARATOR])?;
+
+	Ok(msg)
+}
@@ -0,0 +1,669 @@
+//! A send queue with backpressure for producers.
+use std::{
+	collections::VecDeque,
+	fmt,
+	sync::{Arc, Condvar, Mutex},
+	time::{Duration, Instant},
+};
+#[cfg(feature = "async-api")]
+use std::task::{self, Poll, Waker};
+
+use crate::{
+	aio::{Aio, AioResult},
+	error::{Error, Result, SendError, SendResult},
+	message::Message,
+	socket::Socket,
+};
+
+/// The callback run when a full queue has room again.
+type WritableFn = dyn Fn() + Send + Sync + 'static;
+
+/// The callback run with every message that could not be sent.
+type ErrorFn = dyn Fn(Message, Error) + Send + Sync + 'static;
+
+/// Queues messages for a socket and sends them one after the other, telling
+/// producers when to hold back.
+///
+/// Messages are pushed onto the queue without blocking and sent in order by
+/// an AIO of the queue, which always has one send in progress while there are
+/// messages waiting. The depth of the queue is the number of messages that
+/// were pushed but not taken by the socket yet, including the one being
+/// sent. Once it reaches the high watermark, the queue is full and refuses
+/// new messages until it has drained to the low watermark or below. That is
+/// when it becomes writable again: blocked `PushQueue::push_blocking` calls
+/// continue, the `PushQueue::on_writable` callback runs, and, with the
+/// `async-api` feature, the task waiting in `PushQueue::poll_ready` is woken.
+///
+/// This is meant for _push_ sockets, whose sends wait until a peer is ready
+/// for the message, but works with any socket that can send. The socket
+/// itself buffers up to `SendBufferSize` messages before that, which are not
+/// part of the depth.
+///
+/// A message the socket fails to send is handed to the `PushQueue::on_error`
+/// callback along with the error, or logged if there is none, and the queue
+/// carries on with the next one. If the socket is closed, the queue closes as
+/// well and hands every message it still holds to the callback with
+/// `Error::Closed`.
+///
+/// `PushQueue::shutdown` stops the queue, waiting for it to drain for as long
+/// as asked to and returning the messages that are left. Dropping the queue
+/// stops it right away and hands the messages that are left to the error
+/// callback with `Error::Canceled`.
+///
+/// ## Example
+///
+/// ```
+/// use std::{sync::{mpsc, Mutex}, time::Duration};
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/queue/example";
+///
+/// let push = Socket::new(Protocol::Push0)?;
+/// push.listen(ADDRESS)?;
+/// let queue = PushQueue::new(push, 4, 1)?;
+/// let (tx, writable) = mpsc::channel();
+/// let tx = Mutex::new(tx);
+/// queue.on_writable(move || tx.lock().unwrap().send(()).unwrap());
+///
+/// // Nobody is there to take the messages, so the queue fills up.
+/// for i in 0..4u8 {
+///     queue.try_push(&[i][..])?;
+/// }
+/// let err = queue.try_push(&[4][..]).unwrap_err();
+/// assert_eq!((err.error, &err.message[..]), (Error::TryAgain, &[4][..]));
+/// assert!(queue.is_full());
+/// assert!(writable.recv_timeout(Duration::from_millis(50)).is_err());
+///
+/// // Once a peer has taken all but one, there is room again.
+/// let pull = Socket::new(Protocol::Pull0)?;
+/// pull.dial(ADDRESS)?;
+/// for i in 0..3u8 {
+///     assert_eq!(&pull.recv()?[..], [i]);
+/// }
+/// writable.recv_timeout(Duration::from_secs(5)).expect("Queue didn't become writable");
+/// assert!(queue.len() <= 1 && !queue.is_full());
+///
+/// queue.try_push(&[4][..])?;
+/// for i in 3..5u8 {
+///     assert_eq!(&pull.recv()?[..], [i]);
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub struct PushQueue
+{
+	/// The state shared with the AIO callback.
+	shared: Arc<Shared>,
+
+	/// The AIO sending the messages.
+	aio: Aio,
+}
+
+impl PushQueue
+{
+	/// Creates a queue sending on the socket, which is full at
+	/// `high_watermark` messages and writable again at `low_watermark`.
+	///
+	/// Returns `Error::InvalidInput` unless the low watermark is below the
+	/// high one.
+	pub fn new(socket: Socket, high_watermark: usize, low_watermark: usize) -> Result<Self>
+	{
+		if low_watermark >= high_watermark {
+			return Err(Error::InvalidInput);
+		}
+
+		let shared = Arc::new(Shared {
+			socket,
+			high: high_watermark,
+			low: low_watermark,
+			state: Mutex::new(State {
+				queue: VecDeque::new(),
+				sending: false,
+				full: false,
+				stage: Stage::Open,
+				#[cfg(feature = "async-api")]
+				waker: None,
+			}),
+			changed: Condvar::new(),
+			callbacks: Mutex::new(Callbacks { writable: None, error: None }),
+		});
+
+		let cb_shared = Arc::clone(&shared);
+		let aio = Aio::new(move |aio, res| cb_shared.completed(&aio, res))?;
+
+		Ok(PushQueue { shared, aio })
+	}
+
+	/// Returns the socket the messages are sent on.
+	pub fn socket(&self) -> &Socket { &self.shared.socket }
+
+	/// Adds the message to the end of the queue without blocking.
+	///
+	/// The message is handed back along with `Error::TryAgain` if the queue
+	/// is full, or along with `Error::Closed` if the queue has been shut down
+	/// or its socket closed.
+	pub fn try_push<M: Into<Message>>(&self, msg: M) -> SendResult<()>
+	{
+		let msg = msg.into();
+		let mut state = self.shared.state.lock().unwrap();
+		if let Err(error) = state.admit() {
+			return Err(SendError { message: msg, error });
+		}
+
+		self.shared.enqueue(&mut state, msg);
+		drop(state);
+		self.shared.pump(&self.aio);
+		Ok(())
+	}
+
+	/// Adds the message to the end of the queue, waiting for the queue to
+	/// become writable if it is full.
+	///
+	/// This gives up after the timeout with `Error::TimedOut`, or right away
+	/// with `Error::Closed` if the queue has been shut down or its socket
+	/// closed, handing the message back in both cases. A timeout of `None`
+	/// waits forever.
+	///
+	/// ## Example
+	///
+	/// Messages pushed by a single producer are sent in order, however often
+	/// it has to wait:
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/queue/push_blocking";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// let queue = PushQueue::new(push.clone(), 8, 2)?;
+	/// let producer = thread::spawn(move || -> Result<()> {
+	///     for i in 0..2000u32 {
+	///         queue.push_blocking(&i.to_be_bytes()[..], Some(Duration::from_secs(5)))?;
+	///     }
+	///     // Dropping the queue would discard the messages it still holds.
+	///     assert!(queue.shutdown(None).is_empty());
+	///     Ok(())
+	/// });
+	///
+	/// for i in 0..2000u32 {
+	///     assert_eq!(&pull.recv()?[..], i.to_be_bytes());
+	/// }
+	/// producer.join().unwrap()?;
+	///
+	/// // Without a peer, the queue never drains.
+	/// let lonely = PushQueue::new(Socket::new(Protocol::Push0)?, 1, 0)?;
+	/// lonely.push_blocking(&b"stuck"[..], None)?;
+	/// let err = lonely.push_blocking(&b"waiting"[..], Some(Duration::from_millis(20))).unwrap_err();
+	/// assert_eq!((err.error, &err.message[..]), (Error::TimedOut, &b"waiting"[..]));
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn push_blocking<M: Into<Message>>(
+		&self,
+		msg: M,
+		timeout: Option<Duration>,
+	) -> SendResult<()>
+	{
+		let msg = msg.into();
+		let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+		let mut state = self.shared.state.lock().unwrap();
+		loop {
+			let error = match state.admit() {
+				Ok(()) => break,
+				Err(Error::TryAgain) => Error::TimedOut,
+				Err(e) => return Err(SendError { message: msg, error: e }),
+			};
+
+			let now = Instant::now();
+			state = match deadline {
+				Some(d) if d <= now => return Err(SendError { message: msg, error }),
+				Some(d) => self.shared.changed.wait_timeout(state, d - now).unwrap().0,
+				None => self.shared.changed.wait(state).unwrap(),
+			};
+		}
+
+		self.shared.enqueue(&mut state, msg);
+		drop(state);
+		self.shared.pump(&self.aio);
+		Ok(())
+	}
+
+	/// Sets the callback that runs whenever the queue becomes writable again
+	/// after it was full, replacing any earlier one.
+	///
+	/// The callback runs on the thread of an NNG callback, so it should only
+	/// notify a producer rather than push messages itself.
+	pub fn on_writable<F>(&self, callback: F)
+	where
+		F: Fn() + Send + Sync + 'static,
+	{
+		self.shared.callbacks.lock().unwrap().writable = Some(Arc::new(callback));
+	}
+
+	/// Sets the callback that receives every message the queue could not
+	/// send along with the error, replacing any earlier one.
+	///
+	/// Without a callback, the errors are logged and the messages dropped. The
+	/// callback runs on the thread of an NNG callback or of the producer whose
+	/// push started the failed send.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{sync::{Arc, Mutex}, thread, time::Duration};
+	/// use nng::*;
+	///
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// let queue = PushQueue::new(push.clone(), 8, 4)?;
+	/// let failed = Arc::new(Mutex::new(Vec::new()));
+	/// let cb_failed = Arc::clone(&failed);
+	/// queue.on_error(move |msg, e| cb_failed.lock().unwrap().push((msg, e)));
+	///
+	/// for body in &[&b"first"[..], b"second", b"third"] {
+	///     queue.try_push(*body)?;
+	/// }
+	///
+	/// // Closing the socket fails every message that is still queued.
+	/// push.close();
+	/// # for _ in 0..500 { if failed.lock().unwrap().len() == 3 { break; } thread::sleep(Duration::from_millis(10)); }
+	/// let failed = failed.lock().unwrap();
+	/// let bodies: Vec<_> = failed.iter().map(|(msg, e)| (&msg[..], *e)).collect();
+	/// assert_eq!(bodies, [
+	///     (&b"first"[..], Error::Closed),
+	///     (b"second", Error::Closed),
+	///     (b"third", Error::Closed),
+	/// ]);
+	/// assert_eq!(queue.try_push(&b"late"[..]).unwrap_err().error, Error::Closed);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn on_error<F>(&self, callback: F)
+	where
+		F: Fn(Message, Error) + Send + Sync + 'static,
+	{
+		self.shared.callbacks.lock().unwrap().error = Some(Arc::new(callback));
+	}
+
+	/// Checks whether the queue can take a message, registering the task to
+	/// be woken once it can if it is full.
+	///
+	/// This returns `Poll::Ready(Ok(()))` when a push would be accepted and
+	/// `Poll::Ready(Err(Error::Closed))` once the queue has been shut down or
+	/// its socket closed. Only the task that polled last is woken. This is
+	/// only available with the `async-api` feature.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// # #[cfg(feature = "async-api")] {
+	/// use std::future::poll_fn;
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/queue/poll_ready";
+	///
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.listen(ADDRESS)?;
+	/// let queue = PushQueue::new(push, 2, 0)?;
+	///
+	/// let received = std::thread::spawn(|| -> Result<Vec<Message>> {
+	///     let pull = Socket::new(Protocol::Pull0)?;
+	///     pull.dial(ADDRESS)?;
+	///     (0..10).map(|_| pull.recv()).collect()
+	/// });
+	///
+	/// async_std::task::block_on(async {
+	///     for i in 0..10u8 {
+	///         poll_fn(|cx| queue.poll_ready(cx)).await?;
+	///         queue.try_push(&[i][..])?;
+	///     }
+	///     Ok::<(), Error>(())
+	/// })?;
+	///
+	/// let received = received.join().unwrap()?;
+	/// assert!(received.iter().enumerate().all(|(i, msg)| msg[..] == [i as u8]));
+	/// # }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	#[cfg(feature = "async-api")]
+	pub fn poll_ready(&self, cx: &mut task::Context<'_>) -> Poll<Result<()>>
+	{
+		let mut state = self.shared.state.lock().unwrap();
+		match state.admit() {
+			Err(Error::TryAgain) => {
+				match &state.waker {
+					Some(w) if w.will_wake(cx.waker()) => {},
+					_ => state.waker = Some(cx.waker().clone()),
+				}
+				Poll::Pending
+			},
+			res => Poll::Ready(res),
+		}
+	}
+
+	/// Returns the depth of the queue, which includes the message being sent.
+	pub fn len(&self) -> usize { self.shared.state.lock().unwrap().depth() }
+
+	/// Returns whether the queue holds no messages.
+	pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+	/// Returns whether the queue is full and refuses new messages.
+	pub fn is_full(&self) -> bool { self.shared.state.lock().unwrap().full }
+
+	/// Stops the queue and returns the messages that were not sent, in order.
+	///
+	/// The queue refuses new messages right away and keeps sending the ones
+	/// it holds until it is empty or the timeout expires. The send in
+	/// progress is then canceled and its message, if the socket didn't take it
+	/// in the meantime, returned first. A timeout of `None` drains the queue
+	/// completely, blocking for as long as that takes, while a timeout of zero
+	/// returns every message without waiting. If the socket was closed, the
+	/// messages have already been handed to the error callback and nothing is
+	/// returned.
+	///
+	/// Messages the socket has taken may still wait in its buffers, and closing
+	/// the socket discards them. The queue closes its handle to the socket, so
+	/// another handle has to keep the socket open until they are delivered.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/queue/shutdown";
+	///
+	/// // Without a peer, nothing is sent and everything comes back.
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.listen(ADDRESS)?;
+	/// let queue = PushQueue::new(push.clone(), 8, 4)?;
+	/// for body in &[&b"one"[..], b"two", b"three"] {
+	///     queue.try_push(*body)?;
+	/// }
+	/// let left = queue.shutdown(Some(Duration::from_secs(0)));
+	/// let bodies: Vec<_> = left.iter().map(|msg| &msg[..]).collect();
+	/// assert_eq!(bodies, [&b"one"[..], b"two", b"three"]);
+	///
+	/// // With one, draining sends everything before returning.
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.dial(ADDRESS)?;
+	/// let queue = PushQueue::new(push.clone(), 8, 4)?;
+	/// for msg in left {
+	///     queue.try_push(msg)?;
+	/// }
+	/// assert!(queue.shutdown(None).is_empty());
+	/// for body in &[&b"one"[..], b"two", b"three"] {
+	///     assert_eq!(&pull.recv()?[..], *body);
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn shutdown(self, timeout: Option<Duration>) -> Vec<Message> { self.finish(timeout) }
+
+	/// Stops the queue after waiting for it to drain, returning the messages
+	/// that are left.
+	///
+	/// Once stopped, this returns right away with nothing.
+	fn finish(&self, timeout: Option<Duration>) -> Vec<Message>
+	{
+		let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+		let mut state = self.shared.state.lock().unwrap();
+		if state.stage == Stage::Open {
+			state.stage = Stage::Draining;
+
+			// Blocked producers and waiting tasks have to learn that it's closed.
+			self.shared.changed.notify_all();
+			#[cfg(feature = "async-api")]
+			{
+				if let Some(waker) = state.waker.take() {
+					waker.wake();
+				}
+			}
+		}
+
+		while state.stage == Stage::Draining && state.depth() > 0 {
+			let now = Instant::now();
+			state = match deadline {
+				Some(d) if d <= now => break,
+				Some(d) => self.shared.changed.wait_timeout(state, d - now).unwrap().0,
+				None => self.shared.changed.wait(state).unwrap(),
+			};
+		}
+		if state.stage == Stage::Draining {
+			state.stage = Stage::Stopping;
+		}
+
+		// The callback may run right away once canceled, so the lock has to go.
+		drop(state);
+		self.aio.cancel();
+		let mut state = self.shared.state.lock().unwrap();
+		while state.sending {
+			state = self.shared.changed.wait(state).unwrap();
+		}
+		drop(state);
+		self.aio.stop();
+
+		self.shared.state.lock().unwrap().queue.drain(..).collect()
+	}
+}
+
+impl fmt::Debug for PushQueue
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let state = self.shared.state.lock().unwrap();
+		f.debug_struct("PushQueue")
+			.field("high_watermark", &self.shared.high)
+			.field("low_watermark", &self.shared.low)
+			.field("len", &state.depth())
+			.field("full", &state.full)
+			.finish()
+	}
+}
+
+impl Drop for PushQueue
+{
+	fn drop(&mut self)
+	{
+		let left = self.finish(Some(Duration::from_secs(0)));
+		if left.is_empty() {
+			return;
+		}
+
+		let callback = self.shared.callbacks.lock().unwrap().error.clone();
+		match callback {
+			Some(cb) => left.into_iter().for_each(|msg| cb(msg, Error::Canceled)),
+			None => log_warn!("Dropping {} messages left in a push queue", left.len()),
+		}
+	}
+}
+
+/// The state shared between a `PushQueue` and its AIO.
+struct Shared
+{
+	/// The socket the messages are sent on.
+	socket: Socket,
+
+	/// The depth at which the queue is full.
+	high: usize,
+
+	/// The depth at which a full queue is writable again.
+	low: usize,
+
+	/// The messages and the progress of sending them.
+	state: Mutex<State>,
+
+	/// Signalled whenever a send completes or the queue stops.
+	changed: Condvar,
+
+	/// The callbacks of the application.
+	callbacks: Mutex<Callbacks>,
+}
+
+impl Shared
+{
+	/// Adds the message to the end of the queue, which must be open and not
+	/// full.
+	fn enqueue(&self, state: &mut State, msg: Message)
+	{
+		state.queue.push_back(msg);
+		if state.depth() >= self.high {
+			state.full = true;
+		}
+	}
+
+	/// Starts sending the next message, unless a send is already running.
+	///
+	/// Sends are started without holding the lock, as the protocol may
+	/// complete them right away on this thread.
+	fn pump(&self, aio: &Aio)
+	{
+		loop {
+			let msg = {
+				let mut state = self.state.lock().unwrap();
+				if state.sending || !matches!(state.stage, Stage::Open | Stage::Draining) {
+					return;
+				}
+
+				match state.queue.pop_front() {
+					Some(msg) => {
+						state.sending = true;
+						msg
+					},
+					None => return,
+				}
+			};
+
+			match self.socket.send_async(aio, msg) {
+				Ok(()) => return,
+				Err(SendError { message, error }) => self.settle(AioResult::SendErr(message, error)),
+			}
+		}
+	}
+
+	/// Handles the completion of a send and starts the next one.
+	fn completed(&self, aio: &Aio, res: AioResult)
+	{
+		self.settle(res);
+		self.pump(aio);
+	}
+
+	/// Records the result of a send and notifies whoever waits for it.
+	fn settle(&self, res: AioResult)
+	{
+		let mut failed = Vec::new();
+		let mut state = self.state.lock().unwrap();
+		state.sending = false;
+		match res {
+			AioResult::SendOk => {},
+
+			// Canceled sends go back to the front of the line.
+			AioResult::SendErr(msg, _) if state.stage == Stage::Stopping => {
+				state.queue.push_front(msg);
+			},
+			AioResult::SendErr(msg, Error::Closed) => {
+				state.stage = Stage::Closed;
+				failed.push((msg, Error::Closed));
+				failed.extend(state.queue.drain(..).map(|msg| (msg, Error::Closed)));
+			},
+			AioResult::SendErr(msg, e) => failed.push((msg, e)),
+			AioResult::SendErrLost(e) => log_error!("Queued message was lost ({})", e),
+			res => unreachable!("Push queue AIO completed as {:?}", res),
+		}
+
+		let writable = state.full && state.depth() <= self.low;
+		if writable {
+			state.full = false;
+		}
+		#[cfg(feature = "async-api")]
+		let waker = if writable || state.stage == Stage::Closed { state.waker.take() } else { None };
+		self.changed.notify_all();
+		drop(state);
+
+		let callbacks = self.callbacks.lock().unwrap();
+		let (on_writable, on_error) = (callbacks.writable.clone(), callbacks.error.clone());
+		drop(callbacks);
+
+		for (msg, e) in failed {
+			match &on_error {
+				Some(cb) => cb(msg, e),
+				None => log_error!("Unable to send a queued message ({})", e),
+			}
+		}
+		if let (true, Some(cb)) = (writable, on_writable) {
+			cb();
+		}
+		#[cfg(feature = "async-api")]
+		{
+			if let Some(waker) = waker {
+				waker.wake();
+			}
+		}
+	}
+}
+
+/// The messages of a `PushQueue` and the progress of sending them.
+struct State
+{
+	/// The messages waiting to be sent, in order.
+	queue: VecDeque<Message>,
+
+	/// Whether a message is being sent.
+	sending: bool,
+
+	/// Whether the queue reached the high watermark and has not drained to
+	/// the low one since.
+	full: bool,
+
+	/// Whether the queue is still running.
+	stage: Stage,
+
+	/// The task that last found the queue full.
+	#[cfg(feature = "async-api")]
+	waker: Option<Waker>,
+}
+
+impl State
+{
+	/// Returns the number of messages the socket has yet to take.
+	fn depth(&self) -> usize { self.queue.len() + usize::from(self.sending) }
+
+	/// Checks that a message can be pushed.
+	fn admit(&self) -> Result<()>
+	{
+		match self.stage {
+			Stage::Open if self.full => Err(Error::TryAgain),
+			Stage::Open => Ok(()),
+			_ => Err(Error::Closed),
+		}
+	}
+}
+
+/// How far a `PushQueue` is in shutting down.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Stage
+{
+	/// Messages are accepted and sent.
+	Open,
+
+	/// No more messages are accepted, but the queued ones are still sent.
+	Draining,
+
+	/// Nothing more is sent and the queued messages are kept until they are
+	/// returned.
+	Stopping,
+
+	/// The socket was closed and the queued messages handed to the error
+	/// callback.
+	Closed,
+}
+
+/// The callbacks set on a `PushQueue`.
+struct Callbacks
+{
+	/// The callback run when a full queue has room again.
+	writable: Option<Arc<WritableFn>>,
+
+	/// The callback run with every message that could not be sent.
+	error: Option<Arc<ErrorFn>>,
+}
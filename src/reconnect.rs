@@ -0,0 +1,307 @@
+//! Dialers with an application-defined reconnect schedule.
+use std::{
+	fmt,
+	sync::{Arc, Condvar, Mutex},
+	thread::{self, JoinHandle},
+	time::{Duration, Instant},
+};
+
+use crate::{
+	dialer::{Dialer, DialerOptions},
+	error::{Error, Result},
+	options::{Options, ReconnectMaxTime, ReconnectMinTime},
+	pipe::{Pipe, PipeEvent},
+	socket::{PipeNotifyFn, Socket},
+};
+
+/// How long NNG is told to wait before reconnecting on its own.
+///
+/// Every dialer is closed and replaced as soon as its connection is lost, so
+/// this only needs to be long enough for that to happen first.
+const NNG_RECONNECT_TIME: Duration = Duration::from_secs(3600);
+
+/// A dialer that asks the application how long to wait before reconnecting.
+///
+/// NNG reconnects with an exponential backoff between `ReconnectMinTime` and
+/// `ReconnectMaxTime`, which can't express schedules such as decorrelated
+/// jitter or giving up on a peer for a while after many failures. This dialer
+/// instead calls the backoff hook before every reconnect attempt with the
+/// number of the attempt and the error that caused it, and waits for the
+/// returned delay. The attempt number starts at one and goes back to one once
+/// a connection has been established. The error is `Error::ConnectionReset`
+/// when an established connection was lost and the error of the failed dial
+/// otherwise. The very first attempt is made right away.
+///
+/// Each attempt is a blocking dial on a background thread, made with a new
+/// dialer that is closed again once its connection is lost. Any
+/// `ReconnectMinTime` and `ReconnectMaxTime` set on the socket are therefore
+/// ignored, as NNG never gets to reconnect these dialers itself. Lost
+/// connections are noticed through the pipe events of the socket, which works
+/// alongside any callback set with `Socket::pipe_notify`.
+///
+/// The dialer stops when the socket is closed, or when it is closed or
+/// dropped itself, which also closes its connection. It holds a handle to the
+/// socket until then, so dropping every other handle does not close the
+/// socket.
+///
+/// ## Example
+///
+/// ```
+/// use std::{sync::{mpsc, Arc, Mutex}, thread, time::Duration};
+/// use nng::*;
+///
+/// const ADDRESS: &str = "inproc://nng/reconnect/example";
+///
+/// let client = Socket::new(Protocol::Push0)?;
+/// let attempts = Arc::new(Mutex::new(Vec::new()));
+/// let hook_attempts = Arc::clone(&attempts);
+/// let dialer = ReconnectingDialer::new(&client, ADDRESS, move |attempt, err| {
+///     hook_attempts.lock().unwrap().push((attempt, err));
+///     Duration::from_millis(10) * attempt
+/// })?;
+///
+/// // Nobody is listening yet, so the first few attempts fail.
+/// thread::sleep(Duration::from_millis(50));
+/// let server = Socket::new(Protocol::Pull0)?;
+/// let (tx, connected) = mpsc::channel();
+/// let tx = Mutex::new(tx);
+/// server.pipe_notify(move |_, ev| if ev == PipeEvent::AddPost {
+///     tx.lock().unwrap().send(()).unwrap();
+/// })?;
+/// let listener = Listener::new(&server, ADDRESS, false)?;
+///
+/// connected.recv_timeout(Duration::from_secs(1)).unwrap();
+/// assert_eq!(attempts.lock().unwrap()[0], (1, Error::ConnectionRefused));
+///
+/// // Losing the connection starts a new round of attempts.
+/// attempts.lock().unwrap().clear();
+/// listener.close()?;
+/// Listener::new(&server, ADDRESS, false)?;
+///
+/// connected.recv_timeout(Duration::from_secs(1)).unwrap();
+/// assert_eq!(attempts.lock().unwrap()[0], (1, Error::ConnectionReset));
+///
+/// client.send(&b"hello"[..])?;
+/// assert_eq!(&server.recv()?[..], b"hello");
+///
+/// dialer.close();
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub struct ReconnectingDialer
+{
+	/// The state shared with the background thread and the pipe watcher.
+	shared: Arc<Shared>,
+
+	/// The pipe watcher, which is unregistered once this is dropped.
+	_watcher: Arc<PipeNotifyFn>,
+
+	/// The background thread making the connection attempts.
+	thread: Option<JoinHandle<()>>,
+}
+
+impl ReconnectingDialer
+{
+	/// Starts dialing the URL, using the hook to schedule reconnects.
+	///
+	/// The hook is called on a background thread. Errors in the URL are only
+	/// detected by the first attempt and are passed to the hook like any other
+	/// failure to dial.
+	pub fn new<F>(socket: &Socket, url: &str, backoff: F) -> Result<Self>
+	where
+		F: FnMut(u32, Error) -> Duration + Send + 'static,
+	{
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State { closed: false, dialer: None, pipe: None, lost: false }),
+			cond:  Condvar::new(),
+		});
+
+		let watcher_shared = Arc::clone(&shared);
+		let watcher: Arc<PipeNotifyFn> =
+			Arc::new(move |pipe, ev| watcher_shared.pipe_event(pipe, ev));
+		socket.watch_pipes(&watcher)?;
+
+		let thread_shared = Arc::clone(&shared);
+		let socket = socket.clone();
+		let url = url.to_string();
+		let thread = thread::spawn(move || thread_shared.run(&socket, &url, backoff));
+
+		Ok(ReconnectingDialer { shared, _watcher: watcher, thread: Some(thread) })
+	}
+
+	/// Stops reconnecting and closes the current connection, if any.
+	///
+	/// This is the same as dropping the dialer and waits for the background
+	/// thread to finish, including any call to the hook in progress.
+	pub fn close(self) {}
+
+	/// Returns the dialer of the current connection attempt or connection.
+	pub fn dialer(&self) -> Option<Dialer> { self.shared.state.lock().unwrap().dialer.clone() }
+}
+
+impl fmt::Debug for ReconnectingDialer
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("ReconnectingDialer").field("dialer", &self.dialer()).finish()
+	}
+}
+
+impl Drop for ReconnectingDialer
+{
+	fn drop(&mut self)
+	{
+		let dialer = {
+			let mut state = self.shared.state.lock().unwrap();
+			state.closed = true;
+			state.dialer.clone()
+		};
+		self.shared.cond.notify_all();
+
+		// Closing the dialer is what ends a blocking dial, so it can't wait for the
+		// thread. The thread closes it again once it notices, which is harmless.
+		if let Some(d) = dialer {
+			let _ = d.close();
+		}
+
+		if let Some(thread) = self.thread.take() {
+			if thread.join().is_err() {
+				log_error!("Reconnect hook panicked");
+			}
+		}
+	}
+}
+
+/// The state shared between a `ReconnectingDialer` and its thread.
+struct Shared
+{
+	/// The mutable state.
+	state: Mutex<State>,
+
+	/// Signalled when the dialer is closed or its connection is lost.
+	cond: Condvar,
+}
+
+impl Shared
+{
+	/// The body of the background thread.
+	fn run<F>(&self, socket: &Socket, url: &str, mut backoff: F)
+	where
+		F: FnMut(u32, Error) -> Duration,
+	{
+		let mut attempt = 0;
+		loop {
+			let err = match self.connect(socket, url) {
+				Ok(dialer) => {
+					attempt = 0;
+					self.wait_for_loss();
+					let _ = dialer.close();
+					Error::ConnectionReset
+				},
+				Err(e) => e,
+			};
+
+			{
+				let mut state = self.state.lock().unwrap();
+				state.dialer = None;
+				state.pipe = None;
+				state.lost = false;
+			}
+
+			// Either this dialer or the socket has been closed, so there is nothing left
+			// to connect.
+			if err == Error::Closed || self.state.lock().unwrap().closed {
+				return;
+			}
+
+			attempt += 1;
+			let delay = backoff(attempt, err);
+			if self.sleep(delay) {
+				return;
+			}
+		}
+	}
+
+	/// Makes a single, blocking connection attempt.
+	fn connect(&self, socket: &Socket, url: &str) -> Result<Dialer>
+	{
+		let options = DialerOptions::new(socket, url)?;
+		options.set_opt::<ReconnectMinTime>(Some(NNG_RECONNECT_TIME))?;
+		options.set_opt::<ReconnectMaxTime>(Some(NNG_RECONNECT_TIME))?;
+
+		// The dialer has to be known before it starts, as its pipe may be added before
+		// the start returns.
+		{
+			let mut state = self.state.lock().unwrap();
+			if state.closed {
+				return Err(Error::Closed);
+			}
+			state.dialer = Some(options.to_dialer());
+		}
+
+		options.start(false).map_err(|(_, e)| e)
+	}
+
+	/// Waits until the connection of the current dialer is lost or the dialer
+	/// is closed.
+	fn wait_for_loss(&self)
+	{
+		let state = self.state.lock().unwrap();
+		drop(self.cond.wait_while(state, |s| !s.lost && !s.closed).unwrap());
+	}
+
+	/// Waits for the given duration, returning `true` if the dialer was closed
+	/// in the meantime.
+	fn sleep(&self, delay: Duration) -> bool
+	{
+		// A delay too long for an `Instant` lasts until the dialer is closed.
+		let deadline = Instant::now().checked_add(delay);
+		let mut state = self.state.lock().unwrap();
+		while !state.closed {
+			let now = Instant::now();
+			state = match deadline {
+				Some(d) if d <= now => return false,
+				Some(d) => self.cond.wait_timeout(state, d - now).unwrap().0,
+				None => self.cond.wait(state).unwrap(),
+			};
+		}
+		drop(state);
+
+		true
+	}
+
+	/// Follows the pipes of the current dialer.
+	fn pipe_event(&self, pipe: Pipe, ev: PipeEvent)
+	{
+		let mut state = self.state.lock().unwrap();
+		match ev {
+			// The dialer of a pipe can no longer be looked up once it has been removed,
+			// so the pipe has to be remembered while it is being added. A pipe that is
+			// closed before it was fully added gets no `AddPost` but is still removed.
+			PipeEvent::AddPre if state.dialer.is_some() && pipe.dialer() == state.dialer => {
+				state.pipe = Some(pipe);
+			},
+			PipeEvent::RemovePost if state.pipe == Some(pipe) => {
+				state.lost = true;
+				drop(state);
+				self.cond.notify_all();
+			},
+			_ => {},
+		}
+	}
+}
+
+/// The mutable state of a `ReconnectingDialer`.
+struct State
+{
+	/// Whether the dialer has been closed.
+	closed: bool,
+
+	/// The dialer of the current attempt or connection.
+	dialer: Option<Dialer>,
+
+	/// The pipe of the current connection.
+	pipe: Option<Pipe>,
+
+	/// Whether the pipe of the current connection has been removed.
+	lost: bool,
+}
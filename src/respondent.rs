@@ -0,0 +1,166 @@
+//! Asynchronous workers for answering surveys.
+use crate::{
+	aio::{Aio, AioResult},
+	ctx::Context,
+	error::{Error, Result},
+	message::Message,
+	protocol::Protocol,
+	socket::Socket,
+};
+
+/// An asynchronous worker that answers surveys on a _respondent_ socket.
+///
+/// The _respondent_ protocol only allows one outstanding survey per context.
+/// Starting a new receive before replying to the previous survey resets the
+/// state machine and the reply is silently discarded. This worker drives an
+/// AIO through the strict `receive -> (optional) send -> receive` cycle so
+/// that can never happen.
+///
+/// Each received survey is passed to the handler. Returning `Some` sends the
+/// message as the response, while returning `None` abstains from the survey
+/// and the worker immediately waits for the next one. Multiple workers can be
+/// created on the same socket to answer surveys concurrently.
+///
+/// ## Late Responses
+///
+/// The surveyor discards any response that arrives after its survey has
+/// expired, so a slow handler cannot tell whether its response was counted.
+/// If NNG rejects a response with `Error::IncorrectState`, which happens when
+/// there is no longer a survey for it to answer, the worker logs a warning and
+/// goes back to receiving instead of shutting down.
+///
+/// ## Panicking
+///
+/// The handler is run from within an AIO callback, so the same rules apply as
+/// to `Aio::new`: if the handler panics, the program aborts.
+///
+/// ## Example
+///
+/// ```
+/// use std::{thread, time::Duration};
+/// use nng::{options::{Options, protocol::survey::SurveyTime}, *};
+///
+/// const ADDRESS: &str = "inproc://nng/respondent/example";
+///
+/// let surveyor = Socket::new(Protocol::Surveyor0)?;
+/// surveyor.set_opt::<SurveyTime>(Some(Duration::from_millis(200)))?;
+/// surveyor.listen(ADDRESS)?;
+///
+/// let mut workers = Vec::new();
+/// for &name in &["normal", "abstain", "slow"] {
+///     let socket = Socket::new(Protocol::Respondent0)?;
+///     socket.dial(ADDRESS)?;
+///
+///     let worker = RespondentWorker::new(&socket, move |_survey| match name {
+///         "normal" => Some(Message::from(name.as_bytes())),
+///         "slow" => {
+///             // Answer after the survey has expired.
+///             thread::sleep(Duration::from_millis(400));
+///             Some(Message::from(name.as_bytes()))
+///         },
+///         _ => None,
+///     })?;
+///
+///     workers.push((socket, worker));
+/// }
+///
+/// // Give the respondents time to connect, as surveys are only sent to
+/// // connected peers.
+/// thread::sleep(Duration::from_millis(100));
+///
+/// for _ in 0..3 {
+///     surveyor.send(&b"who is there?"[..])?;
+///
+///     let mut responses = Vec::new();
+///     loop {
+///         match surveyor.recv() {
+///             Ok(m) => responses.push(String::from_utf8_lossy(&m).into_owned()),
+///             Err(Error::TimedOut) => break,
+///             Err(e) => return Err(e),
+///         }
+///     }
+///
+///     assert_eq!(responses, ["normal"]);
+/// }
+///
+/// for (_, worker) in workers {
+///     worker.shutdown();
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct RespondentWorker
+{
+	/// The AIO driving the worker.
+	aio: Aio,
+
+	/// The context the surveys are received on.
+	ctx: Context,
+}
+impl RespondentWorker
+{
+	/// Creates a new worker on the socket and starts waiting for surveys.
+	///
+	/// Returns `Error::NotSupported` if the socket does not use the
+	/// _respondent_ protocol.
+	pub fn new<F>(socket: &Socket, handler: F) -> Result<Self>
+	where
+		F: Fn(Message) -> Option<Message> + Sync + Send + 'static,
+	{
+		socket.require_protocol(Protocol::Respondent0)?;
+
+		let ctx = Context::new(socket)?;
+		let aio = Aio::for_context(&ctx, move |aio, ctx, res| {
+			RespondentWorker::callback(&aio, ctx, &handler, res)
+		})?;
+
+		ctx.recv(&aio)?;
+		Ok(RespondentWorker { aio, ctx })
+	}
+
+	/// Stops the worker and closes its context.
+	///
+	/// This blocks until the handler has returned, if it is running. The
+	/// socket is not closed. This is the same as dropping the worker, but more
+	/// explicit.
+	pub fn shutdown(self) { drop(self) }
+
+	/// Advances the state machine of the worker.
+	fn callback<F>(aio: &Aio, ctx: &Context, handler: &F, res: AioResult)
+	where
+		F: Fn(Message) -> Option<Message>,
+	{
+		// Starting an operation only fails once the worker is shutting down, in
+		// which case there is nothing left to do. Any other case is logged.
+		let res = match res {
+			AioResult::RecvOk(survey) => match handler(survey) {
+				Some(response) => ctx.send(aio, response).map_err(|e| e.error),
+				None => ctx.recv(aio),
+			},
+			AioResult::SendOk | AioResult::RecvErr(Error::TimedOut) => ctx.recv(aio),
+
+			AioResult::SendErr(_, Error::IncorrectState) | AioResult::SendErrLost(Error::IncorrectState) => {
+				log_warn!("Survey response was rejected as the survey is no longer active");
+				ctx.recv(aio)
+			},
+
+			AioResult::SendErr(_, e) | AioResult::SendErrLost(e) | AioResult::RecvErr(e) => Err(e),
+			AioResult::SleepOk | AioResult::SleepErr(_) => unreachable!("Respondent worker never sleeps"),
+		};
+
+		match res {
+			Ok(()) | Err(Error::Closed) | Err(Error::Canceled) => {},
+			Err(e) => log_error!("Respondent worker stopped due to an error: {}", e),
+		}
+	}
+}
+
+impl Drop for RespondentWorker
+{
+	fn drop(&mut self)
+	{
+		// Same order as `AioGroup`: nothing may be using the context when it closes.
+		self.aio.stop();
+		self.ctx.close();
+	}
+}
@@ -0,0 +1,157 @@
+//! Cooperative shutdown signaling.
+use std::{
+	mem,
+	sync::{Arc, Condvar, Mutex},
+	time::{Duration, Instant},
+};
+
+use crate::aio::Aio;
+
+/// A token used to signal that the application is shutting down.
+///
+/// The token can be cloned freely and all clones refer to the same signal.
+/// Once triggered, the token stays triggered. This is intended to be used as
+/// the glue between a process-level signal (such as Ctrl-C) and the loops that
+/// drive sockets and AIO objects, allowing them to finish in-flight work before
+/// the sockets are closed.
+///
+/// Blocking operations can be tied to the token using functions such as
+/// `Socket::recv_cancellable`, which return `Error::Canceled` once the token is
+/// triggered.
+///
+/// Note that triggering the token is not async-signal-safe. Signal handling
+/// crates that run the handler on a regular thread (such as `ctrlc`) are fine.
+///
+/// ## Example
+///
+/// ```
+/// use std::{thread, time::Duration};
+/// use nng::ShutdownToken;
+///
+/// let token = ShutdownToken::new();
+/// let worker_token = token.clone();
+///
+/// let worker = thread::spawn(move || {
+///     while !worker_token.is_shutdown() {
+///         // Do some work...
+///         worker_token.wait_timeout(Duration::from_millis(10));
+///     }
+/// });
+///
+/// token.trigger();
+/// worker.join().unwrap();
+/// assert!(token.is_shutdown());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownToken
+{
+	/// The state shared between all clones of the token.
+	inner: Arc<Inner>,
+}
+impl ShutdownToken
+{
+	/// Creates a new token that has not been triggered.
+	pub fn new() -> Self { ShutdownToken::default() }
+
+	/// Triggers the shutdown, waking all threads waiting on the token.
+	///
+	/// This also cancels any operations started with the `_cancellable`
+	/// functions that are watching this token. Triggering a token more than
+	/// once has no additional effect.
+	pub fn trigger(&self)
+	{
+		let aios = {
+			let mut state = self.inner.state.lock().unwrap();
+			state.triggered = true;
+			self.inner.cvar.notify_all();
+			mem::take(&mut state.aios)
+		};
+
+		// Don't hold the lock while calling into NNG.
+		for aio in aios {
+			aio.cancel();
+		}
+	}
+
+	/// Returns whether or not the shutdown has been triggered.
+	pub fn is_shutdown(&self) -> bool { self.inner.state.lock().unwrap().triggered }
+
+	/// Blocks the current thread until the shutdown is triggered.
+	pub fn wait(&self)
+	{
+		let state = self.inner.state.lock().unwrap();
+		drop(self.inner.cvar.wait_while(state, |s| !s.triggered).unwrap());
+	}
+
+	/// Blocks the current thread until the shutdown is triggered or the
+	/// timeout expires.
+	///
+	/// Returns whether or not the shutdown has been triggered. A timeout too
+	/// long to be represented waits as long as `wait`.
+	pub fn wait_timeout(&self, dur: Duration) -> bool
+	{
+		let deadline = match Instant::now().checked_add(dur) {
+			Some(d) => d,
+			None => {
+				self.wait();
+				return true;
+			},
+		};
+		let mut state = self.inner.state.lock().unwrap();
+		while !state.triggered {
+			let now = Instant::now();
+			if now >= deadline {
+				break;
+			}
+
+			state = self.inner.cvar.wait_timeout(state, deadline - now).unwrap().0;
+		}
+
+		state.triggered
+	}
+
+	/// Registers the AIO to be canceled when the shutdown is triggered.
+	///
+	/// If the shutdown has already been triggered, the AIO is canceled
+	/// immediately. The operation should already be started, otherwise there
+	/// is nothing for the cancellation to stop.
+	pub(crate) fn watch(&self, aio: &Aio)
+	{
+		let mut state = self.inner.state.lock().unwrap();
+		if state.triggered {
+			drop(state);
+			aio.cancel();
+		}
+		else {
+			state.aios.push(aio.clone());
+		}
+	}
+
+	/// Stops canceling the AIO when the shutdown is triggered.
+	pub(crate) fn unwatch(&self, aio: &Aio)
+	{
+		self.inner.state.lock().unwrap().aios.retain(|a| a != aio);
+	}
+}
+
+/// The shared portion of a `ShutdownToken`.
+#[derive(Debug, Default)]
+struct Inner
+{
+	/// The state of the shutdown.
+	state: Mutex<State>,
+
+	/// Used to wake threads waiting on the shutdown.
+	cvar: Condvar,
+}
+
+/// The state protected by the mutex of a `ShutdownToken`.
+#[derive(Debug, Default)]
+struct State
+{
+	/// Whether or not the shutdown has been triggered.
+	triggered: bool,
+
+	/// The AIO objects to cancel when the shutdown is triggered.
+	aios: Vec<Aio>,
+}
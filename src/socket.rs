@@ -6,20 +6,37 @@ use std::{
 	os::raw::{c_int, c_void},
 	panic::catch_unwind,
 	ptr,
-	sync::{Arc, Mutex},
+	sync::{mpsc, Arc, Mutex, RwLock, Weak},
+	thread,
+	time::{Duration, Instant},
 };
 
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+#[cfg(feature = "async-api")]
+use crate::future::{PreReceived, RecvFuture, SendFuture};
 use crate::{
-	aio::Aio,
-	error::{Error, Result, SendResult},
+	aio::{Aio, AioResult},
+	dialer::{Dialer, DialerOptions, OneShotDialers},
+	error::{DialAnyError, Error, OptionApplyError, Result, SendError, SendResult},
 	message::Message,
+	options::{
+		self,
+		protocol::pubsub::{Subscribe, Unsubscribe},
+		OptionOp, Options, SocketConfig,
+	},
+	panic_policy::{self, PanicSource},
 	pipe::{Pipe, PipeEvent},
-	protocol::Protocol,
-	util::validate_ptr,
+	protocol::{Protocol, ProtocolKind},
+	shutdown::ShutdownToken,
+	split::{RecvHalf, SendHalf},
+	survey::SurveyResponses,
+	topics::TopicMatcher,
+	tracked::{SendPool, SendToken},
+	util::{missing_is_closed, validate_ptr},
 };
-use log::error;
 
-type PipeNotifyFn = dyn Fn(Pipe, PipeEvent) + Send + Sync + 'static;
+pub(crate) type PipeNotifyFn = dyn Fn(Pipe, PipeEvent) + Send + Sync + 'static;
 
 /// A nanomsg-next-generation socket.
 ///
@@ -45,30 +62,216 @@ pub struct Socket
 impl Socket
 {
 	/// Creates a new socket which uses the specified protocol.
+	///
+	/// Every protocol is included when NNG is built by this crate, which is
+	/// the default. An NNG library built separately may leave some out with
+	/// the `NNG_PROTO_*` build options, such as `NNG_PROTO_BUS0`, and builds of
+	/// NNG 1.1 drop the functions of those protocols entirely, so linking
+	/// fails before any socket is opened. A library that still has the
+	/// functions but reports the protocol as not supported makes this return
+	/// `Error::ProtocolUnavailable` naming the protocol, which
+	/// `Protocol::is_available` checks for ahead of time.
 	pub fn new(t: Protocol) -> Result<Socket>
+	{
+		let opener: unsafe extern "C" fn(*mut nng_sys::nng_socket) -> c_int = match t {
+			Protocol::Bus0 => nng_sys::nng_bus0_open,
+			Protocol::Pair0 => nng_sys::nng_pair0_open,
+			Protocol::Pair1 => nng_sys::nng_pair1_open,
+			Protocol::Pub0 => nng_sys::nng_pub0_open,
+			Protocol::Pull0 => nng_sys::nng_pull0_open,
+			Protocol::Push0 => nng_sys::nng_push0_open,
+			Protocol::Rep0 => nng_sys::nng_rep0_open,
+			Protocol::Req0 => nng_sys::nng_req0_open,
+			Protocol::Respondent0 => nng_sys::nng_respondent0_open,
+			Protocol::Sub0 => nng_sys::nng_sub0_open,
+			Protocol::Surveyor0 => nng_sys::nng_surveyor0_open,
+		};
+
+		// The openers above all come from NNG itself, so they uphold the contract.
+		unsafe { Socket::open(ProtocolKind::Known(t), opener) }
+	}
+
+	/// Creates a new socket which uses the specified protocol in raw mode.
+	///
+	/// Raw sockets leave the protocol semantics to the application. They don't
+	/// keep track of requests, surveys, or subscriptions and instead pass the
+	/// routing information in the message header along as-is. Their main use
+	/// is with `forwarder`, which connects two raw sockets to build brokers
+	/// and other intermediaries. See the [nng documentation][1] for more
+	/// information.
+	///
+	/// [1]: https://nanomsg.github.io/nng/man/v1.1.0/nng.7.html#raw_mode
+	pub fn new_raw(t: Protocol) -> Result<Socket>
+	{
+		let opener: unsafe extern "C" fn(*mut nng_sys::nng_socket) -> c_int = match t {
+			Protocol::Bus0 => nng_sys::nng_bus0_open_raw,
+			Protocol::Pair0 => nng_sys::nng_pair0_open_raw,
+			Protocol::Pair1 => nng_sys::nng_pair1_open_raw,
+			Protocol::Pub0 => nng_sys::nng_pub0_open_raw,
+			Protocol::Pull0 => nng_sys::nng_pull0_open_raw,
+			Protocol::Push0 => nng_sys::nng_push0_open_raw,
+			Protocol::Rep0 => nng_sys::nng_rep0_open_raw,
+			Protocol::Req0 => nng_sys::nng_req0_open_raw,
+			Protocol::Respondent0 => nng_sys::nng_respondent0_open_raw,
+			Protocol::Sub0 => nng_sys::nng_sub0_open_raw,
+			Protocol::Surveyor0 => nng_sys::nng_surveyor0_open_raw,
+		};
+
+		// The openers above all come from NNG itself, so they uphold the contract.
+		unsafe { Socket::open(ProtocolKind::Known(t), opener) }
+	}
+
+	/// Creates a new socket using the _bus_ protocol.
+	///
+	/// There is one of these for every protocol, each the same as calling
+	/// `Socket::new` with that protocol. The protocol is recorded with the
+	/// socket, so it can be read back with `Socket::protocol` and methods that
+	/// only make sense for one protocol, such as `Socket::subscribe`, check it.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::{Protocol, ProtocolKind, Socket};
+	///
+	/// let sockets = [
+	///     (Socket::bus0()?, Protocol::Bus0),
+	///     (Socket::pair0()?, Protocol::Pair0),
+	///     (Socket::pair1()?, Protocol::Pair1),
+	///     (Socket::pub0()?, Protocol::Pub0),
+	///     (Socket::pull0()?, Protocol::Pull0),
+	///     (Socket::push0()?, Protocol::Push0),
+	///     (Socket::rep0()?, Protocol::Rep0),
+	///     (Socket::req0()?, Protocol::Req0),
+	///     (Socket::respondent0()?, Protocol::Respondent0),
+	///     (Socket::sub0()?, Protocol::Sub0),
+	///     (Socket::surveyor0()?, Protocol::Surveyor0),
+	/// ];
+	///
+	/// for (socket, protocol) in &sockets {
+	///     assert_eq!(socket.protocol(), ProtocolKind::Known(*protocol));
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn bus0() -> Result<Socket> { Socket::new(Protocol::Bus0) }
+
+	/// Creates a new socket using the _pair_ (version 0) protocol.
+	pub fn pair0() -> Result<Socket> { Socket::new(Protocol::Pair0) }
+
+	/// Creates a new socket using the _pair_ (version 1) protocol.
+	pub fn pair1() -> Result<Socket> { Socket::new(Protocol::Pair1) }
+
+	/// Creates a new socket using the _pub_ protocol.
+	pub fn pub0() -> Result<Socket> { Socket::new(Protocol::Pub0) }
+
+	/// Creates a new socket using the _pull_ protocol.
+	pub fn pull0() -> Result<Socket> { Socket::new(Protocol::Pull0) }
+
+	/// Creates a new socket using the _push_ protocol.
+	pub fn push0() -> Result<Socket> { Socket::new(Protocol::Push0) }
+
+	/// Creates a new socket using the _rep_ protocol.
+	pub fn rep0() -> Result<Socket> { Socket::new(Protocol::Rep0) }
+
+	/// Creates a new socket using the _req_ protocol.
+	pub fn req0() -> Result<Socket> { Socket::new(Protocol::Req0) }
+
+	/// Creates a new socket using the _respondent_ protocol.
+	pub fn respondent0() -> Result<Socket> { Socket::new(Protocol::Respondent0) }
+
+	/// Creates a new socket using the _sub_ protocol.
+	pub fn sub0() -> Result<Socket> { Socket::new(Protocol::Sub0) }
+
+	/// Creates a new socket using the _surveyor_ protocol.
+	pub fn surveyor0() -> Result<Socket> { Socket::new(Protocol::Surveyor0) }
+
+	/// Creates a new socket using a protocol that is not directly supported by
+	/// this crate.
+	///
+	/// This is an escape hatch for applications that link against a build of
+	/// NNG which provides additional protocols. The `opener` is called exactly
+	/// once with a pointer to an uninitialized `nng_socket` and should behave
+	/// like the `nng_*_open` family of functions. The provided `name` is only
+	/// used to identify the protocol via `Socket::protocol`.
+	///
+	/// ## Safety
+	///
+	/// The `opener` must either fully initialize the socket and return zero or
+	/// return a non-zero NNG error code. Any other behavior will result in
+	/// undefined behavior once the socket is used.
+	///
+	/// An opener that returns `NNG_ENOTSUP` results in
+	/// `Error::ProtocolUnavailable` with the given name.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::os::raw::c_int;
+	/// use nng::{Error, ProtocolKind, Socket};
+	///
+	/// let socket = unsafe { Socket::open_with("pair1", nng_sys::nng_pair1_open).unwrap() };
+	/// assert_eq!(socket.protocol(), ProtocolKind::Custom("pair1"));
+	///
+	/// // A protocol that the library was built without.
+	/// unsafe extern "C" fn missing(_: *mut nng_sys::nng_socket) -> c_int {
+	///     nng_sys::NNG_ENOTSUP as c_int
+	/// }
+	///
+	/// let err = unsafe { Socket::open_with("missing0", missing).unwrap_err() };
+	/// assert_eq!(err, Error::ProtocolUnavailable(ProtocolKind::Custom("missing0")));
+	/// assert_eq!(err.to_string(), "Protocol missing0 is not available in this build of NNG");
+	/// ```
+	pub unsafe fn open_with(
+		name: &'static str,
+		opener: unsafe extern "C" fn(*mut nng_sys::nng_socket) -> c_int,
+	) -> Result<Socket>
+	{
+		Socket::open(ProtocolKind::Custom(name), opener)
+	}
+
+	/// Returns the protocol used by this socket.
+	pub fn protocol(&self) -> ProtocolKind { self.inner.protocol }
+
+	/// Checks that the socket uses the given protocol.
+	///
+	/// Returns `Error::NotSupported` if it doesn't, which is what all of the
+	/// methods and types tied to a single protocol report.
+	pub(crate) fn require_protocol(&self, protocol: Protocol) -> Result<()>
+	{
+		if self.protocol() == ProtocolKind::Known(protocol) {
+			Ok(())
+		}
+		else {
+			Err(Error::NotSupported)
+		}
+	}
+
+	/// Opens the socket and performs the initialization common to all
+	/// protocols.
+	unsafe fn open(
+		protocol: ProtocolKind,
+		opener: unsafe extern "C" fn(*mut nng_sys::nng_socket) -> c_int,
+	) -> Result<Socket>
 	{
 		// Create the uninitialized nng_socket
 		let mut socket = nng_sys::nng_socket::NNG_SOCKET_INITIALIZER;
 
 		// Try to open a socket of the specified type
-		let rv = unsafe {
-			match t {
-				Protocol::Bus0 => nng_sys::nng_bus0_open(&mut socket as *mut _),
-				Protocol::Pair0 => nng_sys::nng_pair0_open(&mut socket as *mut _),
-				Protocol::Pair1 => nng_sys::nng_pair1_open(&mut socket as *mut _),
-				Protocol::Pub0 => nng_sys::nng_pub0_open(&mut socket as *mut _),
-				Protocol::Pull0 => nng_sys::nng_pull0_open(&mut socket as *mut _),
-				Protocol::Push0 => nng_sys::nng_push0_open(&mut socket as *mut _),
-				Protocol::Rep0 => nng_sys::nng_rep0_open(&mut socket as *mut _),
-				Protocol::Req0 => nng_sys::nng_req0_open(&mut socket as *mut _),
-				Protocol::Respondent0 => nng_sys::nng_respondent0_open(&mut socket as *mut _),
-				Protocol::Sub0 => nng_sys::nng_sub0_open(&mut socket as *mut _),
-				Protocol::Surveyor0 => nng_sys::nng_surveyor0_open(&mut socket as *mut _),
-			}
-		};
+		let rv = opener(&mut socket as *mut _);
+		if rv as u32 == nng_sys::NNG_ENOTSUP {
+			return Err(Error::ProtocolUnavailable(protocol));
+		}
 
 		rv2res!(rv, Socket {
-			inner:       Arc::new(Inner { handle: socket, pipe_notify: Mutex::new(None) }),
+			inner:       Arc::new(Inner {
+				handle: socket,
+				protocol,
+				pipe_notify: RwLock::new(None),
+				pipe_watchers: Mutex::new(Vec::new()),
+				tracked: SendPool::new(),
+				one_shot: Mutex::new(None),
+				#[cfg(feature = "async-api")]
+				pre_received: Arc::default(),
+			}),
 			nonblocking: false,
 		})
 	}
@@ -108,9 +311,177 @@ impl Socket
 			nng_sys::nng_dial(self.inner.handle, addr.as_ptr(), ptr::null_mut(), flags as c_int)
 		};
 
+		trace_event!(tracing::Level::DEBUG, url, rv, "socket.dial");
 		rv2res!(rv)
 	}
 
+	/// Initiates a remote connection to a listener, giving up after the
+	/// timeout.
+	///
+	/// This behaves like a blocking `Socket::dial`, except that the first
+	/// connection attempt is abandoned with `Error::TimedOut` if it has not
+	/// completed within the timeout. NNG v1.1 has no connect timeout of its
+	/// own, so without this a dial to an address that silently drops packets
+	/// blocks for as long as the operating system keeps trying, which is
+	/// around two minutes for TCP on Linux. The send and receive timeouts of
+	/// the socket have no effect on connection establishment.
+	///
+	/// When the timeout expires, the dialer is closed before this function
+	/// returns. It will not keep retrying in the background and no pipe will
+	/// be created by it later on. The `nonblocking` setting of the socket is
+	/// ignored.
+	///
+	/// ## Example
+	///
+	/// A TCP listener that never answers stands in for an unresponsive host
+	/// (such as `10.255.255.1`), as the connection is never fully established.
+	///
+	/// ```
+	/// use std::{
+	///     io::Read,
+	///     net::TcpListener,
+	///     sync::{atomic::{AtomicUsize, Ordering}, Arc},
+	///     thread,
+	///     time::{Duration, Instant},
+	/// };
+	/// use nng::{Error, PipeEvent, Protocol, Socket};
+	///
+	/// let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+	/// let url = format!("tcp://{}", silent.local_addr().unwrap());
+	///
+	/// let socket = Socket::new(Protocol::Pair1)?;
+	/// let pipes = Arc::new(AtomicUsize::new(0));
+	/// let cb_pipes = Arc::clone(&pipes);
+	/// socket.pipe_notify(move |_, ev| if ev == PipeEvent::AddPost {
+	///     cb_pipes.fetch_add(1, Ordering::Relaxed);
+	/// })?;
+	///
+	/// let start = Instant::now();
+	/// assert_eq!(socket.dial_timeout(&url, Duration::from_millis(200)), Err(Error::TimedOut));
+	/// assert!(start.elapsed() < Duration::from_secs(2));
+	///
+	/// // The abandoned attempt was closed: the connection is shut down from the
+	/// // NNG side and no pipe ever shows up.
+	/// let (mut conn, _) = silent.accept().unwrap();
+	/// conn.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+	/// conn.read_to_end(&mut Vec::new()).expect("Connection was not closed");
+	///
+	/// thread::sleep(Duration::from_millis(100));
+	/// assert_eq!(pipes.load(Ordering::Relaxed), 0);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn dial_timeout(&self, url: &str, timeout: Duration) -> Result<()>
+	{
+		let dialer = DialerOptions::new(self, url)?;
+		let handle = dialer.handle();
+
+		// The blocking start can't be interrupted by a timer, but closing the
+		// dialer causes it to return `Error::Closed` right away.
+		let (tx, rx) = mpsc::channel();
+		let starter = thread::spawn(move || {
+			let rv = unsafe { nng_sys::nng_dialer_start(handle, 0) };
+			let _ = tx.send(rv);
+		});
+
+		let res = match rx.recv_timeout(timeout) {
+			Ok(0) => Ok(()),
+			Ok(rv) => Err(missing_is_closed(Error::from_code(rv as u32))),
+			Err(_) => Err(Error::TimedOut),
+		};
+
+		if res.is_ok() {
+			// The dialer is up and running and now belongs to the socket.
+			dialer.into_dialer();
+		}
+		else {
+			// Closing the dialer is what allows a blocked start to return, so it has
+			// to happen before waiting for the thread.
+			drop(dialer);
+		}
+		starter.join().expect("Dialer thread panicked");
+
+		trace_event!(tracing::Level::DEBUG, url, result = ?res, "socket.dial");
+		res
+	}
+
+	/// Dials all of the URLs and returns as soon as one of them connects.
+	///
+	/// A dialer is created for every URL and started synchronously, as with a
+	/// blocking `Socket::dial`, except that all of the first connection
+	/// attempts are made in parallel. The dialer of the first one to succeed
+	/// is returned. The other attempts carry on in the background: those that
+	/// succeed later also stay attached to the socket, while those that fail
+	/// are closed and not retried. If every attempt fails, the error lists the
+	/// reason for each URL. The `nonblocking` setting of the socket is
+	/// ignored.
+	///
+	/// Once connected, a dialer redials its URL whenever the connection is
+	/// lost, and most protocols make use of all the connections of a socket
+	/// without any help. A _req_ socket sends every request over one of its
+	/// connections and resends it over another if the peer goes away, a
+	/// _sub_ socket receives what is published by all of its peers, and a
+	/// _pub_ or _rep_ socket that dials serves all of them. This makes a list
+	/// of equivalent servers a simple form of failover.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::*;
+	///
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// server.listen("inproc://nng/dial_any/up")?;
+	///
+	/// let client = Socket::new(Protocol::Req0)?;
+	/// let urls = ["inproc://nng/dial_any/down", "inproc://nng/dial_any/up"];
+	/// let dialer = client.dial_any(&urls).map_err(Error::from)?;
+	/// assert_eq!(dialer.url(), "inproc://nng/dial_any/up");
+	///
+	/// client.send(&b"ping"[..])?;
+	/// server.send(server.recv()?)?;
+	/// assert_eq!(&client.recv()?[..], b"ping");
+	///
+	/// let err = client.dial_any(&urls[..1]).unwrap_err();
+	/// let expected = ("inproc://nng/dial_any/down".to_string(), Error::ConnectionRefused);
+	/// assert_eq!(err.errors, vec![expected]);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn dial_any(&self, urls: &[&str]) -> std::result::Result<Dialer, DialAnyError>
+	{
+		let mut errors = vec![None; urls.len()];
+		let (tx, rx) = mpsc::channel();
+		for (i, url) in urls.iter().enumerate() {
+			match DialerOptions::new(self, url) {
+				Ok(dialer) => {
+					let tx = tx.clone();
+					thread::spawn(move || {
+						// A dialer that fails to start is closed along with its options.
+						let _ = tx.send((i, dialer.start(false).map_err(|(_, e)| e)));
+					});
+				},
+				Err(e) => errors[i] = Some(e),
+			}
+		}
+
+		// The receiver runs dry once every attempt has failed.
+		drop(tx);
+		for (i, res) in rx {
+			match res {
+				Ok(dialer) => {
+					trace_event!(tracing::Level::DEBUG, url = dialer.url(), "socket.dial_any");
+					return Ok(dialer);
+				},
+				Err(e) => errors[i] = Some(e),
+			}
+		}
+
+		let errors = urls
+			.iter()
+			.zip(errors)
+			.filter_map(|(url, e)| e.map(|e| ((*url).to_string(), e)))
+			.collect();
+		Err(DialAnyError { errors })
+	}
+
 	/// Initiates and starts a listener on the specified address.
 	///
 	/// Listeners are used to accept connections initiated by remote dialers.
@@ -141,6 +512,7 @@ impl Socket
 			nng_sys::nng_listen(self.inner.handle, addr.as_ptr(), ptr::null_mut(), flags as c_int)
 		};
 
+		trace_event!(tracing::Level::DEBUG, url, rv, "socket.listen");
 		rv2res!(rv)
 	}
 
@@ -152,9 +524,277 @@ impl Socket
 	/// the operation can complete or any configured timer expires.
 	///
 	/// The default is blocking operations. This setting is _not_ propagated to
-	/// other handles cloned from this one.
+	/// other handles cloned from this one. The halves made by `Socket::split`
+	/// ignore it and decide with every call instead.
 	pub fn set_nonblocking(&mut self, nonblocking: bool) { self.nonblocking = nonblocking; }
 
+	/// Splits the socket into a half that sends and a half that receives.
+	///
+	/// Both halves share the socket with this handle, can be moved to other
+	/// threads, and only have the operations of their direction, with a
+	/// blocking and a nonblocking version of each. Pipelines usually keep one
+	/// of them, such as the sending half of a _push_ socket, while sockets
+	/// that do both, like _pair_ ones, can hand each half to its own thread.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::{Error, Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/split";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// let (_, consumer) = pull.split();
+	/// let (producer, _) = push.split();
+	///
+	/// let consumer = thread::spawn(move || -> nng::Result<u32> {
+	///     let mut sum = 0;
+	///     for _ in 0..100 {
+	///         let msg = consumer.recv()?;
+	///         sum += u32::from(msg[0]);
+	///     }
+	///     assert_eq!(consumer.try_recv().unwrap_err(), Error::TryAgain);
+	///     assert_eq!(consumer.recv_timeout(Duration::from_millis(10)).unwrap_err(), Error::TimedOut);
+	///     Ok(sum)
+	/// });
+	///
+	/// let producer = thread::spawn(move || -> nng::Result<()> {
+	///     for i in 0..100_u8 {
+	///         producer.send(&[i][..])?;
+	///     }
+	///     Ok(())
+	/// });
+	///
+	/// producer.join().unwrap()?;
+	/// assert_eq!(consumer.join().unwrap()?, (0..100).sum::<u32>());
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn split(&self) -> (SendHalf, RecvHalf)
+	{
+		(SendHalf::new(self.clone()), RecvHalf::new(self.clone()))
+	}
+
+	/// Sets the largest message that will be accepted from a peer, in bytes.
+	///
+	/// This is the same as setting the `RecvMaxSize` option, with zero meaning
+	/// that there is no limit. The limit is enforced by the transports while
+	/// messages arrive, before the protocol or any receive operation gets to
+	/// see them, so it applies to `Socket::recv`, `Socket::recv_async`,
+	/// contexts, and every other way of receiving alike. Nothing reports an
+	/// oversized message to the receiver and its sender isn't told either.
+	/// What happens to the message depends on the transport:
+	///
+	/// * The stream transports, such as TCP, IPC, TLS, and WebSocket, close
+	///   the connection as soon as a message announces a size over the limit,
+	///   without reading any of it. Messages sent after it on the same
+	///   connection are lost with it, and a dialer on the other side
+	///   reconnects.
+	/// * The inproc transport drops the message and keeps the connection.
+	///
+	/// Dialers and listeners can override the limit of the socket. Each
+	/// connection takes the limit of its endpoint when it is established and
+	/// keeps it, so this should be called before dialing or listening.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{sync::mpsc, time::Duration};
+	/// use nng::{options::{Options, RecvTimeout}, *};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/recv_max_size";
+	/// let big = vec![0; 2048];
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.set_recv_max_size(1024)?;
+	/// pull.set_opt::<RecvTimeout>(Some(Duration::from_secs(5)))?;
+	/// pull.listen(ADDRESS)?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// // Oversized messages never reach either kind of receive.
+	/// push.send(&big[..])?;
+	/// push.send(&b"blocking"[..])?;
+	/// assert_eq!(&pull.recv()?[..], b"blocking");
+	///
+	/// push.send(&big[..])?;
+	/// push.send(&b"async"[..])?;
+	/// let aio = Aio::new_manual()?;
+	/// pull.recv_async(&aio)?;
+	/// aio.wait();
+	/// match aio.result() {
+	///     Some(AioResult::RecvOk(msg)) => assert_eq!(&msg[..], b"async"),
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// }
+	///
+	/// // Nor do they reach contexts.
+	/// let rep = Socket::new(Protocol::Rep0)?;
+	/// rep.set_recv_max_size(1024)?;
+	/// rep.listen("inproc://nng/socket/recv_max_size/ctx")?;
+	/// let req = Socket::new(Protocol::Req0)?;
+	/// req.dial("inproc://nng/socket/recv_max_size/ctx")?;
+	///
+	/// let ctx = Context::new(&rep)?;
+	/// req.send(&big[..])?;
+	/// aio.set_timeout(Some(Duration::from_millis(100)))?;
+	/// ctx.recv(&aio)?;
+	/// aio.wait();
+	/// match aio.result() {
+	///     Some(AioResult::RecvErr(e)) => assert_eq!(e, Error::TimedOut),
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// }
+	///
+	/// // Over TCP, the connection is closed instead.
+	/// let (_, _, url) = bind_ephemeral(&pull, "tcp://127.0.0.1")?;
+	/// let (tx, rx) = mpsc::channel();
+	/// pull.pipe_notify(move |_, ev| if ev == PipeEvent::RemovePost {
+	///     let _ = tx.send(());
+	/// })?;
+	///
+	/// let remote = Socket::new(Protocol::Push0)?;
+	/// remote.dial(&url)?;
+	/// remote.send(&big[..])?;
+	/// rx.recv_timeout(Duration::from_secs(5)).expect("Connection was not closed");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn set_recv_max_size(&self, max: usize) -> Result<()>
+	{
+		self.set_opt::<options::RecvMaxSize>(max)
+	}
+
+	/// Sets options given by name, returning the result for each of them.
+	///
+	/// The names are those of the option types, as listed by `options::all`.
+	/// Each option is set independently and in order, so a failure does not
+	/// stop the remaining options from being applied. Besides the errors NNG
+	/// reports for the option, an option fails with `Error::EntryNotFound` if
+	/// its name is not known, with `Error::NotSupported` if sockets can't set
+	/// it, and with `Error::BadType` if the value is of the wrong kind.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::{options::{Options, RecvTimeout, OptionValue}, Error, Protocol, Socket};
+	///
+	/// let socket = Socket::new(Protocol::Req0)?;
+	/// let results = socket.apply_options(&[
+	///     ("RecvTimeout", Duration::from_millis(250).into()),
+	///     ("SendTimeout", OptionValue::Bool(true)),
+	///     ("Subscribe", b"topic"[..].into()),
+	///     ("Raw", true.into()),
+	///     ("MaxTtl", 0.into()),
+	///     ("Bogus", 1.into()),
+	/// ]);
+	///
+	/// assert_eq!(results, vec![
+	///     Ok(()),
+	///     Err(Error::BadType),
+	///     Err(Error::NotSupported),
+	///     Err(Error::NotSupported),
+	///     Err(Error::InvalidInput),
+	///     Err(Error::EntryNotFound),
+	/// ]);
+	/// assert_eq!(socket.get_opt::<RecvTimeout>()?, Some(Duration::from_millis(250)));
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn apply_options(&self, options: &[OptionOp]) -> Vec<Result<()>>
+	{
+		options::apply(self, options)
+	}
+
+	/// Sets options given by name, either all of them or none.
+	///
+	/// The current value of every option in the batch is read first, and the
+	/// options are then set in order. If one of them fails, the options that
+	/// were already set are restored to the values they had before, in
+	/// reverse order, and the error says which option failed and whether
+	/// restoring the others succeeded. The names and errors are those of
+	/// `Socket::apply_options`.
+	///
+	/// Options that sockets can set but not read, such as `Subscribe` or the
+	/// TCP options, can't be restored, so a batch containing one fails with
+	/// `Error::NotSupported` before anything is set. Such options have to be
+	/// set separately, after the batch has succeeded.
+	///
+	/// Other threads using the socket at the same time may see the new values
+	/// before they are restored, and changes they make to the same options
+	/// while the batch is applied are overwritten when it is rolled back.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::{options::{Options, RecvBufferSize, RecvTimeout, SendBufferSize}, *};
+	///
+	/// let socket = Socket::new(Protocol::Req0)?;
+	/// let before = (
+	///     socket.get_opt::<RecvTimeout>()?,
+	///     socket.get_opt::<SendBufferSize>()?,
+	///     socket.get_opt::<RecvBufferSize>()?,
+	/// );
+	///
+	/// // The TTL is out of range, so the options before it are restored.
+	/// let err = socket.apply_options_atomic(&[
+	///     ("RecvTimeout", Duration::from_millis(250).into()),
+	///     ("SendBufferSize", 16.into()),
+	///     ("MaxTtl", 0.into()),
+	///     ("RecvBufferSize", 32.into()),
+	/// ]).unwrap_err();
+	///
+	/// assert_eq!((err.index, err.option.as_str(), err.error), (2, "MaxTtl", Error::InvalidInput));
+	/// assert!(err.rolled_back());
+	/// let after = (
+	///     socket.get_opt::<RecvTimeout>()?,
+	///     socket.get_opt::<SendBufferSize>()?,
+	///     socket.get_opt::<RecvBufferSize>()?,
+	/// );
+	/// assert_eq!(after, before);
+	///
+	/// // Options that can't be read back are rejected up front.
+	/// let sub = Socket::new(Protocol::Sub0)?;
+	/// let err = sub.apply_options_atomic(&[
+	///     ("RecvTimeout", Duration::from_millis(250).into()),
+	///     ("Subscribe", b"topic"[..].into()),
+	/// ]).unwrap_err();
+	/// assert_eq!((err.index, err.error), (1, Error::NotSupported));
+	/// assert_eq!(sub.get_opt::<RecvTimeout>()?, None);
+	///
+	/// socket.apply_options_atomic(&[
+	///     ("RecvTimeout", Duration::from_millis(250).into()),
+	///     ("SendBufferSize", 16.into()),
+	/// ]).map_err(Error::from)?;
+	/// assert_eq!(socket.get_opt::<RecvTimeout>()?, Some(Duration::from_millis(250)));
+	/// assert_eq!(socket.get_opt::<SendBufferSize>()?, 16);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn apply_options_atomic(
+		&self,
+		options: &[OptionOp],
+	) -> std::result::Result<(), OptionApplyError>
+	{
+		options::apply_atomic(self, options)
+	}
+
+	/// Sets the options of the configuration that are set.
+	///
+	/// The configuration is first checked with `SocketConfig::validate`
+	/// against the protocol of the socket, so that a configuration with an
+	/// option the protocol doesn't have or a value out of range fails without
+	/// changing anything. The options are then set in the order of
+	/// `SocketConfig::options`, and the first error NNG reports for any of them
+	/// is returned after the rest have been set. See `SocketConfig` for an
+	/// example.
+	pub fn apply_config(&self, config: &SocketConfig) -> Result<()>
+	{
+		config.validate(self.protocol())?;
+		options::apply(self, &config.options()).into_iter().collect()
+	}
+
 	/// Receives a message from the socket.
 	///
 	/// The semantics of what receiving a message means vary from protocol to
@@ -162,10 +802,41 @@ impl Socket
 	/// For example, with a _req_ socket a message may only be received after a
 	/// request has been sent. Furthermore, some protocols may not support
 	/// receiving data at all, such as _pub_.
-	pub fn recv(&self) -> Result<Message>
+	///
+	/// ## Allocation
+	///
+	/// Received messages are allocated by NNG and handed over without copying.
+	/// The stream transports (TCP, IPC, TLS) read the length prefix of each
+	/// frame and allocate a message of exactly that size, while `inproc`
+	/// passes the sender's message along without allocating at all. There is
+	/// no receive buffer that needs to warm up, so NNG offers no size hint to
+	/// preallocate one and this crate does not add one. `RecvBufferSize`
+	/// controls the number of queued messages, not their size.
+	///
+	/// Latency spikes on the first request usually come from establishing the
+	/// connection rather than from allocation. Waiting for the pipe to be added
+	/// (see `Socket::pipe_notify`) or sending a warm-up request before the
+	/// latency sensitive traffic starts avoids them.
+	///
+	/// The wrapper adds nothing measurable to the cost of a receive, which is
+	/// dominated by NNG setting up an operation for every call. The
+	/// `recv_overhead` example compares this function to calling
+	/// `nng_recvmsg` directly.
+	pub fn recv(&self) -> Result<Message> { self.recv_blocking(!self.nonblocking) }
+
+	/// Receives a message, blocking only if asked to, whatever the setting
+	/// of this handle.
+	pub(crate) fn recv_blocking(&self, block: bool) -> Result<Message>
 	{
+		#[cfg(feature = "async-api")]
+		{
+			if let Some(msg) = self.inner.pre_received.pop_front() {
+				return Ok(msg);
+			}
+		}
+
 		let mut msgp: *mut nng_sys::nng_msg = ptr::null_mut();
-		let flags = if self.nonblocking { nng_sys::NNG_FLAG_NONBLOCK } else { 0 };
+		let flags = if block { 0 } else { nng_sys::NNG_FLAG_NONBLOCK };
 
 		let rv = unsafe { nng_sys::nng_recvmsg(self.inner.handle, &mut msgp as _, flags as c_int) };
 
@@ -173,6 +844,579 @@ impl Socket
 		Ok(Message::from_ptr(msgp))
 	}
 
+	/// Receives a message from the socket along with the pipe it came from.
+	///
+	/// This behaves like `Socket::recv` and returns the same pipe as
+	/// `Message::pipe` on the received message. The pipe is valid as soon as
+	/// the message is returned, but the connection may close at any point
+	/// afterwards, so anything read from the pipe, such as its options, only
+	/// describes the peer at the time of the receive. This is the place to
+	/// look up per-connection state, like the credentials of the peer, for
+	/// authorization or logging.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/recv_with_pipe";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	/// let first = Socket::new(Protocol::Push0)?;
+	/// first.dial(ADDRESS)?;
+	/// let second = Socket::new(Protocol::Push0)?;
+	/// second.dial(ADDRESS)?;
+	///
+	/// first.send(&b"one"[..])?;
+	/// let (msg, first_pipe) = pull.recv_with_pipe()?;
+	/// assert_eq!(&msg[..], b"one");
+	///
+	/// second.send(&b"two"[..])?;
+	/// let (_, second_pipe) = pull.recv_with_pipe()?;
+	/// assert_ne!(first_pipe, second_pipe);
+	///
+	/// // Every message of a connection arrives on the same pipe.
+	/// first.send(&b"three"[..])?;
+	/// let (mut msg, pipe) = pull.recv_with_pipe()?;
+	/// assert_eq!(pipe, first_pipe);
+	/// assert_eq!(msg.pipe(), Some(pipe));
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn recv_with_pipe(&self) -> Result<(Message, Pipe)>
+	{
+		let mut msg = self.recv()?;
+
+		match msg.pipe() {
+			Some(p) => Ok((msg, p)),
+			None => {
+				log_error!("NNG received a message without a pipe");
+				Err(Error::Internal)
+			},
+		}
+	}
+
+	/// Receives a message from the socket, rejecting bodies larger than the
+	/// limit.
+	///
+	/// This behaves like `Socket::recv`, except that a message whose body is
+	/// longer than `max` bytes is discarded and `Error::MessageExceedsLimit` is
+	/// returned with the actual size. Unlike `RecvMaxSize`, which makes NNG
+	/// drop oversized messages without telling anyone, the application gets
+	/// to see the rejection and can respond to it. For _rep_ sockets, the
+	/// peer is still waiting for a reply to the rejected request.
+	///
+	/// The message has already been transferred by the time it is checked, so
+	/// this does not save any bandwidth or memory. Use `RecvMaxSize` to
+	/// protect against peers which send huge messages.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::thread;
+	/// use nng::{Error, Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/recv_limited";
+	/// const LIMIT: usize = 16;
+	///
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// server.listen(ADDRESS)?;
+	///
+	/// let jh = thread::spawn(move || -> nng::Result<()> {
+	///     for _ in 0..2 {
+	///         let reply = match server.recv_limited(LIMIT) {
+	///             Ok(_) => b"ok".to_vec(),
+	///             Err(Error::MessageExceedsLimit { size, limit }) => {
+	///                 format!("too large: {} > {}", size, limit).into_bytes()
+	///             },
+	///             Err(e) => return Err(e),
+	///         };
+	///         server.send(&reply[..])?;
+	///     }
+	///     Ok(())
+	/// });
+	///
+	/// let client = Socket::new(Protocol::Req0)?;
+	/// client.dial(ADDRESS)?;
+	///
+	/// client.send(&[0; LIMIT][..])?;
+	/// assert_eq!(&client.recv()?[..], b"ok");
+	///
+	/// client.send(&[0; LIMIT + 1][..])?;
+	/// assert_eq!(&client.recv()?[..], b"too large: 17 > 16");
+	///
+	/// jh.join().unwrap()?;
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn recv_limited(&self, max: usize) -> Result<Message>
+	{
+		self.recv_limited_lossy(max).map_err(|(_, e)| e)
+	}
+
+	/// Receives a message from the socket, returning oversized messages along
+	/// with the error.
+	///
+	/// This is the same as `Socket::recv_limited`, except that a message
+	/// larger than the limit is handed back with `Error::MessageExceedsLimit`
+	/// instead of being discarded. All other errors are returned without a
+	/// message.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::{Error, Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/recv_limited_lossy";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// push.send(&b"four"[..])?;
+	/// push.send(&b"five!"[..])?;
+	///
+	/// assert_eq!(&pull.recv_limited_lossy(4).unwrap()[..], b"four");
+	/// match pull.recv_limited_lossy(4) {
+	///     Err((Some(msg), Error::MessageExceedsLimit { size: 5, limit: 4 })) => {
+	///         assert_eq!(&msg[..], b"five!");
+	///     },
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn recv_limited_lossy(
+		&self,
+		max: usize,
+	) -> std::result::Result<Message, (Option<Message>, Error)>
+	{
+		let msg = self.recv().map_err(|e| (None, e))?;
+
+		let size = msg.len();
+		if size > max {
+			return Err((Some(msg), Error::MessageExceedsLimit { size, limit: max }));
+		}
+
+		Ok(msg)
+	}
+
+	/// Receives a message exactly the size of an array from the socket into it.
+	///
+	/// This is meant for protocols where every message has the same fixed
+	/// size, such as a telemetry record. The message body is copied into a
+	/// default `A`, usually a byte array on the stack, and the message is freed
+	/// before returning, so there is no `Message` to handle. A message of any
+	/// other length than `A` is discarded and `Error::MessageLengthMismatch` is
+	/// returned with its actual size.
+	///
+	/// The standard library implements `Default` for arrays of up to 32 bytes.
+	/// Larger records can use a type of their own that implements `Default` and
+	/// `AsMut<[u8]>`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::{Error, Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/recv_array";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// push.send(&42u64.to_le_bytes()[..])?;
+	/// push.send(&[1, 2, 3][..])?;
+	///
+	/// let value = u64::from_le_bytes(pull.recv_array::<[u8; 8]>()?);
+	/// assert_eq!(value, 42);
+	///
+	/// let err = pull.recv_array::<[u8; 8]>().unwrap_err();
+	/// assert_eq!(err, Error::MessageLengthMismatch { size: 3, expected: 8 });
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn recv_array<A: AsMut<[u8]> + Default>(&self) -> Result<A>
+	{
+		let msg = self.recv()?;
+
+		let mut buf = A::default();
+		let (size, expected) = (msg.len(), buf.as_mut().len());
+		if size != expected {
+			return Err(Error::MessageLengthMismatch { size, expected });
+		}
+
+		buf.as_mut().copy_from_slice(msg.as_slice());
+		Ok(buf)
+	}
+
+	/// Receives every queued message from the socket, up to `max` of them.
+	///
+	/// This performs non-blocking receives until there are no more messages
+	/// waiting or `max` messages have been received, regardless of the
+	/// non-blocking setting of the socket. It never blocks, so the returned
+	/// vector is empty if nothing was queued. The messages are in the order in
+	/// which they were received.
+	///
+	/// If a receive fails for any reason other than there being no message,
+	/// the messages received up to that point are returned along with the
+	/// error.
+	///
+	/// See `DrainReceiver` for an asynchronous version of this.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::{options::{Options, RecvBufferSize}, Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/recv_drain";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.set_opt::<RecvBufferSize>(1000)?;
+	/// pull.listen(ADDRESS)?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// for i in 0..1000u32 {
+	///     push.send(&i.to_be_bytes()[..])?;
+	/// }
+	/// thread::sleep(Duration::from_millis(50));
+	///
+	/// let first = pull.recv_drain(600).map_err(|(_, e)| e)?;
+	/// let rest = pull.recv_drain(600).map_err(|(_, e)| e)?;
+	/// assert_eq!((first.len(), rest.len()), (600, 400));
+	/// for (i, msg) in first.iter().chain(&rest).enumerate() {
+	///     assert_eq!(msg[..], (i as u32).to_be_bytes());
+	/// }
+	///
+	/// // Nothing is left, so this returns immediately.
+	/// assert!(pull.recv_drain(600).map_err(|(_, e)| e)?.is_empty());
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn recv_drain(
+		&self,
+		max: usize,
+	) -> std::result::Result<Vec<Message>, (Vec<Message>, Error)>
+	{
+		let mut received = Vec::new();
+		while received.len() < max {
+			#[cfg(feature = "async-api")]
+			{
+				if let Some(msg) = self.inner.pre_received.pop_front() {
+					received.push(msg);
+					continue;
+				}
+			}
+
+			let mut msgp: *mut nng_sys::nng_msg = ptr::null_mut();
+			let flags = nng_sys::NNG_FLAG_NONBLOCK as c_int;
+
+			let rv = unsafe { nng_sys::nng_recvmsg(self.inner.handle, &mut msgp as _, flags) };
+
+			match validate_ptr(rv, msgp) {
+				Ok(msgp) => received.push(Message::from_ptr(msgp)),
+				Err(Error::TryAgain) => break,
+				Err(e) => return Err((received, e)),
+			}
+		}
+
+		Ok(received)
+	}
+
+	/// Receives a message from the socket, giving up when the token is
+	/// triggered.
+	///
+	/// This behaves like `Socket::recv`, including respecting the receive
+	/// timeout and the non-blocking setting, except that a blocked receive
+	/// returns `Error::Canceled` as soon as the shutdown token is triggered.
+	/// If the token has already been triggered, this returns `Error::Canceled`
+	/// without receiving anything. This removes the need for a separate thread
+	/// that closes the socket just to unblock the receiver.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::*;
+	///
+	/// let socket = Socket::new(Protocol::Pull0)?;
+	/// socket.listen("inproc://nng/socket/recv_cancellable")?;
+	///
+	/// let token = ShutdownToken::new();
+	/// let trigger = token.clone();
+	/// thread::spawn(move || {
+	///     thread::sleep(Duration::from_millis(50));
+	///     trigger.trigger();
+	/// });
+	///
+	/// // Nobody ever sends anything, so this only returns because of the token.
+	/// assert_eq!(socket.recv_cancellable(&token).unwrap_err(), Error::Canceled);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn recv_cancellable(&self, token: &ShutdownToken) -> Result<Message>
+	{
+		if token.is_shutdown() {
+			return Err(Error::Canceled);
+		}
+
+		if self.nonblocking {
+			return self.recv();
+		}
+
+		// Waiting on the AIO rather than on its callback means that the AIO is
+		// dropped here and not inside of the callback.
+		let aio = Aio::new_manual()?;
+		aio.use_default_timeout()?;
+
+		self.recv_async(&aio)?;
+		token.watch(&aio);
+		aio.wait();
+		token.unwatch(&aio);
+
+		match aio.result() {
+			Some(AioResult::RecvOk(m)) => Ok(m),
+			Some(AioResult::RecvErr(e)) => Err(e),
+			res => unreachable!("Receive AIO completed with a non-receive result: {:?}", res),
+		}
+	}
+
+	/// Sends a request on a _req_ socket and waits for the reply until the
+	/// deadline.
+	///
+	/// The deadline covers the whole request, including the resends of
+	/// `ResendTime`, so this returns `Error::TimedOut` once it passes no matter
+	/// how many times the request was sent. The receive timeout of the socket
+	/// is not used and the non-blocking setting is ignored. If sending fails,
+	/// the request is dropped. Returns `Error::NotSupported` if the socket does
+	/// not use the _req_ protocol.
+	///
+	/// When the deadline passes, NNG abandons the request, so a reply that
+	/// arrives later is discarded instead of being handed to the next receive.
+	/// Until the next request is sent, receiving on the socket returns
+	/// `Error::IncorrectState`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::{Duration, Instant}};
+	/// use nng::{options::{protocol::reqrep::ResendTime, Options}, *};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/request_with_deadline";
+	///
+	/// // The server sits on the first request for longer than the client waits.
+	/// let server = Socket::new(Protocol::Rep0)?;
+	/// server.listen(ADDRESS)?;
+	/// thread::spawn(move || {
+	///     let mut first = true;
+	///     while let Ok(msg) = server.recv() {
+	///         let reply = if first {
+	///             thread::sleep(Duration::from_millis(300));
+	///             Message::from_slice(b"stale")?
+	///         }
+	///         else {
+	///             msg
+	///         };
+	///         first = false;
+	///         server.send(reply)?;
+	///     }
+	///     Ok::<(), Error>(())
+	/// });
+	///
+	/// let client = Socket::new(Protocol::Req0)?;
+	/// client.set_opt::<ResendTime>(Some(Duration::from_millis(20)))?;
+	/// client.dial(ADDRESS)?;
+	///
+	/// // The request is resent several times, but the deadline still holds.
+	/// let start = Instant::now();
+	/// let deadline = start + Duration::from_millis(100);
+	/// let res = client.request_with_deadline(&b"first"[..], deadline);
+	/// assert_eq!(res.unwrap_err(), Error::TimedOut);
+	/// assert!(start.elapsed() >= Duration::from_millis(100));
+	/// assert_eq!(client.recv().unwrap_err(), Error::IncorrectState);
+	///
+	/// // Neither the late reply nor the echoes of the resends reach the next request.
+	/// let deadline = Instant::now() + Duration::from_secs(5);
+	/// let reply = client.request_with_deadline(&b"second"[..], deadline)?;
+	/// assert_eq!(&reply[..], b"second");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn request_with_deadline<M: Into<Message>>(
+		&self,
+		msg: M,
+		deadline: Instant,
+	) -> Result<Message>
+	{
+		self.require_protocol(Protocol::Req0)?;
+
+		let remaining = deadline.saturating_duration_since(Instant::now());
+		if remaining == Duration::from_millis(0) {
+			return Err(Error::TimedOut);
+		}
+
+		let aio = Aio::new_manual()?;
+		aio.set_timeout(Some(remaining))?;
+		self.send_async(&aio, msg).map_err(|e| e.error)?;
+		aio.wait();
+		match aio.result() {
+			Some(AioResult::SendOk) => {},
+			Some(AioResult::SendErr(_, e)) | Some(AioResult::SendErrLost(e)) => return Err(e),
+			res => unreachable!("Send AIO completed with a non-send result: {:?}", res),
+		}
+
+		// A receive that times out makes NNG abandon the request, which is what keeps a
+		// late reply away from the next receive. NNG fails a zero timeout before the
+		// receive is even queued and keeps the request, and its clock only counts whole
+		// milliseconds, so an extra millisecond makes sure the receive is queued and
+		// doesn't time out before the deadline.
+		let remaining = deadline.saturating_duration_since(Instant::now());
+		aio.set_timeout(Some(remaining + Duration::from_millis(1)))?;
+		self.recv_async(&aio)?;
+		aio.wait();
+		match aio.result() {
+			Some(AioResult::RecvOk(m)) => Ok(m),
+			Some(AioResult::RecvErr(e)) => Err(e),
+			res => unreachable!("Receive AIO completed with a non-receive result: {:?}", res),
+		}
+	}
+
+	/// Subscribes a _sub_ socket to all messages that start with the topic.
+	///
+	/// This is the same as setting the `Subscribe` option, but checks the
+	/// protocol first and returns `Error::NotSupported` if the socket does not
+	/// use the _sub_ protocol. An empty topic subscribes to every message.
+	///
+	/// Messages are only checked against the subscriptions once they leave the
+	/// receive buffer, unless a receive is already waiting for them. Until
+	/// then, messages that don't match take up room in the buffer, which holds
+	/// a single message by default, and can cause matching ones to be dropped.
+	/// A larger `RecvBufferSize` avoids that.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::{options::{Options, RecvBufferSize, RecvTimeout}, Error, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/subscribe";
+	///
+	/// let publisher = Socket::pub0()?;
+	/// publisher.listen(ADDRESS)?;
+	///
+	/// let subscriber = Socket::sub0()?;
+	/// subscriber.set_opt::<RecvTimeout>(Some(Duration::from_secs(5)))?;
+	/// subscriber.set_opt::<RecvBufferSize>(8)?;
+	/// subscriber.subscribe(b"news")?;
+	/// subscriber.dial(ADDRESS)?;
+	/// # std::thread::sleep(Duration::from_millis(50));
+	///
+	/// publisher.send(&b"weather: sunny"[..])?;
+	/// publisher.send(&b"news: none"[..])?;
+	/// assert_eq!(&subscriber.recv()?[..], b"news: none");
+	///
+	/// // Publishers have nothing to subscribe to.
+	/// assert_eq!(publisher.subscribe(b"news"), Err(Error::NotSupported));
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn subscribe(&self, topic: &[u8]) -> Result<()>
+	{
+		self.require_protocol(Protocol::Sub0)?;
+		self.set_opt::<Subscribe>(topic.to_vec())
+	}
+
+	/// Subscribes a _sub_ socket to the prefixes of the patterns, as given by
+	/// `TopicMatcher::nng_prefixes`.
+	///
+	/// This lets through every message starting with a topic the patterns
+	/// match, along with some others, so received messages still have to be
+	/// checked with `TopicMatcher::matches`. Returns `Error::NotSupported` if
+	/// the socket does not use the _sub_ protocol.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::{options::{Options, RecvBufferSize}, topics::TopicMatcher, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/subscribe_patterns";
+	///
+	/// let publisher = Socket::pub0()?;
+	/// publisher.listen(ADDRESS)?;
+	///
+	/// let matcher = TopicMatcher::new(&["metrics.*.cpu"])?;
+	/// let subscriber = Socket::sub0()?;
+	/// subscriber.set_opt::<RecvBufferSize>(8)?;
+	/// subscriber.subscribe_patterns(&matcher)?;
+	/// subscriber.dial(ADDRESS)?;
+	/// # std::thread::sleep(Duration::from_millis(50));
+	///
+	/// for body in &["logs.host42 started", "metrics.host42.mem 512", "metrics.host42.cpu 0.5"] {
+	///     publisher.send(body.as_bytes())?;
+	/// }
+	///
+	/// // The topic ends at the first space, which the pattern can't express.
+	/// let msg = loop {
+	///     let msg = subscriber.recv()?;
+	///     let topic = msg.split(|&b| b == b' ').next().unwrap();
+	///     if matcher.matches(topic) {
+	///         break msg;
+	///     }
+	/// };
+	/// assert_eq!(&msg[..], b"metrics.host42.cpu 0.5");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn subscribe_patterns(&self, matcher: &TopicMatcher) -> Result<()>
+	{
+		matcher.nng_prefixes().iter().try_for_each(|prefix| self.subscribe(prefix))
+	}
+
+	/// Removes a subscription made with `Socket::subscribe`.
+	///
+	/// Returns `Error::NotSupported` if the socket does not use the _sub_
+	/// protocol and `Error::EntryNotFound` if there is no such subscription.
+	pub fn unsubscribe(&self, topic: &[u8]) -> Result<()>
+	{
+		self.require_protocol(Protocol::Sub0)?;
+		self.set_opt::<Unsubscribe>(topic.to_vec())
+	}
+
+	/// Sends a survey on a _surveyor_ socket and returns an iterator over the
+	/// responses.
+	///
+	/// The iterator ends once the survey expires, see `SurveyResponses`. Sending
+	/// another survey on the socket ends the previous one, so the borrow of the
+	/// socket generally lasts as long as the survey. Returns
+	/// `Error::NotSupported` if the socket does not use the _surveyor_
+	/// protocol.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::{options::{Options, protocol::survey::SurveyTime}, *};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/survey";
+	///
+	/// let surveyor = Socket::new(Protocol::Surveyor0)?;
+	/// surveyor.set_opt::<SurveyTime>(Some(Duration::from_millis(100)))?;
+	/// surveyor.listen(ADDRESS)?;
+	///
+	/// let respondent = Socket::new(Protocol::Respondent0)?;
+	/// respondent.dial(ADDRESS)?;
+	/// let _worker = RespondentWorker::new(&respondent, |survey| Some(survey))?;
+	///
+	/// let responses = surveyor.survey(&b"ping"[..])?.collect::<Result<Vec<_>>>()?;
+	/// assert_eq!(responses.len(), 1);
+	/// assert_eq!(&responses[0][..], b"ping");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn survey<M: Into<Message>>(&self, msg: M) -> Result<SurveyResponses<'_>>
+	{
+		self.require_protocol(Protocol::Surveyor0)?;
+
+		self.send(msg)?;
+		Ok(SurveyResponses::new(self))
+	}
+
 	/// Sends a message on the socket.
 	///
 	/// The semantics of what sending a message means vary from protocol to
@@ -185,19 +1429,82 @@ impl Socket
 	/// have first received a request.
 	///
 	/// If the message cannot be sent, then it is returned to the caller as a
-	/// part of the `Error`.
+	/// part of the `SendError`.
+	///
+	/// ## Failed Sends
+	///
+	/// NNG leaves the message with the caller whenever the send fails, no
+	/// matter the error, so the message always comes back intact. That
+	/// includes closing the socket while a send is waiting, where a _req_
+	/// socket has already taken the message out of the operation and puts it
+	/// back. A message that was sent moves into this call, so it can't be used
+	/// afterwards:
+	///
+	/// ```compile_fail
+	/// use nng::{Message, Protocol, Socket};
+	///
+	/// let push = Socket::new(Protocol::Push0).unwrap();
+	/// let msg = Message::from(&b"sent"[..]);
+	/// let _ = push.send(msg);
+	/// println!("{}", msg.len()); // Won't compile
+	/// ```
+	///
+	/// A _req_ socket without peers waits for one, until the socket is closed:
+	///
+	/// ```
+	/// use std::{thread, time::Duration};
+	/// use nng::*;
+	///
+	/// let req = Socket::new(Protocol::Req0)?;
+	/// let closer = req.clone();
+	/// let thread = thread::spawn(move || {
+	///     thread::sleep(Duration::from_millis(50));
+	///     closer.close();
+	/// });
+	///
+	/// let err = req.send(&b"waiting"[..]).unwrap_err();
+	/// assert_eq!(err.error, Error::Closed);
+	/// assert_eq!(&err.message[..], b"waiting");
+	/// thread.join().unwrap();
+	///
+	/// // Once closed, the socket refuses messages right away and returns them too.
+	/// let err = req.send(&b"late"[..]).unwrap_err();
+	/// assert_eq!(err.error, Error::Closed);
+	/// assert_eq!(&err.message[..], b"late");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	///
+	/// ## Reusing Messages
+	///
+	/// Once a message has been sent, it belongs to NNG, which frees it after
+	/// delivery. NNG v1.1 has no way of handing the allocation back, so loops
+	/// sending many messages allocate one for every send. What can be reused
+	/// are received messages, which are as good as new once cleared: a server
+	/// can answer with the request (see `Aio::stash`) and, over `inproc`, a
+	/// receiver gets the very allocation that was sent and can hand it back
+	/// to the sender. The `send_alloc` example compares these approaches.
 	pub fn send<M: Into<Message>>(&self, msg: M) -> SendResult<()>
 	{
-		let msg = msg.into();
+		self.send_blocking(msg.into(), !self.nonblocking)
+	}
 
-		let flags = if self.nonblocking { nng_sys::NNG_FLAG_NONBLOCK } else { 0 };
+	/// Sends a message, blocking only if asked to, whatever the setting of
+	/// this handle.
+	pub(crate) fn send_blocking(&self, msg: Message, block: bool) -> SendResult<()>
+	{
+		let flags = if block { 0 } else { nng_sys::NNG_FLAG_NONBLOCK };
 
 		unsafe {
 			let msgp = msg.into_ptr();
 			let rv = nng_sys::nng_sendmsg(self.inner.handle, msgp.as_ptr(), flags as c_int);
 
+			// NNG only takes the message when the send succeeds and leaves it with the caller on
+			// every error, which holds for all of the protocols and error paths of NNG 1.1, so
+			// the message can be taken back no matter the code. The AIO sends can't rely on this,
+			// see `AioResult::SendErrLost`.
 			if rv != 0 {
-				Err((Message::from_ptr(msgp), Error::from_code(rv as u32)))
+				let error = Error::from_code(rv as u32);
+				Err(SendError { message: Message::from_ptr(msgp), error })
 			}
 			else {
 				Ok(())
@@ -205,11 +1512,129 @@ impl Socket
 		}
 	}
 
+	/// Seals the message and sends it on the socket.
+	///
+	/// This is `Message::seal` followed by `Socket::send`. The peer should
+	/// receive the message with `Socket::recv_sealed`. If the message cannot
+	/// be sealed or sent it is returned to the caller, possibly sealed.
+	pub fn send_sealed<M: Into<Message>>(&self, msg: M) -> SendResult<()>
+	{
+		let mut msg = msg.into();
+		match msg.seal() {
+			Ok(()) => self.send(msg),
+			Err(e) => Err(SendError { message: msg, error: e }),
+		}
+	}
+
+	/// Receives a message from the socket and verifies its integrity trailer.
+	///
+	/// This is `Socket::recv` followed by `Message::verify_and_unseal`.
+	/// Messages that fail the check are dropped and
+	/// `Error::IntegrityCheckFailed` is returned.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::{Error, Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/sealed";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// push.send_sealed(&b"checked"[..])?;
+	/// assert_eq!(&pull.recv_sealed()?[..], b"checked");
+	///
+	/// // A message sent without the trailer is rejected.
+	/// push.send(&b"unchecked"[..])?;
+	/// assert_eq!(pull.recv_sealed().unwrap_err(), Error::IntegrityCheckFailed);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn recv_sealed(&self) -> Result<Message>
+	{
+		let mut msg = self.recv()?;
+		msg.verify_and_unseal()?;
+		Ok(msg)
+	}
+
+	/// Compresses the message and sends it on the socket.
+	///
+	/// This is `Message::compress` followed by `Socket::send`, so messages
+	/// that don't get any smaller are sent uncompressed. The peer should
+	/// receive the message with `Socket::recv_maybe_compressed`. If the message
+	/// cannot be compressed or sent it is returned to the caller, possibly
+	/// compressed.
+	#[cfg(feature = "compression")]
+	pub fn send_compressed<M: Into<Message>>(&self, msg: M, algo: Compression) -> SendResult<()>
+	{
+		let mut msg = msg.into();
+		match msg.compress(algo) {
+			Ok(()) => self.send(msg),
+			Err(e) => Err(SendError { message: msg, error: e }),
+		}
+	}
+
+	/// Receives a message from the socket and decompresses it if necessary.
+	///
+	/// This is `Socket::recv` followed by `Message::decompress`, so messages
+	/// from peers that don't compress are received as they are. Messages that
+	/// fail to decompress, including those that would be larger than 1 MiB,
+	/// are dropped and the error is returned. Use `Message::decompress_limited`
+	/// for a different limit.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// # #[cfg(feature = "compression-zstd")] {
+	/// use nng::{Compression, Error, Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/compressed";
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen(ADDRESS)?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial(ADDRESS)?;
+	///
+	/// let text = "All work and no play makes Jack a dull boy. ".repeat(100);
+	/// push.send_compressed(text.as_bytes(), Compression::Zstd)?;
+	/// assert_eq!(&pull.recv_maybe_compressed()?[..], text.as_bytes());
+	///
+	/// // Messages from peers that don't compress are passed through.
+	/// push.send(&b"plain"[..])?;
+	/// assert_eq!(&pull.recv_maybe_compressed()?[..], b"plain");
+	///
+	/// // A few kilobytes that would decompress to 100 MiB are rejected.
+	/// let bomb = vec![0; 100 * 1024 * 1024];
+	/// push.send_compressed(&bomb[..], Compression::Zstd)?;
+	/// assert_eq!(
+	///     pull.recv_maybe_compressed().unwrap_err(),
+	///     Error::MessageExceedsLimit { size: bomb.len(), limit: 1024 * 1024 }
+	/// );
+	/// # }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	#[cfg(feature = "compression")]
+	pub fn recv_maybe_compressed(&self) -> Result<Message>
+	{
+		let mut msg = self.recv()?;
+		msg.decompress()?;
+		Ok(msg)
+	}
+
 	/// Receive a message using the socket asynchronously.
 	///
 	/// This function will return immediately. If there is already an I/O
 	/// operation in progress that is _not_ a receive operation, this function
 	/// will return `Error::TryAgain`.
+	///
+	/// There is no variant of this that receives into a supplied message. The
+	/// stream transports of NNG allocate every received message themselves,
+	/// sized by the frame that arrived, so a supplied message could only ever
+	/// be copied into. See `Socket::recv` for the details. An AIO server gets
+	/// to one allocation per request by answering with the request message
+	/// itself, cleared and refilled, rather than with a new one.
 	pub fn recv_async(&self, aio: &Aio) -> Result<()> { aio.recv_socket(self) }
 
 	/// Send a message using the socket asynchronously.
@@ -223,6 +1648,82 @@ impl Socket
 		aio.send_socket(self, msg)
 	}
 
+	/// Sends a message and returns a token that tells when the send completes.
+	///
+	/// A successful `Socket::send` only means that the protocol has taken the
+	/// message, which for _push_ and _pub_ sockets may still sit in a send
+	/// buffer. This instead sends the message through an AIO and does not wait
+	/// for it: `SendToken::wait` blocks until the AIO completes, which is when
+	/// the message has been accepted, and reports the error along with the
+	/// message if it could not be sent. Without a send buffer and with
+	/// nobody connected, the send of a _push_ socket does not complete.
+	///
+	/// The AIOs are kept in a pool that is shared by all clones of the socket,
+	/// which limits the number of tokens that can be outstanding at a time to
+	/// 64, unless changed with `Socket::set_tracked_send_limit`. Once the limit
+	/// is reached, this blocks until a token is resolved or dropped, or, if the
+	/// socket is nonblocking, fails with `Error::TryAgain` and returns the
+	/// message.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::*;
+	///
+	/// let pull = Socket::new(Protocol::Pull0)?;
+	/// pull.listen("inproc://nng/socket/send_tracked")?;
+	/// let push = Socket::new(Protocol::Push0)?;
+	/// push.dial("inproc://nng/socket/send_tracked")?;
+	///
+	/// let mut token = push.send_tracked(&b"tracked"[..])?;
+	/// assert_eq!(token.wait(Some(Duration::from_secs(5))), Ok(()));
+	/// assert_eq!(&pull.recv()?[..], b"tracked");
+	///
+	/// // Without a peer the send is never accepted, but nothing is lost.
+	/// let mut lonely = Socket::new(Protocol::Push0)?;
+	/// let mut token = lonely.send_tracked(&b"lost?"[..])?;
+	/// assert_eq!(token.wait(Some(Duration::from_millis(50))), Err(Error::TimedOut));
+	/// assert_eq!(&token.take_message().unwrap()[..], b"lost?");
+	///
+	/// // Only one send can be outstanding once the limit is lowered.
+	/// lonely.set_tracked_send_limit(1);
+	/// lonely.set_nonblocking(true);
+	/// let pending = lonely.send_tracked(&b"first"[..])?;
+	/// let err = lonely.send_tracked(&b"second"[..]).unwrap_err();
+	/// assert_eq!(err.error, Error::TryAgain);
+	/// drop(pending);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn send_tracked<M: Into<Message>>(&self, msg: M) -> SendResult<SendToken>
+	{
+		self.inner.tracked.send(self, msg.into(), self.nonblocking)
+	}
+
+	/// Changes the number of tracked sends that can be outstanding at a time.
+	///
+	/// See `Socket::send_tracked`. The limit applies to all clones of the
+	/// socket and a limit of zero is treated as one. Lowering it does not
+	/// affect the sends that are already running.
+	pub fn set_tracked_send_limit(&self, limit: usize) { self.inner.tracked.set_limit(limit) }
+
+	/// Returns a future that receives a message on the socket.
+	///
+	/// The receive starts when the future is first polled. See the `future`
+	/// module for how it interacts with executors.
+	#[cfg(feature = "async-api")]
+	pub fn recv_future(&self) -> RecvFuture { RecvFuture::socket(self) }
+
+	/// Returns a future that sends the message on the socket.
+	///
+	/// The send starts when the future is first polled. See `SendFuture` for
+	/// what happens to the message when the send fails.
+	#[cfg(feature = "async-api")]
+	pub fn send_future<M: Into<Message>>(&self, msg: M) -> SendFuture
+	{
+		SendFuture::socket(self, msg.into())
+	}
+
 	/// Register a callback function to be called whenever a pipe event occurs
 	/// on the socket.
 	///
@@ -231,6 +1732,49 @@ impl Socket
 	/// error is returned, then the callback could have been registered for a
 	/// subset of the events.
 	///
+	/// ## Concurrency
+	///
+	/// The callback is invoked without holding any locks, so it may be running
+	/// on several threads at once when pipes are added or removed concurrently.
+	/// Registering a new callback never waits for running invocations of the
+	/// old one to finish. As a consequence, the old callback may still be
+	/// called for events that were already being dispatched when it was
+	/// replaced.
+	///
+	/// ```
+	/// use std::{sync::{atomic::{AtomicUsize, Ordering}, Arc}, thread};
+	/// use nng::{Protocol, Socket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/pipe_notify/stress";
+	///
+	/// let server = Socket::new(Protocol::Pair1)?;
+	/// server.listen(ADDRESS)?;
+	///
+	/// let events = Arc::new(AtomicUsize::new(0));
+	/// let dialers: Vec<_> = (0..50)
+	///     .map(|_| {
+	///         thread::spawn(|| {
+	///             for _ in 0..10 {
+	///                 let client = Socket::new(Protocol::Pair1).unwrap();
+	///                 let _ = client.dial(ADDRESS);
+	///                 client.close();
+	///             }
+	///         })
+	///     })
+	///     .collect();
+	///
+	/// // Keep replacing the callback while the pipes come and go.
+	/// for _ in 0..100 {
+	///     let events = Arc::clone(&events);
+	///     server.pipe_notify(move |_, _| { events.fetch_add(1, Ordering::Relaxed); })?;
+	/// }
+	///
+	/// for d in dialers {
+	///     d.join().unwrap();
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	///
 	/// ## Panicking
 	///
 	/// If the callback function panics, the program will abort. This is to
@@ -239,17 +1783,73 @@ impl Socket
 	/// produce the abort regardless of which version of Rustc is being used.
 	///
 	/// The user is responsible for either having a callback that never panics
-	/// or catching and handling the panic within the callback.
+	/// or catching and handling the panic within the callback. Test harnesses
+	/// can capture the panics instead with `panic_policy::set`.
 	pub fn pipe_notify<F>(&self, callback: F) -> Result<()>
 	where
 		F: Fn(Pipe, PipeEvent) + Send + Sync + 'static,
 	{
 		// Place the new callback into the inner portion.
 		{
-			let mut l = self.inner.pipe_notify.lock().unwrap();
+			let mut l = self.inner.pipe_notify.write().unwrap();
 			*l = Some(Arc::new(callback));
 		}
 
+		self.register_pipe_notify()
+	}
+
+	/// Registers a watcher for the pipe events of the socket.
+	///
+	/// Watchers are used by this crate to follow pipe events without taking
+	/// the user's callback slot. They are called after the user's callback and
+	/// are removed once the watcher is dropped.
+	pub(crate) fn watch_pipes(&self, watcher: &Arc<PipeNotifyFn>) -> Result<()>
+	{
+		{
+			let mut l = self.inner.pipe_watchers.lock().unwrap();
+			l.retain(|w| w.strong_count() > 0);
+			l.push(Arc::downgrade(watcher));
+		}
+
+		self.register_pipe_notify()
+	}
+
+	/// Registers a dialer that is closed once its connection is lost.
+	///
+	/// The registry and its pipe watcher are only set up for the first such
+	/// dialer of the socket.
+	pub(crate) fn add_one_shot_dialer(&self, dialer: Dialer) -> Result<()>
+	{
+		let mut one_shot = self.inner.one_shot.lock().unwrap();
+		if one_shot.is_none() {
+			let dialers = Arc::new(OneShotDialers::default());
+			let watcher_dialers = Arc::clone(&dialers);
+			let watcher: Arc<PipeNotifyFn> =
+				Arc::new(move |pipe, ev| watcher_dialers.pipe_event(pipe, ev));
+			self.watch_pipes(&watcher)?;
+			*one_shot = Some((dialers, watcher));
+		}
+
+		let dialers = one_shot.as_ref().map(|(dialers, _)| Arc::clone(dialers));
+		drop(one_shot);
+
+		if let Some(dialers) = dialers {
+			dialers.add(dialer);
+		}
+		Ok(())
+	}
+
+	/// Unregisters a dialer added with `Socket::add_one_shot_dialer`.
+	pub(crate) fn remove_one_shot_dialer(&self, dialer: &Dialer)
+	{
+		if let Some((dialers, _)) = &*self.inner.one_shot.lock().unwrap() {
+			dialers.remove(dialer);
+		}
+	}
+
+	/// Points the NNG pipe notifications of the socket at the trampoline.
+	fn register_pipe_notify(&self) -> Result<()>
+	{
 		// Because we're going to override the stored closure, we absolutely need to try
 		// and set the callback function for every single event. We cannot return
 		// early or we risk nng trying to call into a closure that has been freed.
@@ -276,26 +1876,155 @@ impl Socket
 			.fold(Ok(()), std::result::Result::and)
 	}
 
+	/// Closes the pipe of this socket with the given ID.
+	///
+	/// This forcibly disconnects a single peer, for example a misbehaving
+	/// client identified from its pipe statistics, without needing to hold on
+	/// to the `Pipe` from a pipe notification. The peer observes the
+	/// disconnect just as if the connection had been lost, so a dialing peer
+	/// will normally reconnect. Messages queued for the pipe may be lost.
+	///
+	/// Returns `Error::EntryNotFound` if the socket has no open pipe with that
+	/// ID, including when the pipe has already been closed or belongs to a
+	/// different socket.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{sync::{mpsc, Mutex}, time::Duration};
+	/// use nng::*;
+	///
+	/// const ADDRESS: &str = "inproc://nng/socket/close_pipe";
+	///
+	/// let server = Socket::new(Protocol::Pair0)?;
+	/// let (tx, rx) = mpsc::channel();
+	/// let tx = Mutex::new(tx);
+	/// server.pipe_notify(move |pipe, ev| {
+	///     if ev == PipeEvent::AddPost {
+	///         tx.lock().unwrap().send(pipe.id()).unwrap();
+	///     }
+	/// })?;
+	/// server.listen(ADDRESS)?;
+	///
+	/// let client = Socket::new(Protocol::Pair0)?;
+	/// let (client_tx, client_rx) = mpsc::channel();
+	/// let client_tx = Mutex::new(client_tx);
+	/// client.pipe_notify(move |pipe, ev| {
+	///     client_tx.lock().unwrap().send((pipe.id(), ev)).unwrap();
+	/// })?;
+	/// client.dial(ADDRESS)?;
+	///
+	/// let id = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+	/// server.close_pipe(id)?;
+	///
+	/// // The client sees its side of the connection go away.
+	/// let timeout = Duration::from_secs(1);
+	/// let (client_id, _) = client_rx.recv_timeout(timeout).unwrap();
+	/// assert_eq!(client_rx.recv_timeout(timeout).unwrap(), (client_id, PipeEvent::AddPost));
+	/// assert_eq!(client_rx.recv_timeout(timeout).unwrap(), (client_id, PipeEvent::RemovePost));
+	///
+	/// // Pipes of other sockets can't be closed through this one.
+	/// assert_eq!(server.close_pipe(client_id), Err(Error::EntryNotFound));
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn close_pipe(&self, id: i32) -> Result<()>
+	{
+		if id <= 0 {
+			return Err(Error::EntryNotFound);
+		}
+
+		// NNG has no function for looking up a pipe, but the handle is nothing more than
+		// the ID, which Bindgen exposes as an opaque field.
+		let pipe = nng_sys::nng_pipe { _bindgen_opaque_blob: id as u32 };
+		let owner = unsafe { nng_sys::nng_socket_id(nng_sys::nng_pipe_socket(pipe)) };
+		if owner != unsafe { nng_sys::nng_socket_id(self.inner.handle) } {
+			return Err(Error::EntryNotFound);
+		}
+
+		let rv = unsafe { nng_sys::nng_pipe_close(pipe) };
+		rv2res!(rv)
+	}
+
 	/// Close the underlying socket.
 	///
-	/// Messages that have been submitted for sending may be flushed or
-	/// delivered depending on the transport and the linger option. Further
-	/// attempts to use the socket (via this handle or any other) after this
-	/// call returns will result in an error. Threads waiting for operations on
-	/// the socket when this call is executed may also return with an error.
+	/// Further attempts to use the socket (via this handle or any other) after
+	/// this call returns will result in an error. Threads waiting for
+	/// operations on the socket when this call is executed may also return
+	/// with an error.
+	///
+	/// ## Queued Messages
 	///
-	/// Closing the socket while data is in transmission will likely lead to
-	/// loss of that data. There is no automatic linger or flush to ensure that
-	/// the socket send buffers have completely transmitted. It is recommended
-	/// to wait a brief period after sending data before calling this function.
+	/// A successful send only means that the message was accepted by the
+	/// socket, not that it has been transmitted. Closing the socket discards
+	/// any messages still waiting in the send buffers of the socket or its
+	/// pipes, and messages in the middle of being written may be cut off.
+	///
+	/// NNG v1.1 has no linger option and does not report how many messages
+	/// are still queued, so this crate cannot offer a `flush` that waits for
+	/// the buffers to drain. Applications that must not lose the last few
+	/// messages should have the peer acknowledge them (for example, by using
+	/// the _req_/_rep_ protocols) and only close the socket after the
+	/// acknowledgement arrives. Otherwise, waiting a brief period after the
+	/// last send makes loss less likely but does not prevent it.
 	///
 	/// This function will be called automatically when all handles have been
 	/// dropped.
+	///
+	/// ## Use After Close
+	///
+	/// Once the socket is closed, every operation on it, on its other handles,
+	/// and on the contexts, dialers, and listeners created from it returns
+	/// `Error::Closed`, including getting and setting options. Closing and
+	/// dropping any of those objects afterwards is harmless.
+	///
+	/// ```
+	/// use nng::options::{Options, RecvTimeout, Url};
+	/// use nng::*;
+	///
+	/// let socket = Socket::new(Protocol::Req0)?;
+	/// let clone = socket.clone();
+	/// let ctx = Context::new(&socket)?;
+	/// let dialer = Dialer::new(&socket, "inproc://nng/socket/closed/dialer", true)?;
+	/// let listener = Listener::new(&socket, "inproc://nng/socket/closed/listener", false)?;
+	/// let options = DialerOptions::new(&socket, "inproc://nng/socket/closed/options")?;
+	///
+	/// socket.close();
+	///
+	/// assert_eq!(clone.send(&b"hello"[..]).unwrap_err().error, Error::Closed);
+	/// assert_eq!(clone.recv().unwrap_err(), Error::Closed);
+	/// assert_eq!(clone.dial("inproc://nng/socket/closed/other"), Err(Error::Closed));
+	/// assert_eq!(clone.listen("inproc://nng/socket/closed/other"), Err(Error::Closed));
+	/// assert_eq!(clone.get_opt::<RecvTimeout>(), Err(Error::Closed));
+	/// assert_eq!(clone.set_opt::<RecvTimeout>(None), Err(Error::Closed));
+	/// assert_eq!(Context::new(&clone).unwrap_err(), Error::Closed);
+	///
+	/// let aio = Aio::new_manual()?;
+	/// ctx.recv(&aio)?;
+	/// aio.wait();
+	/// match aio.result() {
+	///     Some(AioResult::RecvErr(e)) => assert_eq!(e, Error::Closed),
+	///     res => panic!("Unexpected result: {:?}", res),
+	/// }
+	///
+	/// assert_eq!(dialer.get_opt::<Url>(), Err(Error::Closed));
+	/// assert_eq!(listener.get_opt::<Url>(), Err(Error::Closed));
+	/// assert_eq!(options.get_opt::<Url>(), Err(Error::Closed));
+	/// assert_eq!(options.start(false).unwrap_err().1, Error::Closed);
+	///
+	/// ctx.close();
+	/// dialer.close();
+	/// listener.close();
+	/// # Ok::<(), nng::Error>(())
+	/// ```
 	pub fn close(&self) { self.inner.close() }
 
 	/// Returns the underlying `nng_socket`.
 	pub(crate) fn handle(&self) -> nng_sys::nng_socket { self.inner.handle }
 
+	/// Returns the messages received by dropped futures, see `RecvFuture`.
+	#[cfg(feature = "async-api")]
+	pub(crate) fn pre_received(&self) -> &Arc<PreReceived> { &self.inner.pre_received }
+
 	/// Trampoline function for calling the pipe event closure from C.
 	///
 	/// This is unsafe because you have to be absolutely positive that you
@@ -303,27 +2032,48 @@ impl Socket
 	extern "C" fn trampoline(pipe: nng_sys::nng_pipe, ev: i32, arg: *mut c_void)
 	{
 		let res = catch_unwind(|| unsafe {
+			#[cfg(feature = "tracing")]
+			let id = nng_sys::nng_pipe_id(pipe);
 			let pipe = Pipe::from_nng_sys(pipe);
 			let ev = PipeEvent::from_code(ev);
 
+			// Reading the remote address is comparatively expensive, so only do it
+			// when someone is going to see it.
+			#[cfg(feature = "tracing")]
+			{
+				if tracing::enabled!(tracing::Level::DEBUG) {
+					use crate::options::{Options, RemAddr};
+					let remote = pipe.get_opt::<RemAddr>().ok();
+					tracing::debug!(pipe = id, event = ?ev, remote = ?remote, "pipe.event");
+				}
+			}
+
 			assert!(!arg.is_null(), "Null pointer passed as argument to trampoline");
 			let inner = &*(arg as *const _ as *const Inner);
 			let callback = {
 				// Don't hold the lock during the callback, just long enough to increment
-				// the Arc's counter.
-				let l = inner.pipe_notify.lock().unwrap();
-				match l.as_ref() {
-					Some(c) => Arc::clone(c),
-					None => return,
-				}
+				// the Arc's counter. Using a read lock means that concurrent pipe events
+				// don't serialize on each other either.
+				let l = inner.pipe_notify.read().unwrap();
+				l.as_ref().map(Arc::clone)
 			};
+			let watchers: Vec<_> =
+				inner.pipe_watchers.lock().unwrap().iter().filter_map(Weak::upgrade).collect();
 
-			(*callback)(pipe, ev)
+			// A captured panic only skips the callback that panicked.
+			let handler = panic_policy::handler();
+			let source = || PanicSource::PipeNotify(pipe, ev);
+			if let Some(callback) = callback {
+				panic_policy::run(handler.as_deref(), || (*callback)(pipe, ev), source);
+			}
+			for watcher in watchers {
+				panic_policy::run(handler.as_deref(), || (*watcher)(pipe, ev), source);
+			}
 		});
 
 		// See #6 for a "discussion" about why we abort.
 		if res.is_err() {
-			error!("Panic in pipe notify callback function");
+			log_error!("Panic in pipe notify callback function");
 			std::process::abort();
 		}
 	}
@@ -387,7 +2137,8 @@ expose_options!{
 	SETOPT_SIZE = nng_sys::nng_setopt_size;
 	SETOPT_STRING = nng_sys::nng_setopt_string;
 
-	Gets -> [Raw, MaxTtl, RecvBufferSize,
+	Gets -> [Raw, MaxTtl, ReconnectMinTime,
+	         ReconnectMaxTime, RecvBufferSize, RecvMaxSize,
 	         RecvTimeout, SendBufferSize,
 	         SendTimeout, SocketName,
 	         protocol::pair::Polyamorous,
@@ -429,8 +2180,28 @@ struct Inner
 	/// Handle to the underlying nng socket.
 	handle: nng_sys::nng_socket,
 
+	/// The protocol the socket was opened with.
+	protocol: ProtocolKind,
+
 	/// The current pipe event callback.
-	pipe_notify: Mutex<Option<Arc<PipeNotifyFn>>>,
+	///
+	/// The lock is only ever held long enough to clone or replace the `Arc`,
+	/// never while the callback is running.
+	pipe_notify: RwLock<Option<Arc<PipeNotifyFn>>>,
+
+	/// The pipe watchers registered by this crate, see `Socket::watch_pipes`.
+	pipe_watchers: Mutex<Vec<Weak<PipeNotifyFn>>>,
+
+	/// The AIOs used by `Socket::send_tracked`.
+	tracked: Arc<SendPool>,
+
+	/// The dialers started with `DialerOptions::no_reconnect` and the pipe
+	/// watcher that follows them, once there has been one.
+	one_shot: Mutex<Option<(Arc<OneShotDialers>, Arc<PipeNotifyFn>)>>,
+
+	/// The messages received by dropped futures, see `RecvFuture`.
+	#[cfg(feature = "async-api")]
+	pre_received: Arc<PreReceived>,
 }
 impl Inner
 {
@@ -455,7 +2226,9 @@ impl fmt::Debug for Inner
 	{
 		f.debug_struct("Inner")
 			.field("handle", &self.handle)
-			.field("pipe_notify", &self.pipe_notify.lock().unwrap().is_some())
+			.field("protocol", &self.protocol)
+			.field("pipe_notify", &self.pipe_notify.read().unwrap().is_some())
+			.field("pipe_watchers", &self.pipe_watchers.lock().unwrap().len())
 			.finish()
 	}
 }
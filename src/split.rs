@@ -0,0 +1,95 @@
+//! The sending and receiving halves of a socket.
+use std::time::Duration;
+
+use crate::{
+	error::{Result, SendResult},
+	message::Message,
+	socket::Socket,
+	transport::MessageTransport,
+};
+
+/// The sending half of a socket, created with `Socket::split`.
+///
+/// This can only send, and decides with every call whether to block instead
+/// of following the nonblocking setting of a socket handle. `SendHalf::send`
+/// blocks until the message has been accepted, honoring the `SendTimeout` of
+/// the socket, while `SendHalf::try_send` fails with `Error::TryAgain`
+/// right away if it can't be.
+///
+/// The half shares the socket with the handle it was split from and all of
+/// its clones, so it keeps the socket open and `Socket::close` closes it for
+/// everyone. Halves can be cloned to send from several threads.
+#[derive(Clone, Debug)]
+pub struct SendHalf
+{
+	/// The socket the messages are sent on.
+	socket: Socket,
+}
+
+impl SendHalf
+{
+	/// Creates the sending half of the socket.
+	pub(crate) fn new(socket: Socket) -> Self { SendHalf { socket } }
+
+	/// Sends the message, blocking until it has been accepted.
+	///
+	/// The message is returned along with the error if it could not be sent.
+	pub fn send<M: Into<Message>>(&self, msg: M) -> SendResult<()>
+	{
+		self.socket.send_blocking(msg.into(), true)
+	}
+
+	/// Sends the message without blocking.
+	///
+	/// If the socket can't accept the message right away, this fails with
+	/// `Error::TryAgain` and returns the message along with the error.
+	pub fn try_send<M: Into<Message>>(&self, msg: M) -> SendResult<()>
+	{
+		self.socket.send_blocking(msg.into(), false)
+	}
+}
+
+/// The receiving half of a socket, created with `Socket::split`.
+///
+/// This can only receive, and decides with every call whether to block
+/// instead of following the nonblocking setting of a socket handle. It
+/// shares the socket in the same way as `SendHalf`.
+///
+/// In particular, it has nothing to send with:
+///
+/// ```compile_fail
+/// use nng::{Protocol, Socket};
+///
+/// let (_, recv) = Socket::new(Protocol::Pair1).unwrap().split();
+/// recv.send(&b"hello"[..]).unwrap(); // Won't compile
+/// ```
+#[derive(Clone, Debug)]
+pub struct RecvHalf
+{
+	/// The socket the messages are received from.
+	socket: Socket,
+}
+
+impl RecvHalf
+{
+	/// Creates the receiving half of the socket.
+	pub(crate) fn new(socket: Socket) -> Self { RecvHalf { socket } }
+
+	/// Receives a message, blocking until one arrives.
+	///
+	/// This honors the `RecvTimeout` of the socket.
+	pub fn recv(&self) -> Result<Message> { self.socket.recv_blocking(true) }
+
+	/// Receives a message without blocking.
+	///
+	/// If no message is waiting, this fails with `Error::TryAgain`.
+	pub fn try_recv(&self) -> Result<Message> { self.socket.recv_blocking(false) }
+
+	/// Receives a message, giving up with `Error::TimedOut` after the timeout.
+	///
+	/// The timeout replaces the `RecvTimeout` of the socket for this call.
+	pub fn recv_timeout(&self, timeout: Duration) -> Result<Message>
+	{
+		MessageTransport::recv_timeout(&self.socket, timeout)
+	}
+}
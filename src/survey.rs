@@ -0,0 +1,243 @@
+//! Iterators over the responses to a survey.
+use std::time::{Duration, Instant};
+
+use crate::{
+	aio::{Aio, AioResult},
+	error::{Error, Result},
+	message::Message,
+	pipe::Pipe,
+	socket::Socket,
+};
+
+/// An iterator over the responses to a survey.
+///
+/// This is created by `Socket::survey` and receives responses until the
+/// survey expires, which happens once `SurveyTime` has passed. Receive
+/// errors other than the expiry of the survey are yielded once, after which
+/// the iterator ends. Blocking receives are used, so the receive timeout of
+/// the socket also ends the survey early, while a non-blocking socket ends
+/// it as soon as no response is queued.
+///
+/// Responses are yielded as they arrive. Nothing is deduplicated, so a peer
+/// that answers a survey more than once, such as a raw _respondent_, shows up
+/// once for each response.
+///
+/// An overall deadline can be set with `SurveyResponses::with_deadline`,
+/// which takes the place of the receive timeout and non-blocking setting.
+#[derive(Debug)]
+pub struct SurveyResponses<'s>
+{
+	/// The _surveyor_ socket the survey was sent on.
+	socket: &'s Socket,
+
+	/// The time at which the iterator stops waiting for responses, if any.
+	deadline: Option<Instant>,
+
+	/// The AIO used to receive until the deadline, created by the first
+	/// receive that needs it.
+	aio: Option<Aio>,
+
+	/// Whether the survey is over.
+	done: bool,
+}
+impl<'s> SurveyResponses<'s>
+{
+	/// Creates an iterator over the responses to the survey that was just sent.
+	pub(crate) fn new(socket: &'s Socket) -> Self
+	{
+		SurveyResponses { socket, deadline: None, aio: None, done: false }
+	}
+
+	/// Stops waiting for responses at the deadline.
+	///
+	/// Responses keep being yielded as they arrive and the iterator ends once
+	/// the deadline passes, even if there is time left on the survey. The
+	/// survey itself is left alone, so a response that arrives later is
+	/// received by the next receive on the socket, until another survey is
+	/// sent. The survey still ends early if `SurveyTime` runs out first, so
+	/// it needs to be at least as long as the deadline for every response to
+	/// be waited for. The receive timeout and the non-blocking setting of the
+	/// socket are not used.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::{thread, time::{Duration, Instant}};
+	/// use nng::{options::{Options, protocol::survey::SurveyTime}, *};
+	///
+	/// const ADDRESS: &str = "inproc://nng/survey/with_deadline";
+	///
+	/// // The survey would run for ten seconds.
+	/// let surveyor = Socket::new(Protocol::Surveyor0)?;
+	/// surveyor.set_opt::<SurveyTime>(Some(Duration::from_secs(10)))?;
+	/// surveyor.listen(ADDRESS)?;
+	///
+	/// // One respondent answers right away, the other one takes its time.
+	/// for &(name, delay) in &[("fast", 0), ("slow", 2000)] {
+	///     let respondent = Socket::new(Protocol::Respondent0)?;
+	///     respondent.dial(ADDRESS)?;
+	///     thread::spawn(move || {
+	///         while respondent.recv().is_ok() {
+	///             thread::sleep(Duration::from_millis(delay));
+	///             let _ = respondent.send(name.as_bytes());
+	///         }
+	///     });
+	/// }
+	///
+	/// let start = Instant::now();
+	/// let deadline = start + Duration::from_millis(200);
+	/// let responses = surveyor
+	///     .survey(&b"who is there?"[..])?
+	///     .with_deadline(deadline)
+	///     .collect::<Result<Vec<_>>>()?;
+	///
+	/// assert_eq!(responses.len(), 1);
+	/// assert_eq!(&responses[0][..], b"fast");
+	/// assert!(start.elapsed() >= Duration::from_millis(200));
+	/// assert!(start.elapsed() < Duration::from_secs(2));
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn with_deadline(mut self, deadline: Instant) -> Self
+	{
+		self.deadline = Some(deadline);
+		self
+	}
+
+	/// Turns this into an iterator that also yields the pipe each response
+	/// arrived on.
+	///
+	/// Respondents that are otherwise identical can be told apart by their
+	/// pipe, which is unique to each connection, or by the address of the
+	/// peer, as returned by `Pipe::remote_addr`.
+	pub fn with_peers(self) -> SurveyPeers<'s> { SurveyPeers { responses: self } }
+
+	/// Receives the next response and converts it with the provided function.
+	fn next_with<T, F>(&mut self, convert: F) -> Option<Result<T>>
+	where
+		F: FnOnce(Message) -> Result<T>,
+	{
+		if self.done {
+			return None;
+		}
+
+		match self.recv().and_then(convert) {
+			Ok(r) => Some(Ok(r)),
+
+			// Surveys that have expired fail the pending receive with a timeout and
+			// every later one with an incorrect state.
+			Err(Error::TimedOut) | Err(Error::IncorrectState) | Err(Error::TryAgain) => {
+				self.done = true;
+				None
+			},
+			Err(e) => {
+				self.done = true;
+				Some(Err(e))
+			},
+		}
+	}
+
+	/// Receives the next response, giving up at the deadline if there is one.
+	fn recv(&mut self) -> Result<Message>
+	{
+		let deadline = match self.deadline {
+			Some(d) => d,
+			None => return self.socket.recv(),
+		};
+
+		let remaining = deadline.saturating_duration_since(Instant::now());
+		if remaining == Duration::from_millis(0) {
+			return Err(Error::TimedOut);
+		}
+
+		if self.aio.is_none() {
+			self.aio = Some(Aio::new_manual()?);
+		}
+		let aio = self.aio.as_ref().expect("The AIO was just created");
+
+		// The clock of NNG only counts whole milliseconds, so an extra one makes sure
+		// the receive doesn't time out before the deadline.
+		aio.set_timeout(Some(remaining + Duration::from_millis(1)))?;
+		self.socket.recv_async(aio)?;
+		aio.wait();
+		match aio.result() {
+			Some(AioResult::RecvOk(m)) => Ok(m),
+			Some(AioResult::RecvErr(e)) => Err(e),
+			res => unreachable!("Receive AIO completed with a non-receive result: {:?}", res),
+		}
+	}
+}
+
+/// Pairs a response with the pipe it arrived on.
+fn with_pipe(mut msg: Message) -> Result<(Message, Pipe)>
+{
+	match msg.pipe() {
+		Some(p) => Ok((msg, p)),
+		None => {
+			log_error!("NNG received a message without a pipe");
+			Err(Error::Internal)
+		},
+	}
+}
+
+impl Iterator for SurveyResponses<'_>
+{
+	type Item = Result<Message>;
+
+	fn next(&mut self) -> Option<Self::Item> { self.next_with(Ok) }
+}
+
+/// An iterator over the responses to a survey along with the pipes they
+/// arrived on.
+///
+/// This is created by `SurveyResponses::with_peers` and otherwise behaves the
+/// same.
+///
+/// ## Example
+///
+/// ```
+/// use std::{collections::HashSet, thread, time::Duration};
+/// use nng::{options::{Options, protocol::survey::SurveyTime, Url}, *};
+///
+/// let surveyor = Socket::new(Protocol::Surveyor0)?;
+/// surveyor.set_opt::<SurveyTime>(Some(Duration::from_millis(300)))?;
+/// let listener = Listener::new(&surveyor, "tcp://127.0.0.1:0", false)?;
+/// let url = listener.get_opt::<Url>()?;
+///
+/// // The respondents are identical apart from their connection.
+/// let mut respondents = Vec::new();
+/// for _ in 0..3 {
+///     let socket = Socket::new(Protocol::Respondent0)?;
+///     socket.dial(&url)?;
+///     let worker = RespondentWorker::new(&socket, |_| Some(Message::from(&b"here"[..])))?;
+///     respondents.push((socket, worker));
+/// }
+///
+/// // Surveys are only sent to connected peers.
+/// thread::sleep(Duration::from_millis(100));
+///
+/// let mut pipes = HashSet::new();
+/// let mut addrs = HashSet::new();
+/// for res in surveyor.survey(&b"who is there?"[..])?.with_peers() {
+///     let (msg, pipe) = res?;
+///     assert_eq!(&msg[..], b"here");
+///     pipes.insert(pipe.id());
+///     addrs.insert(pipe.remote_addr()?);
+/// }
+///
+/// assert_eq!(pipes.len(), 3);
+/// assert_eq!(addrs.len(), 3);
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct SurveyPeers<'s>
+{
+	/// The responses the pipes are looked up for.
+	responses: SurveyResponses<'s>,
+}
+
+impl Iterator for SurveyPeers<'_>
+{
+	type Item = Result<(Message, Pipe)>;
+
+	fn next(&mut self) -> Option<Self::Item> { self.responses.next_with(with_pipe) }
+}
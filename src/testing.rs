@@ -0,0 +1,246 @@
+//! Support for unit-testing code that sends and receives messages.
+//!
+//! Code that is written against the `MessageTransport` trait instead of a
+//! `Socket` can be driven by a `MockTransport` in its tests. The mock follows a
+//! script of the sends it expects and the messages or errors it delivers, so
+//! the tests are deterministic and need no sockets, threads, or sleeps. This
+//! module is only available with the `testing` feature.
+//!
+//! ## Example
+//!
+//! The server of the `reqrep` example answers date requests with a function
+//! much like the following one, which can be tested on its own:
+//!
+//! ```
+//! use nng::{testing::MockTransport, Error, MessageTransport, Result};
+//!
+//! const DATE_REQUEST: u64 = 1;
+//!
+//! /// Answers a single date request, ignoring any other command.
+//! fn serve_one<T: MessageTransport>(transport: &T, now: u64) -> Result<()> {
+//!     let mut msg = transport.recv()?;
+//!     if msg.as_slice() != DATE_REQUEST.to_le_bytes() {
+//!         return Ok(());
+//!     }
+//!
+//!     msg.clear();
+//!     msg.push_back(&now.to_le_bytes())?;
+//!     transport.send(msg).map_err(Error::from)
+//! }
+//!
+//! let mock = MockTransport::new()
+//!     .deliver(&DATE_REQUEST.to_le_bytes()[..])
+//!     .expect_send(1234_u64.to_le_bytes())
+//!     .deliver(&b"unknown"[..])
+//!     .fail_recv(Error::TimedOut);
+//!
+//! serve_one(&mock, 1234)?;
+//! serve_one(&mock, 1234)?;
+//! assert_eq!(serve_one(&mock, 1234), Err(Error::TimedOut));
+//!
+//! mock.assert_done();
+//! mock.assert_sent(&[&1234_u64.to_le_bytes()]);
+//! # Ok::<(), nng::Error>(())
+//! ```
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use crate::{
+	error::{Error, Result, SendError, SendResult},
+	message::Message,
+	transport::MessageTransport,
+};
+
+/// A scripted stand-in for a socket.
+///
+/// The script is a list of steps that are taken one at a time, in the order in
+/// which they were added. Every send takes the next step, which has to be
+/// either an expected send, whose body has to match the body of the message,
+/// or a failed send. Every receive likewise takes a delivered message or a
+/// failed receive. An operation that doesn't match the next step panics, which
+/// fails the test that made it. The headers of messages are not compared.
+///
+/// Once the script is done, sends and receives fail with `Error::Closed`, as
+/// if the socket had been closed, so that loops receiving until an error come
+/// to an end. The timeouts given to the operations are ignored, as the script
+/// decides right away how each of them turns out: a step failing with
+/// `Error::TimedOut` stands in for a timeout.
+///
+/// All successfully sent messages are recorded and can be checked with
+/// `MockTransport::sent` or `MockTransport::assert_sent`.
+///
+/// ## Example
+///
+/// ```
+/// use nng::{testing::MockTransport, Error, Message, MessageTransport};
+///
+/// let mock = MockTransport::new()
+///     .fail_send(Error::TryAgain)
+///     .expect_send(b"retried");
+///
+/// let err = mock.send(Message::from(&b"retried"[..])).unwrap_err();
+/// assert_eq!(err.error, Error::TryAgain);
+/// mock.send(err.message)?;
+///
+/// assert_eq!(mock.recv().unwrap_err(), Error::Closed);
+/// assert_eq!(mock.sent().len(), 1);
+/// # Ok::<(), nng::Error>(())
+/// ```
+///
+/// Anything that doesn't follow the script panics:
+///
+/// ```should_panic
+/// use nng::{testing::MockTransport, Message, MessageTransport};
+///
+/// let mock = MockTransport::new().expect_send(b"hello");
+/// mock.send(Message::from(&b"goodbye"[..])).unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct MockTransport
+{
+	/// The script and the record of sent messages.
+	state: Mutex<MockState>,
+}
+
+/// The mutable state of a `MockTransport`.
+#[derive(Debug, Default)]
+struct MockState
+{
+	/// The steps that have yet to be taken.
+	script: VecDeque<Step>,
+
+	/// The messages that were sent successfully.
+	sent: Vec<Message>,
+}
+
+/// A single step of the script of a `MockTransport`.
+#[derive(Debug)]
+enum Step
+{
+	/// A send of a message with this body, which succeeds.
+	Send(Vec<u8>),
+
+	/// A send of any message, which fails with this error.
+	SendErr(Error),
+
+	/// A receive that returns this message.
+	Recv(Message),
+
+	/// A receive that fails with this error.
+	RecvErr(Error),
+}
+
+impl MockTransport
+{
+	/// Creates a mock with an empty script.
+	pub fn new() -> Self { MockTransport::default() }
+
+	/// Adds a send of a message with the body, which succeeds, to the script.
+	pub fn expect_send<B: AsRef<[u8]>>(self, body: B) -> Self
+	{
+		self.push(Step::Send(body.as_ref().to_vec()))
+	}
+
+	/// Adds a send of any message, which fails with the error, to the script.
+	///
+	/// The message is returned to the caller along with the error and is not
+	/// recorded as sent.
+	pub fn fail_send(self, error: Error) -> Self { self.push(Step::SendErr(error)) }
+
+	/// Adds a receive that returns the message to the script.
+	pub fn deliver<M: Into<Message>>(self, msg: M) -> Self { self.push(Step::Recv(msg.into())) }
+
+	/// Adds a receive that fails with the error to the script.
+	pub fn fail_recv(self, error: Error) -> Self { self.push(Step::RecvErr(error)) }
+
+	/// Returns copies of all messages that were sent successfully, in order.
+	pub fn sent(&self) -> Vec<Message> { self.state.lock().unwrap().sent.clone() }
+
+	/// Returns the number of steps of the script that have yet to be taken.
+	pub fn remaining(&self) -> usize { self.state.lock().unwrap().script.len() }
+
+	/// Panics unless the bodies of the sent messages are the expected ones.
+	pub fn assert_sent(&self, expected: &[&[u8]])
+	{
+		let sent: Vec<Vec<u8>> = self.sent().iter().map(|m| m.as_slice().to_vec()).collect();
+		let expected: Vec<Vec<u8>> = expected.iter().map(|b| b.to_vec()).collect();
+		assert_eq!(sent, expected, "Sent messages differ from the expected ones");
+	}
+
+	/// Panics unless every step of the script has been taken.
+	pub fn assert_done(&self)
+	{
+		let state = self.state.lock().unwrap();
+		if let Some(step) = state.script.front() {
+			let (remaining, step) = (state.script.len(), format!("{:?}", step));
+			drop(state);
+			panic!("{} steps of the script were not taken, starting with {}", remaining, step);
+		}
+	}
+
+	/// Adds a step to the end of the script.
+	fn push(self, step: Step) -> Self
+	{
+		self.state.lock().unwrap().script.push_back(step);
+		self
+	}
+
+	/// Takes the next step of the script, if it is one the test expects.
+	///
+	/// The lock is released before panicking, so that the mock can still be
+	/// inspected if the panic is caught.
+	fn take<T, F>(&self, op: &str, accept: F) -> Option<T>
+	where
+		F: FnOnce(&mut MockState, Step) -> std::result::Result<T, Step>,
+	{
+		let mut state = self.state.lock().unwrap();
+		let step = state.script.pop_front()?;
+		match accept(&mut state, step) {
+			Ok(res) => Some(res),
+			Err(step) => {
+				let msg = format!("Unexpected {}, the next step of the script is {:?}", op, step);
+				state.script.push_front(step);
+				drop(state);
+				panic!("{}", msg);
+			},
+		}
+	}
+}
+
+impl MessageTransport for MockTransport
+{
+	fn send(&self, msg: Message) -> SendResult<()>
+	{
+		let op = format!("send of {:?}", msg.as_slice());
+		let mut msg = Some(msg);
+		let res = self.take(&op, |state, step| match step {
+			Step::Send(ref body) if body[..] == msg.as_ref().unwrap()[..] => {
+				state.sent.push(msg.take().unwrap());
+				Ok(None)
+			},
+			Step::SendErr(e) => Ok(Some(e)),
+			step => Err(step),
+		});
+
+		match (res, msg) {
+			(Some(None), None) => Ok(()),
+			(Some(Some(error)), Some(message)) => Err(SendError { message, error }),
+			(None, Some(message)) => Err(SendError { message, error: Error::Closed }),
+			_ => unreachable!("Sent message went missing"),
+		}
+	}
+
+	fn recv(&self) -> Result<Message>
+	{
+		let res = self.take("receive", |_, step| match step {
+			Step::Recv(msg) => Ok(Ok(msg)),
+			Step::RecvErr(e) => Ok(Err(e)),
+			step => Err(step),
+		});
+
+		res.unwrap_or(Err(Error::Closed))
+	}
+
+	fn send_timeout(&self, msg: Message, _: Duration) -> SendResult<()> { self.send(msg) }
+
+	fn recv_timeout(&self, _: Duration) -> Result<Message> { self.recv() }
+}
@@ -0,0 +1,167 @@
+//! Conversions between Rust and NNG representations of time.
+//!
+//! NNG represents durations as a signed number of milliseconds, with a few
+//! negative values reserved for special meanings. This crate consistently
+//! represents those durations as an `Option<Duration>`, where `None` means
+//! that the operation never times out, while NNG's request for the default
+//! duration is kept apart by `from_nng_ms`. These functions perform the same
+//! conversions that the options and AIO types use internally, which is useful
+//! when working with `nng_sys` directly.
+//!
+//! See the [nng documentation][1] for more information.
+//!
+//! [1]: https://nanomsg.github.io/nng/man/v1.1.0/nng_duration.5.html
+use std::time::Duration;
+
+// The `nng-sys` crate only binds the platform supplement of NNG with its
+// `nng-supplemental` feature, which needs Bindgen at build time. Its functions
+// are always part of `libnng`, so the one used here is declared as in
+// `supplemental/util/platform.h`, where `nng_time` is a `uint64_t`.
+extern "C" {
+	fn nng_clock() -> u64;
+}
+
+/// Converts a Rust duration into an NNG duration in milliseconds.
+///
+/// `None` becomes `NNG_DURATION_INFINITE`. Durations longer than NNG can
+/// represent (`i32::MAX` milliseconds, a little under 25 days) saturate
+/// instead of wrapping. Any fractional millisecond is rounded up so that a
+/// non-zero duration never becomes the zero duration, which NNG treats as
+/// "time out immediately".
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nng::time::to_nng_ms;
+///
+/// assert_eq!(to_nng_ms(None), nng_sys::NNG_DURATION_INFINITE);
+/// assert_eq!(to_nng_ms(Some(Duration::from_millis(0))), nng_sys::NNG_DURATION_ZERO as i32);
+/// assert_eq!(to_nng_ms(Some(Duration::from_micros(1))), 1);
+/// assert_eq!(to_nng_ms(Some(Duration::from_millis(1500))), 1500);
+/// assert_eq!(to_nng_ms(Some(Duration::from_millis(i32::MAX as u64 + 1))), i32::MAX);
+/// assert_eq!(to_nng_ms(Some(Duration::from_secs(u64::MAX))), i32::MAX);
+/// ```
+#[allow(clippy::cast_possible_truncation)]
+pub fn to_nng_ms(dur: Option<Duration>) -> nng_sys::nng_duration
+{
+	match dur {
+		None => nng_sys::NNG_DURATION_INFINITE,
+		Some(d) => {
+			let partial = if d.subsec_nanos() % 1_000_000 != 0 { 1 } else { 0 };
+			let ms = d
+				.as_secs()
+				.checked_mul(1000)
+				.and_then(|ms| ms.checked_add(u64::from(d.subsec_millis()) + partial));
+
+			match ms {
+				Some(ms) if ms <= i32::MAX as u64 => ms as i32,
+				_ => i32::MAX,
+			}
+		},
+	}
+}
+
+/// Converts an NNG duration in milliseconds into a Rust duration.
+///
+/// `NNG_DURATION_INFINITE` becomes `Some(None)`, like the `Option<Duration>`
+/// taken by `to_nng_ms`. `NNG_DURATION_DEFAULT` asks NNG for whatever default
+/// applies, such as the timeout of the socket for an AIO, which isn't the same
+/// as never timing out, so it becomes `None`. NNG gives no meaning to the other
+/// negative values, which become `None` as well.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nng::time::{from_nng_ms, to_nng_ms};
+///
+/// assert_eq!(from_nng_ms(nng_sys::NNG_DURATION_INFINITE), Some(None));
+/// assert_eq!(from_nng_ms(nng_sys::NNG_DURATION_DEFAULT), None);
+/// let zero = nng_sys::NNG_DURATION_ZERO as i32;
+/// assert_eq!(from_nng_ms(zero), Some(Some(Duration::from_millis(0))));
+/// assert_eq!(from_nng_ms(250), Some(Some(Duration::from_millis(250))));
+///
+/// // Whole milliseconds survive the round trip.
+/// let dur = Some(Duration::from_millis(1234));
+/// assert_eq!(from_nng_ms(to_nng_ms(dur)), Some(dur));
+/// assert_eq!(from_nng_ms(to_nng_ms(None)), Some(None));
+/// ```
+pub fn from_nng_ms(ms: nng_sys::nng_duration) -> Option<Option<Duration>>
+{
+	match ms {
+		nng_sys::NNG_DURATION_INFINITE => Some(None),
+		ms if ms >= 0 => Some(Some(Duration::from_millis(ms as u64))),
+		_ => None,
+	}
+}
+
+/// Returns the current time of NNG's monotonic clock, in milliseconds.
+///
+/// This clock has an arbitrary starting point and is only useful for
+/// measuring elapsed time or for comparing against other values obtained from
+/// NNG.
+///
+/// ## Example
+///
+/// ```
+/// let start = nng::time::clock();
+/// std::thread::sleep(std::time::Duration::from_millis(10));
+/// assert!(nng::time::clock() >= start + 10);
+/// ```
+pub fn clock() -> u64 { unsafe { nng_clock() } }
+
+#[cfg(test)]
+mod tests
+{
+	use std::time::Duration;
+
+	use super::*;
+
+	#[test]
+	fn zero_stays_zero()
+	{
+		let zero = nng_sys::NNG_DURATION_ZERO as i32;
+		assert_eq!(to_nng_ms(Some(Duration::from_millis(0))), zero);
+		assert_eq!(from_nng_ms(zero), Some(Some(Duration::from_millis(0))));
+	}
+
+	#[test]
+	fn sub_millisecond_rounds_up()
+	{
+		assert_eq!(to_nng_ms(Some(Duration::from_nanos(1))), 1);
+		assert_eq!(to_nng_ms(Some(Duration::from_micros(999))), 1);
+		assert_eq!(to_nng_ms(Some(Duration::from_micros(1001))), 2);
+		assert_eq!(to_nng_ms(Some(Duration::new(1, 1))), 1001);
+	}
+
+	#[test]
+	fn long_durations_saturate()
+	{
+		assert_eq!(to_nng_ms(Some(Duration::from_millis(i32::MAX as u64))), i32::MAX);
+		assert_eq!(to_nng_ms(Some(Duration::from_millis(i32::MAX as u64 + 1))), i32::MAX);
+		assert_eq!(to_nng_ms(Some(Duration::from_secs(u64::MAX))), i32::MAX);
+		assert_eq!(to_nng_ms(Some(Duration::new(u64::MAX, 999_999_999))), i32::MAX);
+	}
+
+	#[test]
+	fn infinite_is_none()
+	{
+		assert_eq!(nng_sys::NNG_DURATION_INFINITE, -1);
+		assert_eq!(to_nng_ms(None), nng_sys::NNG_DURATION_INFINITE);
+		assert_eq!(from_nng_ms(nng_sys::NNG_DURATION_INFINITE), Some(None));
+	}
+
+	#[test]
+	fn default_is_not_infinite()
+	{
+		assert_eq!(nng_sys::NNG_DURATION_DEFAULT, -2);
+		assert_eq!(from_nng_ms(nng_sys::NNG_DURATION_DEFAULT), None);
+		assert_ne!(
+			from_nng_ms(nng_sys::NNG_DURATION_DEFAULT),
+			from_nng_ms(nng_sys::NNG_DURATION_INFINITE)
+		);
+		assert_eq!(from_nng_ms(-3), None);
+		assert_eq!(from_nng_ms(i32::MIN), None);
+	}
+}
@@ -0,0 +1,214 @@
+//! TLS configuration and information about the TLS support compiled into
+//! NNG.
+use std::{
+	ffi::CString,
+	os::raw::{c_char, c_int, c_void},
+	ptr::{self, NonNull},
+};
+
+use crate::{
+	error::{Error, Result},
+	listener::ListenerOptions,
+	protocol::Protocol,
+	socket::Socket,
+	util::validate_ptr,
+};
+
+/// The URL used to probe for TLS support.
+///
+/// Creating a listener does not bind the address, so this never touches the
+/// network.
+const PROBE_URL: &str = "tls+tcp://127.0.0.1:0";
+
+/// Returns the name of the TLS engine that NNG is using, if any.
+///
+/// TLS support in NNG is optional and is selected when the library is built,
+/// so the same application may end up with or without it depending on how
+/// `libnng` was compiled. NNG v1.1 only supports [mbed TLS][1], so this
+/// returns either `Some("mbedTLS")` or `None` when TLS is not available at all.
+/// Because TLS behavior and cipher support vary between engines, this is
+/// mostly useful as a diagnostic when debugging handshake failures.
+///
+/// The engine is detected by creating and immediately closing a TLS listener,
+/// so this works regardless of whether NNG was built by this crate or linked
+/// from the system.
+///
+/// ## Example
+///
+/// ```
+/// match nng::tls_backend() {
+///     Some(engine) => println!("TLS is provided by {}", engine),
+///     None => println!("TLS is not supported"),
+/// }
+/// ```
+///
+/// [1]: https://nanomsg.github.io/nng/man/v1.1.0/nng_tls.7.html
+pub fn tls_backend() -> Option<&'static str>
+{
+	// Without TLS, NNG doesn't know the scheme and fails with `NotSupported`.
+	let socket = Socket::new(Protocol::Pair1).ok()?;
+	ListenerOptions::new(&socket, PROBE_URL).ok().map(|_| "mbedTLS")
+}
+
+/// The side of the connection a TLS configuration is used for.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TlsMode
+{
+	/// The configuration is used by dialers.
+	Client,
+
+	/// The configuration is used by listeners.
+	Server,
+}
+
+/// How the certificate of the peer is verified.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TlsAuthMode
+{
+	/// The certificate of the peer is not verified at all.
+	///
+	/// This is the default for servers.
+	None,
+
+	/// The certificate of the peer is verified if it presents one.
+	Optional,
+
+	/// The peer must present a valid certificate or the connection is closed.
+	///
+	/// This is the default for clients.
+	Required,
+}
+
+/// A TLS configuration that can be shared by several endpoints.
+///
+/// The `CaFile` and `CertKeyFile` options configure the TLS settings of a
+/// single endpoint before it is started. A configuration object instead can
+/// also be handed to a running listener, see `Listener::rebind_tls`.
+///
+/// NNG keeps its own reference to the configuration for every endpoint and
+/// connection using it, so dropping this object does not affect them. Once a
+/// configuration has been used for a connection, it cannot be modified
+/// anymore and the setters return `Error::IncorrectState`.
+///
+/// When NNG was built without TLS support, creating a configuration returns
+/// `Error::NotSupported`.
+#[derive(Debug)]
+pub struct TlsConfig
+{
+	/// The underlying NNG configuration.
+	ptr: NonNull<nng_tls_config>,
+}
+impl TlsConfig
+{
+	/// Creates a new configuration for the given side of the connection.
+	pub fn new(mode: TlsMode) -> Result<Self>
+	{
+		let mode = match mode {
+			TlsMode::Client => NNG_TLS_MODE_CLIENT,
+			TlsMode::Server => NNG_TLS_MODE_SERVER,
+		};
+
+		let mut ptr = ptr::null_mut();
+		let rv = unsafe { nng_tls_config_alloc(&mut ptr as _, mode) };
+
+		Ok(TlsConfig { ptr: validate_ptr(rv, ptr)? })
+	}
+
+	/// Loads the certificate chain and private key of this side of the
+	/// connection from a PEM file.
+	///
+	/// This is required for servers. The file must contain the certificate,
+	/// followed by any intermediate certificates, and the private key, which
+	/// is decrypted with the password if one is given.
+	pub fn cert_key_file(&self, path: &str, password: Option<&str>) -> Result<()>
+	{
+		let path = CString::new(path).map_err(|_| Error::InvalidInput)?;
+		let password = password.map(CString::new).transpose().map_err(|_| Error::InvalidInput)?;
+		let password = password.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+
+		let rv =
+			unsafe { nng_tls_config_cert_key_file(self.ptr.as_ptr(), path.as_ptr(), password) };
+		rv2res!(rv)
+	}
+
+	/// Loads the certificate authorities used to verify peers from a PEM file.
+	///
+	/// The file may also contain certificate revocation lists.
+	pub fn ca_file(&self, path: &str) -> Result<()>
+	{
+		let path = CString::new(path).map_err(|_| Error::InvalidInput)?;
+
+		let rv = unsafe { nng_tls_config_ca_file(self.ptr.as_ptr(), path.as_ptr()) };
+		rv2res!(rv)
+	}
+
+	/// Sets how the certificate of the peer is verified.
+	pub fn auth_mode(&self, mode: TlsAuthMode) -> Result<()>
+	{
+		let mode = match mode {
+			TlsAuthMode::None => NNG_TLS_AUTH_MODE_NONE,
+			TlsAuthMode::Optional => NNG_TLS_AUTH_MODE_OPTIONAL,
+			TlsAuthMode::Required => NNG_TLS_AUTH_MODE_REQUIRED,
+		};
+
+		let rv = unsafe { nng_tls_config_auth_mode(self.ptr.as_ptr(), mode) };
+		rv2res!(rv)
+	}
+
+	/// Sets the name the certificate of the server is checked against.
+	///
+	/// This is only used by clients. Dialers otherwise use the host name of
+	/// their URL.
+	pub fn server_name(&self, name: &str) -> Result<()>
+	{
+		let name = CString::new(name).map_err(|_| Error::InvalidInput)?;
+
+		let rv = unsafe { nng_tls_config_server_name(self.ptr.as_ptr(), name.as_ptr()) };
+		rv2res!(rv)
+	}
+
+	/// Returns the pointer to the underlying configuration for use as an
+	/// option value.
+	pub(crate) fn as_ptr(&self) -> *mut c_void { self.ptr.as_ptr() as _ }
+}
+
+impl Drop for TlsConfig
+{
+	fn drop(&mut self)
+	{
+		// This only releases our reference, endpoints using it hold their own.
+		unsafe { nng_tls_config_free(self.ptr.as_ptr()) }
+	}
+}
+
+// NNG protects the configuration with a lock of its own.
+unsafe impl Send for TlsConfig {}
+unsafe impl Sync for TlsConfig {}
+
+/// The opaque TLS configuration type of NNG.
+#[allow(non_camel_case_types)]
+enum nng_tls_config {}
+
+/// Values of the `nng_tls_mode` enumeration.
+const NNG_TLS_MODE_CLIENT: c_int = 0;
+const NNG_TLS_MODE_SERVER: c_int = 1;
+
+/// Values of the `nng_tls_auth_mode` enumeration.
+const NNG_TLS_AUTH_MODE_NONE: c_int = 0;
+const NNG_TLS_AUTH_MODE_OPTIONAL: c_int = 1;
+const NNG_TLS_AUTH_MODE_REQUIRED: c_int = 2;
+
+// The `nng-sys` crate doesn't bind the TLS supplement of NNG. Its functions
+// are always part of `libnng` and simply fail when TLS support is disabled.
+extern "C" {
+	fn nng_tls_config_alloc(cfg: *mut *mut nng_tls_config, mode: c_int) -> c_int;
+	fn nng_tls_config_free(cfg: *mut nng_tls_config);
+	fn nng_tls_config_server_name(cfg: *mut nng_tls_config, name: *const c_char) -> c_int;
+	fn nng_tls_config_auth_mode(cfg: *mut nng_tls_config, mode: c_int) -> c_int;
+	fn nng_tls_config_ca_file(cfg: *mut nng_tls_config, path: *const c_char) -> c_int;
+	fn nng_tls_config_cert_key_file(
+		cfg: *mut nng_tls_config,
+		path: *const c_char,
+		pass: *const c_char,
+	) -> c_int;
+}
@@ -0,0 +1,339 @@
+//! Matching of hierarchical topics beyond the prefixes NNG filters on.
+//!
+//! The _sub_ protocol only checks whether a message starts with one of the
+//! subscribed byte strings. Topics made of levels, such as
+//! `metrics.host42.cpu`, usually call for subscriptions like `metrics.*.cpu`
+//! as well, which NNG cannot express. A `TopicMatcher` compiles a set of such
+//! patterns and does the precise matching in the application, while
+//! `TopicMatcher::nng_prefixes` gives the tightest prefix subscriptions that
+//! let through everything the patterns match, so that NNG still drops as
+//! much as it can. `Socket::subscribe_patterns` and
+//! `Subscriber::subscribe_patterns` subscribe to those prefixes.
+//!
+//! Patterns are split into levels at the separator, which is a dot unless
+//! the matcher is created with `TopicMatcher::with_separator`:
+//!
+//! | Pattern | Matches |
+//! |---------|---------|
+//! | `metrics.host42.cpu` | Only that topic. |
+//! | `metrics.` | Every topic starting with `metrics.`, as NNG would. |
+//! | `metrics.*.cpu` | `*` stands for any single level, including an empty one. |
+//! | `metrics.#` | `#` stands for any number of levels, including none, so this matches `metrics` as well. It can only be the last level. |
+//!
+//! Wildcards have to be whole levels: patterns such as `metrics.cpu*` or
+//! `metrics.#.cpu` are rejected with `Error::InvalidInput`.
+//!
+//! ## Example
+//!
+//! ```
+//! use nng::{options::{Options, RecvBufferSize}, topics::TopicMatcher, Publisher, Subscriber};
+//!
+//! const ADDRESS: &str = "inproc://nng/topics/example";
+//!
+//! let publisher = Publisher::new()?;
+//! publisher.socket().listen(ADDRESS)?;
+//!
+//! let matcher = TopicMatcher::new(&["metrics.*.cpu", "alerts.#"])?;
+//! let subscriber = Subscriber::new()?;
+//! subscriber.socket().set_opt::<RecvBufferSize>(8)?;
+//! subscriber.subscribe_patterns(&matcher)?;
+//! subscriber.socket().dial(ADDRESS)?;
+//! # std::thread::sleep(std::time::Duration::from_millis(50));
+//!
+//! // NNG drops the logs, the matcher skips the memory metrics.
+//! publisher.publish("logs.host42", b"started")?;
+//! publisher.publish("metrics.host42.mem", b"512")?;
+//! publisher.publish("metrics.host42.cpu", b"0.5")?;
+//! publisher.publish("alerts", b"none")?;
+//!
+//! let (topic, _) = subscriber.recv_matching(&matcher)?;
+//! assert_eq!(topic, "metrics.host42.cpu");
+//! let (topic, _) = subscriber.recv_matching(&matcher)?;
+//! assert_eq!(topic, "alerts");
+//! # Ok::<(), nng::Error>(())
+//! ```
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+
+/// The level that stands for any single level.
+const ANY_LEVEL: &[u8] = b"*";
+
+/// The level that stands for any number of levels.
+const ANY_LEVELS: &[u8] = b"#";
+
+/// A compiled set of topic patterns.
+///
+/// The patterns are stored as a tree of their levels, so matching a topic
+/// takes one lookup per level and only branches where patterns have a `*`.
+/// See the [module documentation](index.html) for the syntax.
+///
+/// ## Example
+///
+/// ```
+/// use nng::topics::TopicMatcher;
+///
+/// let table: &[(&str, &str, bool)] = &[
+///     ("metrics.host42.cpu", "metrics.host42.cpu", true),
+///     ("metrics.host42.cpu", "metrics.host42.cpu.user", false),
+///     ("metrics.host42.cpu", "metrics.host42", false),
+///     ("metrics.", "metrics.host42.cpu", true),
+///     ("metrics.", "metrics.", true),
+///     ("metrics.", "metrics", false),
+///     ("metrics.", "metricsx.cpu", false),
+///     ("metrics.*.cpu", "metrics.host42.cpu", true),
+///     ("metrics.*.cpu", "metrics..cpu", true),
+///     ("metrics.*.cpu", "metrics.cpu", false),
+///     ("metrics.*.cpu", "metrics.host42.rack1.cpu", false),
+///     ("metrics.*.cpu", "metrics.host42.mem", false),
+///     ("metrics.*.", "metrics.host42.cpu.user", true),
+///     ("metrics.*.", "metrics.host42", false),
+///     ("metrics.#", "metrics", true),
+///     ("metrics.#", "metrics.host42.cpu", true),
+///     ("metrics.#", "metricsx", false),
+///     ("*.*.cpu", "metrics.host42.cpu", true),
+///     ("*", "metrics", true),
+///     ("*", "metrics.host42", false),
+///     ("#", "", true),
+///     ("#", "metrics.host42.cpu", true),
+///     ("", "", true),
+///     ("", "metrics", false),
+/// ];
+///
+/// for &(pattern, topic, expected) in table {
+///     let matcher = TopicMatcher::new(&[pattern])?;
+///     assert_eq!(matcher.matches(topic.as_bytes()), expected, "{} on {}", pattern, topic);
+/// }
+///
+/// // A set matches what any of its patterns matches.
+/// let matcher = TopicMatcher::new(&["metrics.*.cpu", "metrics.host42.#"])?;
+/// assert!(matcher.matches(b"metrics.host7.cpu"));
+/// assert!(matcher.matches(b"metrics.host42.mem"));
+/// assert!(!matcher.matches(b"metrics.host7.mem"));
+///
+/// for invalid in &["metrics.cpu*", "metrics.#.cpu", "metrics.#."] {
+///     assert_eq!(TopicMatcher::new(&[invalid]).unwrap_err(), nng::Error::InvalidInput);
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct TopicMatcher
+{
+	/// The byte between levels.
+	separator: u8,
+
+	/// The root of the tree of levels.
+	root: Node,
+
+	/// The prefixes to subscribe to, sorted and without redundant ones.
+	prefixes: Vec<Vec<u8>>,
+}
+
+/// A level in the tree of patterns.
+#[derive(Clone, Debug, Default)]
+struct Node
+{
+	/// The patterns continuing with a literal level.
+	literal: HashMap<Vec<u8>, Node>,
+
+	/// The patterns continuing with `*`.
+	any: Option<Box<Node>>,
+
+	/// Whether a pattern ends here.
+	exact: bool,
+
+	/// Whether a pattern ends here with a separator, matching anything after
+	/// it.
+	prefix: bool,
+
+	/// Whether a pattern ends here with `#`, matching any remaining levels.
+	rest: bool,
+}
+
+impl TopicMatcher
+{
+	/// Compiles the patterns, with levels separated by dots.
+	///
+	/// Returns `Error::InvalidInput` if any of the patterns is invalid. An
+	/// empty set of patterns matches nothing.
+	pub fn new<I, P>(patterns: I) -> Result<Self>
+	where
+		I: IntoIterator<Item = P>,
+		P: AsRef<str>,
+	{
+		TopicMatcher::with_separator(b'.', patterns)
+	}
+
+	/// Compiles the patterns, with levels separated by the given byte.
+	///
+	/// Returns `Error::InvalidInput` if any of the patterns is invalid or the
+	/// separator is one of the wildcards.
+	pub fn with_separator<I, P>(separator: u8, patterns: I) -> Result<Self>
+	where
+		I: IntoIterator<Item = P>,
+		P: AsRef<str>,
+	{
+		if separator == ANY_LEVEL[0] || separator == ANY_LEVELS[0] {
+			return Err(Error::InvalidInput);
+		}
+
+		let mut matcher = TopicMatcher { separator, root: Node::default(), prefixes: Vec::new() };
+		for pattern in patterns {
+			let prefix = matcher.insert(pattern.as_ref().as_bytes())?;
+			matcher.prefixes.push(prefix);
+		}
+
+		// Once sorted, the prefixes starting with another one come right after
+		// it, or after others that start with it as well.
+		matcher.prefixes.sort();
+		matcher.prefixes.dedup_by(|prefix, kept| prefix.starts_with(kept));
+
+		Ok(matcher)
+	}
+
+	/// Returns whether any of the patterns matches the topic.
+	pub fn matches(&self, topic: &[u8]) -> bool
+	{
+		let separator = self.separator;
+		self.root.matches(topic.split(|&b| b == separator))
+	}
+
+	/// Returns the prefixes to subscribe to so that NNG lets through every
+	/// topic the patterns match.
+	///
+	/// Every pattern contributes the part before its first wildcard, and
+	/// prefixes that start with another one are left out, as NNG lets
+	/// through their topics anyway. The result is therefore a superset of
+	/// the matching topics, which is as tight as prefixes can make it for a
+	/// single pattern. An empty prefix, which subscribes to everything, is
+	/// the only one returned if any pattern starts with a wildcard.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::topics::TopicMatcher;
+	///
+	/// let matcher = TopicMatcher::new(&["metrics.*.cpu", "metrics.host42.mem", "alerts.#"])?;
+	/// assert_eq!(matcher.nng_prefixes(), &[b"alerts".to_vec(), b"metrics.".to_vec()][..]);
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	///
+	/// No topic that a pattern matches is ever filtered out by the prefixes:
+	///
+	/// ```
+	/// use nng::topics::TopicMatcher;
+	///
+	/// // Every topic of up to three levels made of these.
+	/// let levels = ["", "a", "b", "ab", "*", "#"];
+	/// let mut topics: Vec<String> = levels.iter().map(|l| l.to_string()).collect();
+	/// let mut longest = topics.clone();
+	/// for _ in 0..2 {
+	///     longest = longest
+	///         .iter()
+	///         .flat_map(|t| levels.iter().map(move |l| format!("{}.{}", t, l)))
+	///         .collect();
+	///     topics.extend(longest.iter().cloned());
+	/// }
+	/// assert_eq!(topics.len(), 6 + 36 + 216);
+	///
+	/// let sets: &[&[&str]] = &[
+	///     &["a"], &["a."], &["a.*"], &["a.#"], &["*"], &["#"], &[""], &["*.b"],
+	///     &["a.*.b", "a.b.#"], &["ab.", "a.b", "a"], &["a.*.", "b.#", "a.b.ab"],
+	/// ];
+	/// for patterns in sets {
+	///     let matcher = TopicMatcher::new(*patterns)?;
+	///     for topic in &topics {
+	///         let topic = topic.as_bytes();
+	///         if matcher.matches(topic) {
+	///             assert!(
+	///                 matcher.nng_prefixes().iter().any(|p| topic.starts_with(p)),
+	///                 "{:?} filters out {:?}", patterns, String::from_utf8_lossy(topic),
+	///             );
+	///         }
+	///     }
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	pub fn nng_prefixes(&self) -> &[Vec<u8>] { &self.prefixes }
+
+	/// Adds the pattern to the tree and returns its prefix.
+	fn insert(&mut self, pattern: &[u8]) -> Result<Vec<u8>>
+	{
+		let mut levels: Vec<&[u8]> = pattern.split(|&b| b == self.separator).collect();
+
+		// A trailing separator leaves an empty last level, which stands for
+		// anything after it.
+		let prefix = levels.len() > 1 && levels[levels.len() - 1].is_empty();
+		let rest = !prefix && levels.last() == Some(&ANY_LEVELS);
+		if prefix || rest {
+			levels.pop();
+		}
+
+		let wildcard = |b: &u8| ANY_LEVEL.contains(b) || ANY_LEVELS.contains(b);
+		if levels.iter().any(|l| *l != ANY_LEVEL && l.iter().any(wildcard)) {
+			return Err(Error::InvalidInput);
+		}
+
+		// The prefix covers the levels before the first wildcard, along with
+		// their separators. Without a `*`, the `#` and the separator before it
+		// are left out, as they also match no levels at all.
+		let end = match levels.iter().position(|l| *l == ANY_LEVEL) {
+			Some(i) => levels[..i].iter().map(|l| l.len() + 1).sum(),
+			None if rest => pattern.len() - if levels.is_empty() { 1 } else { 2 },
+			None => pattern.len(),
+		};
+
+		let mut node = &mut self.root;
+		for level in levels {
+			node = if level == ANY_LEVEL {
+				node.any.get_or_insert_with(Box::default)
+			}
+			else {
+				node.literal.entry(level.to_vec()).or_default()
+			};
+		}
+
+		if prefix {
+			node.prefix = true;
+		}
+		else if rest {
+			node.rest = true;
+		}
+		else {
+			node.exact = true;
+		}
+
+		Ok(pattern[..end].to_vec())
+	}
+}
+
+impl Node
+{
+	/// Returns whether any pattern from here on matches the remaining levels.
+	fn matches<'a, I>(&self, mut levels: I) -> bool
+	where
+		I: Iterator<Item = &'a [u8]> + Clone,
+	{
+		if self.rest {
+			return true;
+		}
+
+		let level = match levels.next() {
+			Some(level) => level,
+			None => return self.exact,
+		};
+
+		if self.prefix {
+			return true;
+		}
+		if let Some(next) = self.literal.get(level) {
+			if next.matches(levels.clone()) {
+				return true;
+			}
+		}
+		match &self.any {
+			Some(next) => next.matches(levels),
+			None => false,
+		}
+	}
+}
@@ -0,0 +1,354 @@
+//! Sends whose completion can be waited for.
+use std::{
+	fmt,
+	sync::{Arc, Condvar, Mutex, Weak},
+	time::{Duration, Instant},
+};
+
+use crate::{
+	aio::{Aio, AioResult},
+	error::{Error, Result, SendError, SendResult},
+	message::Message,
+	socket::Socket,
+};
+
+/// The number of tracked sends a socket allows at a time, unless changed with
+/// `Socket::set_tracked_send_limit`.
+pub(crate) const DEFAULT_TRACKED_SENDS: usize = 64;
+
+/// The AIOs used for the tracked sends of a socket.
+///
+/// An AIO is taken from the pool for every tracked send and returned to it
+/// once the send has completed and the token no longer needs it, which is
+/// either when the result was collected or, for abandoned tokens, from the
+/// completion callback.
+pub(crate) struct SendPool
+{
+	/// The state of the pool.
+	state: Mutex<PoolState>,
+
+	/// Signaled whenever an AIO is returned to the pool.
+	returned: Condvar,
+}
+
+/// The mutable state of a `SendPool`.
+struct PoolState
+{
+	/// The AIOs that are ready for a new send.
+	idle: Vec<Slot>,
+
+	/// The number of AIOs that are currently lent out.
+	busy: usize,
+
+	/// The maximum number of AIOs lent out at a time.
+	limit: usize,
+}
+
+impl SendPool
+{
+	/// Creates an empty pool.
+	pub(crate) fn new() -> Arc<Self>
+	{
+		let state = PoolState { idle: Vec::new(), busy: 0, limit: DEFAULT_TRACKED_SENDS };
+		Arc::new(SendPool { state: Mutex::new(state), returned: Condvar::new() })
+	}
+
+	/// Changes the number of AIOs lent out at a time.
+	///
+	/// The sends that are already running are not affected, but no new ones
+	/// start until enough of them have completed.
+	pub(crate) fn set_limit(&self, limit: usize)
+	{
+		let mut state = self.state.lock().unwrap();
+		state.limit = limit.max(1);
+		let keep = state.limit.saturating_sub(state.busy);
+		let excess = if state.idle.len() > keep { state.idle.split_off(keep) } else { Vec::new() };
+		drop(state);
+
+		self.returned.notify_all();
+		drop(excess);
+	}
+
+	/// Starts a tracked send of the message on the socket.
+	pub(crate) fn send(
+		self: &Arc<Self>,
+		socket: &Socket,
+		msg: Message,
+		nonblocking: bool,
+	) -> SendResult<SendToken>
+	{
+		let slot = match self.acquire(nonblocking) {
+			Ok(slot) => slot,
+			Err(error) => return Err(SendError { message: msg, error }),
+		};
+
+		if let Err(e) = socket.send_async(&slot.aio, msg) {
+			self.release(slot);
+			return Err(e);
+		}
+
+		Ok(SendToken {
+			pool:    Arc::clone(self),
+			slot:    Some(slot),
+			message: None,
+			result:  None,
+		})
+	}
+
+	/// Takes an AIO out of the pool, waiting for one if the limit has been
+	/// reached and the socket is blocking.
+	fn acquire(self: &Arc<Self>, nonblocking: bool) -> Result<Slot>
+	{
+		let mut state = self.state.lock().unwrap();
+		loop {
+			if state.busy < state.limit {
+				state.busy += 1;
+				if let Some(slot) = state.idle.pop() {
+					return Ok(slot);
+				}
+
+				// Creating an AIO doesn't need the lock, which is only needed again if
+				// that fails.
+				drop(state);
+				let res = Slot::new(Arc::downgrade(self));
+				if res.is_err() {
+					self.state.lock().unwrap().busy -= 1;
+					self.returned.notify_one();
+				}
+				return res;
+			}
+
+			if nonblocking {
+				return Err(Error::TryAgain);
+			}
+			state = self.returned.wait(state).unwrap();
+		}
+	}
+
+	/// Returns an AIO that is done with its send to the pool.
+	fn release(&self, slot: Slot)
+	{
+		let mut state = self.state.lock().unwrap();
+		state.busy -= 1;
+		let excess = if state.busy + state.idle.len() < state.limit {
+			state.idle.push(slot);
+			None
+		}
+		else {
+			Some(slot)
+		};
+		drop(state);
+
+		self.returned.notify_one();
+		drop(excess);
+	}
+}
+
+/// An AIO of the pool along with the place its callback reports to.
+struct Slot
+{
+	/// The AIO the message is sent with.
+	aio: Aio,
+
+	/// The progress of the send.
+	status: Arc<Status>,
+}
+
+/// The progress of a tracked send, as reported by the AIO callback.
+struct Status
+{
+	/// The current progress.
+	progress: Mutex<Progress>,
+
+	/// Signaled when the send completes.
+	completed: Condvar,
+}
+
+/// The stages of a tracked send.
+enum Progress
+{
+	/// The send has been started and not completed yet.
+	Running,
+
+	/// The token was dropped, so the callback returns the AIO to the pool.
+	///
+	/// This keeps a handle to the AIO, as the send would be canceled if the
+	/// last one went away.
+	Abandoned(Aio),
+
+	/// The send completed and the token has yet to collect the result.
+	Done(AioResult),
+}
+
+impl Slot
+{
+	/// Creates a new AIO that belongs to the pool.
+	fn new(pool: Weak<SendPool>) -> Result<Self>
+	{
+		let status = Arc::new(Status {
+			progress:  Mutex::new(Progress::Running),
+			completed: Condvar::new(),
+		});
+
+		let cb_status = Arc::clone(&status);
+		let aio = Aio::new(move |_, res| {
+			let mut progress = cb_status.progress.lock().unwrap();
+			match std::mem::replace(&mut *progress, Progress::Running) {
+				Progress::Abandoned(aio) => {
+					drop(progress);
+					if let Some(pool) = pool.upgrade() {
+						pool.release(Slot { aio, status: Arc::clone(&cb_status) });
+					}
+				},
+				_ => {
+					*progress = Progress::Done(res);
+					drop(progress);
+					cb_status.completed.notify_all();
+				},
+			}
+		})?;
+
+		Ok(Slot { aio, status })
+	}
+}
+
+/// A token for a message sent with `Socket::send_tracked`.
+///
+/// The token resolves once the AIO used for the send completes, which is when
+/// the protocol of the socket has accepted the message. For a _push_ or
+/// _pair_ socket without a send buffer, that means it has been handed to the
+/// transport of a connected peer. What happens after that, such as whether the
+/// peer receives it, is not tracked.
+///
+/// Every token takes up one of the limited number of tracked sends of its
+/// socket until it is resolved with `SendToken::wait` or dropped. A dropped
+/// token does not cancel the send, which carries on in the background.
+#[must_use]
+pub struct SendToken
+{
+	/// The pool the AIO is returned to.
+	pool: Arc<SendPool>,
+
+	/// The AIO used for the send, until the result has been collected.
+	slot: Option<Slot>,
+
+	/// The message, if the send failed.
+	message: Option<Message>,
+
+	/// The result, once it has been collected.
+	result: Option<Result<()>>,
+}
+
+impl SendToken
+{
+	/// Waits for the send to complete, giving up after the timeout.
+	///
+	/// Returns `Ok` once the message has been accepted. If the send failed,
+	/// the error is returned and the message can be taken back with
+	/// `SendToken::take_message`. If the timeout expires first, the send is
+	/// canceled and `Error::TimedOut` is returned, unless it completed in the
+	/// meantime. A timeout of `None` waits forever. Once the send has
+	/// completed, this returns the same result right away.
+	pub fn wait(&mut self, timeout: Option<Duration>) -> Result<()>
+	{
+		if let Some(res) = self.result {
+			return res;
+		}
+
+		let slot = self.slot.take().expect("Token has neither a result nor an AIO");
+		let mut timed_out = false;
+		let mut progress = slot.status.progress.lock().unwrap();
+		let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+		while let Progress::Running = *progress {
+			let now = Instant::now();
+			match deadline {
+				Some(d) if d <= now => {
+					// Canceling has to happen without the lock, as the callback may
+					// run right away.
+					drop(progress);
+					slot.aio.cancel();
+					timed_out = true;
+					progress = slot.status.progress.lock().unwrap();
+					while let Progress::Running = *progress {
+						progress = slot.status.completed.wait(progress).unwrap();
+					}
+				},
+				Some(d) => {
+					progress = slot.status.completed.wait_timeout(progress, d - now).unwrap().0
+				},
+				None => progress = slot.status.completed.wait(progress).unwrap(),
+			}
+		}
+
+		let res = match std::mem::replace(&mut *progress, Progress::Running) {
+			Progress::Done(AioResult::SendOk) => Ok(()),
+			Progress::Done(AioResult::SendErr(msg, e)) => {
+				self.message = Some(msg);
+				Err(e)
+			},
+			Progress::Done(AioResult::SendErrLost(e)) => Err(e),
+			Progress::Done(res) => unreachable!("Tracked send completed as {:?}", res),
+			Progress::Running | Progress::Abandoned(_) => {
+				unreachable!("Tracked send didn't complete")
+			},
+		};
+		drop(progress);
+		self.pool.release(slot);
+
+		let res = match res {
+			Err(Error::Canceled) if timed_out => Err(Error::TimedOut),
+			res => res,
+		};
+		self.result = Some(res);
+		res
+	}
+
+	/// Returns whether the send has completed, without waiting for it.
+	pub fn is_done(&self) -> bool
+	{
+		match &self.slot {
+			Some(slot) => matches!(*slot.status.progress.lock().unwrap(), Progress::Done(_)),
+			None => true,
+		}
+	}
+
+	/// Takes back the message of a failed send.
+	///
+	/// This returns `None` if the send has not been resolved with
+	/// `SendToken::wait` yet, if it succeeded, if NNG did not return the
+	/// message, or if the message was already taken.
+	pub fn take_message(&mut self) -> Option<Message> { self.message.take() }
+}
+
+impl fmt::Debug for SendToken
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("SendToken")
+			.field("done", &self.is_done())
+			.field("result", &self.result)
+			.finish()
+	}
+}
+
+impl Drop for SendToken
+{
+	fn drop(&mut self)
+	{
+		let slot = match self.slot.take() {
+			Some(slot) => slot,
+			None => return,
+		};
+
+		// Whoever comes second, the callback or the token, returns the AIO.
+		let mut progress = slot.status.progress.lock().unwrap();
+		if let Progress::Running = *progress {
+			*progress = Progress::Abandoned(slot.aio.clone());
+			return;
+		}
+
+		*progress = Progress::Running;
+		drop(progress);
+		self.pool.release(slot);
+	}
+}
@@ -0,0 +1,166 @@
+//! The sending and receiving of messages, independently of what does it.
+use std::time::Duration;
+
+use crate::{
+	aio::Aio,
+	ctx::Context,
+	error::{Result, SendError, SendResult},
+	message::Message,
+	socket::Socket,
+};
+
+/// The operations used by code that only sends and receives messages.
+///
+/// Application code written against this trait, rather than taking a `Socket`
+/// or `Context`, can be handed a `testing::MockTransport` in unit tests (with
+/// the `testing` feature), so that its logic can be tested without opening any
+/// sockets or starting any threads.
+///
+/// The trait only forwards to the methods of the types that implement it. The
+/// inherent methods stay as they are, so code using sockets directly neither
+/// changes nor goes through the trait, and generic code is monomorphized as
+/// usual.
+///
+/// ## Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nng::*;
+///
+/// /// Answers a single request with its body reversed.
+/// fn reverse_one<T: MessageTransport>(transport: &T) -> Result<()> {
+///     let mut msg = transport.recv()?;
+///     msg.as_mut_slice().reverse();
+///     transport.send(msg).map_err(Error::from)
+/// }
+///
+/// let server = Socket::new(Protocol::Rep0)?;
+/// server.listen("inproc://nng/transport")?;
+/// let client = Socket::new(Protocol::Req0)?;
+/// client.dial("inproc://nng/transport")?;
+///
+/// client.send(&b"olleh"[..])?;
+/// reverse_one(&server)?;
+/// assert_eq!(&client.recv()?[..], b"hello");
+///
+/// // Contexts work the same way.
+/// let ctx = Context::new(&server)?;
+/// assert_eq!(ctx.recv_timeout(Duration::from_millis(10)).unwrap_err(), Error::TimedOut);
+/// client.send(&b"dlrow"[..])?;
+/// reverse_one(&ctx)?;
+/// assert_eq!(&client.recv()?[..], b"world");
+/// # Ok::<(), nng::Error>(())
+/// ```
+pub trait MessageTransport
+{
+	/// Sends the message, blocking until it has been accepted.
+	///
+	/// The message is returned along with the error if it could not be sent.
+	fn send(&self, msg: Message) -> SendResult<()>;
+
+	/// Receives a message, blocking until one arrives.
+	fn recv(&self) -> Result<Message>;
+
+	/// Sends the message, giving up with `Error::TimedOut` after the timeout.
+	fn send_timeout(&self, msg: Message, timeout: Duration) -> SendResult<()>;
+
+	/// Receives a message, giving up with `Error::TimedOut` after the timeout.
+	fn recv_timeout(&self, timeout: Duration) -> Result<Message>;
+}
+
+/// Sockets use their blocking methods, along with their nonblocking setting
+/// and their `SendTimeout` and `RecvTimeout`. The methods with an explicit
+/// timeout go through an AIO created for the call and ignore all of those.
+impl MessageTransport for Socket
+{
+	fn send(&self, msg: Message) -> SendResult<()> { Socket::send(self, msg) }
+
+	fn recv(&self) -> Result<Message> { Socket::recv(self) }
+
+	fn send_timeout(&self, msg: Message, timeout: Duration) -> SendResult<()>
+	{
+		send_with(msg, Some(timeout), |aio, msg| self.send_async(aio, msg))
+	}
+
+	fn recv_timeout(&self, timeout: Duration) -> Result<Message>
+	{
+		recv_with(Some(timeout), |aio| self.recv_async(aio))
+	}
+}
+
+/// Contexts only have asynchronous operations, so every call creates an AIO
+/// for the operation and waits for it. Without a timeout, the operations wait
+/// for as long as it takes.
+impl MessageTransport for Context
+{
+	fn send(&self, msg: Message) -> SendResult<()>
+	{
+		send_with(msg, None, |aio, msg| Context::send(self, aio, msg))
+	}
+
+	fn recv(&self) -> Result<Message> { recv_with(None, |aio| Context::recv(self, aio)) }
+
+	fn send_timeout(&self, msg: Message, timeout: Duration) -> SendResult<()>
+	{
+		send_with(msg, Some(timeout), |aio, msg| Context::send(self, aio, msg))
+	}
+
+	fn recv_timeout(&self, timeout: Duration) -> Result<Message>
+	{
+		recv_with(Some(timeout), |aio| Context::recv(self, aio))
+	}
+}
+
+/// Sends a message through a new AIO and waits for the send to complete.
+///
+/// If NNG doesn't give the message back after a failed send, the error comes
+/// with an empty message instead.
+fn send_with<F>(msg: Message, timeout: Option<Duration>, start: F) -> SendResult<()>
+where
+	F: FnOnce(&Aio, Message) -> SendResult<()>,
+{
+	let aio = match new_aio(timeout) {
+		Ok(aio) => aio,
+		Err(error) => return Err(SendError { message: msg, error }),
+	};
+
+	start(&aio, msg)?;
+	aio.wait();
+	aio.result().expect("Send completed without a result").into_send().map_err(|(m, error)| {
+		SendError { message: m.unwrap_or_default(), error }
+	})
+}
+
+/// Receives a message through a new AIO and waits for it.
+fn recv_with<F>(timeout: Option<Duration>, start: F) -> Result<Message>
+where
+	F: FnOnce(&Aio) -> Result<()>,
+{
+	let aio = new_aio(timeout)?;
+	start(&aio)?;
+	aio.wait();
+	aio.result().expect("Receive completed without a result").into_recv()
+}
+
+/// Creates a manual AIO with the timeout.
+fn new_aio(timeout: Option<Duration>) -> Result<Aio>
+{
+	let aio = Aio::new_manual()?;
+	aio.set_timeout(timeout)?;
+	Ok(aio)
+}
+
+/// References to transports are transports as well.
+impl<T: MessageTransport + ?Sized> MessageTransport for &T
+{
+	fn send(&self, msg: Message) -> SendResult<()> { (**self).send(msg) }
+
+	fn recv(&self) -> Result<Message> { (**self).recv() }
+
+	fn send_timeout(&self, msg: Message, timeout: Duration) -> SendResult<()>
+	{
+		(**self).send_timeout(msg, timeout)
+	}
+
+	fn recv_timeout(&self, timeout: Duration) -> Result<Message> { (**self).recv_timeout(timeout) }
+}
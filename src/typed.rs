@@ -0,0 +1,275 @@
+//! Sockets that only expose the options of their protocol.
+use crate::{
+	error::{Error, Result, SendError, SendResult},
+	message::Message,
+	options::{protocol::pair::Polyamorous, Options},
+	pipe::Pipe,
+	protocol::Protocol,
+	socket::Socket,
+};
+
+/// Defines a socket wrapper for a single protocol.
+///
+/// Every wrapper gets the options that apply to all protocols, followed by
+/// the extra ones that are given.
+macro_rules! typed_socket
+{
+	(
+		$(#[$attr:meta])*
+		$name:ident($protocol:ident);
+		Gets -> [$($($getters:ident)::+),*];
+		Sets -> [$($($setters:ident)::+),*];
+	) => {
+		$(#[$attr])*
+		#[derive(Clone, Debug)]
+		pub struct $name
+		{
+			/// The underlying socket.
+			socket: Socket,
+
+			/// The handle of the socket, which stays valid for as long as the socket.
+			handle: nng_sys::nng_socket,
+		}
+		impl $name
+		{
+			#[doc = concat!("Creates a new socket using the `", stringify!($protocol), "` protocol.")]
+			pub fn new() -> Result<Self> { Self::from_socket(Socket::new(Protocol::$protocol)?) }
+
+			#[doc = concat!("Wraps an existing socket using the `", stringify!($protocol), "` protocol.")]
+			///
+			/// Returns `Error::NotSupported` if the socket uses another protocol.
+			pub fn from_socket(socket: Socket) -> Result<Self>
+			{
+				socket.require_protocol(Protocol::$protocol)?;
+				Ok($name { handle: socket.handle(), socket })
+			}
+
+			/// Returns the underlying socket, used for everything but the options.
+			pub fn socket(&self) -> &Socket { &self.socket }
+
+			/// Returns the underlying socket, giving up the checking of options.
+			pub fn into_socket(self) -> Socket { self.socket }
+		}
+
+		impl From<$name> for Socket
+		{
+			fn from(s: $name) -> Socket { s.socket }
+		}
+
+		expose_options!{
+			$name :: handle -> nng_sys::nng_socket;
+
+			GETOPT_BOOL = nng_sys::nng_getopt_bool;
+			GETOPT_INT = nng_sys::nng_getopt_int;
+			GETOPT_MS = nng_sys::nng_getopt_ms;
+			GETOPT_SIZE = nng_sys::nng_getopt_size;
+			GETOPT_SOCKADDR = crate::util::fake_opt;
+			GETOPT_STRING = nng_sys::nng_getopt_string;
+			GETOPT_UINT64 = nng_sys::nng_getopt_uint64;
+
+			SETOPT = nng_sys::nng_setopt;
+			SETOPT_BOOL = nng_sys::nng_setopt_bool;
+			SETOPT_INT = nng_sys::nng_setopt_int;
+			SETOPT_MS = nng_sys::nng_setopt_ms;
+			SETOPT_PTR = nng_sys::nng_setopt_ptr;
+			SETOPT_SIZE = nng_sys::nng_setopt_size;
+			SETOPT_STRING = nng_sys::nng_setopt_string;
+
+			Gets -> [Raw, ReconnectMinTime, ReconnectMaxTime,
+			         RecvBufferSize, RecvMaxSize,
+			         RecvTimeout, SendBufferSize,
+			         SendTimeout, SocketName
+			         $(, $($getters)::+)*];
+			Sets -> [ReconnectMinTime, ReconnectMaxTime,
+			         RecvBufferSize, RecvMaxSize,
+			         RecvTimeout, SendBufferSize,
+			         SendTimeout, SocketName,
+			         transport::tcp::NoDelay,
+			         transport::tcp::KeepAlive,
+			         transport::tls::CaFile,
+			         transport::tls::CertKeyFile,
+			         transport::websocket::RequestHeaders,
+			         transport::websocket::ResponseHeaders
+			         $(, $($setters)::+)*];
+		}
+	};
+}
+
+typed_socket! {
+	/// A _pair_ (version 0) socket.
+	///
+	/// This only has the options of all sockets. Its peer has to be a version
+	/// 0 socket as well.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use nng::Pair0Socket;
+	///
+	/// let server = Pair0Socket::new()?;
+	/// server.socket().listen("inproc://nng/typed/pair0")?;
+	/// let client = Pair0Socket::new()?;
+	/// client.socket().dial("inproc://nng/typed/pair0")?;
+	///
+	/// client.socket().send(&b"ping"[..])?;
+	/// assert_eq!(&server.socket().recv()?[..], b"ping");
+	/// server.socket().send(&b"pong"[..])?;
+	/// assert_eq!(&client.socket().recv()?[..], b"pong");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	Pair0Socket(Pair0);
+	Gets -> [];
+	Sets -> [];
+}
+
+typed_socket! {
+	/// A _pair_ (version 1) socket.
+	///
+	/// On top of the options of all sockets, this has `MaxTtl` and
+	/// `Polyamorous`. A socket in polyamorous mode, created with
+	/// `Pair1Socket::new_polyamorous`, accepts any number of peers and sends
+	/// replies to a particular one with `Pair1Socket::send_to`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::Pair1Socket;
+	///
+	/// const ADDRESS: &str = "inproc://nng/typed/pair1";
+	///
+	/// let server = Pair1Socket::new_polyamorous()?;
+	/// server.socket().listen(ADDRESS)?;
+	///
+	/// let clients = [Pair1Socket::new()?, Pair1Socket::new()?];
+	/// for (i, client) in clients.iter().enumerate() {
+	///     client.socket().dial(ADDRESS)?;
+	///     client.socket().send(&[i as u8][..])?;
+	/// }
+	///
+	/// // Answer every client through the pipe its message came from.
+	/// for _ in 0..clients.len() {
+	///     let mut msg = server.socket().recv()?;
+	///     let pipe = msg.pipe().expect("Received message without a pipe");
+	///     msg.as_mut_slice()[0] += 10;
+	///     server.send_to(pipe, msg)?;
+	/// }
+	///
+	/// for (i, client) in clients.iter().enumerate() {
+	///     assert_eq!(&client.socket().recv()?[..], &[i as u8 + 10][..]);
+	/// }
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	Pair1Socket(Pair1);
+	Gets -> [MaxTtl, protocol::pair::Polyamorous];
+	Sets -> [MaxTtl, protocol::pair::Polyamorous];
+}
+
+impl Pair1Socket
+{
+	/// Creates a new socket in polyamorous mode.
+	///
+	/// The mode can only be changed before the socket connects to anything,
+	/// after which setting `Polyamorous` fails with `Error::IncorrectState`,
+	/// so this sets it right away.
+	pub fn new_polyamorous() -> Result<Self>
+	{
+		let socket = Self::new()?;
+		socket.socket.set_opt::<Polyamorous>(true)?;
+		Ok(socket)
+	}
+
+	/// Returns whether the socket is in polyamorous mode.
+	pub fn is_polyamorous(&self) -> Result<bool> { self.socket.get_opt::<Polyamorous>() }
+
+	/// Sends the message to the peer on the other end of the pipe.
+	///
+	/// This only works in polyamorous mode, as the pipe of the message is
+	/// ignored otherwise, and fails with `Error::IncorrectState` if the socket
+	/// is in the normal mode. NNG does not report messages it cannot
+	/// deliver: if the pipe has been closed, or its queue is full, the message
+	/// is silently dropped, so that a slow peer cannot hold up the others.
+	pub fn send_to<M: Into<Message>>(&self, pipe: Pipe, msg: M) -> SendResult<()>
+	{
+		let mut msg = msg.into();
+		match self.is_polyamorous() {
+			Ok(true) => {},
+			Ok(false) => return Err(SendError { message: msg, error: Error::IncorrectState }),
+			Err(error) => return Err(SendError { message: msg, error }),
+		}
+
+		msg.set_pipe(pipe);
+		self.socket.send(msg)
+	}
+}
+
+typed_socket! {
+	/// A _pub_ socket.
+	///
+	/// This only has the options of all sockets. In particular, it cannot be
+	/// subscribed to anything:
+	///
+	/// ```compile_fail
+	/// use nng::{options::{protocol::pubsub::Subscribe, Options}, PubSocket};
+	///
+	/// let socket = PubSocket::new().unwrap();
+	/// socket.set_opt::<Subscribe>(b"topic".to_vec()).unwrap(); // Won't compile
+	/// ```
+	PubSocket(Pub0);
+	Gets -> [];
+	Sets -> [];
+}
+
+typed_socket! {
+	/// A _sub_ socket.
+	///
+	/// On top of the options of all sockets, this has `Subscribe` and
+	/// `Unsubscribe`.
+	///
+	/// ## Example
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use nng::{options::{protocol::pubsub::Subscribe, Options}, PubSocket, SubSocket};
+	///
+	/// const ADDRESS: &str = "inproc://nng/typed/sub";
+	///
+	/// let publisher = PubSocket::new()?;
+	/// publisher.socket().listen(ADDRESS)?;
+	///
+	/// let subscriber = SubSocket::new()?;
+	/// subscriber.set_opt::<Subscribe>(b"news".to_vec())?;
+	/// # subscriber.set_opt::<nng::options::RecvBufferSize>(8)?;
+	/// # subscriber.set_opt::<nng::options::RecvTimeout>(Some(Duration::from_secs(5)))?;
+	/// subscriber.socket().dial(ADDRESS)?;
+	/// # std::thread::sleep(Duration::from_millis(50));
+	///
+	/// publisher.socket().send(&b"weather: sunny"[..])?;
+	/// publisher.socket().send(&b"news: none"[..])?;
+	/// assert_eq!(&subscriber.socket().recv()?[..], b"news: none");
+	/// # Ok::<(), nng::Error>(())
+	/// ```
+	SubSocket(Sub0);
+	Gets -> [];
+	Sets -> [protocol::pubsub::Subscribe, protocol::pubsub::Unsubscribe];
+}
+
+typed_socket! {
+	/// A _req_ socket.
+	///
+	/// On top of the options of all sockets, this has `MaxTtl` and
+	/// `ResendTime`.
+	ReqSocket(Req0);
+	Gets -> [MaxTtl, protocol::reqrep::ResendTime];
+	Sets -> [MaxTtl, protocol::reqrep::ResendTime];
+}
+
+typed_socket! {
+	/// A _surveyor_ socket.
+	///
+	/// On top of the options of all sockets, this has `MaxTtl` and
+	/// `SurveyTime`.
+	SurveyorSocket(Surveyor0);
+	Gets -> [MaxTtl, protocol::survey::SurveyTime];
+	Sets -> [MaxTtl, protocol::survey::SurveyTime];
+}
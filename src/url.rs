@@ -0,0 +1,231 @@
+//! The components of the URLs used by NNG.
+use std::{
+	ffi::{CStr, CString},
+	fmt,
+	os::raw::c_char,
+	ptr,
+	str::FromStr,
+};
+
+use crate::{
+	error::{Error, Result},
+	util::validate_ptr,
+};
+
+/// A URL split into its components, the way NNG parses it.
+///
+/// The URLs given to NNG, and read back with the `Url` option, look like
+/// standard URLs but differ in a few places:
+///
+/// * The schemes are those of the transports, such as `tcp`, `tls+tcp`, or
+///   `ws`, and are converted to lower case along with the host.
+/// * Everything after `ipc://` and `inproc://` is taken as the path, so
+///   `ipc:///tmp/socket` has the path `/tmp/socket` and an empty host.
+/// * A host of `*`, as in `tcp://*:5555`, is the same as an empty host and
+///   means all interfaces.
+/// * Ports are only filled in for schemes that have a well known default,
+///   such as `ws`, and not for `tcp` or `tls+tcp`.
+///
+/// This relies on the URL parser of NNG itself, so the components always
+/// match what the transports will use.
+///
+/// ## Example
+///
+/// ```
+/// use nng::ParsedUrl;
+///
+/// let url: ParsedUrl = "ws://user@[::1]:8080/chat?room=1#top".parse()?;
+/// assert_eq!(url.scheme(), "ws");
+/// assert_eq!(url.userinfo(), Some("user"));
+/// assert_eq!(url.host(), "[::1]:8080");
+/// assert_eq!(url.hostname(), "::1");
+/// assert_eq!(url.port(), Some(8080));
+/// assert_eq!(url.path(), "/chat");
+/// assert_eq!(url.query(), Some("room=1"));
+/// assert_eq!(url.fragment(), Some("top"));
+///
+/// let url: ParsedUrl = "ipc:///tmp/nng.sock".parse()?;
+/// assert_eq!(url.scheme(), "ipc");
+/// assert_eq!(url.host(), "");
+/// assert_eq!(url.path(), "/tmp/nng.sock");
+/// assert_eq!(url.port(), None);
+///
+/// let url: ParsedUrl = "tcp://*:5555".parse()?;
+/// assert_eq!(url.hostname(), "");
+/// assert_eq!(url.port(), Some(5555));
+/// # Ok::<(), nng::Error>(())
+/// ```
+///
+/// The URLs read back from endpoints parse the same way for every transport:
+///
+/// ```
+/// use nng::{options::{Options, Url}, *};
+///
+/// let socket = Socket::new(Protocol::Pair0)?;
+/// let cases = [
+///     ("inproc://nng/url/round-trip", "inproc", "", "nng/url/round-trip"),
+///     ("ipc:///tmp/nng-url-round-trip.sock", "ipc", "", "/tmp/nng-url-round-trip.sock"),
+///     ("tcp://127.0.0.1:0", "tcp", "127.0.0.1", ""),
+///     ("ws://127.0.0.1:0/path", "ws", "127.0.0.1", "/path"),
+/// ];
+///
+/// for &(raw, scheme, hostname, path) in &cases {
+///     let listener = Listener::new(&socket, raw, false)?;
+///     let url = ParsedUrl::parse(&listener.get_opt::<Url>()?)?;
+///     assert_eq!((url.scheme(), url.hostname(), url.path()), (scheme, hostname, path));
+///
+///     // TCP listeners report the port that was bound in place of zero.
+///     match url.scheme() {
+///         "tcp" => assert_ne!(url.port(), Some(0)),
+///         "ws" => assert_eq!(url.port(), Some(0)),
+///         _ => assert_eq!(url.port(), None),
+///     }
+///     listener.close()?;
+/// }
+///
+/// // Dialers are configured with URLs of transports that cannot be listened on here.
+/// let cases = [
+///     ("tcp6://[::1]:5555", "tcp6", "::1", Some(5555), ""),
+///     ("tls+tcp://example.com:4433", "tls+tcp", "example.com", Some(4433), ""),
+///     ("wss://example.com/sp", "wss", "example.com", Some(443), "/sp"),
+/// ];
+///
+/// for &(raw, scheme, hostname, port, path) in &cases {
+///     // The TLS transports are optional.
+///     let dialer = match Dialer::new(&socket, raw, true) {
+///         Err(Error::NotSupported) => continue,
+///         res => res?,
+///     };
+///     let url = ParsedUrl::parse(&dialer.get_opt::<Url>()?)?;
+///     let parts = (url.scheme(), url.hostname(), url.port(), url.path());
+///     assert_eq!(parts, (scheme, hostname, port, path));
+///     assert_eq!(url.to_string(), raw);
+///     dialer.close()?;
+/// }
+/// # Ok::<(), nng::Error>(())
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ParsedUrl
+{
+	/// The URL as it was given.
+	raw: String,
+
+	/// The scheme, in lower case.
+	scheme: String,
+
+	/// The user information before the host, if any.
+	userinfo: Option<String>,
+
+	/// The host, including the port.
+	host: String,
+
+	/// The host, without the port or the brackets of IPv6 addresses.
+	hostname: String,
+
+	/// The port, which is empty if there is none.
+	port: String,
+
+	/// The path.
+	path: String,
+
+	/// The query after the path, if any.
+	query: Option<String>,
+
+	/// The fragment at the end, if any.
+	fragment: Option<String>,
+}
+impl ParsedUrl
+{
+	/// Parses the URL.
+	///
+	/// Returns `Error::InvalidInput` if this is not a URL, such as when the
+	/// scheme is not followed by `://`. The scheme is not checked against the
+	/// transports, so this also parses URLs that NNG cannot use.
+	pub fn parse(url: &str) -> Result<Self>
+	{
+		let raw = CString::new(url).map_err(|_| Error::InvalidInput)?;
+
+		let mut urlp: *mut nng_sys::nng_url = ptr::null_mut();
+		let rv = unsafe { nng_sys::nng_url_parse(&mut urlp as _, raw.as_ptr()) };
+		let urlp = validate_ptr(rv, urlp)?;
+
+		let parsed = unsafe {
+			let u = urlp.as_ref();
+			ParsedUrl {
+				raw:      url.to_string(),
+				scheme:   to_string(u.u_scheme).unwrap_or_default(),
+				userinfo: to_string(u.u_userinfo),
+				host:     to_string(u.u_host).unwrap_or_default(),
+				hostname: to_string(u.u_hostname).unwrap_or_default(),
+				port:     to_string(u.u_port).unwrap_or_default(),
+				path:     to_string(u.u_path).unwrap_or_default(),
+				query:    to_string(u.u_query),
+				fragment: to_string(u.u_fragment),
+			}
+		};
+
+		unsafe { nng_sys::nng_url_free(urlp.as_ptr()) }
+		Ok(parsed)
+	}
+
+	/// Returns the URL as it was given.
+	pub fn as_str(&self) -> &str { &self.raw }
+
+	/// Returns the scheme, such as `tcp` or `ipc`, in lower case.
+	pub fn scheme(&self) -> &str { &self.scheme }
+
+	/// Returns the user information before the host, if any.
+	pub fn userinfo(&self) -> Option<&str> { self.userinfo.as_deref() }
+
+	/// Returns the host along with the port, if any.
+	///
+	/// This is the authority without the user information. It is empty for
+	/// `ipc` and `inproc` URLs and for hosts given as `*`.
+	pub fn host(&self) -> &str { &self.host }
+
+	/// Returns the host without the port.
+	///
+	/// The brackets around IPv6 addresses are removed.
+	pub fn hostname(&self) -> &str { &self.hostname }
+
+	/// Returns the port, if there is one and it is a number.
+	///
+	/// A port without a number is a service name, such as `http`, which can
+	/// be read from `ParsedUrl::host`.
+	pub fn port(&self) -> Option<u16> { self.port.parse().ok() }
+
+	/// Returns the path.
+	///
+	/// For `ipc` URLs this is the path of the socket file and for `inproc`
+	/// URLs it is the name of the address.
+	pub fn path(&self) -> &str { &self.path }
+
+	/// Returns the query after the path, without the `?`, if any.
+	pub fn query(&self) -> Option<&str> { self.query.as_deref() }
+
+	/// Returns the fragment at the end, without the `#`, if any.
+	pub fn fragment(&self) -> Option<&str> { self.fragment.as_deref() }
+}
+
+impl FromStr for ParsedUrl
+{
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self> { ParsedUrl::parse(s) }
+}
+
+impl fmt::Display for ParsedUrl
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(&self.raw) }
+}
+
+/// Copies one of the components of an `nng_url`, which may be missing.
+unsafe fn to_string(s: *const c_char) -> Option<String>
+{
+	if s.is_null() {
+		None
+	}
+	else {
+		Some(CStr::from_ptr(s).to_string_lossy().into_owned())
+	}
+}
@@ -4,7 +4,6 @@
 use std::{
 	os::raw::{c_char, c_int, c_void},
 	ptr::NonNull,
-	time::Duration,
 };
 
 use crate::error::{Error, Result};
@@ -23,6 +22,35 @@ macro_rules! rv2res {
 	};
 }
 
+/// Logs an error through `log` and, with the `tracing` feature, `tracing`.
+macro_rules! log_error {
+	($($arg:tt)+) => {{
+		log::error!($($arg)+);
+		#[cfg(feature = "tracing")]
+		tracing::error!($($arg)+);
+	}};
+}
+
+/// Logs a warning through `log` and, with the `tracing` feature, `tracing`.
+macro_rules! log_warn {
+	($($arg:tt)+) => {{
+		log::warn!($($arg)+);
+		#[cfg(feature = "tracing")]
+		tracing::warn!($($arg)+);
+	}};
+}
+
+/// Emits a `tracing` event, taking the same arguments as `tracing::event!`.
+///
+/// This expands to nothing without the `tracing` feature, so the arguments
+/// must not have side effects.
+macro_rules! trace_event {
+	($($arg:tt)+) => {{
+		#[cfg(feature = "tracing")]
+		tracing::event!($($arg)+);
+	}};
+}
+
 /// Utility macro for creating a new option type.
 ///
 /// This is 90% me just playing around with macros. It is probably a terrible
@@ -31,6 +59,7 @@ macro_rules! rv2res {
 macro_rules! create_option
 {
 	(
+		@impl ($readable:expr, $writable:expr)
 		$(#[$attr:meta])*
 		$opt:ident -> $ot:ty:
 		Get $g:ident = $gexpr:stmt;
@@ -48,6 +77,12 @@ macro_rules! create_option
 		#[allow(clippy::cast_possible_truncation)]
 		impl $crate::options::private::OptOps for $opt
 		{
+			const NAME: &'static str = stringify!($opt);
+			const VALUE_TYPE: $crate::options::ValueType =
+				<$ot as $crate::options::private::OptValue>::VALUE_TYPE;
+			const READABLE: bool = $readable;
+			const WRITABLE: bool = $writable;
+
 			fn get<T: $crate::options::private::HasOpts>($g: &T) -> $crate::error::Result<Self::OptType> { $gexpr }
 			fn set<T: $crate::options::private::HasOpts>($s: &T, $v: Self::OptType) -> $crate::error::Result<()> { $sexpr }
 		}
@@ -61,12 +96,28 @@ macro_rules! create_option
 		}
 	};
 
+	(
+		$(#[$attr:meta])*
+		$opt:ident -> $ot:ty:
+		Get $g:ident = $gexpr:stmt;
+		Set $s:ident $v:ident = $sexpr:stmt;
+	) => {
+		create_option!(
+		@impl (true, true)
+		$(#[$attr])*
+		$opt -> $ot:
+		Get $g = $gexpr;
+		Set $s $v = $sexpr;
+		);
+	};
+
 	(
 		$(#[$attr:meta])*
 		$opt:ident -> $ot:ty:
 		Set $s:ident $v:ident = $sexpr:stmt;
 	) => {
 		create_option!(
+		@impl (false, true)
 		$(#[$attr])*
 		$opt -> $ot:
 		Get _g = unreachable!("should not have been implemented - option is write-only");
@@ -80,6 +131,7 @@ macro_rules! create_option
 		Get $g:ident = $gexpr:stmt;
 	) => {
 		create_option!(
+		@impl (true, false)
 		$(#[$attr])*
 		$opt -> $ot:
 		Get $g = $gexpr;
@@ -93,6 +145,7 @@ macro_rules! expose_options
 {
 	(
 		$struct:ident :: $($member:ident).+ -> $handle:ty;
+		$(MISSING_IS_CLOSED = $missing:expr;)?
 		GETOPT_BOOL = $go_b:path;
 		GETOPT_INT = $go_i:path;
 		GETOPT_MS = $go_ms:path;
@@ -116,6 +169,7 @@ macro_rules! expose_options
 		{
 			type Handle = $handle;
 			fn handle(&self) -> Self::Handle { self.$($member).+ }
+			$(const MISSING_IS_CLOSED: bool = $missing;)?
 
 			const GETOPT_BOOL: unsafe extern "C" fn(Self::Handle, *const std::os::raw::c_char, *mut bool) -> std::os::raw::c_int = $go_b;
 			const GETOPT_INT: unsafe extern "C" fn(Self::Handle, *const std::os::raw::c_char, *mut std::os::raw::c_int) -> std::os::raw::c_int = $go_i;
@@ -132,6 +186,34 @@ macro_rules! expose_options
 			const SETOPT_PTR: unsafe extern "C" fn(Self::Handle, *const std::os::raw::c_char, *mut std::os::raw::c_void) -> std::os::raw::c_int = $so_ptr;
 			const SETOPT_SIZE: unsafe extern "C" fn(Self::Handle, *const std::os::raw::c_char, usize) -> std::os::raw::c_int = $so_sz;
 			const SETOPT_STRING: unsafe extern "C" fn(Self::Handle, *const std::os::raw::c_char, *const std::os::raw::c_char) -> std::os::raw::c_int = $so_str;
+
+			#[allow(unused_variables)]
+			fn set_named(&self, name: &str, value: $crate::options::OptionValue) -> Option<$crate::error::Result<()>>
+			{
+				$(
+					if name == <$crate::options::$($setters)::+ as $crate::options::private::OptOps>::NAME {
+						let res = $crate::options::private::OptValue::from_value(value)
+							.and_then(|v| <$crate::options::$($setters)::+ as $crate::options::private::OptOps>::set(self, v))
+							.map_err(Self::opt_error);
+						return Some(res);
+					}
+				)*
+				None
+			}
+
+			#[allow(unused_variables)]
+			fn get_named(&self, name: &str) -> Option<$crate::error::Result<$crate::options::OptionValue>>
+			{
+				$(
+					if name == <$crate::options::$($getters)::+ as $crate::options::private::OptOps>::NAME {
+						let res = <$crate::options::$($getters)::+ as $crate::options::private::OptOps>::get(self)
+							.map_err(Self::opt_error)
+							.and_then($crate::options::private::OptValue::into_value);
+						return Some(res);
+					}
+				)*
+				None
+			}
 		}
 
 		$(impl $crate::options::GetOpt<$crate::options::$($getters)::+> for $struct {})*
@@ -139,6 +221,17 @@ macro_rules! expose_options
 	}
 }
 
+/// Reports a dialer, listener, or pipe that no longer exists as closed.
+///
+/// NNG looks these objects up by ID and reports an ID that is gone, because
+/// the object or its socket has been closed, as `NNG_ENOENT`. Sockets and
+/// contexts report `NNG_ECLOSED` in the same situation, so this keeps the
+/// errors consistent across all types.
+pub(crate) fn missing_is_closed(e: Error) -> Error
+{
+	if e == Error::EntryNotFound { Error::Closed } else { e }
+}
+
 /// A catch-all function for unsupported options operations.
 pub(crate) unsafe extern "C" fn fake_opt<H, T>(_: H, _: *const c_char, _: T) -> c_int
 {
@@ -156,40 +249,6 @@ pub(crate) unsafe extern "C" fn fake_genopt<H>(
 	unimplemented!("{} does not support the generic option operation", stringify!(H))
 }
 
-/// Converts a Rust Duration into an `nng_duration`.
-#[allow(clippy::cast_possible_truncation)]
-pub(crate) fn duration_to_nng(dur: Option<Duration>) -> nng_sys::nng_duration
-{
-	// The subsecond milliseconds is guaranteed to be less than 1000, which
-	// means converting from `u32` to `i32` is safe. The only other
-	// potential issue is converting the `u64` of seconds to an `i32`.
-	use std::i32::MAX;
-
-	match dur {
-		None => nng_sys::NNG_DURATION_INFINITE,
-		Some(d) => {
-			let secs = if d.as_secs() > MAX as u64 { MAX } else { d.as_secs() as i32 };
-			let millis = d.subsec_millis() as i32;
-
-			secs.saturating_mul(1000).saturating_add(millis)
-		},
-	}
-}
-
-/// Converts an `nng_duration` into a Rust Duration.
-pub(crate) fn nng_to_duration(ms: nng_sys::nng_duration) -> Option<Duration>
-{
-	if ms == nng_sys::NNG_DURATION_INFINITE {
-		None
-	}
-	else if ms >= 0 {
-		Some(Duration::from_millis(ms as u64))
-	}
-	else {
-		panic!("Unexpected value for `nng_duration` ({})", ms)
-	}
-}
-
 /// Checks an `nng` return code and validates the pointer, returning a
 /// `NonNull`.
 #[inline]
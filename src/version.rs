@@ -0,0 +1,25 @@
+//! Information about the version of NNG in use.
+use std::ffi::CStr;
+
+/// Returns the version of the NNG library this crate is linked against.
+///
+/// This is the version reported by `libnng` at runtime, in the form
+/// `"major.minor.patch"`, which may differ from the version of the headers
+/// `nng_sys` was generated from when linking against a system library.
+///
+/// ## Example
+///
+/// ```
+/// let version = nng::nng_version();
+/// assert!(version.starts_with("1."));
+/// println!("Using NNG v{}", version);
+/// ```
+pub fn nng_version() -> &'static str
+{
+	// The string is a static constant inside of the library and is always
+	// plain ASCII.
+	unsafe {
+		let ptr = nng_sys::nng_version();
+		CStr::from_ptr(ptr).to_str().expect("NNG version was not valid UTF-8")
+	}
+}
@@ -0,0 +1,263 @@
+//! Interoperability tests against peers in other processes.
+//!
+//! A test within a single process can't show that two implementations agree,
+//! as both ends share the same library and the same options. These tests run
+//! the peer in a process of its own instead, configured with
+//! `compat::nanomsg_mode` on both sides, which is how a legacy peer would
+//! expect them to be set.
+//!
+//! The `spawned_*` tests start this test binary again as the peer, with the
+//! `NNG_INTEROP_ROLE` environment variable naming what it does and
+//! `NNG_INTEROP_URL` where it listens. The peer runs as the `peer` test, which
+//! does nothing when the role isn't set, and is killed once the test is done.
+//!
+//! The `external_peer` test talks to a peer running outside of the test, such
+//! as a pynng script, at the address in `NNG_INTEROP_PEER`. It is skipped when
+//! the variable isn't set. See `README.md` for how to run it.
+use std::{
+	env,
+	io::{BufRead, BufReader},
+	net::TcpListener,
+	process::{Child, Command, Stdio},
+	sync::mpsc,
+	thread,
+	time::Duration,
+};
+
+use nng::{
+	bind_ephemeral,
+	compat,
+	options::{
+		protocol::pubsub::Subscribe,
+		Options,
+		ReconnectMaxTime,
+		ReconnectMinTime,
+		RecvMaxSize,
+		RecvTimeout,
+	},
+	Error,
+	Protocol,
+	Socket,
+};
+
+/// The variable naming the role of a spawned peer.
+const ROLE_VAR: &str = "NNG_INTEROP_ROLE";
+
+/// The variable with the address a spawned peer listens on.
+const URL_VAR: &str = "NNG_INTEROP_URL";
+
+/// The variable with the address of an external peer.
+const PEER_VAR: &str = "NNG_INTEROP_PEER";
+
+/// The prefix of the line a spawned peer prints once it is listening.
+const READY: &str = "NNG_INTEROP_READY ";
+
+/// The request that a _rep_ peer answers with its options instead of an echo.
+const DESCRIBE: &[u8] = b"describe";
+
+/// How long to wait for a peer before failing the test.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A peer running in a child process, which is killed when this is dropped.
+struct Peer
+{
+	/// The child process running the peer.
+	child: Child,
+
+	/// The address the peer listens on.
+	url: String,
+}
+impl Peer
+{
+	/// Starts a peer with the role, listening on an address made from the base.
+	///
+	/// This waits for the peer to print the address it listens on.
+	fn spawn(role: &str, base: &str) -> Peer
+	{
+		let exe = env::current_exe().expect("test binary has no path");
+		let mut child = Command::new(exe)
+			.args(&["peer", "--exact", "--nocapture", "--quiet"])
+			.env(ROLE_VAR, role)
+			.env(URL_VAR, base)
+			.stdout(Stdio::piped())
+			.spawn()
+			.expect("unable to spawn the peer");
+
+		// The reading continues after the address, so that the output of the
+		// test harness in the child never fills the pipe.
+		let stdout = child.stdout.take().unwrap();
+		let (tx, rx) = mpsc::channel();
+		thread::spawn(move || {
+			for line in BufReader::new(stdout).lines() {
+				match line {
+					Ok(l) if l.starts_with(READY) => {
+						let _ = tx.send(l[READY.len()..].to_string());
+					},
+					Ok(_) => {},
+					Err(_) => break,
+				}
+			}
+		});
+
+		let mut peer = Peer { child, url: String::new() };
+		peer.url = rx.recv_timeout(TIMEOUT).expect("the peer didn't start listening");
+		peer
+	}
+}
+
+impl Drop for Peer
+{
+	fn drop(&mut self)
+	{
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+	}
+}
+
+/// Describes the options `compat::nanomsg_mode` sets on every socket.
+fn describe(socket: &Socket) -> String
+{
+	format!(
+		"RecvMaxSize={} ReconnectMinTime={:?} ReconnectMaxTime={:?}",
+		socket.get_opt::<RecvMaxSize>().unwrap(),
+		socket.get_opt::<ReconnectMinTime>().unwrap(),
+		socket.get_opt::<ReconnectMaxTime>().unwrap(),
+	)
+}
+
+/// Creates a socket of the protocol configured for nanomsg peers.
+fn nanomsg_socket(protocol: Protocol) -> Socket
+{
+	let socket = Socket::new(protocol).unwrap();
+	compat::nanomsg_mode(&socket).unwrap();
+	socket.set_opt::<RecvTimeout>(Some(TIMEOUT)).unwrap();
+	socket
+}
+
+/// Listens on an address made from the base and returns the dialable URL.
+///
+/// The websocket transport can't report the port a listener got, so its base
+/// is a complete URL on a port that was free a moment ago.
+fn listen(socket: &Socket, base: &str) -> String
+{
+	if base.starts_with("ws://") {
+		socket.listen(base).unwrap();
+		return base.to_string();
+	}
+
+	let (_, _, url) = bind_ephemeral(socket, base).unwrap();
+	url
+}
+
+/// The addresses the spawned peers are tested on.
+fn bases() -> Vec<String>
+{
+	let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+	let mut bases = vec!["tcp://127.0.0.1".to_string(), format!("ws://127.0.0.1:{}/interop", port)];
+	if cfg!(unix) {
+		bases.push(format!("ipc://{}/nng-interop", env::temp_dir().display()));
+	}
+
+	bases
+}
+
+/// Runs the peer of a `spawned_*` test when this binary was started as one.
+#[test]
+fn peer()
+{
+	let role = match env::var(ROLE_VAR) {
+		Ok(r) => r,
+		Err(_) => return,
+	};
+	let base = env::var(URL_VAR).expect("the peer has no address");
+
+	match &role[..] {
+		"rep" => {
+			let socket = nanomsg_socket(Protocol::Rep0);
+			println!("{}{}", READY, listen(&socket, &base));
+			socket.set_opt::<RecvTimeout>(None).unwrap();
+
+			loop {
+				let mut msg = socket.recv().unwrap();
+				if &msg[..] == DESCRIBE {
+					msg.clear();
+					msg.push_back(describe(&socket).as_bytes()).unwrap();
+				}
+				socket.send(msg).unwrap();
+			}
+		},
+		"pub" => {
+			let socket = nanomsg_socket(Protocol::Pub0);
+			println!("{}{}", READY, listen(&socket, &base));
+
+			// Subscribers connect at any time and only see what is published
+			// afterwards.
+			loop {
+				socket.send(&b"weather sunny"[..]).unwrap();
+				socket.send(&b"news today"[..]).unwrap();
+				thread::sleep(Duration::from_millis(10));
+			}
+		},
+		r => panic!("unknown peer role {:?}", r),
+	}
+}
+
+/// Requests go back and forth on every transport with both sides configured.
+#[test]
+fn spawned_req_rep()
+{
+	for base in bases() {
+		let peer = Peer::spawn("rep", &base);
+		let req = nanomsg_socket(Protocol::Req0);
+		req.dial(&peer.url).unwrap();
+
+		req.send(&b"hello"[..]).unwrap();
+		assert_eq!(&req.recv().unwrap()[..], b"hello", "echo over {}", base);
+
+		// The peer reports the options it has, which must be the ones set here.
+		req.send(DESCRIBE).unwrap();
+		let reply = req.recv().unwrap();
+		assert_eq!(String::from_utf8_lossy(&reply), describe(&req), "options over {}", base);
+		assert_eq!(req.get_opt::<RecvMaxSize>().unwrap(), 1024 * 1024);
+		assert_eq!(req.get_opt::<ReconnectMinTime>().unwrap(), Some(Duration::from_millis(100)));
+	}
+}
+
+/// Topics are matched byte for byte, so a terminating `NUL` doesn't match.
+#[test]
+fn spawned_pub_sub_topics()
+{
+	let peer = Peer::spawn("pub", "tcp://127.0.0.1");
+
+	let sub = nanomsg_socket(Protocol::Sub0);
+	sub.set_opt::<Subscribe>(b"weather".to_vec()).unwrap();
+	sub.dial(&peer.url).unwrap();
+
+	let terminated = nanomsg_socket(Protocol::Sub0);
+	terminated.set_opt::<Subscribe>(b"weather\0".to_vec()).unwrap();
+	terminated.set_opt::<RecvTimeout>(Some(Duration::from_millis(500))).unwrap();
+	terminated.dial(&peer.url).unwrap();
+
+	for _ in 0..10 {
+		assert_eq!(&sub.recv().unwrap()[..], b"weather sunny");
+	}
+	assert_eq!(terminated.recv().unwrap_err(), Error::TimedOut);
+}
+
+/// An external _rep_ peer echoes a request.
+#[test]
+fn external_peer()
+{
+	let url = match env::var(PEER_VAR) {
+		Ok(u) => u,
+		Err(_) => {
+			eprintln!("skipping the external peer, {} isn't set", PEER_VAR);
+			return;
+		},
+	};
+
+	let req = nanomsg_socket(Protocol::Req0);
+	req.dial(&url).unwrap();
+	req.send(&b"hello from nng-rs"[..]).unwrap();
+	assert_eq!(&req.recv().unwrap()[..], b"hello from nng-rs");
+}
@@ -0,0 +1,129 @@
+//! Shutting down a server in the middle of a load.
+//!
+//! Clients keep sending requests to an `AioGroup` while a `ShutdownToken` is
+//! triggered. Every request that a worker received before the group was
+//! drained has to be answered before the socket is closed, while the ones
+//! still queued in NNG are dropped with the socket.
+use std::{
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	thread,
+	time::{Duration, Instant},
+};
+
+use nng::{
+	options::{Options, RecvTimeout, SendTimeout},
+	AioGroup,
+	AioResult,
+	Error,
+	Protocol,
+	ShutdownToken,
+	Socket,
+};
+
+/// The address the server listens on.
+const ADDRESS: &str = "inproc://nng/tests/shutdown";
+
+/// The number of workers in the server.
+const WORKERS: usize = 8;
+
+/// The number of clients sending requests at the same time.
+const CLIENTS: usize = 16;
+
+/// How many requests the server accepts before the shutdown is triggered.
+const ACCEPTED_BEFORE_SHUTDOWN: usize = 200;
+
+/// How long a worker works on a request.
+const WORK: Duration = Duration::from_millis(2);
+
+/// Sends requests until one of them isn't answered, returning how many were.
+fn client(token: &ShutdownToken) -> usize
+{
+	let socket = Socket::new(Protocol::Req0).unwrap();
+	socket.set_opt::<RecvTimeout>(Some(Duration::from_millis(500))).unwrap();
+	socket.set_opt::<SendTimeout>(Some(Duration::from_millis(500))).unwrap();
+	socket.dial(ADDRESS).unwrap();
+
+	let mut answered = 0;
+	loop {
+		// A request sent after the socket was closed can't be answered either.
+		if let Err(e) = socket.send(&b"request"[..]) {
+			assert!(token.is_shutdown(), "send failed before the shutdown: {}", e.error);
+			return answered;
+		}
+
+		match socket.recv() {
+			Ok(reply) => {
+				assert_eq!(&reply[..], b"reply");
+				answered += 1;
+			},
+			Err(Error::TimedOut) if token.is_shutdown() => return answered,
+			Err(e) => panic!("request failed before the shutdown: {}", e),
+		}
+	}
+}
+
+/// Every request that the workers received is answered during the shutdown.
+#[test]
+fn drain_answers_accepted_requests()
+{
+	let token = ShutdownToken::new();
+	let accepted = Arc::new(AtomicUsize::new(0));
+
+	let server = Socket::new(Protocol::Rep0).unwrap();
+	let cb_accepted = Arc::clone(&accepted);
+	let workers = AioGroup::new(&server, WORKERS, move |aio, ctx, res| {
+		let res = match res {
+			AioResult::RecvOk(m) => {
+				cb_accepted.fetch_add(1, Ordering::SeqCst);
+				aio.stash(m);
+				aio.sleep(WORK)
+			},
+			AioResult::SleepOk => {
+				let mut reply = aio.take_stash().unwrap();
+				reply.clear();
+				reply.push_back(b"reply").unwrap();
+				ctx.send(&aio, reply).map_err(|e| e.error)
+			},
+			AioResult::SendOk => ctx.recv(&aio),
+
+			// Canceled while waiting for a request, which is how the drain
+			// stops the idle workers.
+			AioResult::RecvErr(Error::Canceled) => Ok(()),
+			res => panic!("unexpected result: {:?}", res),
+		};
+
+		// Receives fail once the group is draining, everything else must work.
+		match res {
+			Ok(()) | Err(Error::Closed) => {},
+			Err(e) => panic!("unable to continue: {}", e),
+		}
+	})
+	.unwrap();
+	server.listen(ADDRESS).unwrap();
+	workers.start_recv().unwrap();
+
+	let clients: Vec<_> = (0..CLIENTS)
+		.map(|_| {
+			let token = token.clone();
+			thread::spawn(move || client(&token))
+		})
+		.collect();
+
+	// Trigger the shutdown while all of the clients are busy.
+	let start = Instant::now();
+	while accepted.load(Ordering::SeqCst) < ACCEPTED_BEFORE_SHUTDOWN {
+		assert!(start.elapsed() < Duration::from_secs(10), "the server isn't getting requests");
+		thread::sleep(Duration::from_millis(1));
+	}
+	token.trigger();
+	workers.drain();
+	server.close();
+
+	let answered: usize = clients.into_iter().map(|c| c.join().unwrap()).sum();
+	let accepted = accepted.load(Ordering::SeqCst);
+	assert!(accepted >= ACCEPTED_BEFORE_SHUTDOWN);
+	assert_eq!(answered, accepted, "accepted requests were not answered");
+}